@@ -0,0 +1,90 @@
+use crate::storage::{UserSettings, UserStorage};
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use teloxide::types::{Update, UpdateKind};
+use tokio::time::{self, Duration};
+
+// Как часто накопленная активность записывается в хранилище: отметка на каждое обновление
+// переписывала бы файл хранилища при каждом сообщении
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Активность, накопленная в памяти с прошлой записи в хранилище
+static PENDING: LazyLock<Mutex<HashMap<i64, Activity>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Активность пользователя с прошлой записи: последнее взаимодействие и число новых команд
+#[derive(Debug, Clone, Copy)]
+pub struct Activity {
+    pub user_id: i64,
+    pub last_seen: DateTime<Utc>,
+    pub commands: u64,
+}
+
+impl Activity {
+    pub fn apply(&self, user: &mut UserSettings) {
+        user.last_seen = Some(user.last_seen.map_or(self.last_seen, |seen| seen.max(self.last_seen)));
+        user.commands_used += self.commands;
+    }
+
+    fn merge(&mut self, other: Activity) {
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.commands += other.commands;
+    }
+}
+
+// Перед обработкой любого обновления от пользователя отмечает его активность (last_seen,
+// счетчик команд). Отметка копится в памяти и попадает в хранилище при следующей записи
+pub fn track_activity(update: Update) {
+    let (chat_id, is_command) = match &update.kind {
+        UpdateKind::Message(msg) => (msg.chat.id, msg.text().map(|t| t.starts_with('/')).unwrap_or(false)),
+        UpdateKind::EditedMessage(msg) => (msg.chat.id, false),
+        UpdateKind::CallbackQuery(q) => match q.message.as_ref() {
+            Some(msg) => (msg.chat.id, false),
+            None => return,
+        },
+        _ => return,
+    };
+
+    // Групповые чаты регистрируются отдельно через my_chat_member
+    if chat_id.0 > 0 {
+        let activity = Activity { user_id: chat_id.0, last_seen: Utc::now(), commands: u64::from(is_command) };
+        PENDING
+            .lock()
+            .unwrap()
+            .entry(chat_id.0)
+            .and_modify(|pending| pending.merge(activity))
+            .or_insert(activity);
+    }
+}
+
+// Фоновая задача: раз в минуту записывает накопленную активность
+pub async fn start_activity_flush(storage: Arc<dyn UserStorage>) {
+    let mut interval = time::interval(FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        flush(&*storage).await;
+    }
+}
+
+// Записывает накопленную активность одной пачкой. При сбое записи она возвращается
+// в очередь и попадет в хранилище со следующей попыткой
+pub async fn flush(storage: &dyn UserStorage) {
+    let batch: Vec<Activity> = std::mem::take(&mut *PENDING.lock().unwrap()).into_values().collect();
+    if batch.is_empty() {
+        return;
+    }
+
+    if let Err(e) = storage.record_activity(batch.clone()).await {
+        warn!("Активность пользователей не записана, повторим позже: {}", e);
+        let mut pending = PENDING.lock().unwrap();
+        for activity in batch {
+            pending.entry(activity.user_id).and_modify(|newer| newer.merge(activity)).or_insert(activity);
+        }
+    }
+}
+
+// Удаленный пользователь: накопленная отметка не должна заново создать его запись
+pub fn forget(user_id: i64) {
+    PENDING.lock().unwrap().remove(&user_id);
+}
@@ -0,0 +1,235 @@
+use crate::config::Config;
+use crate::fsck;
+use crate::user_import::{self, ConflictStrategy};
+use crate::broadcast_report;
+use crate::metrics::metrics;
+use crate::reengagement::{self, ReengagementStore};
+use crate::outbox::Outbox;
+use crate::reporting;
+use crate::schema_watch;
+use crate::scheduler;
+use crate::utils;
+use crate::storage::UserStorage;
+use crate::weather::WeatherClient;
+use chrono::{Duration, Utc};
+use log::{info, warn, LevelFilter};
+use teloxide::prelude::*;
+
+// Обработка служебных команд /admin <подкоманда>
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_admin_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    config: &Config,
+    reengagement_store: &ReengagementStore,
+    outbox: &Outbox,
+    weather_client: &WeatherClient,
+    args: &str,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+
+    if !config.is_admin(user_id) {
+        warn!("Пользователь ID: {} попытался выполнить команду администратора", user_id);
+        bot.send_message(msg.chat.id, "⛔ Эта команда доступна только администраторам бота").await?;
+        return Ok(());
+    }
+
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    info!("Администратор ID: {} выполняет /admin {}", user_id, args.trim());
+
+    // Шаблон может содержать переносы строк, поэтому берем его из исходного текста целиком
+    if let Some(template) = args.trim().strip_prefix("reengage template") {
+        let template = template.trim();
+        let response = if template.is_empty() {
+            "Укажите текст шаблона после команды. {city} будет заменен на город пользователя".to_string()
+        } else {
+            reengagement_store.set_template(template).await;
+            format!("✅ Шаблон сообщения «мы скучали» обновлен:\n\n{}", template)
+        };
+        bot.send_message(msg.chat.id, response).await?;
+        return Ok(());
+    }
+
+    let response = match parts.as_slice() {
+        ["stats"] => format!("{}\n\n📮 В очереди исходящих: {}", users_stats(storage).await, outbox.len().await),
+        ["stats", "latency"] => metrics().latency_report(),
+        ["metrics"] => metrics().render(),
+        ["outbox"] => outbox.status().await,
+        ["outbox", "dead"] => outbox.dead_letters_report().await,
+        ["outbox", "replay"] => format!("✅ Возвращено в очередь: {}", outbox.replay(None).await),
+        ["outbox", "replay", id] => match id.parse::<u64>() {
+            Ok(id) => match outbox.replay(Some(id)).await {
+                0 => format!("Недоставленного уведомления #{} нет", id),
+                _ => format!("✅ Уведомление #{} возвращено в очередь", id),
+            },
+            Err(_) => "Укажите номер из /admin outbox dead: /admin outbox replay <номер>".to_string(),
+        },
+        ["schema"] => schema_watch::report(),
+        ["keys"] => weather_client.key_report(),
+        ["loglevel"] => format!("Уровень логирования: {}", log::max_level().to_string().to_lowercase()),
+        ["loglevel", level] => set_log_level(user_id, level),
+        ["simulate", time] => match utils::parse_time(time) {
+            Some(time) => scheduler::simulate(storage, weather_client, config, time).await,
+            None => "Укажите время: /admin simulate 08:00".to_string(),
+        },
+        ["testsend", target] => match target.parse::<i64>() {
+            Ok(target_id) => match storage.get_user(target_id).await {
+                Ok(Some(user)) => scheduler::send_test_notification(bot, weather_client, config, user).await,
+                Ok(None) => format!("Пользователь {} не найден", target_id),
+                Err(e) => format!("❌ Не удалось загрузить пользователя {}: {}", target_id, e),
+            },
+            Err(_) => "Укажите числовой ID пользователя: /admin testsend <user_id>".to_string(),
+        },
+        ["fsck"] => fsck::check(storage, false).await.render(),
+        ["fsck", "repair"] => fsck::check(storage, true).await.render(),
+        ["import"] => import_from_reply(bot, msg, storage, weather_client, ConflictStrategy::Merge).await,
+        ["import", strategy] => match ConflictStrategy::parse(strategy) {
+            Some(strategy) => import_from_reply(bot, msg, storage, weather_client, strategy).await,
+            None => "Правило конфликтов: keep, overwrite или merge".to_string(),
+        },
+        ["reengage"] => {
+            let settings = reengagement_store.get().await;
+            format!(
+                "💌 Кампания «мы скучали»: {}\n\nШаблон:\n{}",
+                if settings.enabled { "включена" } else { "выключена" },
+                settings.template
+            )
+        }
+        ["reengage", "on"] => {
+            reengagement_store.set_enabled(true).await;
+            "✅ Кампания «мы скучали» включена".to_string()
+        }
+        ["reengage", "off"] => {
+            reengagement_store.set_enabled(false).await;
+            "⏸ Кампания «мы скучали» выключена".to_string()
+        }
+        ["reengage", "run"] => {
+            let sent = reengagement::run_campaign(bot, storage, reengagement_store).await;
+            format!("📨 Кампания запущена вручную, отправлено сообщений: {}", sent)
+        }
+        _ => admin_help(),
+    };
+
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+// Импорт пользователей из файла (JSON, JSONL или CSV), на который администратор ответил командой
+async fn import_from_reply(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    strategy: ConflictStrategy,
+) -> String {
+    let Some(document) = msg.reply_to_message().and_then(|reply| reply.document()) else {
+        return "Отправьте файл экспорта и ответьте на него командой /admin import [keep|overwrite|merge]".to_string();
+    };
+    let file_name = document.file_name.clone().unwrap_or_default();
+
+    let content = match utils::download_text(bot, &document.file.id).await {
+        Ok(content) => content,
+        Err(e) => return format!("❌ Не удалось скачать файл: {}", e),
+    };
+
+    match user_import::parse_users(&content, &file_name) {
+        Ok(users) => user_import::import_users(storage, weather_client, users, strategy).await.render(),
+        Err(e) => format!("❌ Не удалось разобрать файл {}: {}", file_name, e),
+    }
+}
+
+// Общая статистика по пользователям в хранилище
+async fn users_stats(storage: &dyn UserStorage) -> String {
+    let users = match storage.get_all_users().await {
+        Ok(users) => users,
+        Err(e) => return format!("❌ Не удалось получить список пользователей: {}", e),
+    };
+    let with_city = users.iter().filter(|u| !u.cities.is_empty()).count();
+    let with_time = users.iter().filter(|u| u.notification_time.is_some()).count();
+    let cute = users.iter().filter(|u| u.cute_mode).count();
+    let inactive = users.iter().filter(|u| !u.active).count();
+    let groups = users.iter().filter(|u| u.user_id < 0).count();
+
+    let week_ago = Utc::now() - Duration::days(7);
+    let month_ago = Utc::now() - Duration::days(30);
+    let active_week = users.iter().filter(|u| u.last_seen.map(|t| t >= week_ago).unwrap_or(false)).count();
+    let active_month = users.iter().filter(|u| u.last_seen.map(|t| t >= month_ago).unwrap_or(false)).count();
+    let new_week = users.iter().filter(|u| u.created_at.map(|t| t >= week_ago).unwrap_or(false)).count();
+    let commands: u64 = users.iter().map(|u| u.commands_used).sum();
+
+    let last_broadcast = broadcast_report::last_summary()
+        .map(|summary| summary.render())
+        .unwrap_or_else(|| "📬 С момента запуска рассылок еще не было".to_string());
+
+    format!(
+        "📊 Статистика бота\n\n\
+        Всего пользователей: {}\n\
+        С установленным городом: {}\n\
+        С временем уведомлений: {}\n\
+        В милом режиме: {}\n\
+        Заблокировали бота: {}\n\
+        Групповых чатов: {}\n\n\
+        Активны за 7 дней: {}\n\
+        Активны за 30 дней: {}\n\
+        Новых за 7 дней: {}\n\
+        Всего выполнено команд: {}\n\
+        Удалено по сроку хранения с запуска: {}\n\n\
+        {}",
+        users.len(),
+        with_city,
+        with_time,
+        cute,
+        inactive,
+        groups,
+        active_week,
+        active_month,
+        new_week,
+        commands,
+        metrics().get("users_pruned_total"),
+        last_broadcast
+    )
+}
+
+// Уровень логирования на лету: чтобы снять подробный лог воспроизведения, не теряя
+// позицию получения обновлений при перезапуске
+fn set_log_level(admin_id: i64, level: &str) -> String {
+    let level = match level.to_lowercase().as_str() {
+        "reset" | "default" => None,
+        name => match name.parse::<LevelFilter>() {
+            Ok(level) => Some(level),
+            Err(_) => return "Уровень: trace, debug, info, warn, error или reset".to_string(),
+        },
+    };
+    match reporting::set_log_level(level) {
+        Ok(max_level) => {
+            let max_level = max_level.to_string().to_lowercase();
+            warn!("Администратор ID: {} сменил уровень логирования на {}", admin_id, max_level);
+            format!("✅ Уровень логирования: {}", max_level)
+        }
+        Err(e) => format!("❌ Не удалось сменить уровень логирования: {}", e),
+    }
+}
+
+fn admin_help() -> String {
+    "🛠 Команды администратора:\n\n\
+    /admin stats - статистика пользователей\n\
+    /admin stats latency - задержка доставки уведомлений (p50/p95 по дням)\n\
+    /admin metrics - метрики в формате Prometheus\n\
+    /admin outbox - очередь исходящих уведомлений и повторов\n\
+    /admin outbox dead - уведомления, не доставленные за все попытки\n\
+    /admin outbox replay [номер] - отправить недоставленные заново (все или одно)\n\
+    /admin schema - новые и пропавшие поля в ответах OpenWeather\n\
+    /admin keys - использование ключей OpenWeather (запросы, ответы 429 и 401)\n\
+    /admin loglevel debug|info|warn - сменить уровень логирования без перезапуска (reset - вернуть RUST_LOG)\n\
+    /admin simulate <ЧЧ:ММ> - кому и какие уведомления ушли бы сегодня в это время (без отправки)\n\
+    /admin testsend <user_id> - сформировать и отправить пользователю его уведомление прямо сейчас (с пометкой о тесте)\n\
+    /admin fsck - проверить хранилище (пробный прогон)\n\
+    /admin fsck repair - проверить и исправить хранилище\n\
+    /admin import [keep|overwrite|merge] - ответом на файл: импорт пользователей (JSON, JSONL, CSV)\n\
+    /admin reengage - настройки кампании «мы скучали»\n\
+    /admin reengage on|off - включить или выключить кампанию\n\
+    /admin reengage template <текст> - задать текст ({city} - город пользователя)\n\
+    /admin reengage run - отправить сообщения прямо сейчас"
+        .to_string()
+}
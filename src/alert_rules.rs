@@ -0,0 +1,212 @@
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::weather::{DaySummary, WeatherClient};
+use crate::escape_markdown_v2;
+use chrono::Local;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Время ежедневной проверки правил оператора по прогнозу на сегодня
+pub const CHECK_TIME: &str = "07:00";
+
+// Правило оператора из ALERT_RULES_FILE: условие над прогнозом на день и текст предупреждения.
+// Пример: temp_min < -20 && wind > 10 -> "Опасный мороз"
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    // Условие в исходном виде - для логов
+    pub source: String,
+    // Условие в дизъюнктивной форме: правило срабатывает, если выполнены все сравнения
+    // хотя бы одной группы (&& связывает сильнее, чем ||)
+    any_of: Vec<Vec<Comparison>>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Comparison {
+    field: Field,
+    op: Op,
+    value: f32,
+}
+
+// Поля прогноза на день, доступные в условиях
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    TempMin,
+    TempMax,
+    Wind,
+    Humidity,
+    Precipitation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "temp_min" => Some(Field::TempMin),
+            "temp_max" => Some(Field::TempMax),
+            "wind" => Some(Field::Wind),
+            "humidity" => Some(Field::Humidity),
+            "precipitation" => Some(Field::Precipitation),
+            _ => None,
+        }
+    }
+
+    fn value(self, day: &DaySummary) -> f32 {
+        match self {
+            Field::TempMin => day.temp_min,
+            Field::TempMax => day.temp_max,
+            Field::Wind => day.wind_max.unwrap_or(0.0),
+            Field::Humidity => day.humidity,
+            Field::Precipitation => day.precipitation,
+        }
+    }
+}
+
+impl Op {
+    fn apply(self, left: f32, right: f32) -> bool {
+        match self {
+            Op::Less => left < right,
+            Op::LessOrEqual => left <= right,
+            Op::Greater => left > right,
+            Op::GreaterOrEqual => left >= right,
+            Op::Equal => left == right,
+            Op::NotEqual => left != right,
+        }
+    }
+}
+
+impl AlertRule {
+    // Разбор строки вида: temp_min < -20 && wind > 10 -> "Опасный мороз"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (condition, message) = text
+            .split_once("->")
+            .ok_or_else(|| format!("нет \"->\" между условием и текстом: {}", text))?;
+        let message = message.trim().trim_matches('"').trim();
+        if message.is_empty() {
+            return Err(format!("пустой текст предупреждения: {}", text));
+        }
+
+        let any_of = condition
+            .split("||")
+            .map(|group| group.split("&&").map(parse_comparison).collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(AlertRule {
+            source: condition.trim().to_string(),
+            any_of,
+            message: message.to_string(),
+        })
+    }
+
+    pub fn matches(&self, day: &DaySummary) -> bool {
+        self.any_of.iter().any(|group| {
+            group.iter().all(|comparison| comparison.op.apply(comparison.field.value(day), comparison.value))
+        })
+    }
+}
+
+// Одно сравнение: поле, оператор и число
+fn parse_comparison(text: &str) -> Result<Comparison, String> {
+    let text = text.trim();
+    // Двухсимвольные операторы проверяем раньше, чтобы "<=" не разобрался как "<"
+    const OPERATORS: [(&str, Op); 6] = [
+        ("<=", Op::LessOrEqual),
+        (">=", Op::GreaterOrEqual),
+        ("==", Op::Equal),
+        ("!=", Op::NotEqual),
+        ("<", Op::Less),
+        (">", Op::Greater),
+    ];
+    let (field, op, value) = OPERATORS
+        .iter()
+        .find_map(|(symbol, op)| text.split_once(symbol).map(|(field, value)| (field, *op, value)))
+        .ok_or_else(|| format!("нет оператора сравнения в «{}»", text))?;
+
+    let field = Field::parse(field.trim()).ok_or_else(|| {
+        format!(
+            "неизвестное поле «{}» (доступны temp_min, temp_max, wind, humidity, precipitation)",
+            field.trim()
+        )
+    })?;
+    let value = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("некорректное число в «{}»", text))?;
+    Ok(Comparison { field, op, value })
+}
+
+// Файл правил в TOML: rules = ['temp_min < -20 && wind > 10 -> "Опасный мороз"', ...]
+pub fn parse_rules_file(content: &str) -> Result<Vec<AlertRule>, String> {
+    let table = content.parse::<toml::Table>().map_err(|e| e.to_string())?;
+    let Some(rules) = table.get("rules") else {
+        return Ok(Vec::new());
+    };
+    let rules = rules.as_array().ok_or("rules должен быть списком строк")?;
+    rules
+        .iter()
+        .map(|rule| rule.as_str().ok_or("каждое правило - строка".to_string()).and_then(AlertRule::parse))
+        .collect()
+}
+
+// Ежедневная проверка: прогноз на сегодня в городе каждого пользователя сверяется с правилами,
+// о сработавших пользователь получает одно сообщение. Прогноз запрашиваем один раз на город
+pub async fn check_rules(bot: &Bot, storage: &dyn UserStorage, weather_client: &WeatherClient, rules: &[AlertRule]) {
+    if rules.is_empty() {
+        return;
+    }
+    let today = Local::now().date_naive();
+    let mut forecasts: HashMap<String, Option<DaySummary>> = HashMap::new();
+
+    for user in storage.all_users_or_log().await {
+        if !user.active {
+            continue;
+        }
+        let Some(city) = user.notification_city(today) else {
+            continue;
+        };
+
+        let key = city.to_lowercase();
+        if !forecasts.contains_key(&key) {
+            let summary = match weather_client.get_day_summary(&city, 0).await {
+                Ok(summary) => Some(summary),
+                Err(e) => {
+                    warn!("Не удалось получить прогноз {} для проверки правил: {}", city, e);
+                    None
+                }
+            };
+            forecasts.insert(key.clone(), summary);
+        }
+        let Some(summary) = forecasts.get(&key).and_then(Option::as_ref) else {
+            continue;
+        };
+
+        let warnings: Vec<&str> = rules
+            .iter()
+            .filter(|rule| rule.matches(summary))
+            .map(|rule| rule.message.as_str())
+            .collect();
+        if warnings.is_empty() {
+            continue;
+        }
+
+        let message = templates::render("rules.alert", &[
+            ("city", &escape_markdown_v2(&crate::utils::echo(&city))),
+            ("warnings", &escape_markdown_v2(&warnings.join("\n"))),
+        ]);
+        match bot.send_message(ChatId(user.user_id), message).parse_mode(ParseMode::MarkdownV2).await {
+            Ok(_) => info!("Пользователю ID: {} отправлены предупреждения по правилам: {}", user.user_id, warnings.join("; ")),
+            Err(e) => error!("Не удалось отправить предупреждение пользователю {}: {}", user.user_id, e),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use tokio::sync::mpsc;
+
+// Одинаковые сбои сообщаем администраторам не чаще, чем раз в этот интервал
+const REPEAT_ALERT_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Критический сбой: kind - вид сбоя для подавления повторов, details - подробности для сообщения
+struct Alert {
+    kind: &'static str,
+    details: String,
+}
+
+static SENDER: OnceLock<mpsc::UnboundedSender<Alert>> = OnceLock::new();
+
+// Сообщает администраторам о критическом сбое (запись хранилища, недоступность источника погоды).
+// Вызов не ждет отправки; до запуска start_alerts сбой только попадает в лог
+pub fn critical(kind: &'static str, details: impl Into<String>) {
+    if let Some(sender) = SENDER.get() {
+        let _ = sender.send(Alert { kind, details: details.into() });
+    }
+}
+
+// Доставка критических сбоев: сначала администраторам из ADMIN_IDS, а если ни до кого
+// из них сообщение не дошло - в запасной чат (ADMIN_FALLBACK_CHAT_ID)
+pub async fn start_alerts(bot: Bot, admin_ids: Vec<i64>, fallback_chat: Option<i64>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    if SENDER.set(sender).is_err() {
+        warn!("Оповещение администраторов о сбоях уже запущено");
+        return;
+    }
+    info!("Оповещение администраторов о сбоях запущено");
+
+    let mut last_sent: HashMap<&'static str, Instant> = HashMap::new();
+    while let Some(alert) = receiver.recv().await {
+        if last_sent.get(alert.kind).is_some_and(|sent| sent.elapsed() < REPEAT_ALERT_INTERVAL) {
+            continue;
+        }
+        last_sent.insert(alert.kind, Instant::now());
+        deliver(&bot, &admin_ids, fallback_chat, &format!("🚨 Критический сбой: {}", alert.details)).await;
+    }
+}
+
+async fn deliver(bot: &Bot, admin_ids: &[i64], fallback_chat: Option<i64>, text: &str) {
+    let mut delivered = false;
+    for admin_id in admin_ids {
+        match bot.send_message(ChatId(*admin_id), text).await {
+            Ok(_) => delivered = true,
+            Err(e) => warn!("Не удалось сообщить о сбое администратору {}: {}", admin_id, e),
+        }
+    }
+    if delivered {
+        return;
+    }
+
+    match fallback_chat {
+        Some(chat_id) => {
+            if let Err(e) = bot.send_message(ChatId(chat_id), text).await {
+                error!("Не удалось сообщить о сбое ни администраторам, ни в запасной чат {}: {}", chat_id, e);
+            }
+        }
+        None => warn!("Сообщение о сбое не доставлено администраторам, запасной чат не настроен: {}", text),
+    }
+}
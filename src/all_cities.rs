@@ -0,0 +1,174 @@
+use crate::handlers::load_or_apologize;
+use crate::formatter::FormatOptions;
+use crate::storage::{UserSettings, UserStorage};
+use crate::templates;
+use crate::utils;
+use crate::weather::WeatherClient;
+use crate::escape_markdown_v2;
+use futures::future::join_all;
+use log::{info, warn};
+use std::time::Instant;
+use teloxide::prelude::*;
+use teloxide::types::{ChatAction, ParseMode};
+
+// Ограничение Telegram на длину одного сообщения
+pub const TELEGRAM_MESSAGE_LIMIT: usize = 4096;
+
+// Аргумент /weather, по которому показываем погоду во всех сохраненных городах
+pub fn is_all_cities(args: &str) -> bool {
+    matches!(args.trim().to_lowercase().as_str(), "all" | "все")
+}
+
+// Обработка /weather all: текущая погода в основном и недавних городах одним сообщением.
+// Города запрашиваются одновременно, в конце - сколько времени заняла сборка
+pub async fn handle_all_cities(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let cities = saved_cities(&user);
+    if cities.is_empty() {
+        bot.send_message(msg.chat.id, templates::text("city.missing"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_chat_action(msg.chat.id, ChatAction::Typing).await?;
+
+    let started = Instant::now();
+    let options = FormatOptions::for_user(&user);
+    let results = join_all(cities.iter().map(|city| weather_client.get_weather(city, options))).await;
+    let sections: Vec<String> = cities
+        .iter()
+        .zip(results)
+        .map(|(city, result)| {
+            let name = escape_markdown_v2(&utils::echo(city));
+            match result {
+                Ok(weather) => templates::render("weather.all_city", &[
+                    ("city", &name),
+                    ("weather", &escape_markdown_v2(&weather)),
+                ]),
+                Err(e) => {
+                    warn!("Не удалось получить погоду {} для пользователя {}: {}", city, user_id, e);
+                    templates::render("weather.all_failed", &[("city", &name), ("error", &escape_markdown_v2(&e))])
+                }
+            }
+        })
+        .collect();
+    let footer = templates::render("weather.all_footer", &[
+        ("seconds", &escape_markdown_v2(&format!("{:.1}", started.elapsed().as_secs_f32()))),
+    ]);
+
+    let mut sections = sections;
+    sections.push(footer);
+    for message in split_messages(sections) {
+        bot.send_message(msg.chat.id, message)
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+    }
+    info!("Пользователю ID: {} отправлена погода в городах: {}", user_id, cities.join(", "));
+    Ok(())
+}
+
+// Города прогноза и недавние без повторов, основной первым
+fn saved_cities(user: &UserSettings) -> Vec<String> {
+    let mut cities: Vec<String> = Vec::new();
+    for city in user.cities.iter().chain(&user.recent_cities) {
+        if !cities.iter().any(|saved| saved.to_lowercase() == city.to_lowercase()) {
+            cities.push(city.clone());
+        }
+    }
+    cities
+}
+
+// Разделы в сообщения не длиннее лимита Telegram; раздел между сообщениями не разрывается,
+// если сам помещается в одно сообщение, иначе делится по строкам
+pub fn split_messages(sections: Vec<String>) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for section in sections.into_iter().flat_map(split_section) {
+        match messages.last_mut() {
+            Some(last) if last.chars().count() + section.chars().count() + 2 <= TELEGRAM_MESSAGE_LIMIT => {
+                last.push_str("\n\n");
+                last.push_str(&section);
+            }
+            _ => messages.push(section),
+        }
+    }
+    messages
+}
+
+// Готовый текст в сообщения не длиннее лимита Telegram, по абзацам
+pub fn split_text(text: &str) -> Vec<String> {
+    split_messages(text.split("\n\n").map(str::to_string).collect())
+}
+
+// Слишком длинный раздел делим по строкам, а слишком длинную строку - по символам
+fn split_section(section: String) -> Vec<String> {
+    if section.chars().count() <= TELEGRAM_MESSAGE_LIMIT {
+        return vec![section];
+    }
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for line in section.split('\n').flat_map(split_line) {
+        if !current.is_empty() && current.chars().count() + line.chars().count() + 1 > TELEGRAM_MESSAGE_LIMIT {
+            parts.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+// Кусок не заканчивается обратной косой чертой, чтобы не отрывать ее от экранируемого символа
+fn split_line(line: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chars: Vec<char> = line.chars().collect();
+    while chars.len() > TELEGRAM_MESSAGE_LIMIT {
+        let mut end = TELEGRAM_MESSAGE_LIMIT;
+        while end > 1 && chars[end - 1] == '\\' {
+            end -= 1;
+        }
+        chunks.push(chars.drain(..end).collect());
+    }
+    chunks.push(chars.into_iter().collect());
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_sections_up_to_the_limit() {
+        let section = "а".repeat(2000);
+        let messages = split_messages(vec![section.clone(), section.clone(), section.clone()]);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], format!("{}\n\n{}", section, section));
+        assert_eq!(messages[1], section);
+    }
+
+    #[test]
+    fn splits_oversized_section_by_lines_and_chars() {
+        let line = "б".repeat(3000);
+        let messages = split_text(&format!("{}\n{}", line, line));
+        assert_eq!(messages, vec![line.clone(), line]);
+
+        // Экранирующая черта не отрывается от следующего символа
+        let long = format!("{}\\.{}", "в".repeat(TELEGRAM_MESSAGE_LIMIT - 1), "г".repeat(10));
+        let messages = split_text(&long);
+        assert!(messages.iter().all(|message| message.chars().count() <= TELEGRAM_MESSAGE_LIMIT));
+        assert!(messages[1].starts_with("\\."));
+        assert_eq!(messages.concat(), long);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::metrics::metrics;
+use log::warn;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Пауза для ключа, упершегося в лимит запросов (у бесплатного тарифа лимит поминутный)
+const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+// Пауза для ключа, который сервис отверг: новый ключ OpenWeather активируется не сразу,
+// поэтому не выключаем его насовсем, а изредка пробуем снова
+const UNAUTHORIZED_COOLDOWN: Duration = Duration::from_secs(3600);
+
+// Почему ключ нужно на время вывести из оборота
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    RateLimited,
+    Unauthorized,
+}
+
+#[derive(Default)]
+struct KeyState {
+    key: String,
+    requests: u64,
+    rate_limited: u64,
+    unauthorized: u64,
+    cooldown_until: Option<Instant>,
+}
+
+struct PoolState {
+    keys: Vec<KeyState>,
+    // Индекс ключа, с которого начнется поиск в следующий раз
+    next: usize,
+}
+
+// Набор ключей OpenWeather: запросы распределяются по кругу, ключ с ответом 429 или 401
+// на время выводится из оборота
+pub struct ApiKeyPool {
+    state: Mutex<PoolState>,
+}
+
+impl ApiKeyPool {
+    pub fn new(keys: Vec<String>) -> Self {
+        ApiKeyPool {
+            state: Mutex::new(PoolState {
+                keys: keys.into_iter().map(|key| KeyState { key, ..Default::default() }).collect(),
+                next: 0,
+            }),
+        }
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.state.lock().unwrap().keys.len()
+    }
+
+    // Следующий доступный ключ (индекс и значение) или None, если все ключи на паузе
+    pub fn next_key(&self) -> Option<(usize, String)> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let count = state.keys.len();
+        for offset in 0..count {
+            let index = (state.next + offset) % count;
+            let key = &mut state.keys[index];
+            if key.cooldown_until.is_some_and(|until| until > now) {
+                continue;
+            }
+            key.cooldown_until = None;
+            key.requests += 1;
+            let value = key.key.clone();
+            state.next = (index + 1) % count;
+            return Some((index, value));
+        }
+        None
+    }
+
+    // Учитывает отказ сервиса по ключу index и ставит ключ на паузу
+    pub fn record(&self, index: usize, outcome: KeyOutcome) {
+        let mut state = self.state.lock().unwrap();
+        let Some(key) = state.keys.get_mut(index) else {
+            return;
+        };
+        let (status, cooldown) = match outcome {
+            KeyOutcome::RateLimited => {
+                key.rate_limited += 1;
+                metrics().increment("weather_api_key_rate_limited_total");
+                (429, RATE_LIMIT_COOLDOWN)
+            }
+            KeyOutcome::Unauthorized => {
+                key.unauthorized += 1;
+                metrics().increment("weather_api_key_unauthorized_total");
+                (401, UNAUTHORIZED_COOLDOWN)
+            }
+        };
+        warn!(
+            "Ключ OpenWeather {} получил ответ {}, пауза {} с",
+            mask(&key.key),
+            status,
+            cooldown.as_secs()
+        );
+        key.cooldown_until = Some(Instant::now() + cooldown);
+    }
+
+    // Отчет для /admin keys; ключи показываются не целиком
+    pub fn report(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let mut text = format!("🔑 Ключи OpenWeather: {}", state.keys.len());
+        for key in &state.keys {
+            let status = match key.cooldown_until {
+                Some(until) if until > now => format!("пауза еще {} с", (until - now).as_secs()),
+                _ => "активен".to_string(),
+            };
+            text.push_str(&format!(
+                "\n• {} - запросов {}, 429: {}, 401: {}, {}",
+                mask(&key.key),
+                key.requests,
+                key.rate_limited,
+                key.unauthorized,
+                status
+            ));
+        }
+        text
+    }
+}
+
+// Первые и последние символы ключа, чтобы различать ключи в логах и отчете
+fn mask(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
@@ -0,0 +1,464 @@
+use crate::capabilities::{self, Capabilities};
+use crate::config::{Config, StorageBackend};
+use crate::daily_extras::{self, DailyExtrasPlugin};
+use crate::delivery_history::DeliveryHistory;
+use crate::outbox::{self, Outbox};
+use crate::plugins::PluginRegistry;
+use crate::reengagement::{self, ReengagementStore};
+use crate::storage::{JsonStorage, MemoryStorage, PgStorage, RedisStorage, SqliteStorage, UserStorage};
+use crate::storage_lock::{self, StorageLock};
+use crate::update_offset::UpdateOffset;
+use crate::history::WeatherHistory;
+use crate::weather::WeatherClient;
+use crate::{activity, alerts, backup, error_throttle, fsck, onboarding, retention, scheduler, templates, webapp};
+use futures::future::{self, FutureExt};
+use log::{error, info, warn};
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+use teloxide::dispatching::{DefaultKey, Dispatcher};
+use teloxide::prelude::*;
+use teloxide::types::BotCommand;
+use teloxide::update_listeners::Polling;
+use tokio::task::JoinHandle;
+
+// Ошибка одного из этапов сборки приложения
+#[derive(Debug)]
+pub enum AppError {
+    // Некорректные или недостающие настройки (все найденные проблемы)
+    Config(Vec<String>),
+    // Хранилище пользователей недоступно
+    Storage(String),
+    // Этап запущен раньше того, от которого он зависит
+    MissingStage(&'static str),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Config(problems) => write!(f, "ошибки конфигурации: {}", problems.join("; ")),
+            AppError::Storage(e) => write!(f, "хранилище недоступно: {}", e),
+            AppError::MissingStage(stage) => write!(f, "не выполнен этап сборки приложения: {}", stage),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+// Фоновая задача и сообщение на случай ее неожиданной остановки
+type Job = (&'static str, JoinHandle<()>);
+
+// Бот, собираемый по этапам: настройки → хранилище → провайдеры → диспетчер → фоновые задачи.
+// Другие точки входа (сценарии, офлайн-команды) собирают только нужные им этапы
+pub struct App {
+    pub config: Arc<Config>,
+    pub storage: Option<Arc<dyn UserStorage>>,
+    pub reengagement_store: Option<Arc<ReengagementStore>>,
+    pub outbox: Option<Arc<Outbox>>,
+    pub bot: Option<Bot>,
+    pub weather_client: Option<WeatherClient>,
+    // Дополнительные команды; по умолчанию плагинов нет
+    pub plugins: Arc<PluginRegistry>,
+    // Файл хранилища занят ботом, пока App существует
+    storage_lock: Option<StorageLock>,
+}
+
+impl App {
+    // Этап 1: проверенные настройки; все найденные проблемы возвращаются одной ошибкой
+    pub fn from_config(config: Arc<Config>) -> Result<App, AppError> {
+        let problems = config.validate();
+        if !problems.is_empty() {
+            return Err(AppError::Config(problems));
+        }
+        Ok(App {
+            config,
+            storage: None,
+            reengagement_store: None,
+            outbox: None,
+            bot: None,
+            weather_client: None,
+            plugins: Arc::new(PluginRegistry::default()),
+            storage_lock: None,
+        })
+    }
+
+    // Подключает плагины команд: они появятся в меню бота и в дереве обработчиков
+    pub fn with_plugins(mut self, plugins: PluginRegistry) -> App {
+        self.plugins = Arc::new(plugins);
+        self
+    }
+
+    // Этап 2: хранилище пользователей, настройки кампаний и очередь исходящих
+    pub async fn init_storage(mut self) -> Result<App, AppError> {
+        let path = &self.config.storage_path;
+        let storage: Arc<dyn UserStorage> = match self.config.storage_backend {
+            StorageBackend::File => {
+                self.storage_lock = Some(storage_lock::acquire(path, "бот").map_err(AppError::Storage)?);
+                // Нечитаемый файл хранилища нельзя подменять пустым списком пользователей
+                if Path::new(path).exists() {
+                    File::open(path).map_err(|e| AppError::Storage(format!("{}: {}", path, e)))?;
+                }
+                Arc::new(
+                    JsonStorage::new(path, self.config.storage_key.as_deref())
+                        .await
+                        .map_err(AppError::Storage)?,
+                )
+            }
+            // Одновременный доступ нескольких процессов SQLite разруливает сама, блокировка файла не нужна
+            StorageBackend::Sqlite => Arc::new(
+                SqliteStorage::open(path, self.config.storage_key.as_deref())
+                    .await
+                    .map_err(AppError::Storage)?,
+            ),
+            StorageBackend::Postgres => {
+                let url = self.config.database_url.as_deref().ok_or_else(|| AppError::Storage("не задан DATABASE_URL".to_string()))?;
+                Arc::new(PgStorage::connect(url).await.map_err(AppError::Storage)?)
+            }
+            StorageBackend::Redis => {
+                let url = self.config.redis_url.as_deref().ok_or_else(|| AppError::Storage("не задан REDIS_URL".to_string()))?;
+                let ttl = self.config.dialogue_state_ttl();
+                Arc::new(RedisStorage::connect(url, &self.config.redis_prefix, ttl).await.map_err(AppError::Storage)?)
+            }
+            StorageBackend::Memory => {
+                warn!("STORAGE_BACKEND=memory: настройки пользователей хранятся только в памяти и пропадут при перезапуске");
+                Arc::new(MemoryStorage::new())
+            }
+        };
+        fsck::startup_check(&*storage).await;
+
+        // В режиме «ничего не сохраняем» и очередь уведомлений не пишется на диск
+        let history = DeliveryHistory::load(&*storage).await;
+        let outbox = match self.config.storage_backend {
+            StorageBackend::File | StorageBackend::Sqlite | StorageBackend::Postgres | StorageBackend::Redis => {
+                Outbox::new("outbox.json", history, self.config.storage_key.as_deref()).map_err(AppError::Storage)?
+            }
+            StorageBackend::Memory => Outbox::in_memory(history),
+        };
+
+        self.storage = Some(storage);
+        self.reengagement_store = Some(Arc::new(ReengagementStore::new("reengagement.json")));
+        self.outbox = Some(Arc::new(outbox));
+        Ok(self)
+    }
+
+    // Этап 3: тексты сообщений, клиент Bot API и сервис погоды
+    pub fn init_providers(mut self) -> Result<App, AppError> {
+        let token = self.config.telegram_bot_token.clone().ok_or_else(|| {
+            AppError::Config(vec!["Не задан TELEGRAM_BOT_TOKEN".to_string()])
+        })?;
+
+        // Тексты сообщений, переопределенные оператором
+        templates::load(&self.config.templates_dir);
+        error_throttle::set_repeat_interval(self.config.error_repeat_interval);
+
+        // Цитаты и гороскоп в утренних сообщениях - первый встроенный плагин
+        if let Some(source) = &self.config.daily_extras_source {
+            daily_extras::init(source).map_err(|e| AppError::Config(vec![e]))?;
+            self.plugins = Arc::new((*self.plugins).clone().register(DailyExtrasPlugin));
+        }
+
+        self.bot = Some(create_bot(token, &self.config));
+        self.weather_client = Some(
+            WeatherClient::new(self.config.openweather_api_keys.clone())
+                .with_attribution(self.config.weather_attribution)
+                .with_history(Arc::new(WeatherHistory::new("history.json"))),
+        );
+        Ok(self)
+    }
+
+    // Этап 4: диспетчер обновлений со всеми зависимостями обработчиков
+    pub fn build_dispatcher(&self) -> Result<Dispatcher<Bot, teloxide::RequestError, DefaultKey>, AppError> {
+        let bot = self.bot()?;
+        let dependencies = dptree::deps![
+            bot.clone(),
+            Arc::clone(self.storage()?),
+            self.weather_client()?.clone(),
+            Arc::clone(&self.config),
+            Arc::clone(self.reengagement_store()?),
+            Arc::clone(self.outbox()?),
+            Arc::clone(&self.plugins)
+        ];
+
+        // Обновления, обработанные до перезапуска, Telegram может прислать повторно - пропускаем их
+        let offset = Arc::new(UpdateOffset::new("update_offset.json"));
+        let handler = dptree::filter(move |update: Update| offset.accept(update.id)).chain(crate::handlers::build_handler());
+
+        Ok(Dispatcher::builder(bot.clone(), handler)
+            .dependencies(dependencies)
+            .enable_ctrlc_handler()
+            .build())
+    }
+
+    // Этап 5: фоновые задачи (планировщик, напоминания, очистка данных и т.д.)
+    pub fn spawn_jobs(&self) -> Result<Vec<Job>, AppError> {
+        // Все зависимости берем заранее, чтобы не запустить часть задач и вернуть ошибку
+        let bot = self.bot()?;
+        let storage = self.storage()?;
+        let weather_client = self.weather_client()?;
+        let reengagement_store = self.reengagement_store()?;
+        let outbox = self.outbox()?;
+        let config = &self.config;
+
+        let mut jobs = vec![
+            (
+                "Планировщик уведомлений остановлен неожиданно",
+                tokio::spawn(scheduler::start_scheduler(
+                    bot.clone(),
+                    Arc::clone(storage),
+                    weather_client.clone(),
+                    Arc::clone(config),
+                    Arc::clone(outbox),
+                )),
+            ),
+            (
+                "Планировщик очистки webhook остановлен неожиданно",
+                tokio::spawn(start_webhook_cleaner(bot.clone())),
+            ),
+            (
+                // Напоминания пользователям, не завершившим настройку
+                "Задача напоминаний о настройке остановлена неожиданно",
+                tokio::spawn(onboarding::start_onboarding_reminders(bot.clone(), Arc::clone(storage))),
+            ),
+            (
+                // Запись накопленной активности пользователей
+                "Запись активности пользователей остановлена неожиданно",
+                tokio::spawn(activity::start_activity_flush(Arc::clone(storage))),
+            ),
+            (
+                // Запись очереди исходящих и истории доставки
+                "Запись очереди исходящих остановлена неожиданно",
+                tokio::spawn(outbox::start_flush(Arc::clone(outbox), Arc::clone(storage))),
+            ),
+            (
+                // Удаление данных пользователей, которые давно не пользуются ботом
+                "Задача очистки данных остановлена неожиданно",
+                tokio::spawn(retention::start_retention_job(bot.clone(), Arc::clone(storage), Arc::clone(config))),
+            ),
+            (
+                // Резервные копии хранилища с ротацией
+                "Резервное копирование хранилища остановлено неожиданно",
+                tokio::spawn(backup::start_backup_job(Arc::clone(storage), Arc::clone(config))),
+            ),
+            (
+                // Перечитываем шаблоны сообщений при изменении файлов
+                "Отслеживание шаблонов сообщений остановлено неожиданно",
+                tokio::spawn(templates::watch_templates(config.templates_dir.clone())),
+            ),
+            (
+                // Ежемесячные сообщения давно не заходившим пользователям
+                "Кампания возврата пользователей остановлена неожиданно",
+                tokio::spawn(reengagement::start_reengagement_campaign(
+                    bot.clone(),
+                    Arc::clone(storage),
+                    Arc::clone(reengagement_store),
+                )),
+            ),
+            (
+                // Сообщения администраторам о критических сбоях
+                "Оповещение администраторов о сбоях остановлено неожиданно",
+                tokio::spawn(alerts::start_alerts(bot.clone(), config.admin_ids.clone(), config.admin_fallback_chat)),
+            ),
+            (
+                // Доставка уведомлений из очереди исходящих
+                "Отправитель исходящих уведомлений остановлен неожиданно",
+                tokio::spawn(outbox::start_sender(bot.clone(), Arc::clone(outbox), config.admin_ids.clone())),
+            ),
+        ];
+        if let Some(addr) = config.webapp_addr {
+            jobs.push((
+                // Страница настроек Web App и ее API
+                "Сервер настроек Web App остановлен неожиданно",
+                tokio::spawn(webapp::start_server(addr, Arc::clone(storage), weather_client.clone(), Arc::clone(config))),
+            ));
+        }
+        info!("Фоновые задачи запущены: {}", jobs.len());
+        Ok(jobs)
+    }
+
+    // Полный запуск бота: подготовка Bot API, фоновые задачи и обработка обновлений
+    // до остановки по Ctrl+C или неожиданного завершения одной из задач
+    pub async fn run(self) -> Result<(), AppError> {
+        let bot = self.bot()?;
+        delete_webhook(bot).await;
+        set_commands(bot, &self.plugins, self.weather_client()?.capabilities()).await;
+
+        let mut dispatcher = self.build_dispatcher()?;
+        let mut polling = Polling::builder(bot.clone());
+        if self.config.skip_pending_updates {
+            info!("Накопившиеся за время простоя обновления будут пропущены");
+            polling = polling.drop_pending_updates();
+        }
+        let listener = polling.build();
+        let jobs = self.spawn_jobs()?;
+
+        let job_stopped = future::select_all(jobs.into_iter().map(|(message, handle)| {
+            async move {
+                let _ = handle.await;
+                message
+            }
+            .boxed()
+        }));
+
+        info!("Бот готов к работе!");
+        tokio::select! {
+            _ = dispatcher.dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("Ошибка получения обновлений"),
+            ) => {
+                info!("Бот остановлен");
+            }
+            (message, _, _) = job_stopped => {
+                error!("{}", message);
+            }
+        }
+        // Активность с последней записи не теряем
+        activity::flush(&**self.storage()?).await;
+        self.outbox()?.flush(&**self.storage()?).await;
+        Ok(())
+    }
+
+    fn bot(&self) -> Result<&Bot, AppError> {
+        self.bot.as_ref().ok_or(AppError::MissingStage("провайдеры"))
+    }
+
+    fn weather_client(&self) -> Result<&WeatherClient, AppError> {
+        self.weather_client.as_ref().ok_or(AppError::MissingStage("провайдеры"))
+    }
+
+    fn storage(&self) -> Result<&Arc<dyn UserStorage>, AppError> {
+        self.storage.as_ref().ok_or(AppError::MissingStage("хранилище"))
+    }
+
+    fn reengagement_store(&self) -> Result<&Arc<ReengagementStore>, AppError> {
+        self.reengagement_store.as_ref().ok_or(AppError::MissingStage("хранилище"))
+    }
+
+    fn outbox(&self) -> Result<&Arc<Outbox>, AppError> {
+        self.outbox.as_ref().ok_or(AppError::MissingStage("хранилище"))
+    }
+}
+
+// Создает клиента Bot API с учетом своего адреса сервера и тестового окружения Telegram
+pub fn create_bot(token: String, config: &Config) -> Bot {
+    // Тестовое окружение доступно по адресу /bot<token>/test/<метод>,
+    // поэтому достаточно добавить "/test" к токену
+    let token = if config.telegram_test_env {
+        info!("Используется тестовое окружение Telegram");
+        format!("{}/test", token)
+    } else {
+        token
+    };
+
+    let bot = Bot::new(token);
+
+    match config.telegram_api_url.as_deref().map(reqwest::Url::parse) {
+        Some(Ok(url)) => {
+            info!("Используется адрес Bot API: {}", url);
+            bot.set_api_url(url)
+        }
+        Some(Err(e)) => {
+            error!("Некорректный TELEGRAM_API_URL, используется адрес по умолчанию: {}", e);
+            bot
+        }
+        None => bot,
+    }
+}
+
+// Удаляем webhook перед запуском бота, чтобы избежать конфликта с getUpdates
+async fn delete_webhook(bot: &Bot) {
+    let mut webhook_deleted = false;
+    let max_attempts = 3;
+    let mut attempt = 0;
+
+    while !webhook_deleted && attempt < max_attempts {
+        attempt += 1;
+        info!("Попытка {} из {}: удаление webhook", attempt, max_attempts);
+
+        match bot.delete_webhook().await {
+            Ok(_) => {
+                info!("Webhook успешно удален");
+                webhook_deleted = true;
+            }
+            Err(e) => {
+                error!("Ошибка при удалении webhook (попытка {}/{}): {}", attempt, max_attempts, e);
+                if attempt < max_attempts {
+                    info!("Ожидание перед следующей попыткой...");
+                    sleep(Duration::from_secs(2));
+                } else {
+                    error!("Достигнуто максимальное количество попыток удаления webhook");
+                }
+            }
+        }
+    }
+
+    if !webhook_deleted {
+        error!("Не удалось удалить webhook после нескольких попыток. Бот может не работать корректно!");
+    } else {
+        // Добавляем небольшую задержку после успешного удаления webhook
+        info!("Ожидание 2 секунды после удаления webhook перед запуском бота...");
+        sleep(Duration::from_secs(2));
+    }
+}
+
+// Принудительно устанавливаем команды в меню бота и проверяем результат
+async fn set_commands(bot: &Bot, plugins: &PluginRegistry, capabilities: Capabilities) {
+    info!("Настраиваю командную панель бота...");
+
+    // Создаем список команд вручную для гарантированной поддержки
+    let mut commands = vec![
+        BotCommand::new("start", "начать работу с ботом"),
+        BotCommand::new("help", "показать список команд"),
+        BotCommand::new("city", "установить город (например, /city Москва)"),
+        BotCommand::new("cities", "несколько городов в одном прогнозе"),
+        BotCommand::new("time", "установить время уведомлений (например, /time 08:00)"),
+        BotCommand::new("weather", "узнать текущую погоду"),
+        BotCommand::new("forecast", "прогноз погоды на неделю (table - таблицей)"),
+        BotCommand::new("card", "текущая погода картинкой"),
+        BotCommand::new("comparechart", "сравнить температуру в недавних городах на графике"),
+        BotCommand::new("records", "самый теплый и самый холодный день в городе"),
+        BotCommand::new("travel", "временно сменить город на время поездки"),
+        BotCommand::new("updates", "сообщать, если прогноз на день изменился"),
+        BotCommand::new("smarttime", "присылать прогноз раньше, если ожидается непогода"),
+        BotCommand::new("every", "прогноз каждые N часов в рабочее время"),
+        BotCommand::new("nightmode", "вечером присылать прогноз на завтра"),
+        BotCommand::new("sections", "выбрать разделы отчета о погоде"),
+        BotCommand::new("style", "обычный или компактный отчет о погоде"),
+        BotCommand::new("precision", "целые градусы или с десятыми"),
+        BotCommand::new("locale", "формат дат и времени в отчетах"),
+        BotCommand::new("settings", "все настройки на одном экране"),
+        BotCommand::new("privacy", "что бот хранит обо мне"),
+        BotCommand::new("export", "прислать все мои данные JSON-файлом"),
+        BotCommand::new("delete_me", "удалить все мои данные"),
+        BotCommand::new("history", "последние уведомления и доставлены ли они"),
+        BotCommand::new("transfer", "перенести настройки в другой аккаунт"),
+        BotCommand::new("household", "общий утренний прогноз для семьи"),
+    ];
+    // Команды, которые текущий источник погоды не может обслужить, в меню не показываем
+    commands.retain(|command| capabilities::supports_command(capabilities, &command.command));
+    commands.extend(plugins.bot_commands());
+
+    // Устанавливаем команды для всех чатов
+    match bot.set_my_commands(commands).await {
+        Ok(_) => info!("Командная панель бота успешно обновлена"),
+        Err(e) => error!("Не удалось установить команды бота: {}", e),
+    }
+}
+
+// Периодическое удаление webhook
+async fn start_webhook_cleaner(bot: Bot) {
+    info!("Запуск планировщика периодической очистки webhook");
+    let mut interval = tokio::time::interval(Duration::from_secs(60)); // Интервал 1 минута
+
+    loop {
+        interval.tick().await;
+        info!("Выполняю периодическую очистку webhook...");
+
+        match bot.delete_webhook().await {
+            Ok(_) => info!("Webhook успешно удален по расписанию"),
+            Err(e) => error!("Ошибка при периодическом удалении webhook: {}", e),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::alerts;
+use crate::config::{Config, StorageBackend};
+use crate::encryption::StorageCipher;
+use crate::storage::UserStorage;
+use chrono::Local;
+use log::{error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{self, Duration};
+
+// Имена копий: users-20250101-080000.json, по имени копии сортируются по времени
+const FILE_PREFIX: &str = "users-";
+const FILE_SUFFIX: &str = ".json";
+
+// Фоновая задача: раз в BACKUP_INTERVAL_HOURS часов сохраняет копию всех записей в BACKUP_DIR
+// и оставляет BACKUP_KEEP последних копий. Первая копия делается сразу после запуска
+pub async fn start_backup_job(storage: Arc<dyn UserStorage>, config: Arc<Config>) {
+    let hours = match config.backup_interval_hours {
+        Some(hours) if config.storage_backend != StorageBackend::Memory => hours,
+        // В режиме «ничего не сохраняем» копии на диске противоречили бы его смыслу
+        _ => {
+            info!("Резервное копирование хранилища отключено");
+            return std::future::pending().await;
+        }
+    };
+
+    info!(
+        "Резервные копии хранилища: каждые {} ч в {}, хранится {}",
+        hours, config.backup_dir, config.backup_keep
+    );
+    // Хранилище с неверным ключом не открылось бы, так что здесь ключ уже проверен
+    let cipher = match config.storage_key.as_deref().map(StorageCipher::new).transpose() {
+        Ok(cipher) => cipher,
+        Err(e) => {
+            error!("Резервное копирование хранилища отключено: {}", e);
+            return std::future::pending().await;
+        }
+    };
+    let mut interval = time::interval(Duration::from_secs(hours as u64 * 60 * 60));
+
+    loop {
+        interval.tick().await;
+        match run_backup(&*storage, Path::new(&config.backup_dir), config.backup_keep, cipher.as_ref()).await {
+            Ok(path) => info!("Резервная копия хранилища сохранена: {}", path.display()),
+            Err(e) => {
+                error!("Не удалось сделать резервную копию хранилища: {}", e);
+                alerts::critical("backup", format!("не удалось сделать резервную копию хранилища: {}", e));
+            }
+        }
+    }
+}
+
+// Сохраняет копию и удаляет самые старые, оставляя keep последних.
+// С ключом копия шифруется так же, как файл хранилища, и восстанавливается простым копированием
+async fn run_backup(storage: &dyn UserStorage, dir: &Path, keep: usize, cipher: Option<&StorageCipher>) -> Result<PathBuf, String> {
+    // Записи берем из хранилища, а не копируем файл: так в копию попадают и изменения
+    // из журнала, и копия одинаково читается для JSON и JSONL
+    let users = storage.get_all_users().await.map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&users).map_err(|e| e.to_string())?;
+    let json = match cipher {
+        Some(cipher) => cipher.seal(&json)?,
+        None => json,
+    };
+
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let name = format!("{}{}{}", FILE_PREFIX, Local::now().format("%Y%m%d-%H%M%S"), FILE_SUFFIX);
+    let path = dir.join(name);
+    // Через временный файл, чтобы оборванная запись не выглядела как целая копия
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json)
+        .and_then(|_| fs::rename(&tmp_path, &path))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    rotate(dir, keep);
+    Ok(path)
+}
+
+// Удаляет лишние копии; чужие файлы в каталоге не трогает
+fn rotate(dir: &Path, keep: usize) {
+    let mut backups: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+            })
+            .collect(),
+        Err(e) => {
+            warn!("Не удалось прочитать каталог резервных копий {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    for path in backups.into_iter().take(excess) {
+        match fs::remove_file(&path) {
+            Ok(()) => info!("Удалена старая резервная копия {}", path.display()),
+            Err(e) => warn!("Не удалось удалить старую резервную копию {}: {}", path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::{DateTime, Local};
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use teloxide::prelude::*;
+use teloxide::{ApiError, RequestError};
+
+// Сколько самых частых видов ошибок показывать в сводке
+const TOP_ERRORS: usize = 3;
+
+// Итог доставки одного уведомления
+pub enum DeliveryOutcome {
+    Sent,
+    // Пользователь заблокировал бота или удалил аккаунт
+    Blocked,
+    // Вид ошибки для сводки
+    Failed(String),
+}
+
+impl DeliveryOutcome {
+    // Разбирает ошибку Bot API: блокировки считаем отдельно, остальное группируем по виду
+    pub fn from_request_error(e: &RequestError) -> Self {
+        match e {
+            RequestError::Api(ApiError::BotBlocked | ApiError::UserDeactivated | ApiError::BotKicked) => {
+                DeliveryOutcome::Blocked
+            }
+            RequestError::Api(api_error) => DeliveryOutcome::Failed(format!("Bot API: {}", api_error)),
+            RequestError::RetryAfter(_) => DeliveryOutcome::Failed("превышен лимит Telegram".to_string()),
+            RequestError::Network(_) => DeliveryOutcome::Failed("сетевая ошибка".to_string()),
+            _ => DeliveryOutcome::Failed("прочие ошибки Telegram".to_string()),
+        }
+    }
+}
+
+// Сводка по одной рассылке: сколько доставлено, сколько заблокировали бота и какие были ошибки
+#[derive(Debug, Clone)]
+pub struct BroadcastSummary {
+    label: String,
+    finished_at: Option<DateTime<Local>>,
+    sent: usize,
+    blocked: usize,
+    failed: usize,
+    errors: HashMap<String, usize>,
+}
+
+impl BroadcastSummary {
+    pub fn new(label: impl Into<String>) -> Self {
+        BroadcastSummary {
+            label: label.into(),
+            finished_at: None,
+            sent: 0,
+            blocked: 0,
+            failed: 0,
+            errors: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, outcome: DeliveryOutcome) {
+        match outcome {
+            DeliveryOutcome::Sent => self.sent += 1,
+            DeliveryOutcome::Blocked => self.blocked += 1,
+            DeliveryOutcome::Failed(kind) => {
+                self.failed += 1;
+                *self.errors.entry(kind).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sent + self.blocked + self.failed == 0
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.blocked + self.failed > 0
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "📬 Рассылка: {}{}\n\nДоставлено: {}\nЗаблокировали бота: {}\nОшибок: {}",
+            self.label,
+            self.finished_at
+                .map(|t| format!(" ({})", t.format("%d.%m %H:%M")))
+                .unwrap_or_default(),
+            self.sent,
+            self.blocked,
+            self.failed
+        );
+
+        let mut errors: Vec<(&String, &usize)> = self.errors.iter().collect();
+        errors.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        if !errors.is_empty() {
+            text.push_str("\n\nЧастые ошибки:");
+            for (kind, count) in errors.into_iter().take(TOP_ERRORS) {
+                text.push_str(&format!("\n• {} - {}", kind, count));
+            }
+        }
+        text
+    }
+}
+
+// Последняя рассылка с доставками, для /admin stats
+static LAST_SUMMARY: LazyLock<Mutex<Option<BroadcastSummary>>> = LazyLock::new(|| Mutex::new(None));
+
+pub fn last_summary() -> Option<BroadcastSummary> {
+    LAST_SUMMARY.lock().unwrap().clone()
+}
+
+// Сохраняет сводку и отправляет ее администраторам. notify_admins = false - только сохранить
+pub async fn finish(bot: &Bot, admin_ids: &[i64], mut summary: BroadcastSummary, notify_admins: bool) {
+    if summary.is_empty() {
+        return;
+    }
+    summary.finished_at = Some(Local::now());
+    info!(
+        "Итог рассылки {}: доставлено {}, заблокировали {}, ошибок {}",
+        summary.label, summary.sent, summary.blocked, summary.failed
+    );
+
+    let text = summary.render();
+    *LAST_SUMMARY.lock().unwrap() = Some(summary);
+
+    if !notify_admins {
+        return;
+    }
+    for admin_id in admin_ids {
+        if let Err(e) = bot.send_message(ChatId(*admin_id), text.clone()).await {
+            error!("Не удалось отправить сводку рассылки администратору {}: {}", admin_id, e);
+        }
+    }
+}
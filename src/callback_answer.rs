@@ -0,0 +1,56 @@
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use teloxide::prelude::*;
+
+// Ответ на нажатие инлайн-кнопки. Пока бот не ответил, Telegram крутит на кнопке "часики",
+// поэтому роутер колбэков после обработки отвечает сам, если обработчик этого не сделал.
+// Отвечать можно один раз: повторные вызовы ничего не делают
+pub struct CallbackAnswer {
+    bot: Bot,
+    callback_id: String,
+    answered: AtomicBool,
+}
+
+impl CallbackAnswer {
+    pub fn new(bot: &Bot, callback_id: &str) -> Self {
+        CallbackAnswer {
+            bot: bot.clone(),
+            callback_id: callback_id.to_string(),
+            answered: AtomicBool::new(false),
+        }
+    }
+
+    // Короткое всплывающее уведомление: "Город сохранен ✅"
+    pub async fn toast(&self, text: impl Into<String>) -> ResponseResult<()> {
+        self.send(Some(text.into()), false).await
+    }
+
+    // Окно с кнопкой OK - для того, что пользователь не должен пропустить
+    pub async fn alert(&self, text: impl Into<String>) -> ResponseResult<()> {
+        self.send(Some(text.into()), true).await
+    }
+
+    // Ответ без текста: просто убрать "часики"
+    pub async fn ack(&self) -> ResponseResult<()> {
+        self.send(None, false).await
+    }
+
+    // Вызывается роутером после обработки нажатия, даже если обработчик упал или не уложился во время
+    pub async fn finish(&self) {
+        if let Err(e) = self.ack().await {
+            warn!("Не удалось ответить на нажатие кнопки {}: {}", self.callback_id, e);
+        }
+    }
+
+    async fn send(&self, text: Option<String>, show_alert: bool) -> ResponseResult<()> {
+        if self.answered.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut request = self.bot.answer_callback_query(&self.callback_id);
+        if let Some(text) = text {
+            request = request.text(text).show_alert(show_alert);
+        }
+        request.await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use bitflags::bitflags;
+
+bitflags! {
+    // Данные, которые умеет отдавать источник погоды
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Capabilities: u32 {
+        // Текущая погода
+        const CURRENT = 1;
+        // Прогноз на ближайшие дни по 3-часовым интервалам
+        const FORECAST = 1 << 1;
+        // Качество воздуха
+        const AIR_QUALITY = 1 << 2;
+        // УФ-индекс
+        const UV_INDEX = 1 << 3;
+        // Официальные штормовые предупреждения
+        const ALERTS = 1 << 4;
+    }
+}
+
+// Что отдают бесплатные API OpenWeather 2.5, которыми пользуется бот
+pub const OPENWEATHER: Capabilities = Capabilities::CURRENT.union(Capabilities::FORECAST);
+
+// Команды, которым нужны данные сверх базовых; остальные работают с любым источником
+const COMMAND_REQUIREMENTS: &[(&str, Capabilities)] = &[
+    ("weather", Capabilities::CURRENT),
+    ("card", Capabilities::CURRENT),
+    ("every", Capabilities::CURRENT),
+    ("forecast", Capabilities::FORECAST),
+    ("comparechart", Capabilities::FORECAST),
+    ("updates", Capabilities::FORECAST),
+    ("smarttime", Capabilities::FORECAST),
+    ("nightmode", Capabilities::FORECAST),
+];
+
+// Может ли источник с такими возможностями обслужить команду (имя без "/")
+pub fn supports_command(available: Capabilities, command: &str) -> bool {
+    COMMAND_REQUIREMENTS
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(command))
+        .all(|(_, required)| available.contains(*required))
+}
+
+// Имя команды из текста сообщения: "/forecast@FerrisBot table" -> "forecast"
+pub fn command_name(text: &str) -> &str {
+    text.split_whitespace()
+        .next()
+        .unwrap_or("")
+        .trim_start_matches('/')
+        .split('@')
+        .next()
+        .unwrap_or("")
+}
@@ -0,0 +1,185 @@
+use crate::handlers::load_or_apologize;
+use crate::error_throttle;
+use crate::formatter;
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::utils;
+use crate::weather::{OpenWeatherResponse, WeatherClient};
+use crate::escape_markdown_v2;
+use chrono::Local;
+use log::{error, info, warn};
+use resvg::{tiny_skia, usvg};
+use std::sync::{Arc, LazyLock};
+use teloxide::prelude::*;
+use teloxide::types::{ChatAction, InputFile, ParseMode};
+
+// Размер карточки в пикселях
+const CARD_WIDTH: u32 = 800;
+const CARD_HEIGHT: u32 = 420;
+
+// Шаблон карточки: значения в фигурных скобках подставляются при отрисовке.
+// Эмодзи шрифты обычно не содержат, поэтому на картинке только текст
+const CARD_TEMPLATE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="800" height="420" viewBox="0 0 800 420">
+  <defs>
+    <linearGradient id="bg" x1="0" y1="0" x2="1" y2="1">
+      <stop offset="0" stop-color="{color_from}"/>
+      <stop offset="1" stop-color="{color_to}"/>
+    </linearGradient>
+  </defs>
+  <rect width="800" height="420" rx="32" fill="url(#bg)"/>
+  <g font-family="DejaVu Sans, Noto Sans, Arial, sans-serif" fill="#ffffff">
+    <text x="48" y="82" font-size="40" font-weight="bold">{city}</text>
+    <text x="48" y="118" font-size="22" fill-opacity="0.8">{date}</text>
+    <text x="40" y="260" font-size="120" font-weight="bold">{temp}</text>
+    <text x="430" y="196" font-size="32">{description}</text>
+    <text x="430" y="240" font-size="24" fill-opacity="0.85">Ощущается как {feels_like}</text>
+    <text x="430" y="274" font-size="24" fill-opacity="0.85">Мин {temp_min} / Макс {temp_max}</text>
+    <rect x="48" y="318" width="704" height="2" fill-opacity="0.3"/>
+    <text x="48" y="370" font-size="24">Ветер {wind} м/с</text>
+    <text x="280" y="370" font-size="24">Влажность {humidity}%</text>
+    <text x="530" y="370" font-size="24">Облачность {clouds}%</text>
+  </g>
+</svg>"##;
+
+// Системные шрифты загружаем один раз: это долго
+static FONTS: LazyLock<Arc<usvg::fontdb::Database>> = LazyLock::new(|| {
+    let mut fonts = usvg::fontdb::Database::new();
+    fonts.load_system_fonts();
+    if fonts.is_empty() {
+        warn!("Не найдено ни одного системного шрифта: текст на карточках погоды не будет отрисован");
+    }
+    Arc::new(fonts)
+});
+
+// Обработка /card: текущая погода картинкой, которую удобно переслать в другой чат
+pub async fn handle_card_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let Some(city) = user.city().map(str::to_string) else {
+        bot.send_message(msg.chat.id, templates::text("city.missing"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+
+    bot.send_chat_action(msg.chat.id, ChatAction::UploadPhoto).await?;
+
+    let data = match weather_client.fetch_current_weather(&city).await {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Ошибка получения погоды для карточки пользователя {}: {}", user_id, e);
+            return error_throttle::send_error(
+                bot,
+                msg.chat.id,
+                templates::render("weather.error", &[("error", &escape_markdown_v2(&e))]),
+            )
+            .await;
+        }
+    };
+
+    let caption = templates::render("card.caption", &[
+        ("emoji", formatter::get_weather_emoji(&data.weather[0].icon)),
+        ("city", &escape_markdown_v2(&utils::echo(&city))),
+        ("weather", &escape_markdown_v2(&format!(
+            "{}, {}",
+            formatter::capitalize_first_letter(&data.weather[0].description),
+            signed_temp(data.main.temp)
+        ))),
+    ]);
+
+    // Отрисовка занимает заметное время, не держим ею поток обработки сообщений
+    let svg = card_svg(&city, &data);
+    let png = match tokio::task::spawn_blocking(move || render_png(&svg, CARD_WIDTH, CARD_HEIGHT)).await {
+        Ok(Ok(png)) => png,
+        Ok(Err(e)) => {
+            error!("Не удалось отрисовать карточку погоды: {}", e);
+            return error_throttle::send_error(
+                bot,
+                msg.chat.id,
+                templates::render("weather.error", &[("error", &escape_markdown_v2(&e))]),
+            )
+            .await;
+        }
+        Err(e) => {
+            error!("Задача отрисовки карточки погоды завершилась с ошибкой: {}", e);
+            return Ok(());
+        }
+    };
+
+    bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("weather.png"))
+        .caption(caption)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    info!("Пользователю ID: {} отправлена карточка погоды для {}", user_id, city);
+    Ok(())
+}
+
+fn signed_temp(temp: f32) -> String {
+    format!("{:+}°", temp.round() as i32)
+}
+
+// Цвета фона: ночью темные, днем - от холодных к теплым в зависимости от температуры
+fn background(data: &OpenWeatherResponse) -> (&'static str, &'static str) {
+    if data.weather[0].icon.ends_with('n') {
+        ("#1f2a48", "#3b3f6b")
+    } else if data.main.temp < 0.0 {
+        ("#4a78c2", "#8fb8e8")
+    } else if data.main.temp < 15.0 {
+        ("#3f8fb5", "#7cc4c9")
+    } else if data.main.temp < 25.0 {
+        ("#f0a23b", "#f6c667")
+    } else {
+        ("#e0563b", "#f29b4b")
+    }
+}
+
+// Подставляет данные о погоде в шаблон карточки
+fn card_svg(city: &str, data: &OpenWeatherResponse) -> String {
+    let (color_from, color_to) = background(data);
+    let values = [
+        ("color_from", color_from.to_string()),
+        ("color_to", color_to.to_string()),
+        ("city", city.to_string()),
+        ("date", Local::now().format("%d.%m.%Y %H:%M").to_string()),
+        ("temp", signed_temp(data.main.temp)),
+        ("description", formatter::capitalize_first_letter(&data.weather[0].description)),
+        ("feels_like", signed_temp(data.main.feels_like)),
+        ("temp_min", signed_temp(data.main.temp_min)),
+        ("temp_max", signed_temp(data.main.temp_max)),
+        ("wind", format!("{:.0}", data.wind.speed)),
+        ("humidity", format!("{:.0}", data.main.humidity)),
+        ("clouds", data.clouds.all.to_string()),
+    ];
+
+    values.iter().fold(CARD_TEMPLATE.to_string(), |svg, (key, value)| {
+        svg.replace(&format!("{{{}}}", key), &escape_xml(value))
+    })
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Отрисовывает SVG в PNG указанного размера; используется и для графиков
+pub(crate) fn render_png(svg: &str, width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let options = usvg::Options {
+        fontdb: Arc::clone(&FONTS),
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| format!("ошибка шаблона изображения: {}", e))?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| "не удалось создать изображение".to_string())?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| format!("ошибка кодирования PNG: {}", e))
+}
@@ -0,0 +1,197 @@
+use crate::handlers::load_or_apologize;
+use crate::card::{escape_xml, render_png};
+use crate::error_throttle;
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::utils;
+use crate::weather::WeatherClient;
+use crate::escape_markdown_v2;
+use futures::future::join_all;
+use log::{error, info, warn};
+use std::fmt::Write;
+use teloxide::prelude::*;
+use teloxide::types::{ChatAction, InputFile, ParseMode};
+
+// Размер графика в пикселях
+const CHART_WIDTH: u32 = 800;
+const CHART_HEIGHT: u32 = 480;
+
+// Область построения внутри картинки
+const PLOT_LEFT: f32 = 70.0;
+const PLOT_RIGHT: f32 = 770.0;
+const PLOT_TOP: f32 = 80.0;
+const PLOT_BOTTOM: f32 = 380.0;
+
+// Цвета линий городов по порядку; городов не больше, чем недавних (RECENT_CITIES_LIMIT)
+const PALETTE: [&str; 5] = ["#e0563b", "#3f8fb5", "#4caf50", "#f0a23b", "#8e5bc2"];
+
+// Температурная кривая одного города: (время UTC, температура)
+struct Series {
+    city: String,
+    points: Vec<(i64, f32)>,
+}
+
+// Обработка /comparechart: температура на сутки во всех недавних городах на одном графике
+pub async fn handle_compare_chart_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let cities = user.recent_cities;
+    if cities.len() < 2 {
+        bot.send_message(msg.chat.id, templates::text("comparechart.few"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_chat_action(msg.chat.id, ChatAction::UploadPhoto).await?;
+
+    // Прогнозы всех городов запрашиваем одновременно
+    let results = join_all(cities.iter().map(|city| weather_client.get_day_temperatures(city))).await;
+    let mut series = Vec::new();
+    let mut failed = Vec::new();
+    for (city, result) in cities.into_iter().zip(results) {
+        match result {
+            Ok(points) if !points.is_empty() => series.push(Series { city, points }),
+            Ok(_) => failed.push(city),
+            Err(e) => {
+                warn!("Не удалось получить прогноз {} для графика пользователя {}: {}", city, user_id, e);
+                failed.push(city);
+            }
+        }
+    }
+
+    if series.is_empty() {
+        error!("Не удалось получить прогноз ни для одного города пользователя {}", user_id);
+        return error_throttle::send_error(
+            bot,
+            msg.chat.id,
+            templates::render("forecast.error", &[("error", &escape_markdown_v2("нет данных ни для одного города"))]),
+        )
+        .await;
+    }
+
+    let names: Vec<&str> = series.iter().map(|s| s.city.as_str()).collect();
+    let echoed: Vec<String> = names.iter().map(|name| utils::echo(name)).collect();
+    let mut caption = templates::render("comparechart.caption", &[("cities", &escape_markdown_v2(&echoed.join(", ")))]);
+    if !failed.is_empty() {
+        caption.push_str(&templates::render("comparechart.failed", &[
+            ("cities", &escape_markdown_v2(&failed.join(", "))),
+        ]));
+    }
+
+    // Отрисовка занимает заметное время, не держим ею поток обработки сообщений
+    let svg = chart_svg(&series);
+    let png = match tokio::task::spawn_blocking(move || render_png(&svg, CHART_WIDTH, CHART_HEIGHT)).await {
+        Ok(Ok(png)) => png,
+        Ok(Err(e)) => {
+            error!("Не удалось отрисовать график сравнения городов: {}", e);
+            return error_throttle::send_error(
+                bot,
+                msg.chat.id,
+                templates::render("forecast.error", &[("error", &escape_markdown_v2(&e))]),
+            )
+            .await;
+        }
+        Err(e) => {
+            error!("Задача отрисовки графика завершилась с ошибкой: {}", e);
+            return Ok(());
+        }
+    };
+
+    bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("compare.png"))
+        .caption(caption)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    info!("Пользователю ID: {} отправлен график сравнения городов: {}", user_id, names.join(", "));
+    Ok(())
+}
+
+// График: по горизонтали часы от первой точки прогноза, по вертикали температура
+fn chart_svg(series: &[Series]) -> String {
+    let start = series.iter().filter_map(|s| s.points.first()).map(|(dt, _)| *dt).min().unwrap_or(0);
+    let temps = series.iter().flat_map(|s| s.points.iter().map(|(_, temp)| *temp));
+    let (min, max) = temps.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), t| (min.min(t), max.max(t)));
+
+    // Шкала кратна 5 градусам и не короче 10 градусов, чтобы ровная погода не выглядела скачками
+    let mut low = (min / 5.0).floor() * 5.0;
+    let mut high = (max / 5.0).ceil() * 5.0;
+    if high - low < 10.0 {
+        let middle = ((low + high) / 10.0).round() * 5.0;
+        low = middle - 5.0;
+        high = middle + 5.0;
+    }
+    let step = if high - low > 40.0 { 10.0 } else { 5.0 };
+
+    let x = |dt: i64| PLOT_LEFT + (dt - start) as f32 / (24.0 * 3600.0) * (PLOT_RIGHT - PLOT_LEFT);
+    let y = |temp: f32| PLOT_BOTTOM - (temp - low) / (high - low) * (PLOT_BOTTOM - PLOT_TOP);
+
+    let mut svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">
+  <rect width="{w}" height="{h}" rx="24" fill="#ffffff"/>
+  <g font-family="DejaVu Sans, Noto Sans, Arial, sans-serif" fill="#333333">
+    <text x="{PLOT_LEFT}" y="48" font-size="26" font-weight="bold">Температура на сутки</text>
+"##,
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+    );
+
+    // Горизонтальная сетка с подписями температуры
+    let mut temp = low;
+    while temp <= high {
+        let _ = writeln!(
+            svg,
+            r##"    <line x1="{PLOT_LEFT}" y1="{y:.1}" x2="{PLOT_RIGHT}" y2="{y:.1}" stroke="#dddddd" stroke-width="1"/>
+    <text x="{lx}" y="{ty:.1}" font-size="16" text-anchor="end">{temp:+}°</text>"##,
+            y = y(temp),
+            lx = PLOT_LEFT - 10.0,
+            ty = y(temp) + 5.0,
+        );
+        temp += step;
+    }
+
+    // Подписи времени: каждые 6 часов от начала прогноза
+    for hours in (0..=24).step_by(6) {
+        let label = if hours == 0 { "сейчас".to_string() } else { format!("+{} ч", hours) };
+        let _ = writeln!(
+            svg,
+            r##"    <text x="{lx:.1}" y="{ly}" font-size="16" text-anchor="middle">{label}</text>"##,
+            lx = x(start + hours * 3600),
+            ly = PLOT_BOTTOM + 26.0,
+        );
+    }
+
+    // Кривые и легенда
+    for (index, s) in series.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        let points: Vec<String> = s.points
+            .iter()
+            .map(|(dt, temp)| format!("{:.1},{:.1}", x(*dt), y(*temp)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            r##"    <polyline points="{points}" fill="none" stroke="{color}" stroke-width="4" stroke-linejoin="round" stroke-linecap="round"/>"##,
+            points = points.join(" "),
+        );
+
+        let legend_x = PLOT_LEFT + (index % 3) as f32 * 230.0;
+        let legend_y = PLOT_BOTTOM + 60.0 + (index / 3) as f32 * 28.0;
+        let _ = writeln!(
+            svg,
+            r##"    <rect x="{legend_x}" y="{ry}" width="18" height="18" rx="4" fill="{color}"/>
+    <text x="{tx}" y="{legend_y}" font-size="18">{city}</text>"##,
+            ry = legend_y - 15.0,
+            tx = legend_x + 26.0,
+            city = escape_xml(&s.city),
+        );
+    }
+
+    svg.push_str("  </g>\n</svg>");
+    svg
+}
@@ -0,0 +1,109 @@
+use crate::config::Config;
+use crate::handlers::{confirm_saved, load_or_apologize};
+use crate::storage::{UserSettings, UserStorage, CITIES_LIMIT};
+use crate::weather::WeatherClient;
+use crate::{escape_markdown_v2, moderation, templates, timezone, utils};
+use log::info;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+enum Action {
+    Add,
+    Remove,
+    Main,
+}
+
+// Обработка /cities: список городов прогноза, add <город> - добавить, remove <город> - убрать,
+// main <город> - сделать основным. Основной город - первый в списке, его же задает /city
+pub async fn handle_cities_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    config: &Config,
+    args: &str,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let args = args.trim();
+    let (action, city_arg) = args
+        .split_once(char::is_whitespace)
+        .map(|(action, city)| (action, city.trim()))
+        .unwrap_or((args, ""));
+
+    let action = match action.to_lowercase().as_str() {
+        "" => return send(bot, msg.chat.id, render_list(&user)).await,
+        "add" | "добавить" => Action::Add,
+        "remove" | "убрать" | "удалить" => Action::Remove,
+        "main" | "основной" => Action::Main,
+        _ => return send(bot, msg.chat.id, templates::text("cities.usage")).await,
+    };
+    if city_arg.is_empty() {
+        return send(bot, msg.chat.id, templates::text("cities.usage")).await;
+    }
+    if city_arg.chars().count() > utils::MAX_CITY_LENGTH {
+        let text = templates::render("input.too_long", &[("max", &utils::MAX_CITY_LENGTH.to_string())]);
+        return send(bot, msg.chat.id, text).await;
+    }
+    // Недопустимое название не сохраняем и не повторяем в ответе, как и в /city
+    let Some(city) = moderation::clean_input(city_arg, &config.blocklist) else {
+        return send(bot, msg.chat.id, templates::text("input.blocked")).await;
+    };
+    if city.is_empty() {
+        return send(bot, msg.chat.id, templates::text("city.empty")).await;
+    }
+
+    let main_before = user.city().map(str::to_string);
+    let (key, changed) = match action {
+        Action::Add if user.add_city(&city) => ("cities.added", true),
+        Action::Add if user.cities.len() >= CITIES_LIMIT => ("cities.full", false),
+        Action::Add => ("cities.exists", false),
+        Action::Remove if user.remove_city(&city) => ("cities.removed", true),
+        Action::Remove => ("cities.unknown", false),
+        Action::Main => {
+            user.make_main_city(&city);
+            user.remember_city(&city);
+            ("cities.main", true)
+        }
+    };
+    let text = templates::render(key, &[
+        ("city", &escape_markdown_v2(&utils::echo(&city))),
+        ("max", &CITIES_LIMIT.to_string()),
+    ]);
+    if !changed {
+        return send(bot, msg.chat.id, text).await;
+    }
+
+    // Сменился основной город - по нему считается часовой пояс уведомлений
+    let main_after = user.city().map(str::to_string);
+    let timezone_notice = match &main_after {
+        Some(main) if main_after != main_before => timezone::infer_for_city(weather_client, &mut user, main).await,
+        _ => None,
+    };
+    let saved = storage.save_user(user).await;
+    if saved.is_ok() {
+        info!("Пользователь ID: {} изменил города прогноза: {}", user_id, city);
+    }
+    send(bot, msg.chat.id, confirm_saved(saved, text)).await?;
+    timezone::send_notice(bot, msg.chat.id, timezone_notice).await
+}
+
+fn render_list(user: &UserSettings) -> String {
+    if user.cities.is_empty() {
+        return templates::text("cities.empty");
+    }
+    let lines: Vec<String> = user
+        .cities
+        .iter()
+        .enumerate()
+        .map(|(index, city)| format!("{}. {}{}", index + 1, utils::echo(city), if index == 0 { " (основной)" } else { "" }))
+        .collect();
+    templates::render("cities.list", &[("cities", &escape_markdown_v2(&lines.join("\n")))])
+}
+
+async fn send(bot: &Bot, chat_id: ChatId, text: String) -> ResponseResult<()> {
+    bot.send_message(chat_id, text).parse_mode(ParseMode::MarkdownV2).await?;
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use crate::config::{Config, StorageBackend};
+use crate::storage::{self, JsonStorage, PgStorage, RedisStorage, SqliteStorage, UserStorage};
+use crate::storage_lock::{self, StorageLock};
+use crate::user_import::{self, ConflictStrategy};
+use crate::weather::WeatherClient;
+use crate::{timezone, utils};
+use chrono::Local;
+use clap::{Parser, Subcommand};
+
+// Аргументы командной строки: без подкоманды бот запускается как обычно
+#[derive(Parser)]
+#[command(name = "ferrisbot", about = "Telegram-бот с ежедневным прогнозом погоды", version)]
+pub struct Cli {
+    #[arg(long, help = "Проверить настройки из окружения и .env и выйти")]
+    pub check_config: bool,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    #[command(about = "Запустить бота (по умолчанию)")]
+    Serve,
+    #[command(about = "Прогнать сценарий через обработчики бота в тестовом окружении Telegram")]
+    Scenario { path: String },
+    #[command(about = "Просмотр и правка пользователей в хранилище без запуска бота")]
+    Users {
+        #[command(subcommand)]
+        command: UsersCommand,
+    },
+    #[command(about = "Сохранить копию хранилища (по умолчанию рядом с ним, с датой в имени)")]
+    Backup { path: Option<String> },
+    #[command(about = "Перенести хранилище в другой формат (формат определяется расширением .json/.jsonl/.db или адресом postgres://)")]
+    MigrateStorage { from: String, to: String },
+    #[command(about = "Импортировать пользователей из JSON, JSONL или CSV другого экземпляра бота")]
+    ImportUsers {
+        file: String,
+        #[arg(long, default_value = "merge", help = "keep, overwrite или merge")]
+        strategy: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum UsersCommand {
+    #[command(about = "Список пользователей")]
+    List,
+    #[command(about = "Все настройки пользователя в JSON")]
+    Show { user_id: i64 },
+    #[command(about = "Установить город пользователя")]
+    SetCity { user_id: i64, city: String },
+    #[command(about = "Установить время уведомлений (ЧЧ:ММ)")]
+    SetTime { user_id: i64, time: String },
+    #[command(about = "Удалить пользователя")]
+    Delete { user_id: i64 },
+}
+
+impl CliCommand {
+    // Команды, которым не нужны токены и подключение к Telegram
+    pub fn is_offline(&self) -> bool {
+        !matches!(self, CliCommand::Serve | CliCommand::Scenario { .. })
+    }
+}
+
+// Выполняет офлайн-команду и возвращает код завершения процесса
+pub async fn run_offline(command: CliCommand, config: &Config) -> i32 {
+    let result = match command {
+        CliCommand::Users { command } => run_users_command(command, config).await,
+        CliCommand::Backup { path } => backup(config, path).await,
+        CliCommand::MigrateStorage { from, to } => migrate(config, &from, &to).await,
+        CliCommand::ImportUsers { file, strategy } => import(config, &file, &strategy).await,
+        CliCommand::Serve | CliCommand::Scenario { .. } => Ok(()),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Ошибка: {}", e);
+            1
+        }
+    }
+}
+
+async fn run_users_command(command: UsersCommand, config: &Config) -> Result<(), String> {
+    let (_lock, storage) = open_storage(config, "команда users").await?;
+
+    match command {
+        UsersCommand::List => {
+            let users = storage.get_all_users().await.map_err(|e| e.to_string())?;
+            println!("{:<14} {:<24} {:<6} {:<8} последний визит", "user_id", "город", "время", "активен");
+            for user in &users {
+                println!(
+                    "{:<14} {:<24} {:<6} {:<8} {}",
+                    user.user_id,
+                    user.city().unwrap_or("-"),
+                    user.notification_time.as_deref().unwrap_or("-"),
+                    if user.active { "да" } else { "нет" },
+                    user.last_seen.map(|t| t.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|| "-".to_string()),
+                );
+            }
+            println!("Всего: {}", users.len());
+        }
+        UsersCommand::Show { user_id } => {
+            let user = storage.get_user(user_id).await.map_err(|e| e.to_string())?.ok_or_else(|| not_found(user_id))?;
+            println!("{}", serde_json::to_string_pretty(&user).map_err(|e| e.to_string())?);
+        }
+        UsersCommand::SetCity { user_id, city } => {
+            let mut user = storage.get_user(user_id).await.map_err(|e| e.to_string())?.ok_or_else(|| not_found(user_id))?;
+            let city = city.trim().to_string();
+            if city.is_empty() {
+                return Err("название города не может быть пустым".to_string());
+            }
+            user.remember_city(&city);
+            user.set_city(&city);
+            // Время уведомлений дальше считается по часам нового города
+            let offset = timezone::infer_silently(&weather_client(config), &mut user, &city).await;
+            storage.save_user(user).await.map_err(|e| e.to_string())?;
+            println!("Пользователю {} установлен город {}", user_id, city);
+            match offset {
+                Some(offset) => println!("Часовой пояс уведомлений: {}", timezone::format_offset(offset)),
+                None => println!("Часовой пояс города не определен, время уведомлений считается по прежнему поясу"),
+            }
+        }
+        UsersCommand::SetTime { user_id, time } => {
+            let mut user = storage.get_user(user_id).await.map_err(|e| e.to_string())?.ok_or_else(|| not_found(user_id))?;
+            let time = utils::normalize_time(&time)
+                .ok_or_else(|| format!("некорректное время {}, нужен формат ЧЧ:ММ", time))?;
+            user.notification_time = Some(time.clone());
+            user.delivery_window = None;
+            storage.save_user(user).await.map_err(|e| e.to_string())?;
+            println!("Пользователю {} установлено время уведомлений {}", user_id, time);
+        }
+        UsersCommand::Delete { user_id } => {
+            storage.get_user(user_id).await.map_err(|e| e.to_string())?.ok_or_else(|| not_found(user_id))?;
+            storage.delete_user(user_id).await.map_err(|e| e.to_string())?;
+            println!("Пользователь {} удален", user_id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn backup(config: &Config, path: Option<String>) -> Result<(), String> {
+    let target = path.unwrap_or_else(|| {
+        format!("{}.{}.bak", config.storage_path, Local::now().format("%Y%m%d-%H%M%S"))
+    });
+    match config.storage_backend {
+        // Базу копирует сама SQLite: простое копирование файла могло бы захватить недописанную транзакцию
+        StorageBackend::Sqlite => {
+            SqliteStorage::open(&config.storage_path, config.storage_key.as_deref()).await?.backup_to(&target).await?;
+        }
+        StorageBackend::Postgres => return Err("копию базы PostgreSQL делает pg_dump".to_string()),
+        StorageBackend::Redis => return Err("копию данных Redis делает сам сервер (BGSAVE)".to_string()),
+        _ => {
+            // Загрузка через хранилище применяет незавершенные изменения из журнала
+            JsonStorage::new(&config.storage_path, config.storage_key.as_deref()).await?;
+            std::fs::copy(&config.storage_path, &target).map_err(|e| format!("не удалось скопировать {}: {}", config.storage_path, e))?;
+        }
+    }
+    println!("Резервная копия сохранена: {}", target);
+    Ok(())
+}
+
+// Исходное хранилище не должно меняться во время переноса
+async fn migrate(config: &Config, from: &str, to: &str) -> Result<(), String> {
+    let _lock = if storage::is_postgres_url(from) {
+        None
+    } else {
+        Some(storage_lock::acquire(from, "команда migrate-storage")?)
+    };
+    let count = storage::migrate(from, to, config.storage_key.as_deref()).await?;
+    println!("Перенесено пользователей из {} в {}: {}", storage_label(from), storage_label(to), count);
+    Ok(())
+}
+
+async fn import(config: &Config, file: &str, strategy: &str) -> Result<(), String> {
+    let strategy = ConflictStrategy::parse(strategy).ok_or("правило конфликтов: keep, overwrite или merge")?;
+    let content = std::fs::read_to_string(file).map_err(|e| format!("не удалось прочитать {}: {}", file, e))?;
+    let users = user_import::parse_users(&content, file)?;
+
+    let (_lock, storage) = open_storage(config, "команда import-users").await?;
+    let summary = user_import::import_users(&*storage, &weather_client(config), users, strategy).await;
+    println!("{}", summary.render());
+    Ok(())
+}
+
+// Хранилище из настроек для офлайн-команды. Файловое блокируется на время команды: пока его
+// держит бот, правки затерлись бы его следующей записью. SQLite, PostgreSQL и Redis сами разруливают одновременную запись
+async fn open_storage(config: &Config, owner: &str) -> Result<(Option<StorageLock>, Box<dyn UserStorage>), String> {
+    let key = config.storage_key.as_deref();
+    match config.storage_backend {
+        StorageBackend::File => {
+            let lock = storage_lock::acquire(&config.storage_path, owner)?;
+            Ok((Some(lock), Box::new(JsonStorage::new(&config.storage_path, key).await?)))
+        }
+        StorageBackend::Sqlite => Ok((None, Box::new(SqliteStorage::open(&config.storage_path, key).await?))),
+        StorageBackend::Postgres => {
+            let url = config.database_url.as_deref().ok_or("не задан DATABASE_URL")?;
+            Ok((None, Box::new(PgStorage::connect(url).await?)))
+        }
+        StorageBackend::Redis => {
+            let url = config.redis_url.as_deref().ok_or("не задан REDIS_URL")?;
+            Ok((None, Box::new(RedisStorage::connect(url, &config.redis_prefix, config.dialogue_state_ttl()).await?)))
+        }
+        StorageBackend::Memory => Err("при STORAGE_BACKEND=memory настройки живут только в памяти запущенного бота".to_string()),
+    }
+}
+
+// Адрес PostgreSQL содержит пароль, в вывод он не попадает
+fn storage_label(path: &str) -> &str {
+    if storage::is_postgres_url(path) {
+        "PostgreSQL"
+    } else {
+        path
+    }
+}
+
+// Сервис погоды без Telegram: нужен только для часового пояса города
+fn weather_client(config: &Config) -> WeatherClient {
+    WeatherClient::new(config.openweather_api_keys.clone())
+}
+
+fn not_found(user_id: i64) -> String {
+    format!("пользователь {} не найден", user_id)
+}
@@ -0,0 +1,86 @@
+use crate::weather::ForecastResponse;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+// Сколько часов вперед сравниваем прогнозы: дальше расхождения обычны и мало о чем говорят
+const COMPARE_HOURS: i64 = 48;
+// Осадки в интервале считаем от 0.1 мм, как и в остальных отчетах
+const WET_THRESHOLD: f32 = 0.1;
+// Сколько интервалов должны "передумать" насчет осадков, чтобы предупредить
+const MIN_RAIN_FLIPS: usize = 2;
+// Среднее расхождение температуры между выпусками, °C, с которого прогноз считаем неуверенным
+const TEMP_SPREAD_THRESHOLD: f32 = 3.0;
+
+// Один интервал прогноза: время, температура и ожидаются ли осадки
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    dt: i64,
+    temp: f32,
+    wet: bool,
+}
+
+// Два последних различающихся выпуска прогноза по городу. Одинаковые ответы подряд -
+// это тот же выпуск из кэша сервиса, он предыдущий не вытесняет
+#[derive(Debug, Default)]
+struct Runs {
+    previous: Option<Vec<Slot>>,
+    latest: Vec<Slot>,
+}
+
+static RUNS: LazyLock<Mutex<HashMap<String, Runs>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Запоминает свежий прогноз по городу для сравнения с предыдущим выпуском
+pub fn observe(city: &str, forecast: &ForecastResponse) {
+    let slots: Vec<Slot> = forecast
+        .list
+        .iter()
+        .map(|item| Slot {
+            dt: item.dt,
+            temp: item.main.temp,
+            wet: item.precipitation() >= WET_THRESHOLD,
+        })
+        .collect();
+
+    let mut runs = RUNS.lock().unwrap();
+    let entry = runs.entry(city.trim().to_lowercase()).or_default();
+    let changed = overlap(&entry.latest, &slots).any(|(old, new)| old != new);
+    if changed {
+        entry.previous = Some(std::mem::replace(&mut entry.latest, slots));
+    } else if entry.latest.is_empty() {
+        entry.latest = slots;
+    }
+}
+
+// Подсказка о неуверенном прогнозе: последние выпуски расходятся насчет осадков или температуры
+// на ближайшие 48 часов. None - выпуски согласны или сравнивать пока не с чем
+pub fn hint(city: &str) -> Option<String> {
+    let runs = RUNS.lock().unwrap();
+    let entry = runs.get(&city.trim().to_lowercase())?;
+    let previous = entry.previous.as_ref()?;
+
+    let horizon = Utc::now().timestamp() + COMPARE_HOURS * 3600;
+    let pairs: Vec<(&Slot, &Slot)> = overlap(previous, &entry.latest).filter(|(_, new)| new.dt <= horizon).collect();
+    if pairs.is_empty() {
+        return None;
+    }
+
+    let rain_flips = pairs.iter().filter(|(old, new)| old.wet != new.wet).count();
+    let temp_spread = pairs.iter().map(|(old, new)| (old.temp - new.temp).abs()).sum::<f32>() / pairs.len() as f32;
+
+    if rain_flips >= MIN_RAIN_FLIPS {
+        Some("🎲 Прогноз осадков может измениться: последние обновления прогноза расходятся".to_string())
+    } else if temp_spread >= TEMP_SPREAD_THRESHOLD {
+        Some(format!(
+            "🎲 Прогноз может измениться: температура в последних обновлениях расходится в среднем на {}°",
+            temp_spread.round() as i32
+        ))
+    } else {
+        None
+    }
+}
+
+// Пары интервалов с одинаковым временем из двух выпусков
+fn overlap<'a>(old: &'a [Slot], new: &'a [Slot]) -> impl Iterator<Item = (&'a Slot, &'a Slot)> {
+    new.iter().filter_map(move |slot| old.iter().find(|o| o.dt == slot.dt).map(|o| (o, slot)))
+}
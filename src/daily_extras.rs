@@ -0,0 +1,149 @@
+use crate::plugins::{CommandPlugin, PluginContext, PluginFuture};
+use crate::storage::UserSettings;
+use crate::templates;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{Datelike, NaiveDate};
+use log::info;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Встроенные цитаты для DAILY_EXTRAS_SOURCE=quotes
+const QUOTES: &[&str] = &[
+    "«Не бывает плохой погоды, бывает неподходящая одежда» - скандинавская пословица",
+    "«После дождя всегда выходит солнце» - народная мудрость",
+    "«Счастье - это когда тебя понимают» - Г. Полонский",
+    "«Дорогу осилит идущий» - пословица",
+    "«Улыбка - кратчайшее расстояние между людьми» - В. Борге",
+    "«Каждый день - маленькая жизнь» - А. Шопенгауэр",
+    "«Делай, что можешь, с тем, что имеешь, там, где ты есть» - Т. Рузвельт",
+    "«Лучшее время, чтобы посадить дерево, было 20 лет назад. Следующее лучшее время - сегодня» - китайская пословица",
+    "«Радость - это тоже погода, которую мы носим с собой» - народная мудрость",
+    "«Кто рано встает, тому Бог подает» - пословица",
+];
+
+// Встроенные предсказания для DAILY_EXTRAS_SOURCE=horoscope
+const HOROSCOPE: &[&str] = &[
+    "Звезды советуют начать день с любимого напитка и не торопиться",
+    "Сегодня удачный день для новых знакомств и смелых решений",
+    "Звезды обещают приятный сюрприз ближе к вечеру",
+    "Хороший день, чтобы закончить то, что давно откладывалось",
+    "Звезды рекомендуют больше гулять и меньше волноваться",
+    "Сегодня вас ждет теплый разговор с близким человеком",
+    "День благоприятен для творчества: доверьтесь своим идеям",
+    "Звезды советуют побаловать себя чем-нибудь вкусным",
+];
+
+// Откуда брать строки для утренних сообщений
+enum Source {
+    Quotes,
+    Horoscope,
+    // Свой файл: одна цитата на строку
+    Lines(Vec<String>),
+}
+
+static SOURCE: OnceLock<Source> = OnceLock::new();
+
+impl Source {
+    fn parse(source: &str) -> Result<Source, String> {
+        match source.trim().to_lowercase().as_str() {
+            "quotes" => Ok(Source::Quotes),
+            "horoscope" => Ok(Source::Horoscope),
+            _ => {
+                let content = fs::read_to_string(Path::new(source.trim()))
+                    .map_err(|e| format!("не удалось прочитать {}: {}", source, e))?;
+                let lines: Vec<String> = content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if lines.is_empty() {
+                    return Err(format!("в файле {} нет ни одной строки", source));
+                }
+                Ok(Source::Lines(lines))
+            }
+        }
+    }
+
+    // Строка на день: цитата одна на всех, предсказание свое для каждого пользователя
+    fn line(&self, user_id: i64, date: NaiveDate) -> String {
+        let day = date.num_days_from_ce() as u64;
+        match self {
+            Source::Quotes => format!("💬 {}", QUOTES[(day % QUOTES.len() as u64) as usize]),
+            Source::Horoscope => {
+                let index = (day + user_id.unsigned_abs()) % HOROSCOPE.len() as u64;
+                format!("🔮 {}", HOROSCOPE[index as usize])
+            }
+            Source::Lines(lines) => format!("💬 {}", lines[(day % lines.len() as u64) as usize]),
+        }
+    }
+}
+
+// Проверяет источник из DAILY_EXTRAS_SOURCE: quotes, horoscope или путь к файлу
+pub fn check_source(source: &str) -> Result<(), String> {
+    Source::parse(source).map(|_| ())
+}
+
+// Включает дополнения с указанным источником; повторный вызов ничего не меняет
+pub fn init(source: &str) -> Result<(), String> {
+    let source = Source::parse(source)?;
+    let _ = SOURCE.set(source);
+    Ok(())
+}
+
+// Строка для утреннего сообщения пользователя или None, если дополнения выключены
+pub fn line_for(user: &UserSettings, date: NaiveDate) -> Option<String> {
+    if !user.daily_extras {
+        return None;
+    }
+    SOURCE.get().map(|source| source.line(user.user_id, date))
+}
+
+// Плагин /extras on|off: цитата или гороскоп в утренних сообщениях милого режима
+pub struct DailyExtrasPlugin;
+
+impl CommandPlugin for DailyExtrasPlugin {
+    fn command(&self) -> &str {
+        "extras"
+    }
+
+    fn description(&self) -> &str {
+        "цитата или гороскоп в утреннем сообщении (/extras on или off)"
+    }
+
+    fn handle<'a>(&'a self, ctx: PluginContext<'a>, args: &'a str) -> PluginFuture<'a> {
+        Box::pin(async move {
+            let user_id = ctx.msg.chat.id.0;
+            let Some(mut user) = load_or_apologize(ctx.bot, ctx.msg.chat.id, ctx.storage, user_id).await? else {
+                return Ok(());
+            };
+
+            let enabled = match args.trim().to_lowercase().as_str() {
+                "on" | "вкл" => Some(true),
+                "off" | "выкл" => Some(false),
+                _ => None,
+            };
+
+            let response = match enabled {
+                Some(enabled) => {
+                    user.daily_extras = enabled;
+                    let saved = ctx.storage.save_user(user).await;
+                    info!("Пользователь ID: {} {} утренние дополнения", user_id, if enabled { "включил" } else { "выключил" });
+                    confirm_saved(saved, templates::text(if enabled { "extras.on" } else { "extras.off" }))
+                }
+                None => templates::render("extras.usage", &[
+                    ("status", if user.daily_extras { "включены" } else { "выключены" }),
+                ]),
+            };
+
+            ctx.bot.send_message(ctx.msg.chat.id, response)
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+            Ok(())
+        })
+    }
+}
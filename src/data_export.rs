@@ -0,0 +1,79 @@
+use crate::delivery_history::{DeliveryHistory, DeliveryRecord};
+use crate::storage::{UserSettings, UserStorage};
+use crate::templates;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, ParseMode};
+
+// Метка формата выгрузки: в отличие от файла настроек, обратно не импортируется
+const FORMAT: &str = "ferrisbot-data-export";
+
+// Все, что бот хранит о пользователе: запись целиком, включая служебные поля,
+// семьи других пользователей, в которых состоит этот чат, и история доставки уведомлений
+#[derive(Debug, Serialize)]
+struct DataExport {
+    format: &'static str,
+    exported_at: DateTime<Utc>,
+    user: Option<UserSettings>,
+    household_memberships: Vec<Membership>,
+    deliveries: Vec<DeliveryRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct Membership {
+    owner_id: i64,
+    name: String,
+}
+
+// Обработка /export: прислать JSON-файл со всеми данными пользователя
+pub async fn handle_export_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    history: &DeliveryHistory,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let (user, owners) = match (storage.get_user(user_id).await, storage.get_all_users().await) {
+        (Ok(user), Ok(owners)) => (user, owners),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("Не удалось выгрузить данные пользователя {}: {}", user_id, e);
+            bot.send_message(msg.chat.id, templates::text("storage.read_failed"))
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+            return Ok(());
+        }
+    };
+    let household_memberships = owners
+        .into_iter()
+        .filter_map(|owner| {
+            let member = owner.household.members.iter().find(|member| member.chat_id == user_id)?;
+            Some(Membership { owner_id: owner.user_id, name: member.name.clone() })
+        })
+        .collect();
+
+    let stored = user.is_some();
+    let export = DataExport {
+        format: FORMAT,
+        exported_at: Utc::now(),
+        user,
+        household_memberships,
+        deliveries: history.recent(user_id, usize::MAX),
+    };
+    let json = match serde_json::to_vec_pretty(&export) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("Не удалось сериализовать данные пользователя {}: {}", user_id, e);
+            return Ok(());
+        }
+    };
+
+    let caption = if stored { "export.sent" } else { "export.empty" };
+    bot.send_document(msg.chat.id, InputFile::memory(json).file_name(format!("ferrisbot-data-{}.json", user_id)))
+        .caption(templates::text(caption))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    info!("Пользователь ID: {} выгрузил свои данные", user_id);
+    Ok(())
+}
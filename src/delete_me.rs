@@ -0,0 +1,78 @@
+use crate::activity;
+use crate::metrics::metrics;
+use crate::outbox::Outbox;
+use crate::storage::{StorageError, UserStorage};
+use crate::templates;
+use crate::utils;
+use log::{info, warn};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+// Префикс данных кнопок подтверждения удаления
+pub const CALLBACK_PREFIX: &str = "delete_me_";
+
+// Обработка /delete_me: удаление только после подтверждения кнопкой
+pub async fn handle_delete_me_command(bot: &Bot, msg: &Message) -> ResponseResult<()> {
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🗑 Удалить все", format!("{}confirm", CALLBACK_PREFIX)),
+        InlineKeyboardButton::callback("❌ Отмена", format!("{}cancel", CALLBACK_PREFIX)),
+    ]]);
+    bot.send_message(msg.chat.id, templates::text("delete_me.confirm"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+// Нажатие на кнопку подтверждения: confirm - удалить данные, cancel - оставить как есть
+pub async fn handle_callback(
+    bot: &Bot,
+    message: Option<&Message>,
+    storage: &dyn UserStorage,
+    outbox: &Outbox,
+    action: &str,
+) -> ResponseResult<()> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+
+    let text = match action {
+        "confirm" => match erase(storage, outbox, chat_id.0).await {
+            Ok(()) => {
+                info!("Пользователь ID: {} удалил все свои данные", chat_id);
+                templates::text("delete_me.done")
+            }
+            Err(e) => {
+                warn!("Не удалось удалить данные пользователя ID: {}: {}", chat_id, e);
+                templates::text("delete_me.failed")
+            }
+        },
+        "cancel" => templates::text("delete_me.cancelled"),
+        _ => return Ok(()),
+    };
+
+    utils::edit_or_resend(bot, chat_id, Some(message.id), text).await
+}
+
+// Удаляет запись пользователя, его участие в чужих семьях, неотправленные уведомления,
+// историю доставки и замеры доставки. Запланированные уведомления строятся по записям хранилища,
+// поэтому после удаления записи новых не будет
+async fn erase(storage: &dyn UserStorage, outbox: &Outbox, user_id: i64) -> Result<(), StorageError> {
+    for mut owner in storage.get_all_users().await? {
+        if owner.household.contains(user_id) {
+            owner.household.members.retain(|member| member.chat_id != user_id);
+            storage.save_user(owner).await?;
+        }
+    }
+    storage.delete_user(user_id).await?;
+
+    let cancelled = outbox.cancel_chat(user_id).await;
+    if cancelled > 0 {
+        info!("Отменено неотправленных уведомлений пользователя ID: {}: {}", user_id, cancelled);
+    }
+    outbox.history().forget(user_id);
+    activity::forget(user_id);
+    metrics().forget_user(user_id);
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use crate::storage::UserStorage;
+use crate::{escape_markdown_v2, templates, timezone};
+use chrono::{DateTime, Duration, FixedOffset, Local, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Сколько последних записей храним по каждому чату
+const MAX_RECORDS_PER_CHAT: usize = 30;
+// Записи старше этого срока удаляются, в том числе записи удаленных пользователей
+const KEEP_DAYS: i64 = 30;
+// Сколько записей показывает /history
+const HISTORY_LIMIT: usize = 10;
+
+// Вид запланированного уведомления
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    // В выбранное пользователем время
+    Daily,
+    // Прогноз каждые N часов (/every)
+    Interval,
+    // Раньше обычного из-за непогоды (/smarttime)
+    Early,
+    // Дневная и вечерняя рассылка всем
+    Mass,
+}
+
+impl NotificationKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            NotificationKind::Daily => "ежедневное",
+            NotificationKind::Interval => "по интервалу",
+            NotificationKind::Early => "заранее из-за непогоды",
+            NotificationKind::Mass => "дневная или вечерняя рассылка",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Queued,
+    Sent,
+    Failed,
+}
+
+// Одно запланированное уведомление одному чату
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    pub chat_id: i64,
+    pub kind: NotificationKind,
+    // Слот расписания, к которому относится уведомление
+    pub slot: DateTime<Utc>,
+    pub status: DeliveryStatus,
+    // Когда статус поменялся последний раз
+    pub at: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+// История доставки запланированных уведомлений. Записи живут в памяти и хранятся вместе
+// с настройками (UserStorage), поэтому после перезапуска планировщик видит, что уведомление
+// этого слота уже было поставлено в очередь. Изменения копятся и записываются пачкой (flush)
+pub struct DeliveryHistory {
+    state: Mutex<HistoryState>,
+}
+
+struct HistoryState {
+    records: Vec<DeliveryRecord>,
+    // Чаты, история которых изменилась с последней записи в хранилище
+    changed: HashSet<i64>,
+}
+
+impl DeliveryHistory {
+    // Загружает историю из хранилища. Нечитаемая история не мешает работе бота:
+    // хранилище уже сохранило копию поврежденных данных, начинаем с пустой
+    pub async fn load(storage: &dyn UserStorage) -> Self {
+        let records = storage.load_deliveries().await.unwrap_or_else(|e| {
+            error!("История доставки не загружена, начинаем с пустой: {}", e);
+            Vec::new()
+        });
+        DeliveryHistory {
+            state: Mutex::new(HistoryState { records, changed: HashSet::new() }),
+        }
+    }
+
+    // Уведомление поставлено в очередь исходящих
+    pub fn record_queued(&self, chat_id: i64, kind: NotificationKind, slot: DateTime<Local>) {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+        state.records.push(DeliveryRecord {
+            chat_id,
+            kind,
+            slot: slot.with_timezone(&Utc),
+            status: DeliveryStatus::Queued,
+            at: now,
+            error: None,
+        });
+
+        // Старые записи (заодно отмечаем чаты, у которых они пропали) и лишние записи этого чата
+        let HistoryState { records, changed } = &mut *state;
+        records.retain(|record| {
+            let keep = now - record.at < Duration::days(KEEP_DAYS);
+            if !keep {
+                changed.insert(record.chat_id);
+            }
+            keep
+        });
+        let excess = records.iter().filter(|record| record.chat_id == chat_id).count().saturating_sub(MAX_RECORDS_PER_CHAT);
+        if excess > 0 {
+            let mut skipped = 0;
+            records.retain(|record| {
+                if record.chat_id == chat_id && skipped < excess {
+                    skipped += 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        changed.insert(chat_id);
+    }
+
+    // Отправитель доставил уведомление или окончательно отказался от него
+    pub fn record_result(&self, chat_id: i64, kind: NotificationKind, slot: DateTime<Utc>, result: Result<(), String>) {
+        let mut state = self.state.lock().unwrap();
+        let Some(record) = state
+            .records
+            .iter_mut()
+            .rev()
+            .find(|record| record.chat_id == chat_id && record.kind == kind && record.slot == slot)
+        else {
+            return;
+        };
+        record.at = Utc::now();
+        match result {
+            Ok(()) => record.status = DeliveryStatus::Sent,
+            Err(e) => {
+                record.status = DeliveryStatus::Failed;
+                record.error = Some(e);
+            }
+        }
+        state.changed.insert(chat_id);
+    }
+
+    // Уведомление этого вида для этого слота уже ставилось в очередь
+    pub fn was_queued(&self, chat_id: i64, kind: NotificationKind, slot: DateTime<Local>) -> bool {
+        let slot = slot.with_timezone(&Utc);
+        self.state
+            .lock()
+            .unwrap()
+            .records
+            .iter()
+            .any(|record| record.chat_id == chat_id && record.kind == kind && record.slot == slot)
+    }
+
+    // Последние записи чата, новые первыми
+    pub fn recent(&self, chat_id: i64, limit: usize) -> Vec<DeliveryRecord> {
+        self.state
+            .lock()
+            .unwrap()
+            .records
+            .iter()
+            .rev()
+            .filter(|record| record.chat_id == chat_id)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Удаляет историю чата (пользователь удалил свои данные)
+    pub fn forget(&self, chat_id: i64) {
+        let mut state = self.state.lock().unwrap();
+        let before = state.records.len();
+        state.records.retain(|record| record.chat_id != chat_id);
+        if state.records.len() != before {
+            state.changed.insert(chat_id);
+        }
+    }
+
+    // Записывает в хранилище историю изменившихся чатов. При сбое чаты остаются
+    // отмеченными, и следующий вызов попробует снова
+    pub async fn flush(&self, storage: &dyn UserStorage) {
+        let changes: Vec<(i64, Vec<DeliveryRecord>)> = {
+            let mut state = self.state.lock().unwrap();
+            let changed: Vec<i64> = state.changed.drain().collect();
+            changed
+                .into_iter()
+                .map(|chat_id| {
+                    let records = state.records.iter().filter(|record| record.chat_id == chat_id).cloned().collect();
+                    (chat_id, records)
+                })
+                .collect()
+        };
+        if changes.is_empty() {
+            return;
+        }
+
+        let chats: Vec<i64> = changes.iter().map(|(chat_id, _)| *chat_id).collect();
+        if let Err(e) = storage.save_deliveries(changes).await {
+            error!("История доставки не записана, повторим при следующей записи: {}", e);
+            self.state.lock().unwrap().changed.extend(chats);
+        }
+    }
+}
+
+// Обработка /history: последние запланированные уведомления и что с ними стало
+pub async fn handle_history_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    history: &DeliveryHistory,
+) -> ResponseResult<()> {
+    let records = history.recent(msg.chat.id.0, HISTORY_LIMIT);
+    // Без записи пользователя время показываем по часам бота
+    let offset = match storage.get_user(msg.chat.id.0).await {
+        Ok(Some(user)) => timezone::user_offset(&user),
+        _ => timezone::server_offset(),
+    };
+    let offset = FixedOffset::east_opt(offset).unwrap_or_else(|| *Local::now().offset());
+    let text = if records.is_empty() {
+        templates::text("history.empty")
+    } else {
+        let items = records.iter().map(|record| describe(record, offset)).collect::<Vec<_>>().join("\n");
+        templates::render("history.list", &[("items", &escape_markdown_v2(&items))])
+    };
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Время слота показываем в часовом поясе пользователя: в нем же задано время уведомлений
+fn describe(record: &DeliveryRecord, offset: FixedOffset) -> String {
+    let status = match record.status {
+        DeliveryStatus::Queued => "в очереди".to_string(),
+        DeliveryStatus::Sent => "доставлено".to_string(),
+        DeliveryStatus::Failed => match &record.error {
+            Some(e) => format!("не доставлено ({})", e),
+            None => "не доставлено".to_string(),
+        },
+    };
+    format!(
+        "• {} - {}: {}",
+        record.slot.with_timezone(&offset).format("%d.%m %H:%M"),
+        record.kind.label(),
+        status
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::JsonStorage;
+
+    #[tokio::test]
+    async fn history_survives_restart_after_flush() {
+        let dir = std::env::temp_dir().join(format!("ferrisbot-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("users.json").to_string_lossy().into_owned();
+        let slot = Local::now();
+
+        let storage = JsonStorage::new(&path, None).await.unwrap();
+        let history = DeliveryHistory::load(&storage).await;
+        history.record_queued(1, NotificationKind::Daily, slot);
+        history.record_queued(2, NotificationKind::Mass, slot);
+        history.record_result(1, NotificationKind::Daily, slot.with_timezone(&Utc), Ok(()));
+        history.forget(2);
+        history.flush(&storage).await;
+
+        // Как после перезапуска: история читается из файла заново
+        let storage = JsonStorage::new(&path, None).await.unwrap();
+        let history = DeliveryHistory::load(&storage).await;
+        assert!(history.was_queued(1, NotificationKind::Daily, slot));
+        assert_eq!(history.recent(1, 10)[0].status, DeliveryStatus::Sent);
+        assert!(history.recent(2, 10).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use crate::weather::{
+    CloudsInfo, ForecastCity, ForecastItem, ForecastResponse, MainInfo, OpenWeatherResponse, PrecipitationInfo,
+    SysInfo, WeatherInfo, WindInfo,
+};
+use chrono::{TimeZone, Utc};
+
+// Демо-город: погода без запроса к сервису, для знакомства с ботом, скриншотов и тестов.
+// Значения всегда одни и те же, от текущего времени зависят только даты
+pub const CITY: &str = "Демо";
+// Демо-город живет по московскому времени
+const UTC_OFFSET: i32 = 3 * 3600;
+
+pub fn is_demo_city(city: &str) -> bool {
+    matches!(city.trim().to_lowercase().as_str(), "демо" | "demo")
+}
+
+// Текущая погода: прохладный облачный день с легким ветром
+pub fn current_weather(now: i64) -> OpenWeatherResponse {
+    let local_midnight = now - (now + UTC_OFFSET as i64).rem_euclid(86_400);
+    OpenWeatherResponse {
+        main: main_info(14.0, 55.0),
+        weather: vec![weather_info("Clouds", "переменная облачность", "03d")],
+        wind: WindInfo { speed: 4.0, deg: 270.0, gust: Some(7.0) },
+        name: CITY.to_string(),
+        dt: now,
+        clouds: CloudsInfo { all: 40 },
+        sys: SysInfo {
+            country: "RU".to_string(),
+            sunrise: local_midnight + 6 * 3600,
+            sunset: local_midnight + 19 * 3600 + 30 * 60,
+        },
+        visibility: Some(10_000),
+        timezone: UTC_OFFSET,
+        rain: None,
+        snow: None,
+    }
+}
+
+// Прогноз с 3-часовым шагом: температура зависит только от часа суток, вечером дождь
+pub fn forecast(now: i64, count: usize) -> ForecastResponse {
+    let first = now - now.rem_euclid(3 * 3600) + 3 * 3600;
+    let list = (0..count as i64)
+        .map(|index| {
+            let dt = first + index * 3 * 3600;
+            let hour = (dt + UTC_OFFSET as i64).rem_euclid(86_400) / 3600;
+            let temp = match hour {
+                0..=5 => 8.0,
+                6..=11 => 12.0,
+                12..=17 => 17.0,
+                _ => 11.0,
+            };
+            let rainy = hour >= 18;
+            ForecastItem {
+                dt,
+                main: main_info(temp, if rainy { 80.0 } else { 55.0 }),
+                weather: vec![if rainy {
+                    weather_info("Rain", "небольшой дождь", "10d")
+                } else {
+                    weather_info("Clouds", "переменная облачность", "03d")
+                }],
+                wind: Some(WindInfo { speed: 4.0, deg: 270.0, gust: None }),
+                rain: rainy.then_some(PrecipitationInfo { one_hour: None, three_hours: 1.2 }),
+                snow: None,
+                dt_txt: Utc.timestamp_opt(dt, 0).unwrap().format("%Y-%m-%d %H:%M:%S").to_string(),
+            }
+        })
+        .collect();
+    ForecastResponse { list, city: Some(ForecastCity { timezone: UTC_OFFSET }) }
+}
+
+fn main_info(temp: f32, humidity: f32) -> MainInfo {
+    MainInfo {
+        temp,
+        feels_like: temp - 2.0,
+        humidity,
+        pressure: 1013.0,
+        temp_min: temp - 1.0,
+        temp_max: temp + 1.0,
+    }
+}
+
+fn weather_info(main: &str, description: &str, icon: &str) -> WeatherInfo {
+    WeatherInfo {
+        description: description.to_string(),
+        icon: icon.to_string(),
+        main: main.to_string(),
+    }
+}
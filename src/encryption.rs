@@ -0,0 +1,161 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use log::error;
+use sha2::{Digest, Sha256};
+
+// Признак зашифрованной строки: по нему отличаем ее от обычной строки JSON
+const PREFIX: &str = "ferrisbot-enc-v2:";
+// Строки, зашифрованные ключом SHA-256(STORAGE_KEY): только читаем, при записи они
+// шифруются заново настоящим ключом
+const LEGACY_PREFIX: &str = "ferrisbot-enc-v1:";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+// Шифрование файлов хранилища ключом из STORAGE_KEY (AES-256-GCM).
+// Шифруется каждая строка отдельно: JSONL и журнал дописываются по строке,
+// а JSON-массив при шифровании записывается одной строкой
+pub struct StorageCipher {
+    cipher: Aes256Gcm,
+    legacy: Aes256Gcm,
+}
+
+// STORAGE_KEY - сам 32-байтный ключ в hex (64 символа) или base64, а не пароль:
+// хеш от короткой строки без соли и растяжения подбирается перебором
+pub fn parse_key(key: &str) -> Result<[u8; KEY_LEN], String> {
+    let key = key.trim();
+    let bytes = if key.len() == KEY_LEN * 2 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+        hex::decode(key).map_err(|e| e.to_string())?
+    } else {
+        base64::engine::general_purpose::STANDARD
+            .decode(key)
+            .map_err(|_| "STORAGE_KEY должен быть 32-байтным ключом в hex или base64 (например, openssl rand -hex 32)".to_string())?
+    };
+    bytes.try_into().map_err(|bytes: Vec<u8>| {
+        format!("STORAGE_KEY должен содержать ровно {} байт, а не {} (например, openssl rand -hex 32)", KEY_LEN, bytes.len())
+    })
+}
+
+impl StorageCipher {
+    // Ошибка означает, что STORAGE_KEY не является ключом (см. parse_key)
+    pub fn new(key: &str) -> Result<Self, String> {
+        let bytes = parse_key(key)?;
+        let legacy = Sha256::digest(key.as_bytes());
+        Ok(StorageCipher {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+            legacy: Aes256Gcm::new(&legacy),
+        })
+    }
+
+    // Строка вида ferrisbot-enc-v2:<hex(nonce + шифротекст)>; nonce случайный для каждой строки
+    pub fn seal(&self, plaintext: &str) -> Result<String, String> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| "не удалось зашифровать данные".to_string())?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!("{}{}", PREFIX, hex::encode(sealed)))
+    }
+
+    pub fn open(&self, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        let (cipher, encoded) = match line.strip_prefix(PREFIX) {
+            Some(encoded) => (&self.cipher, encoded),
+            None => (&self.legacy, line.strip_prefix(LEGACY_PREFIX).ok_or("строка не зашифрована")?),
+        };
+        let sealed = hex::decode(encoded).map_err(|_| "поврежденная зашифрованная строка".to_string())?;
+        if sealed.len() < NONCE_LEN {
+            return Err("поврежденная зашифрованная строка".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "не удалось расшифровать строку".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| e.to_string())
+    }
+}
+
+pub fn is_sealed(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with(PREFIX) || line.starts_with(LEGACY_PREFIX)
+}
+
+// Расшифровывает содержимое файла построчно; незашифрованные строки остаются как есть,
+// так что файл, записанный до появления STORAGE_KEY, читается и зашифруется при следующей записи.
+// Ни одной расшифрованной строки при наличии зашифрованных - значит ключ неверный или не задан:
+// тогда загрузка прерывается, иначе следующая запись затерла бы данные пустым списком
+pub fn open_content(content: &str, cipher: Option<&StorageCipher>, path: &str) -> Result<String, String> {
+    if !content.lines().any(is_sealed) {
+        return Ok(content.to_string());
+    }
+    let Some(cipher) = cipher else {
+        return Err(format!("{} зашифрован, а STORAGE_KEY не задан", path));
+    };
+
+    let mut lines = Vec::new();
+    let mut opened = 0;
+    let mut failed = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if !is_sealed(line) {
+            lines.push(line.to_string());
+            continue;
+        }
+        match cipher.open(line) {
+            Ok(line) => {
+                opened += 1;
+                lines.push(line);
+            }
+            Err(e) => failed.push((number + 1, e)),
+        }
+    }
+
+    if opened == 0 {
+        return Err(format!("не удалось расшифровать {}: проверьте STORAGE_KEY", path));
+    }
+    // Недописанная после сбоя строка: остальные строки от нее не зависят
+    for (number, e) in failed {
+        error!("Пропущена строка {} в {}: {}", number, path, e);
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn key_must_be_32_bytes_in_hex_or_base64() {
+        let bytes = parse_key(HEX_KEY).unwrap();
+        assert_eq!(bytes[31], 0x1f);
+        let base64 = base64::engine::general_purpose::STANDARD.encode(bytes);
+        assert_eq!(parse_key(&base64).unwrap(), bytes);
+
+        assert!(parse_key("длинная_случайная_строка").is_err());
+        assert!(parse_key("password").is_err());
+        assert!(parse_key(&HEX_KEY[..62]).is_err());
+    }
+
+    #[test]
+    fn seals_with_key_and_still_opens_legacy_lines() {
+        let cipher = StorageCipher::new(HEX_KEY).unwrap();
+        let sealed = cipher.seal("[1, 2, 3]").unwrap();
+        assert!(sealed.starts_with(PREFIX));
+        assert_eq!(cipher.open(&sealed).unwrap(), "[1, 2, 3]");
+
+        // Строка, записанная до перехода на настоящий ключ
+        let nonce = [7u8; NONCE_LEN];
+        let legacy = Aes256Gcm::new(&Sha256::digest(HEX_KEY.as_bytes()));
+        let mut line = nonce.to_vec();
+        line.extend(legacy.encrypt(Nonce::from_slice(&nonce), "старые данные".as_bytes()).unwrap());
+        let line = format!("{}{}", LEGACY_PREFIX, hex::encode(line));
+        assert!(is_sealed(&line));
+        assert_eq!(cipher.open(&line).unwrap(), "старые данные");
+
+        let other = StorageCipher::new(&"ff".repeat(32)).unwrap();
+        assert!(other.open(&sealed).is_err());
+    }
+}
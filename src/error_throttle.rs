@@ -0,0 +1,76 @@
+use crate::templates;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use teloxide::prelude::*;
+
+// Последнее сообщение об ошибке, отправленное пользователю
+struct LastError {
+    text: String,
+    sent_at: DateTime<Utc>,
+    // Уже предупредили, что повторять не будем
+    notice_sent: bool,
+}
+
+static LAST_ERRORS: LazyLock<Mutex<HashMap<i64, LastError>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Минимальный интервал между одинаковыми ошибками в секундах, 0 - не ограничивать
+static REPEAT_INTERVAL: AtomicU64 = AtomicU64::new(600);
+
+pub fn set_repeat_interval(seconds: u64) {
+    REPEAT_INTERVAL.store(seconds, Ordering::Relaxed);
+}
+
+enum Action {
+    Send,
+    Notice,
+    Skip,
+}
+
+// Отправляет сообщение об ошибке в MarkdownV2. Если такая же ошибка уже уходила пользователю
+// недавно, вместо повтора один раз отправляется уведомление, дальше бот молчит до конца интервала
+pub async fn send_error(bot: &Bot, chat_id: ChatId, text: String) -> ResponseResult<()> {
+    let text = match next_action(chat_id.0, &text) {
+        Action::Send => text,
+        Action::Notice => templates::text("errors.repeated"),
+        Action::Skip => {
+            info!("Повторная ошибка для чата {} не отправлена", chat_id.0);
+            return Ok(());
+        }
+    };
+
+    bot.send_message(chat_id, text)
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+fn next_action(chat_id: i64, text: &str) -> Action {
+    let interval = REPEAT_INTERVAL.load(Ordering::Relaxed);
+    if interval == 0 {
+        return Action::Send;
+    }
+
+    let now = Utc::now();
+    let mut last_errors = LAST_ERRORS.lock().unwrap();
+
+    // Заодно забываем ошибки, интервал которых давно истек
+    last_errors.retain(|_, last| now - last.sent_at < Duration::seconds(interval as i64));
+
+    match last_errors.get_mut(&chat_id) {
+        Some(last) if last.text == text => {
+            if last.notice_sent {
+                Action::Skip
+            } else {
+                last.notice_sent = true;
+                Action::Notice
+            }
+        }
+        _ => {
+            last_errors.insert(chat_id, LastError { text: text.to_string(), sent_at: now, notice_sent: false });
+            Action::Send
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::weather::{DayOutlook, WeatherClient};
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::Local;
+use log::{error, info, warn};
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Время дневной сверки прогноза с утренним
+pub const CHECK_TIME: &str = "12:30";
+// Насколько должна измениться температура, чтобы сообщить об обновлении
+const TEMP_CHANGE_THRESHOLD: f32 = 3.0;
+
+// Обработка /updates on|off: подписка на сообщения «прогноз обновился»
+pub async fn handle_updates_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    let enabled = match args.trim().to_lowercase().as_str() {
+        "on" | "вкл" => Some(true),
+        "off" | "выкл" => Some(false),
+        _ => None,
+    };
+
+    let response = match enabled {
+        Some(enabled) => {
+            user.forecast_updates = enabled;
+            if !enabled {
+                user.forecast_snapshot = None;
+            }
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} {} сообщения об обновлении прогноза", user_id, if enabled { "включил" } else { "выключил" });
+
+            confirm_saved(saved, templates::text(if enabled { "updates.on" } else { "updates.off" }))
+        }
+        None => templates::render("updates.usage", &[
+            ("status", if user.forecast_updates { "включены" } else { "выключены" }),
+        ]),
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Запоминает прогноз, с которым пользователь получил утреннее уведомление
+pub async fn save_snapshot(storage: &dyn UserStorage, weather_client: &WeatherClient, user_id: i64, city: &str) {
+    let outlook = match weather_client.get_day_outlook(city).await {
+        Ok(outlook) => outlook,
+        Err(e) => {
+            warn!("Не удалось сохранить утренний прогноз пользователя {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    match storage.get_user(user_id).await {
+        Ok(Some(mut user)) => {
+            user.forecast_snapshot = Some(outlook);
+            storage.save_user_or_log(user).await;
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Не удалось сохранить утренний прогноз пользователя {}: {}", user_id, e),
+    }
+}
+
+// Дневная сверка: сравнивает утренний прогноз со свежим и сообщает о заметных изменениях
+pub async fn check_forecast_changes(bot: &Bot, storage: &dyn UserStorage, weather_client: &WeatherClient) {
+    let today = Local::now().date_naive();
+
+    for mut user in storage.all_users_or_log().await {
+        if !user.active || !user.forecast_updates {
+            continue;
+        }
+        let Some(snapshot) = user.forecast_snapshot.clone().filter(|s| s.date == today) else {
+            continue;
+        };
+        let Some(city) = user.notification_city(today) else {
+            continue;
+        };
+
+        let latest = match weather_client.get_day_outlook(&city).await {
+            Ok(latest) => latest,
+            Err(e) => {
+                warn!("Не удалось обновить прогноз для пользователя {}: {}", user.user_id, e);
+                continue;
+            }
+        };
+
+        let changes = describe_changes(&snapshot, &latest);
+        user.forecast_snapshot = Some(latest);
+        let user_id = user.user_id;
+        storage.save_user_or_log(user).await;
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        let message = templates::render("updates.changed", &[
+            ("city", &crate::escape_markdown_v2(&crate::utils::echo(&city))),
+            ("changes", &crate::escape_markdown_v2(&changes.join("\n"))),
+        ]);
+        match bot.send_message(ChatId(user_id), message).parse_mode(ParseMode::MarkdownV2).await {
+            Ok(_) => info!("Отправлено обновление прогноза пользователю ID: {}", user_id),
+            Err(e) => error!("Не удалось отправить обновление прогноза пользователю {}: {}", user_id, e),
+        }
+    }
+}
+
+fn describe_changes(before: &DayOutlook, after: &DayOutlook) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if after.precipitation && !before.precipitation {
+        changes.push("☔ Появились осадки, захватите зонт".to_string());
+    } else if before.precipitation && !after.precipitation {
+        changes.push("🌤 Осадков больше не ожидается".to_string());
+    }
+
+    if (after.temp_max - before.temp_max).abs() > TEMP_CHANGE_THRESHOLD {
+        changes.push(format!("🌡 Максимум: {:.0}°C вместо {:.0}°C", after.temp_max, before.temp_max));
+    }
+    if (after.temp_min - before.temp_min).abs() > TEMP_CHANGE_THRESHOLD {
+        changes.push(format!("🌡 Минимум: {:.0}°C вместо {:.0}°C", after.temp_min, before.temp_min));
+    }
+
+    changes
+}
@@ -0,0 +1,891 @@
+use crate::locale::Locale;
+use crate::sections::ReportSections;
+use crate::severity;
+use crate::storage::UserSettings;
+use crate::weather::{city_time, humidex, mugginess, DaySummary, ForecastItem, ForecastResponse, OpenWeatherResponse, PrecipitationInfo};
+use chrono::{Datelike, NaiveDate, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Стиль отчета о текущей погоде
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStyle {
+    // Обычный подробный отчет с выбранными разделами
+    #[default]
+    Normal,
+    // Одна строка из эмодзи и чисел, например "☀️ +21° 💨3м/с 💧40%": удобно читать в превью уведомлений на часах
+    Compact,
+}
+
+impl ReportStyle {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "normal" | "обычный" | "подробный" => Some(ReportStyle::Normal),
+            "compact" | "компактный" | "кратко" => Some(ReportStyle::Compact),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportStyle::Normal => "обычный",
+            ReportStyle::Compact => "компактный",
+        }
+    }
+}
+
+// Точность показа температуры в отчетах
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperaturePrecision {
+    // Целые градусы со знаком: "+21°C"
+    #[default]
+    Whole,
+    // С десятыми: "21.3°C"
+    Tenths,
+}
+
+impl TemperaturePrecision {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "whole" | "0" | "целые" | "целая" => Some(TemperaturePrecision::Whole),
+            "tenths" | "decimal" | "1" | "десятые" | "0.1" => Some(TemperaturePrecision::Tenths),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TemperaturePrecision::Whole => "целые градусы",
+            TemperaturePrecision::Tenths => "десятые доли градуса",
+        }
+    }
+
+    // Температура для подробного отчета: "+21°C" или "21.3°C"
+    pub fn celsius(self, temp: f32) -> String {
+        self.with_unit(temp, "°C")
+    }
+
+    fn with_unit(self, temp: f32, unit: &str) -> String {
+        match self {
+            TemperaturePrecision::Whole => format!("{:+}{}", temp.round() as i32, unit),
+            TemperaturePrecision::Tenths => format!("{:.1}{}", temp, unit),
+        }
+    }
+
+    // Короткая запись для компактных строк: "+21°" или "+21.3°"
+    pub fn degrees(self, temp: f32) -> String {
+        match self {
+            TemperaturePrecision::Whole => format!("{:+}°", temp.round() as i32),
+            TemperaturePrecision::Tenths => format!("{:+.1}°", temp),
+        }
+    }
+}
+
+// Единицы измерения в отчетах о погоде. Данные всегда приходят в метрических единицах,
+// пересчитываются только при показе
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    // Градусы Цельсия и метры в секунду
+    #[default]
+    Metric,
+    // Градусы Фаренгейта и мили в час
+    Imperial,
+}
+
+impl Units {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "metric" | "c" | "°c" | "метрические" | "цельсий" => Some(Units::Metric),
+            "imperial" | "f" | "°f" | "имперские" | "фаренгейт" => Some(Units::Imperial),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Units::Metric => "°C и м/с",
+            Units::Imperial => "°F и мили в час",
+        }
+    }
+
+    fn temperature(self, celsius: f32) -> f32 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    fn temperature_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "°C",
+            Units::Imperial => "°F",
+        }
+    }
+
+    pub fn speed(self, meters_per_second: f32) -> f32 {
+        match self {
+            Units::Metric => meters_per_second,
+            Units::Imperial => meters_per_second * 2.23694,
+        }
+    }
+
+    pub fn speed_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "м/с",
+            Units::Imperial => "миль/ч",
+        }
+    }
+}
+
+// Настройки оформления отчета
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatOptions {
+    pub sections: ReportSections,
+    pub style: ReportStyle,
+    // true - отсутствующие данные показываем как "Н/Д", false - такие строки и значения пропускаем
+    pub show_missing: bool,
+    // Формат времени восхода и заката
+    pub locale: Locale,
+    pub precision: TemperaturePrecision,
+    pub units: Units,
+}
+
+impl FormatOptions {
+    // Оформление, которое выбрал пользователь; отсутствующие данные не показываем
+    pub fn for_user(user: &UserSettings) -> Self {
+        FormatOptions {
+            sections: user.report_sections,
+            style: user.report_style,
+            show_missing: false,
+            locale: user.locale.unwrap_or_default(),
+            precision: user.temperature_precision,
+            units: user.units,
+        }
+    }
+
+    // Температура для подробного отчета в выбранных единицах: "+21°C", "21.3°C", "+70°F"
+    pub fn temperature(&self, celsius: f32) -> String {
+        self.precision.with_unit(self.units.temperature(celsius), self.units.temperature_unit())
+    }
+
+    // Короткая запись для компактных строк: "+21°"
+    pub fn degrees(&self, celsius: f32) -> String {
+        self.precision.degrees(self.units.temperature(celsius))
+    }
+
+    // Скорость ветра: "4.0 м/с" или "8.9 миль/ч"
+    pub fn speed(&self, meters_per_second: f32) -> String {
+        format!("{:.1} {}", self.units.speed(meters_per_second), self.units.speed_unit())
+    }
+
+    // Скорость ветра целым числом для компактных строк: "4м/с"
+    fn speed_short(&self, meters_per_second: f32) -> String {
+        format!("{}{}", self.units.speed(meters_per_second).round() as i32, self.units.speed_unit())
+    }
+
+    // Нужен ли отчету прогноз на день (только для температуры по времени суток)
+    pub fn needs_forecast(&self) -> bool {
+        self.style == ReportStyle::Normal && self.sections.contains(ReportSections::DAILY_TEMPS)
+    }
+}
+
+// Заметки по истории погоды (серии, рекорды) отдельным блоком в конце отчета
+pub fn with_notes(report: String, notes: &[String]) -> String {
+    if notes.is_empty() {
+        return report;
+    }
+    format!("{}\n\n{}", report.trim_end(), notes.join("\n"))
+}
+
+// Подпись источника данных под отчетом, как того требуют условия провайдера
+pub fn with_attribution(report: String, provider: &str) -> String {
+    format!("{}\n\nДанные: {}", report.trim_end(), provider)
+}
+
+// Заглушка для отсутствующих данных
+const MISSING: &str = "Н/Д";
+
+pub fn format_weather(data: &OpenWeatherResponse, forecast: Option<ForecastResponse>, options: FormatOptions) -> String {
+    match options.style {
+        ReportStyle::Normal => format_normal(data, forecast, options),
+        ReportStyle::Compact => format_compact(data, options),
+    }
+}
+
+// Компактный отчет: ветер и влажность показываем, только если эти разделы включены
+fn format_compact(data: &OpenWeatherResponse, options: FormatOptions) -> String {
+    let sections = options.sections;
+    let mut parts = vec![format!(
+        "{} {}",
+        get_weather_emoji(&data.weather[0].icon),
+        options.degrees(data.main.temp)
+    )];
+    if sections.contains(ReportSections::WIND) {
+        parts.push(format!("💨{}{}", options.speed_short(data.wind.speed), data.wind.direction_arrow()));
+    }
+    if sections.contains(ReportSections::HUMIDITY) {
+        parts.push(format!("💧{}%", data.main.humidity.round() as i32));
+    }
+    parts.join(" ")
+}
+
+fn format_normal(data: &OpenWeatherResponse, forecast: Option<ForecastResponse>, options: FormatOptions) -> String {
+    let sections = options.sections;
+    // Получаем эмодзи на основе иконки погоды
+    let weather_emoji = get_weather_emoji(&data.weather[0].icon);
+    // В опасный день отчет начинается с предупреждений, в спокойный - без второстепенных строк
+    let severity = severity::assess(data);
+
+    let mut text = format!("{} *{}*\n\n", weather_emoji, capitalize_first_letter(&data.weather[0].description));
+    if severity.is_severe() {
+        text.push_str("⚠️ *Внимание:*\n");
+        for warning in &severity.warnings {
+            text.push_str(&format!("• *{}*\n", warning));
+        }
+        text.push('\n');
+    }
+
+    let temp = options.temperature(data.main.temp);
+    let feels_like = options.temperature(data.main.feels_like);
+    if severity.is_severe() {
+        text.push_str(&format!("🌡 *Температура: {} (ощущается как {})*\n", temp, feels_like));
+    } else {
+        text.push_str(&format!("🌡 *Температура:* {} (ощущается как {})\n", temp, feels_like));
+    }
+
+    // Температуры на разное время суток
+    if sections.contains(ReportSections::DAILY_TEMPS) {
+        let (morning, day, evening) = forecast.as_ref().map(extract_temperatures_by_time).unwrap_or_default();
+        if let Some(line) = times_of_day_line(morning, day, evening, options) {
+            text.push_str(&format!("🕒 *Прогноз на сегодня:* {}\n", line));
+        }
+    }
+
+    if !severity.is_calm() {
+        text.push_str(&format!(
+            "🔸 Мин: {}, Макс: {}\n",
+            options.temperature(data.main.temp_min),
+            options.temperature(data.main.temp_max)
+        ));
+    }
+
+    if sections.contains(ReportSections::HUMIDITY) {
+        text.push_str(&format!("💧 *Влажность:* {}%\n", data.main.humidity));
+        if let Some(line) = mugginess_line(data, forecast.as_ref()) {
+            text.push_str(&line);
+        }
+    }
+    if sections.contains(ReportSections::WIND) {
+        let wind = &data.wind;
+        let gust = wind.gust_note(options.units).map(|note| format!(", {}", note)).unwrap_or_default();
+        text.push_str(&format!(
+            "🍃 *Ветер:* {}, {}{}, направление: {} {}\n",
+            options.speed(wind.speed),
+            wind.beaufort_description(),
+            gust,
+            wind.direction_arrow(),
+            wind.direction_name()
+        ));
+    }
+
+    for (emoji, kind, info) in [("🌧", "дождь", &data.rain), ("🌨", "снег", &data.snow)] {
+        if let Some(volume) = info.as_ref().and_then(precipitation_volume) {
+            text.push_str(&format!("{} *Осадки:* {} {}\n", emoji, kind, volume));
+        }
+    }
+
+    if severity.is_calm() {
+        // Облачность и видимость в спокойный день ничего не добавляют к отчету
+        text.truncate(text.trim_end().len());
+    } else {
+        text.push_str(&format!("☁️ *Облачность:* {}%", data.clouds.all));
+        // Видимость приходит не всегда: без нее строку не показываем
+        match data.visibility {
+            Some(visibility) if visibility >= 1000 => text.push_str(&format!("\n👁 *Видимость:* {} км", visibility / 1000)),
+            Some(visibility) => text.push_str(&format!("\n👁 *Видимость:* {} м", visibility)),
+            None if options.show_missing => text.push_str(&format!("\n👁 *Видимость:* {}", MISSING)),
+            None => {}
+        }
+    }
+
+    if sections.contains(ReportSections::SUN) {
+        // Время восхода и заката - по часам самого города, а не сервера
+        let sunrise = city_time(data.sys.sunrise, data.timezone);
+        let sunset = city_time(data.sys.sunset, data.timezone);
+        text.push_str(&format!(
+            "\n🌅 *Восход солнца:* {}\n\
+            🌇 *Закат солнца:* {} (время местное)",
+            options.locale.time(sunrise.hour(), sunrise.minute()),
+            options.locale.time(sunset.hour(), sunset.minute())
+        ));
+    }
+
+    if sections.contains(ReportSections::RECOMMENDATIONS) {
+        // Рекомендации по одежде
+        let clothing_recommendation = get_clothing_recommendation(data.main.temp, data.weather[0].main.as_str());
+        text.push_str(&format!("\n\n*Рекомендация:* {}", clothing_recommendation));
+    }
+
+    text
+}
+
+// Прогноз на завтра для вечерних уведомлений
+pub fn format_tomorrow(summary: &DaySummary, options: FormatOptions) -> String {
+    let sections = options.sections;
+    let weather_emoji = get_weather_emoji(&summary.icon);
+
+    if options.style == ReportStyle::Compact {
+        let mut parts = vec![format!(
+            "Завтра {} {}…{}",
+            weather_emoji,
+            options.degrees(summary.temp_min),
+            options.degrees(summary.temp_max)
+        )];
+        if let (true, Some(wind)) = (sections.contains(ReportSections::WIND), summary.wind_max) {
+            parts.push(format!("💨{}", options.speed_short(wind)));
+        }
+        if sections.contains(ReportSections::HUMIDITY) {
+            parts.push(format!("💧{}%", summary.humidity.round() as i32));
+        }
+        return parts.join(" ");
+    }
+
+    let mut text = format!(
+        "{} *Завтра: {}*\n\n\
+        🌡 *Температура:* от {} до {}\n",
+        weather_emoji,
+        capitalize_first_letter(&summary.description),
+        options.temperature(summary.temp_min),
+        options.temperature(summary.temp_max),
+    );
+
+    if sections.contains(ReportSections::DAILY_TEMPS) {
+        if let Some(line) = times_of_day_line(summary.morning, summary.day, summary.evening, options) {
+            text.push_str(&format!("🕒 *По времени суток:* {}\n", line));
+        }
+    }
+    if summary.precipitation >= 0.1 {
+        text.push_str(&format!("☔ *Осадки:* {:.1} мм\n", summary.precipitation));
+    }
+    if sections.contains(ReportSections::HUMIDITY) {
+        text.push_str(&format!("💧 *Влажность:* {:.0}%\n", summary.humidity));
+    }
+    if sections.contains(ReportSections::WIND) {
+        match summary.wind_max {
+            Some(wind) => text.push_str(&format!("🍃 *Ветер:* до {}\n", options.speed(wind))),
+            None if options.show_missing => text.push_str(&format!("🍃 *Ветер:* {}\n", MISSING)),
+            None => {}
+        }
+    }
+
+    if sections.contains(ReportSections::RECOMMENDATIONS) {
+        // Одежду подбираем по дневной температуре
+        let temp = summary.day.unwrap_or(summary.temp_max);
+        text.push_str(&format!("\n*Рекомендация:* {}", get_clothing_recommendation(temp, &summary.weather_main)));
+    }
+
+    text.trim_end().to_string()
+}
+
+// Духота сейчас или, если сейчас комфортно, во второй половине дня по местному времени города
+fn mugginess_line(data: &OpenWeatherResponse, forecast: Option<&ForecastResponse>) -> Option<String> {
+    let now = humidex(data.main.temp, data.main.humidity);
+    if let Some(level) = mugginess(now) {
+        return Some(format!("🥵 *{}:* индекс духоты {:.0}\n", capitalize_first_letter(level), now));
+    }
+
+    let forecast = forecast?;
+    let today = city_time(data.dt, data.timezone).date_naive();
+    let afternoon = forecast.list
+        .iter()
+        .filter(|item| item.dt > data.dt)
+        .filter(|item| {
+            let time = city_time(item.dt, forecast.utc_offset());
+            time.date_naive() == today && (12..18).contains(&time.hour())
+        })
+        .map(|item| humidex(item.main.temp, item.main.humidity))
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    mugginess(afternoon).map(|_| format!("🥵 Будет душно после обеда (индекс духоты до {:.0})\n", afternoon))
+}
+
+// Объем осадков в текущей погоде: "2.3 мм за час". OpenWeather присылает его за час, реже за 3 часа
+fn precipitation_volume(info: &PrecipitationInfo) -> Option<String> {
+    match info.one_hour {
+        Some(volume) if volume > 0.0 => Some(format!("{:.1} мм за час", volume)),
+        _ if info.three_hours > 0.0 => Some(format!("{:.1} мм за 3 часа", info.three_hours)),
+        _ => None,
+    }
+}
+
+// Температура утром (6-11), днем (12-17) и вечером (18-23) по первым подходящим записям прогноза
+fn extract_temperatures_by_time(forecast: &ForecastResponse) -> (Option<f32>, Option<f32>, Option<f32>) {
+    let mut morning_temp: Option<f32> = None;
+    let mut day_temp: Option<f32> = None;
+    let mut evening_temp: Option<f32> = None;
+
+    for item in &forecast.list {
+        let hour = city_time(item.dt, forecast.utc_offset()).hour();
+
+        if (6..12).contains(&hour) && morning_temp.is_none() {
+            morning_temp = Some(item.main.temp);
+        } else if (12..18).contains(&hour) && day_temp.is_none() {
+            day_temp = Some(item.main.temp);
+        } else if (18..24).contains(&hour) && evening_temp.is_none() {
+            evening_temp = Some(item.main.temp);
+        }
+
+        // Если собрали все температуры, выходим из цикла
+        if morning_temp.is_some() && day_temp.is_some() && evening_temp.is_some() {
+            break;
+        }
+    }
+
+    (morning_temp, day_temp, evening_temp)
+}
+
+// "Утро: +12°C, День: +18°C, Вечер: +14°C". Без show_missing части без данных пропускаются,
+// None - показывать нечего
+fn times_of_day_line(morning: Option<f32>, day: Option<f32>, evening: Option<f32>, options: FormatOptions) -> Option<String> {
+    let parts: Vec<String> = [("Утро", morning), ("День", day), ("Вечер", evening)]
+        .into_iter()
+        .filter_map(|(name, temp)| match temp {
+            Some(t) => Some(format!("{}: {}", name, options.temperature(t))),
+            None if options.show_missing => Some(format!("{}: {}", name, MISSING)),
+            None => None,
+        })
+        .collect();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(", "))
+    }
+}
+
+pub(crate) fn get_weather_emoji(icon: &str) -> &'static str {
+    match icon {
+        "01d" => "☀️",  // ясно (день)
+        "01n" => "🌙",  // ясно (ночь)
+        "02d" => "🌤️", // малооблачно (день)
+        "02n" => "🌙☁️", // малооблачно (ночь)
+        "03d" | "03n" => "☁️", // облачно
+        "04d" | "04n" => "☁️☁️", // пасмурно
+        "09d" | "09n" => "🌧️", // дождь
+        "10d" => "🌦️", // дождь с прояснениями (день)
+        "10n" => "🌧️🌙", // дождь с прояснениями (ночь)
+        "11d" | "11n" => "⛈️", // гроза
+        "13d" | "13n" => "❄️", // снег
+        "50d" | "50n" => "🌫️", // туман
+        _ => "🌡️",
+    }
+}
+
+fn get_clothing_recommendation(temp: f32, weather_main: &str) -> String {
+    if temp < -25.0 {
+        "🥶 *Крайне холодно!* Нужна очень теплая многослойная одежда: термобелье, теплый свитер, зимняя куртка/пуховик, утепленные брюки, теплая шапка, шарф, варежки/перчатки и зимняя обувь с тёплыми носками.".to_string()
+    } else if temp < -15.0 {
+        "❄️ *Очень холодно!* Наденьте теплую зимнюю куртку/пуховик, утепленные брюки, многослойную одежду (термобелье, свитер), теплую шапку, шарф, перчатки и зимнюю обувь. Не забудьте про теплые носки.".to_string()
+    } else if temp < -5.0 {
+        "🧣 *Холодно.* Необходима зимняя куртка, теплый свитер, шапка, перчатки и шарф. Лучше надеть утепленные брюки и зимнюю обувь. Если планируете долго находиться на улице, подумайте о термобелье.".to_string()
+    } else if temp < 5.0 {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "🌧️ *Холодно и дождливо.* Наденьте теплую водонепроницаемую куртку, шапку, перчатки, шарф. Обязательно возьмите зонт или наденьте куртку с капюшоном. Рекомендуется водонепроницаемая обувь.".to_string()
+        } else if weather_main == "Snow" {
+            "🌨️ *Холодно и снежно.* Наденьте теплую зимнюю куртку, шапку, перчатки, шарф и зимнюю обувь с хорошим протектором. Возможно понадобятся утепленные брюки.".to_string()
+        } else {
+            "🧥 *Прохладно.* Понадобится теплая куртка, свитер или толстовка, шапка и перчатки. Подойдет легкая шапка и шарф, особенно при ветре.".to_string()
+        }
+    } else if temp < 10.0 {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "🌂 *Прохладно и дождливо.* Возьмите водонепроницаемую куртку или плащ, зонт и наденьте водонепроницаемую обувь. Свитер или толстовка не помешают, так как на улице довольно прохладно.".to_string()
+        } else {
+            "🧶 *Прохладно.* Подойдет легкая куртка или плотная кофта, джинсы или брюки. При сильном ветре может понадобиться шарф. Утром и вечером будет прохладнее - возьмите дополнительный слой одежды.".to_string()
+        }
+    } else if temp < 15.0 {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "☔ *Умеренно прохладно и дождливо.* Возьмите зонт и наденьте водонепроницаемую куртку или плащ. Хорошим решением будет легкий свитер или кофта и удобная непромокаемая обувь.".to_string()
+        } else {
+            "👕 *Умеренно прохладно.* Достаточно легкой куртки или кофты, можно надеть джинсы или брюки. Если проведете весь день на улице, возьмите дополнительный слой на вечер.".to_string()
+        }
+    } else if temp < 20.0 {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "🌦️ *Тепло, но дождливо.* Возьмите зонт и легкую водонепроницаемую куртку или дождевик. Подойдет футболка и джинсы/брюки. Не забудьте про удобную непромокаемую обувь.".to_string()
+        } else {
+            "👚 *Тепло.* Достаточно футболки, рубашки или блузки, подойдут легкие брюки, джинсы или юбка. Вечером может быть прохладнее, возьмите с собой легкую кофту или кардиган.".to_string()
+        }
+    } else if temp < 25.0 {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "🌤️ *Довольно тепло, но дождливо.* Легкая одежда (футболка, шорты или легкие брюки) и зонт. Дождевик может пригодиться если дождь сильный. Обувь лучше выбрать непромокаемую.".to_string()
+        } else {
+            "👗 *Довольно тепло.* Легкая одежда: футболка, рубашка или блузка, легкие брюки, шорты или юбка. Вечером может быть прохладнее, так что кофта не помешает.".to_string()
+        }
+    } else if temp < 30.0 {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "🌞 *Жарко, но с дождем.* Максимально легкая одежда и зонтик. После дождя может быть влажно и душно - выбирайте дышащие натуральные ткани.".to_string()
+        } else {
+            "☀️ *Жарко.* Максимально легкая одежда из натуральных тканей: футболка, шорты, сарафан или легкое платье. Обязательны головной убор и солнцезащитный крем. Берегитесь прямых солнечных лучей.".to_string()
+        }
+    } else {
+        if weather_main == "Rain" || weather_main == "Drizzle" {
+            "🔥 *Очень жарко, возможны дожди.* Минимум самой легкой одежды из натуральных тканей. Носите светлые цвета. Зонт может пригодиться как для дождя, так и для защиты от солнца.".to_string()
+        } else {
+            "🔥 *Очень жарко!* Носите минимум самой легкой одежды из натуральных тканей, предпочтительно светлых цветов. Обязательны головной убор и солнцезащитный крем. Пейте больше воды и старайтесь находиться в тени. Избегайте активности на открытом солнце в пиковые часы.".to_string()
+        }
+    }
+}
+
+pub(crate) fn capitalize_first_letter(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+    }
+}
+
+// Вид прогноза на неделю
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForecastLayout {
+    // Текст с описанием погоды по дням
+    Text,
+    // Моноширинная таблица: так удобнее сравнивать дни между собой
+    Table,
+}
+
+impl ForecastLayout {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "text" | "текст" => Some(ForecastLayout::Text),
+            "table" | "таблица" | "таблицей" => Some(ForecastLayout::Table),
+            _ => None,
+        }
+    }
+}
+
+// Длительность одной записи прогноза
+const FORECAST_STEP_SECONDS: i64 = 3 * 3600;
+
+// Прогноз, сгруппированный по дням: дата (ГГГГ-ММ-ДД), день недели и записи за этот день.
+// Уже прошедшие к моменту now интервалы пропускаем, иначе мин/макс сегодняшнего дня
+// искажают ночные температуры, которые уже позади
+fn group_by_day(forecast: &ForecastResponse, now: i64) -> Vec<(String, Weekday, Vec<&ForecastItem>)> {
+    let mut days_forecast: HashMap<String, (Weekday, Vec<&ForecastItem>)> = HashMap::new();
+
+    for item in forecast.list.iter().filter(|item| item.dt + FORECAST_STEP_SECONDS > now) {
+        // День считаем по местному времени города: dt_txt в ответе указан в UTC
+        let time = city_time(item.dt, forecast.utc_offset());
+        let date_str = time.format("%Y-%m-%d").to_string();
+        let weekday = time.weekday();
+        days_forecast.entry(date_str)
+            .or_insert_with(|| (weekday, Vec::new()))
+            .1.push(item);
+    }
+
+    let mut days: Vec<(String, Weekday, Vec<&ForecastItem>)> = days_forecast
+        .into_iter()
+        .map(|(date, (weekday, items))| (date, weekday, items))
+        .collect();
+    days.sort_by(|a, b| a.0.cmp(&b.0));
+    days
+}
+
+// Дата ГГГГ-ММ-ДД в формате пользователя (день и месяц)
+fn short_date(date: &str, locale: Locale) -> String {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(date) => locale.short_date(date),
+        Err(_) => date.to_string(), // в случае ошибки берем исходную строку
+    }
+}
+
+// Прогноз на неделю текстом; now - текущее время (Unix), по нему ближайшие дни
+// называются "Сегодня" и "Завтра", а прошедшие часы не учитываются
+pub fn format_weekly_forecast(forecast: &ForecastResponse, options: FormatOptions, now: i64) -> String {
+    let locale = options.locale;
+    let days = group_by_day(forecast, now);
+    if days.is_empty() {
+        return "Нет данных о прогнозе".to_string();
+    }
+    let today = city_time(now, forecast.utc_offset()).date_naive();
+
+    // Форматируем прогноз для каждого дня; дни группируем по календарным неделям
+    let mut result = String::new();
+    let mut current_week = None;
+
+    for (date, weekday, forecasts) in days {
+        let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok();
+        let week = day.map(|day| day.iso_week().week());
+        if let Some(week) = week.filter(|week| current_week != Some(*week)) {
+            result.push_str(&format!("📅 Неделя {}\n\n", week));
+            current_week = Some(week);
+        }
+
+        // Обрабатываем данные для дня
+        let mut min_temp = f32::MAX;
+        let mut max_temp = f32::MIN;
+        let mut descriptions = Vec::new();
+        
+        for item in &forecasts {
+            min_temp = min_temp.min(item.main.temp_min);
+            max_temp = max_temp.max(item.main.temp_max);
+            
+            if let Some(weather_info) = item.weather.first() {
+                descriptions.push(capitalize_first_letter(&weather_info.description));
+            }
+        }
+        
+        // Убираем дубликаты в описаниях
+        descriptions.sort();
+        descriptions.dedup();
+        
+        let day_name = day
+            .and_then(|day| locale.relative_day_name((day - today).num_days()))
+            .unwrap_or_else(|| locale.weekday_name(weekday));
+        // Выходные отмечаем, чтобы их было видно с первого взгляда
+        let weekend = if matches!(weekday, Weekday::Sat | Weekday::Sun) { " 🎉" } else { "" };
+        result.push_str(&format!("*{}, {}*{}:\n", day_name, short_date(&date, locale), weekend));
+        result.push_str(&format!(
+            "🌡 Температура: {} — {}\n",
+            options.temperature(min_temp),
+            options.temperature(max_temp)
+        ));
+        result.push_str(&format!("🌤 Погода: {}\n\n", descriptions.join(", ")));
+    }
+    
+    result
+}
+
+// Прогноз на неделю таблицей: день | мин | макс | осадки | ветер.
+// Возвращает строки без разметки, их нужно поместить в блок ``` как есть
+pub fn format_weekly_table(forecast: &ForecastResponse, options: FormatOptions, now: i64) -> String {
+    let locale = options.locale;
+    let days = group_by_day(forecast, now);
+    if days.is_empty() {
+        return "Нет данных о прогнозе".to_string();
+    }
+
+    let mut rows = vec![[
+        "День".to_string(),
+        "Мин".to_string(),
+        "Макс".to_string(),
+        "Осадки".to_string(),
+        "Ветер".to_string(),
+    ]];
+
+    for (date, weekday, forecasts) in days {
+        let min_temp = forecasts.iter().map(|item| item.main.temp_min).fold(f32::INFINITY, f32::min);
+        let max_temp = forecasts.iter().map(|item| item.main.temp_max).fold(f32::NEG_INFINITY, f32::max);
+        let precipitation: f32 = forecasts.iter().map(|item| item.precipitation()).sum();
+        let wind = forecasts
+            .iter()
+            .filter_map(|item| item.wind.as_ref().map(|w| w.speed))
+            .fold(None, |max: Option<f32>, speed| Some(max.map_or(speed, |m| m.max(speed))));
+
+        rows.push([
+            format!("{} {}", locale.weekday_short_name(weekday), short_date(&date, locale)),
+            TemperaturePrecision::Whole.degrees(options.units.temperature(min_temp)),
+            TemperaturePrecision::Whole.degrees(options.units.temperature(max_temp)),
+            if precipitation >= 0.1 { format!("{:.1} мм", precipitation) } else { "—".to_string() },
+            wind.map_or("—".to_string(), |speed| {
+                format!("{} {}", options.units.speed(speed).round() as i32, options.units.speed_unit())
+            }),
+        ]);
+    }
+
+    // Ширину считаем в символах, а не в байтах: иначе кириллица, занимающая по два байта, ломает выравнивание
+    let widths: Vec<usize> = (0..5)
+        .map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0))
+        .collect();
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    let padding = " ".repeat(widths[column] - cell.chars().count());
+                    // Название дня выравниваем по левому краю, числа - по правому
+                    if column == 0 { format!("{}{}", cell, padding) } else { format!("{}{}", padding, cell) }
+                })
+                .collect::<Vec<_>>()
+                .join(" | ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo;
+
+    // 12:00 UTC, 15 января 2025
+    const NOW: i64 = 1_736_942_400;
+
+    // Дождливый, но не опасный день: отчет не сокращается до спокойного и не расширяется предупреждениями
+    fn rainy_weather(visibility: Option<i32>, gust: Option<f32>) -> OpenWeatherResponse {
+        let mut data = demo::current_weather(NOW);
+        data.weather[0].main = "Rain".to_string();
+        data.visibility = visibility;
+        data.wind.gust = gust;
+        data
+    }
+
+    fn options(style: ReportStyle, sections: ReportSections, show_missing: bool) -> FormatOptions {
+        FormatOptions { sections, style, show_missing, locale: Locale::Ru, precision: TemperaturePrecision::Whole, units: Units::Metric }
+    }
+
+    fn day_summary(wind_max: Option<f32>, morning: Option<f32>) -> DaySummary {
+        DaySummary {
+            temp_min: 8.0,
+            temp_max: 15.0,
+            morning,
+            day: Some(14.0),
+            evening: None,
+            humidity: 60.0,
+            wind_max,
+            precipitation: 0.0,
+            description: "облачно".to_string(),
+            icon: "03d".to_string(),
+            weather_main: "Clouds".to_string(),
+        }
+    }
+
+    // Все сочетания стиля, показа отсутствующих данных, разделов и наличия видимости, порывов и прогноза
+    #[test]
+    fn current_weather_permutations() {
+        for style in [ReportStyle::Normal, ReportStyle::Compact] {
+            for show_missing in [false, true] {
+                for sections in [ReportSections::all(), ReportSections::empty()] {
+                    for visibility in [Some(10_000), Some(500), None] {
+                        for gust in [Some(9.0), None] {
+                            for with_forecast in [true, false] {
+                                let case = format!(
+                                    "{:?} show_missing={} sections={:?} visibility={:?} gust={:?} forecast={}",
+                                    style, show_missing, sections, visibility, gust, with_forecast
+                                );
+                                let data = rainy_weather(visibility, gust);
+                                let forecast = with_forecast.then(|| demo::forecast(NOW, 8));
+                                let text = format_weather(&data, forecast, options(style, sections, show_missing));
+
+                                let has = |needle: &str| text.contains(needle);
+                                let wind = sections.contains(ReportSections::WIND);
+                                let humidity = sections.contains(ReportSections::HUMIDITY);
+                                if style == ReportStyle::Compact {
+                                    assert!(!text.contains('\n'), "{}: {}", case, text);
+                                    assert!(!has(MISSING), "{}: {}", case, text);
+                                    assert_eq!(has("💨"), wind, "{}: {}", case, text);
+                                    assert_eq!(has("💧"), humidity, "{}: {}", case, text);
+                                    continue;
+                                }
+
+                                assert_eq!(has("Видимость"), visibility.is_some() || show_missing, "{}: {}", case, text);
+                                assert_eq!(has("Видимость:* Н/Д"), visibility.is_none() && show_missing, "{}: {}", case, text);
+                                if !show_missing {
+                                    assert!(!has(MISSING), "{}: {}", case, text);
+                                }
+                                assert_eq!(has("км"), visibility == Some(10_000), "{}: {}", case, text);
+                                assert_eq!(has("Видимость:* 500 м"), visibility == Some(500), "{}: {}", case, text);
+                                assert_eq!(has("порывы"), wind && gust.is_some(), "{}: {}", case, text);
+                                assert_eq!(has("Ветер"), wind, "{}: {}", case, text);
+                                assert_eq!(has("Влажность"), humidity, "{}: {}", case, text);
+                                assert_eq!(has("Восход"), sections.contains(ReportSections::SUN), "{}: {}", case, text);
+                                assert_eq!(has("Рекомендация"), sections.contains(ReportSections::RECOMMENDATIONS), "{}: {}", case, text);
+                                // Без прогноза строку по времени суток показываем только с заглушками
+                                assert_eq!(
+                                    has("Прогноз на сегодня"),
+                                    sections.contains(ReportSections::DAILY_TEMPS) && (with_forecast || show_missing),
+                                    "{}: {}",
+                                    case,
+                                    text
+                                );
+                                assert!(!has("Нет данных") && !has(" 0 м"), "{}: {}", case, text);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tomorrow_permutations() {
+        for style in [ReportStyle::Normal, ReportStyle::Compact] {
+            for show_missing in [false, true] {
+                for sections in [ReportSections::all(), ReportSections::empty()] {
+                    for wind_max in [Some(6.0), None] {
+                        for morning in [Some(9.0), None] {
+                            let case = format!(
+                                "{:?} show_missing={} sections={:?} wind={:?} morning={:?}",
+                                style, show_missing, sections, wind_max, morning
+                            );
+                            let text = format_tomorrow(&day_summary(wind_max, morning), options(style, sections, show_missing));
+                            let has = |needle: &str| text.contains(needle);
+                            let wind = sections.contains(ReportSections::WIND);
+
+                            if style == ReportStyle::Compact {
+                                assert!(text.starts_with("Завтра"), "{}: {}", case, text);
+                                assert!(!has(MISSING), "{}: {}", case, text);
+                                assert_eq!(has("💨"), wind && wind_max.is_some(), "{}: {}", case, text);
+                                continue;
+                            }
+
+                            assert_eq!(has("Ветер"), wind && (wind_max.is_some() || show_missing), "{}: {}", case, text);
+                            assert_eq!(has("Утро"), sections.contains(ReportSections::DAILY_TEMPS) && (morning.is_some() || show_missing), "{}: {}", case, text);
+                            // Вечерней температуры в сводке нет никогда
+                            assert_eq!(has("Вечер"), sections.contains(ReportSections::DAILY_TEMPS) && show_missing, "{}: {}", case, text);
+                            assert_eq!(
+                                has(MISSING),
+                                show_missing && (sections.contains(ReportSections::DAILY_TEMPS) || (wind && wind_max.is_none())),
+                                "{}: {}",
+                                case,
+                                text
+                            );
+                            assert!(!has("Осадки"), "{}: {}", case, text);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn times_of_day_line_skips_or_marks_missing_parts() {
+        let hide = options(ReportStyle::Normal, ReportSections::all(), false);
+        let show = options(ReportStyle::Normal, ReportSections::all(), true);
+        assert_eq!(times_of_day_line(None, None, None, hide), None);
+        assert_eq!(times_of_day_line(None, None, None, show).as_deref(), Some("Утро: Н/Д, День: Н/Д, Вечер: Н/Д"));
+        assert_eq!(times_of_day_line(Some(12.4), None, Some(-3.6), hide).as_deref(), Some("Утро: +12°C, Вечер: -4°C"));
+        let tenths = FormatOptions { precision: TemperaturePrecision::Tenths, ..hide };
+        assert_eq!(times_of_day_line(Some(12.44), None, None, tenths).as_deref(), Some("Утро: 12.4°C"));
+    }
+
+    // Пересчет в °F и мили в час во всех видах отчета; в демо-погоде +14°C (ощущается как +12°C)
+    #[test]
+    fn imperial_units_in_every_report() {
+        let imperial = |style| FormatOptions { units: Units::Imperial, ..options(style, ReportSections::all(), false) };
+        let metric_free = |text: &str| !text.contains("°C") && !text.contains("м/с");
+
+        let text = format_weather(&rainy_weather(Some(10_000), Some(9.0)), None, imperial(ReportStyle::Normal));
+        assert!(text.contains("+57°F (ощущается как +54°F)"), "{}", text);
+        assert!(text.contains("порывы до 20.1 миль/ч"), "{}", text);
+        assert!(metric_free(&text), "{}", text);
+
+        let text = format_weather(&rainy_weather(None, None), None, imperial(ReportStyle::Compact));
+        assert!(text.contains("+57°") && text.contains("миль/ч"), "{}", text);
+        assert!(metric_free(&text), "{}", text);
+
+        let text = format_tomorrow(&day_summary(Some(6.0), Some(9.0)), imperial(ReportStyle::Normal));
+        assert!(text.contains("от +46°F до +59°F"), "{}", text);
+        assert!(text.contains("до 13.4 миль/ч"), "{}", text);
+        assert!(metric_free(&text), "{}", text);
+
+        let text = format_tomorrow(&day_summary(Some(6.0), None), imperial(ReportStyle::Compact));
+        assert!(text.contains("+46°…+59°") && text.contains("💨13миль/ч"), "{}", text);
+
+        let text = format_weekly_table(&demo::forecast(NOW, 8), imperial(ReportStyle::Normal), NOW);
+        assert!(text.contains("миль/ч"), "{}", text);
+        assert!(metric_free(&text), "{}", text);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::storage::{UserSettings, UserStorage};
+use crate::utils;
+use log::{error, info, warn};
+use std::collections::HashMap;
+
+// Состояния ожидания ввода, которые умеет обрабатывать бот
+const KNOWN_STATES: &[&str] = &["waiting_for_time", "waiting_for_city"];
+
+// Сколько проблем перечисляем в ответе администратору (сообщение Telegram ограничено 4096 символами)
+const MAX_PROBLEMS_IN_REPORT: usize = 40;
+
+// Результат проверки хранилища
+pub struct FsckReport {
+    pub users_checked: usize,
+    pub problems: Vec<String>,
+    pub repaired: bool,
+}
+
+impl FsckReport {
+    pub fn render(&self) -> String {
+        if self.problems.is_empty() {
+            return format!("✅ Проверено записей: {}. Проблем не найдено", self.users_checked);
+        }
+
+        let mut text = format!(
+            "🩺 Проверено записей: {}, найдено проблем: {}\n\n",
+            self.users_checked,
+            self.problems.len()
+        );
+        for problem in self.problems.iter().take(MAX_PROBLEMS_IN_REPORT) {
+            text.push_str("• ");
+            text.push_str(problem);
+            text.push('\n');
+        }
+        if self.problems.len() > MAX_PROBLEMS_IN_REPORT {
+            text.push_str(&format!("…и еще {}\n", self.problems.len() - MAX_PROBLEMS_IN_REPORT));
+        }
+        text.push('\n');
+        text.push_str(if self.repaired {
+            "🔧 Проблемы исправлены и сохранены"
+        } else {
+            "Это пробный прогон, ничего не изменено. Исправить: /admin fsck repair"
+        });
+        text
+    }
+}
+
+// Проверяет записи хранилища; при repair = true исправляет найденное и сохраняет результат
+pub async fn check(storage: &dyn UserStorage, repair: bool) -> FsckReport {
+    let users = match storage.get_all_users().await {
+        Ok(users) => users,
+        Err(e) => {
+            error!("Проверка хранилища не выполнена: {}", e);
+            return FsckReport { users_checked: 0, problems: vec![format!("хранилище недоступно: {}", e)], repaired: false };
+        }
+    };
+    let users_checked = users.len();
+    let mut problems = Vec::new();
+
+    // Повторяющиеся user_id: оставляем последнюю запись как самую свежую
+    let mut last_index: HashMap<i64, usize> = HashMap::new();
+    for (index, user) in users.iter().enumerate() {
+        if last_index.insert(user.user_id, index).is_some() {
+            problems.push(format!("ID {}: повторяющаяся запись", user.user_id));
+        }
+    }
+
+    let mut repaired_users: Vec<UserSettings> = users
+        .into_iter()
+        .enumerate()
+        .filter(|(index, user)| last_index.get(&user.user_id) == Some(index))
+        .map(|(_, user)| user)
+        .collect();
+
+    for user in repaired_users.iter_mut() {
+        check_user(user, &mut problems);
+    }
+
+    let mut repaired = false;
+    if repair && !problems.is_empty() {
+        match storage.replace_all(repaired_users).await {
+            Ok(()) => {
+                repaired = true;
+                info!("Хранилище исправлено, устранено проблем: {}", problems.len());
+            }
+            Err(e) => error!("Не удалось сохранить исправленное хранилище: {}", e),
+        }
+    }
+
+    FsckReport {
+        users_checked,
+        problems,
+        repaired,
+    }
+}
+
+// Проверка хранилища при запуске: только сообщает о проблемах в лог
+pub async fn startup_check(storage: &dyn UserStorage) {
+    let report = check(storage, false).await;
+    if !report.problems.is_empty() {
+        warn!(
+            "В хранилище найдено проблем: {}. Подробности и исправление: /admin fsck",
+            report.problems.len()
+        );
+        for problem in &report.problems {
+            warn!("fsck: {}", problem);
+        }
+    }
+}
+
+// Проверяет одну запись и сразу исправляет ее копию
+fn check_user(user: &mut UserSettings, problems: &mut Vec<String>) {
+    let user_id = user.user_id;
+
+    if let Some(time) = user.notification_time.clone() {
+        match utils::normalize_time(&time) {
+            Some(normalized) if normalized == time => {}
+            Some(normalized) => {
+                problems.push(format!("ID {}: время {} записано не в формате ЧЧ:ММ", user_id, time));
+                user.notification_time = Some(normalized);
+            }
+            None => {
+                problems.push(format!("ID {}: некорректное время уведомлений {}", user_id, time));
+                user.notification_time = None;
+            }
+        }
+    }
+
+    if user.cities.iter().any(|city| city.trim().is_empty()) {
+        problems.push(format!("ID {}: пустое название города", user_id));
+        user.cities.retain(|city| !city.trim().is_empty());
+    }
+
+    if let Some(state) = user.state.clone() {
+        if !KNOWN_STATES.contains(&state.as_str()) {
+            problems.push(format!("ID {}: неизвестное состояние {}", user_id, state));
+            user.state = None;
+        }
+    }
+
+    let orphan_input = user
+        .last_input
+        .as_ref()
+        .map(|input| !KNOWN_STATES.contains(&input.state.as_str()))
+        .unwrap_or(false);
+    if orphan_input {
+        problems.push(format!("ID {}: последний ввод ссылается на неизвестное состояние", user_id));
+        user.last_input = None;
+    }
+}
@@ -0,0 +1,1695 @@
+use crate::callback_answer::CallbackAnswer;
+use crate::config::{Branding, Config};
+use crate::metrics::metrics;
+use crate::reengagement::ReengagementStore;
+use crate::formatter::{ForecastLayout, FormatOptions};
+use crate::locale::{self, Locale};
+use crate::outbox::Outbox;
+use crate::plugins::{PluginCall, PluginContext, PluginRegistry};
+use crate::storage::{DeliveryWindow, LastInput, StorageError, UserSettings, UserStorage};
+use crate::{
+    activity, admin, all_cities, capabilities, card, cities, chart, data_export, delete_me, delivery_history, demo, error_throttle, forecast_updates, interval, night_mode, onboarding, privacy, records, reengagement,
+    moderation, scheduler, sections, settings_file, small_talk, smart_time, templates, timezone, transfer, household, travel, utils, weather, webapp,
+};
+use std::sync::Arc;
+use teloxide::prelude::*;
+use log::{info, error, warn};
+use teloxide::utils::command::BotCommands;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use teloxide::types::CallbackQuery;
+use teloxide::types::ChatMemberUpdated;
+use teloxide::payloads::SendMessage;
+use teloxide::requests::JsonRequest;
+use futures::FutureExt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+// Сколько ждем обработчик сообщения пользователя: зависший запрос погоды не должен
+// бесконечно задерживать ответы этому пользователю
+const HANDLER_TIMEOUT: Duration = Duration::from_secs(20);
+
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Доступные команды:")]
+pub(crate) enum Command {
+    #[command(description = "начать работу с ботом")]
+    Start(String),
+    #[command(description = "показать это сообщение")]
+    Help,
+    #[command(description = "установить город (например, /city Москва)")]
+    City(String),
+    #[command(description = "несколько городов в одном прогнозе (/cities add Казань, /cities remove Казань)")]
+    Cities(String),
+    #[command(description = "установить время уведомлений (например, /time 08:00)")]
+    Time(String),
+    #[command(description = "узнать текущую погоду (/weather all - во всех сохраненных городах, /weather Демо - пример отчета)")]
+    Weather(String),
+    #[command(description = "прогноз погоды на неделю (/forecast table - таблицей)")]
+    Forecast(String),
+    #[command(description = "текущая погода картинкой")]
+    Card,
+    #[command(description = "температура на сутки во всех недавних городах на одном графике")]
+    CompareChart,
+    #[command(description = "самый теплый и самый холодный день в городе по данным бота")]
+    Records,
+    #[command(description = "временно сменить город на время поездки (например, /travel Сочи 25.12)")]
+    Travel(String),
+    #[command(description = "сообщать, если прогноз на день изменился (/updates on или off)")]
+    Updates(String),
+    #[command(description = "присылать прогноз раньше, если ожидается непогода (/smarttime on или off)")]
+    SmartTime(String),
+    #[command(description = "прогноз каждые N часов (/every 3 9 18 - каждые 3 часа с 09 до 18)")]
+    Every(String),
+    #[command(description = "вечером присылать прогноз на завтра (/nightmode on или off)")]
+    NightMode(String),
+    #[command(description = "выбрать разделы отчета о погоде")]
+    Sections,
+    #[command(description = "стиль отчета о погоде (/style compact или normal)")]
+    Style(String),
+    #[command(description = "точность температуры (/precision whole - целые градусы, tenths - с десятыми)")]
+    Precision(String),
+    #[command(description = "формат дат и времени (/locale ru, en-us, en-gb, de или fr)")]
+    Locale(String),
+    #[command(description = "все настройки на одном экране (/settings export или import - файлом)")]
+    Settings(String),
+    #[command(description = "что бот хранит обо мне")]
+    Privacy,
+    #[command(description = "прислать все мои данные JSON-файлом")]
+    Export,
+    #[command(rename = "delete_me", description = "удалить все мои данные")]
+    DeleteMe,
+    #[command(description = "последние уведомления и доставлены ли они")]
+    History,
+    #[command(description = "перенести настройки в другой аккаунт Telegram")]
+    Transfer(String),
+    #[command(description = "общий утренний прогноз для семьи")]
+    Household(String),
+    #[command(description = "off")]
+    Admin(String),
+}
+
+// Префикс данных кнопок быстрого переключения на недавний город
+const SWITCH_CITY_PREFIX: &str = "switch_";
+
+// Вспомогательная функция для экранирования специальных символов Markdown
+pub fn escape_markdown_v2(text: &str) -> String {
+    // Создаем новую строку с запасом для экранирующих символов
+    let mut result = String::with_capacity(text.len() * 2);
+    
+    for ch in text.chars() {
+        // Особая обработка для восклицательного знака - двойной escaping
+        if ch == '!' {
+            result.push_str("\\\\!");
+        }
+        // Специальные символы MarkdownV2, которые нужно экранировать
+        else if ['_', '*', '[', ']', '(', ')', '~', '`', '>', '#', '+', '-', '=', '|', '{', '}', '.'].contains(&ch) {
+            result.push('\\');
+            result.push(ch);
+        } 
+        else {
+            result.push(ch);
+        }
+    }
+    
+    result
+}
+
+// Сообщение в MarkdownV2 с пользовательским текстом (городом, именем): предпросмотр
+// отключен, чтобы вставленная пользователем ссылка не разворачивалась в карточку сайта
+pub(crate) fn send_echo(bot: &Bot, chat_id: ChatId, text: String) -> JsonRequest<SendMessage> {
+    bot.send_message(chat_id, text)
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .disable_web_page_preview(true)
+}
+
+// Запись пользователя user_id (или новая, если ее еще нет) для обработчика в чате chat_id.
+// Если хранилище не ответило, извиняется и возвращает None: продолжать с настройками
+// по умолчанию нельзя, следующее сохранение затерло бы настоящую запись
+pub(crate) async fn load_or_apologize(bot: &Bot, chat_id: ChatId, storage: &dyn UserStorage, user_id: i64) -> ResponseResult<Option<UserSettings>> {
+    match storage.get_user(user_id).await {
+        Ok(user) => Ok(Some(user.unwrap_or_else(|| UserSettings::new(user_id)))),
+        Err(e) => {
+            warn!("Не удалось загрузить настройки пользователя ID: {}: {}", user_id, e);
+            bot.send_message(chat_id, templates::text("storage.read_failed"))
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+// Сохраняет изменение настроек, которое пользователь сейчас подтвердит. Если запись не удалась,
+// просит повторить позже и возвращает false: подтверждать изменение, которое пропадет
+// при перезапуске, нельзя
+pub(crate) async fn save_or_apologize(bot: &Bot, chat_id: ChatId, storage: &dyn UserStorage, user: UserSettings) -> ResponseResult<bool> {
+    match storage.save_user(user).await {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            warn!("Не удалось сохранить настройки пользователя ID: {}: {}", chat_id, e);
+            bot.send_message(chat_id, templates::text("storage.save_failed"))
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            Ok(false)
+        }
+    }
+}
+
+// Ответ на изменение настройки: подтверждение, если изменение записано, иначе просьба повторить позже
+pub(crate) fn confirm_saved(saved: Result<(), StorageError>, confirmation: String) -> String {
+    match saved {
+        Ok(()) => confirmation,
+        Err(e) => {
+            warn!("Изменение настроек не сохранено: {}", e);
+            templates::text("storage.save_failed")
+        }
+    }
+}
+
+// Выполняет обработчик обновления, перехватывая панику: сбой при обработке
+// одного сообщения не должен останавливать обработку остальных
+async fn run_isolated<F>(handler_name: &str, handler: F) -> ResponseResult<()>
+where
+    F: Future<Output = ResponseResult<()>>,
+{
+    match AssertUnwindSafe(handler).catch_unwind().await {
+        Ok(result) => result,
+        Err(_) => {
+            error!("Паника в обработчике {}, обновление пропущено", handler_name);
+            metrics().increment("handler_panics_total");
+            Ok(())
+        }
+    }
+}
+
+// То же, что run_isolated, но с ограничением времени: если обработчик не уложился,
+// он прерывается, а пользователю сообщаем, что сервис отвечает медленно
+async fn run_with_timeout<F>(bot: Bot, chat_id: Option<ChatId>, handler_name: &str, handler: F) -> ResponseResult<()>
+where
+    F: Future<Output = ResponseResult<()>>,
+{
+    match tokio::time::timeout(HANDLER_TIMEOUT, run_isolated(handler_name, handler)).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!(
+                "Обработчик {} не уложился в {} с (чат {:?}), обновление прервано",
+                handler_name,
+                HANDLER_TIMEOUT.as_secs(),
+                chat_id.map(|chat_id| chat_id.0)
+            );
+            metrics().increment("handler_timeouts_total");
+            if let Some(chat_id) = chat_id {
+                bot.send_message(chat_id, templates::text("handler.timeout"))
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Дерево обработчиков обновлений: команды, текстовые сообщения и колбэки инлайн-клавиатур
+pub fn build_handler() -> teloxide::dispatching::UpdateHandler<teloxide::RequestError> {
+    // Настраиваем обработчик команд
+    let command_handler = Update::filter_message()
+        .branch(
+            dptree::entry()
+                .filter_command::<Command>()
+                .endpoint(handle_commands),
+        )
+        // Команды подключенных плагинов
+        .branch(
+            dptree::filter_map(|msg: Message, plugins: Arc<PluginRegistry>| plugins.parse(&msg))
+                .endpoint(handle_plugin_command),
+        )
+        .branch(dptree::endpoint(handle_message));
+    
+    // Исправленные сообщения: повторно обрабатываем ответы на запросы ввода и запросы погоды
+    let edited_handler = Update::filter_edited_message()
+        .branch(
+            dptree::entry()
+                .filter_command::<Command>()
+                .endpoint(handle_edited_command),
+        )
+        .branch(dptree::endpoint(handle_edited_message));
+    
+    // Добавляем обработчик для колбэков от инлайн-клавиатуры
+    let callback_handler = Update::filter_callback_query()
+        .branch(dptree::endpoint(handle_callback_query));
+
+    // Блокировка/разблокировка бота пользователем, добавление/удаление бота из групп
+    let my_chat_member_handler = Update::filter_my_chat_member()
+        .endpoint(handle_my_chat_member);
+    
+    // Объединяем обработчики; перед любым из них отмечаем активность пользователя
+    dptree::entry()
+        .inspect(activity::track_activity)
+        .branch(command_handler)
+        .branch(edited_handler)
+        .branch(callback_handler)
+        .branch(my_chat_member_handler)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_commands(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+    reengagement_store: Arc<ReengagementStore>,
+    outbox: Arc<Outbox>,
+) -> ResponseResult<()> {
+    // Команды администратора (рассылки, импорт, проверка хранилища) бывают долгими - их не ограничиваем
+    if matches!(cmd, Command::Admin(_)) {
+        return run_isolated(
+            "команд администратора",
+            process_command(bot, msg, cmd, storage, weather_client, config, reengagement_store, outbox),
+        )
+        .await;
+    }
+    let chat_id = msg.chat.id;
+    run_with_timeout(
+        bot.clone(),
+        Some(chat_id),
+        "команд",
+        process_command(bot, msg, cmd, storage, weather_client, config, reengagement_store, outbox),
+    )
+    .await
+}
+
+async fn handle_plugin_command(
+    bot: Bot,
+    msg: Message,
+    call: PluginCall,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    let username = msg.from()
+        .and_then(|user| user.username.clone())
+        .unwrap_or_else(|| format!("ID: {}", msg.chat.id.0));
+    info!("Пользователь @{} вызвал команду плагина /{}: {}", username, call.plugin.command(), call.args);
+
+    let ctx = PluginContext {
+        bot: &bot,
+        msg: &msg,
+        storage: &*storage,
+        weather_client: &weather_client,
+        config: &config,
+    };
+    run_with_timeout(bot.clone(), Some(msg.chat.id), "плагинов", call.plugin.handle(ctx, &call.args)).await
+}
+
+async fn handle_message(
+    bot: Bot,
+    msg: Message,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    run_with_timeout(bot.clone(), Some(chat_id), "сообщений", process_message(bot, msg, storage, weather_client, config)).await
+}
+
+async fn handle_callback_query(
+    bot: Bot,
+    q: CallbackQuery,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+    outbox: Arc<Outbox>,
+) -> ResponseResult<()> {
+    let chat_id = q.message.as_ref().map(|message| message.chat.id);
+    let answer = CallbackAnswer::new(&bot, &q.id);
+    let result = run_with_timeout(
+        bot.clone(),
+        chat_id,
+        "колбэков",
+        process_callback_query(bot, q, &answer, storage, weather_client, config, outbox),
+    )
+    .await;
+    // На любое нажатие отвечаем, иначе "часики" на кнопке так и будут крутиться
+    answer.finish().await;
+    result
+}
+
+async fn handle_my_chat_member(update: ChatMemberUpdated, storage: Arc<dyn UserStorage>) -> ResponseResult<()> {
+    run_isolated("изменений участия в чатах", process_my_chat_member(update, storage)).await
+}
+
+async fn handle_edited_command(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    run_with_timeout(
+        bot.clone(),
+        Some(chat_id),
+        "исправленных команд",
+        process_edited_command(bot, msg, cmd, storage, weather_client, config),
+    )
+    .await
+}
+
+async fn handle_edited_message(
+    bot: Bot,
+    msg: Message,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    run_with_timeout(
+        bot.clone(),
+        Some(chat_id),
+        "исправленных сообщений",
+        process_edited_message(bot, msg, storage, weather_client, config),
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_command(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+    reengagement_store: Arc<ReengagementStore>,
+    outbox: Arc<Outbox>,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let username = msg.from()
+        .and_then(|user| user.username.clone())
+        .unwrap_or_else(|| format!("ID: {}", user_id));
+    
+    // Логируем полученную команду
+    match &cmd {
+        Command::Start(_) => info!("Пользователь @{} запустил бота", username),
+        Command::Help => info!("Пользователь @{} запросил помощь", username),
+        Command::City(city) => info!("Пользователь @{} устанавливает город: {}", username, city),
+        Command::Time(time) => info!("Пользователь @{} устанавливает время уведомлений: {}", username, time),
+        Command::Weather(args) => info!("Пользователь @{} запрашивает погоду {}", username, args),
+        Command::Forecast(args) => info!("Пользователь @{} запрашивает прогноз на неделю {}", username, args),
+        Command::Card => info!("Пользователь @{} запрашивает карточку погоды", username),
+        Command::CompareChart => info!("Пользователь @{} запрашивает график сравнения городов", username),
+        Command::Records => info!("Пользователь @{} запрашивает рекорды погоды", username),
+        Command::Travel(args) => info!("Пользователь @{} настраивает поездку: {}", username, args),
+        Command::Updates(args) => info!("Пользователь @{} настраивает обновления прогноза: {}", username, args),
+        Command::SmartTime(args) => info!("Пользователь @{} настраивает умное время: {}", username, args),
+        Command::Cities(args) => info!("Пользователь @{} управляет городами прогноза: {}", username, args),
+        Command::Every(args) => info!("Пользователь @{} настраивает прогноз по интервалу: {}", username, args),
+        Command::NightMode(args) => info!("Пользователь @{} настраивает ночной режим: {}", username, args),
+        Command::Sections => info!("Пользователь @{} настраивает разделы отчета", username),
+        Command::Style(args) => info!("Пользователь @{} выбирает стиль отчета: {}", username, args),
+        Command::Precision(args) => info!("Пользователь @{} выбирает точность температуры: {}", username, args),
+        Command::Locale(args) => info!("Пользователь @{} выбирает формат дат: {}", username, args),
+        Command::Settings(args) => info!("Пользователь @{} открывает настройки: {}", username, args),
+        Command::Privacy => info!("Пользователь @{} запросил сводку о хранимых данных", username),
+        Command::Export => info!("Пользователь @{} запросил выгрузку своих данных", username),
+        Command::DeleteMe => info!("Пользователь @{} запросил удаление своих данных", username),
+        Command::History => info!("Пользователь @{} запросил историю уведомлений", username),
+        Command::Transfer(_) => info!("Пользователь @{} переносит настройки", username),
+        Command::Household(args) => info!("Пользователь @{} управляет семьей: {}", username, args),
+        Command::Admin(args) => info!("Пользователь @{} выполняет команду администратора: {}", username, args),
+    }
+
+    // Команду, для которой у источника погоды нет данных, не выполняем, а объясняем почему
+    let command = capabilities::command_name(msg.text().unwrap_or(""));
+    if !capabilities::supports_command(weather_client.capabilities(), command) {
+        info!("Команда /{} не поддерживается источником погоды {}", command, weather::PROVIDER_NAME);
+        bot.send_message(msg.chat.id, templates::render("provider.unsupported", &[("provider", weather::PROVIDER_NAME)]))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+    
+    match cmd {
+        Command::Start(payload) => {
+            let payload = payload.trim();
+            if let Some(code) = payload.strip_prefix(transfer::START_PREFIX) {
+                // Ссылка переноса настроек из другого аккаунта
+                transfer::handle_transfer_code(&bot, &msg, &*storage, code).await?;
+            } else if let Some(code) = payload.strip_prefix(household::START_PREFIX) {
+                // Приглашение в семью
+                household::handle_invite_link(&bot, &msg, &*storage, code).await?;
+            } else {
+                send_start_message(&bot, &msg, &*storage, &config).await?;
+            }
+        }
+        Command::Help => {
+            send_help(&bot, &msg, &*storage).await?;
+        }
+        Command::City(city) => {
+            set_city(&bot, &msg, &*storage, &weather_client, &config, &city).await?;
+        }
+        Command::Time(time) => {
+            set_time(&bot, &msg, &*storage, &weather_client, &config, &time).await?;
+        }
+        Command::Weather(args) => {
+            send_current_weather(&bot, &msg, &*storage, &weather_client, &args).await?;
+        }
+        Command::Forecast(args) => {
+            send_weekly_forecast(&bot, &msg, &*storage, &weather_client, &args).await?;
+        }
+        Command::Card => {
+            card::handle_card_command(&bot, &msg, &*storage, &weather_client).await?;
+        }
+        Command::CompareChart => {
+            chart::handle_compare_chart_command(&bot, &msg, &*storage, &weather_client).await?;
+        }
+        Command::Records => {
+            records::handle_records_command(&bot, &msg, &*storage, &weather_client).await?;
+        }
+        Command::Travel(args) => {
+            travel::handle_travel_command(&bot, &msg, &*storage, &config, &args).await?;
+        }
+        Command::Updates(args) => {
+            forecast_updates::handle_updates_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::SmartTime(args) => {
+            smart_time::handle_smart_time_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Cities(args) => {
+            cities::handle_cities_command(&bot, &msg, &*storage, &weather_client, &config, &args).await?;
+        }
+        Command::Every(args) => {
+            interval::handle_every_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::NightMode(args) => {
+            night_mode::handle_night_mode_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Sections => {
+            sections::handle_sections_command(&bot, &msg, &*storage).await?;
+        }
+        Command::Style(args) => {
+            sections::handle_style_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Precision(args) => {
+            sections::handle_precision_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Locale(args) => {
+            locale::handle_locale_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Settings(args) => match args.trim().to_lowercase().as_str() {
+            action @ ("export" | "import") => {
+                settings_file::handle_settings_file_command(&bot, &msg, &*storage, &weather_client, &config, action).await?;
+            }
+            _ => webapp::handle_settings_command(&bot, &msg, &config).await?,
+        },
+        Command::Privacy => {
+            privacy::handle_privacy_command(&bot, &msg, &*storage, outbox.history()).await?;
+        }
+        Command::Export => {
+            data_export::handle_export_command(&bot, &msg, &*storage, outbox.history()).await?;
+        }
+        Command::DeleteMe => {
+            delete_me::handle_delete_me_command(&bot, &msg).await?;
+        }
+        Command::History => {
+            delivery_history::handle_history_command(&bot, &msg, &*storage, outbox.history()).await?;
+        }
+        Command::Transfer(args) => {
+            transfer::handle_transfer_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Household(args) => {
+            household::handle_household_command(&bot, &msg, &*storage, &args).await?;
+        }
+        Command::Admin(args) => {
+            admin::handle_admin_command(&bot, &msg, &*storage, &config, &reengagement_store, &outbox, &weather_client, &args).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn process_message(
+    bot: Bot,
+    msg: Message,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    if let Some(text) = msg.text() {
+        // Логируем текстовые сообщения
+        let user_id = msg.chat.id.0;
+        let username = msg.from()
+            .and_then(|user| user.username.clone())
+            .unwrap_or_else(|| format!("ID: {}", user_id));
+        
+        info!("Пользователь @{} отправил сообщение: {}", username, text);
+        
+        // Получаем данные пользователя для проверки состояния
+        let Some(user_data) = load_or_apologize(&bot, msg.chat.id, &*storage, user_id).await? else {
+            return Ok(());
+        };
+        
+        // Проверяем состояние пользователя
+        if let Some(state) = &user_data.state {
+            if state == "waiting_for_time" {
+                // Пользователь в режиме ввода времени
+                // Проверяем формат введенного времени и приводим его к ЧЧ:ММ
+                if let Some(time_input) = utils::normalize_time(text) {
+                    // Формируем сообщение об успешной установке времени
+                    let (time_input, message) = confirm_notification_time(time_input, user_data.cute_mode, &config);
+
+                    // Время корректное, сохраняем
+                    let mut updated_user = user_data.clone();
+                    updated_user.notification_time = Some(time_input.clone());
+                    updated_user.delivery_window = None;
+                    updated_user.state = None; // Сбрасываем состояние ожидания
+                    updated_user.last_input = Some(LastInput { message_id: msg.id.0, state: state.clone() });
+                    let preview = user_data.notification_time.is_none().then(|| updated_user.clone());
+                    if !save_or_apologize(&bot, msg.chat.id, &*storage, updated_user).await? {
+                        return Ok(());
+                    }
+                    
+                    bot.send_message(msg.chat.id, message)
+                        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                        .await?;
+                    
+                    info!("Пользователь @{} успешно установил время уведомлений: {}", username, time_input);
+                    if let Some(user) = preview {
+                        send_notification_preview(&bot, msg.chat.id, &weather_client, &config, &user).await?;
+                    }
+                    return Ok(());
+                } else {
+                    // Некорректный формат времени
+                    bot.send_message(
+                        msg.chat.id, 
+                        templates::text("time.invalid")
+                    )
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .await?;
+                    return Ok(());
+                }
+            } else if state == "waiting_for_city" {
+                // Пользователь в режиме ввода города
+                let city_input = moderation::sanitize(text);
+                let city_input = city_input.as_str();
+
+                if city_input.chars().count() > utils::MAX_CITY_LENGTH {
+                    bot.send_message(msg.chat.id, too_long_message(utils::MAX_CITY_LENGTH))
+                        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                        .await?;
+                    return Ok(());
+                }
+
+                // Недопустимое название не сохраняем и не повторяем в ответе
+                if moderation::is_blocked(city_input, &config.blocklist) {
+                    warn!("Пользователь @{} ввел недопустимое название города", username);
+                    bot.send_message(msg.chat.id, templates::text("input.blocked"))
+                        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                        .await?;
+                    return Ok(());
+                }
+                
+                // Проверяем, что ввод не пустой
+                if !city_input.is_empty() {
+                    // Город введен, сохраняем
+                    let mut updated_user = user_data.clone();
+                    updated_user.set_city(city_input);
+                    updated_user.remember_city(city_input);
+                    updated_user.state = None; // Сбрасываем состояние ожидания
+                    updated_user.last_input = Some(LastInput { message_id: msg.id.0, state: state.clone() });
+                    let timezone_notice = timezone::infer_for_city(&weather_client, &mut updated_user, city_input).await;
+                    if !save_or_apologize(&bot, msg.chat.id, &*storage, updated_user).await? {
+                        return Ok(());
+                    }
+                    
+                    let is_cute_mode = user_data.cute_mode;
+                    
+                    // Формируем сообщение об успешной установке города
+                    let message = if is_cute_mode {
+                        templates::render("city.set_cute", &[("city", &escape_markdown_v2(&utils::echo(city_input)))])
+                    } else {
+                        templates::render("city.set", &[("city", &escape_markdown_v2(&utils::echo(city_input)))])
+                    };
+                    
+                    send_echo(&bot, msg.chat.id, message).await?;
+                    timezone::send_notice(&bot, msg.chat.id, timezone_notice).await?;
+                    
+                    info!("Пользователь @{} успешно установил город: {}", username, city_input);
+                    return Ok(());
+                } else {
+                    // Пустой ввод города
+                    bot.send_message(
+                        msg.chat.id, 
+                        templates::text("city.empty")
+                    )
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+        
+        // Секретный код для активации "милого режима"
+        // Используем необычную комбинацию символов, которую сложно угадать случайно
+        if text.trim() == config.branding.cute_mode_trigger {
+            // Включаем милый режим
+            let mut user = user_data;
+            user.cute_mode = true;
+            if !save_or_apologize(&bot, msg.chat.id, &*storage, user).await? {
+                return Ok(());
+            }
+            
+            bot.send_message(
+                msg.chat.id, 
+                templates::text("cute.on")
+            )
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+            
+            info!("Пользователь @{} активировал милый режим", username);
+            return Ok(());
+        }
+        
+        // Код для отключения "милого режима"
+        if text.trim() == "/std" {
+            // Отключаем милый режим, если он был включен
+            let mut user = user_data.clone();
+            if user.cute_mode {
+                user.cute_mode = false;
+                if !save_or_apologize(&bot, msg.chat.id, &*storage, user).await? {
+                    return Ok(());
+                }
+                
+                bot.send_message(
+                    msg.chat.id, 
+                    templates::text("cute.off")
+                )
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+                
+                info!("Пользователь @{} переключился на стандартный режим", username);
+                return Ok(());
+            }
+        }
+        
+        // В милом режиме отвечаем на бытовые фразы вроде "спасибо" и "доброе утро"
+        let cute_mode = user_data.cute_mode;
+        if let Some(intent) = small_talk::match_intent(text).filter(|_| cute_mode) {
+            bot.send_message(msg.chat.id, templates::text(intent))
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            info!("Пользователь @{} получил ответ на фразу: {}", username, intent);
+            return Ok(());
+        }
+
+        // Стандартный ответ на прочие сообщения
+        bot.send_message(msg.chat.id, templates::text("unknown"))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+    }
+    Ok(())
+}
+
+// Ответ на слишком длинный ввод
+fn too_long_message(max: usize) -> String {
+    templates::render("input.too_long", &[("max", &max.to_string())])
+}
+
+// Исправленная команда выполняется заново, если это установка города/времени или запрос погоды.
+// Остальные команды при редактировании не повторяем
+async fn process_edited_command(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+
+    match cmd {
+        Command::City(city) if !city.trim().is_empty() => {
+            info!("Пользователь ID: {} исправил команду установки города: {}", user_id, city);
+            set_city(&bot, &msg, &*storage, &weather_client, &config, &city).await?;
+        }
+        Command::Time(time) if !time.trim().is_empty() => {
+            info!("Пользователь ID: {} исправил команду установки времени: {}", user_id, time);
+            set_time(&bot, &msg, &*storage, &weather_client, &config, &time).await?;
+        }
+        Command::Weather(args) => {
+            info!("Пользователь ID: {} исправил запрос погоды", user_id);
+            send_current_weather(&bot, &msg, &*storage, &weather_client, &args).await?;
+        }
+        Command::Forecast(args) => {
+            info!("Пользователь ID: {} исправил запрос прогноза на неделю", user_id);
+            send_weekly_forecast(&bot, &msg, &*storage, &weather_client, &args).await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// Исправленный текст обрабатываем, если бот все еще ждет ввода или если исправлен
+// последний ответ на запрос ввода (например, опечатка в названии города)
+async fn process_edited_message(
+    bot: Bot,
+    msg: Message,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+
+    let Some(mut user) = storage.get_user(user_id).await.ok().flatten() else {
+        return Ok(());
+    };
+
+    if user.state.is_none() {
+        match &user.last_input {
+            Some(last_input) if last_input.message_id == msg.id.0 => {
+                info!("Пользователь ID: {} исправил ответ на запрос ввода ({})", user_id, last_input.state);
+                // Возвращаем состояние ожидания, чтобы исправленное значение прошло обычную проверку
+                user.state = Some(last_input.state.clone());
+                storage.save_user_or_log(user).await;
+            }
+            _ => return Ok(()),
+        }
+    }
+
+    process_message(bot, msg, storage, weather_client, config).await
+}
+
+// Синхронизирует хранилище с тем, может ли бот писать в чат: личные чаты помечаются
+// неактивными при блокировке бота, группы регистрируются и удаляются вместе с ботом
+async fn process_my_chat_member(update: ChatMemberUpdated, storage: Arc<dyn UserStorage>) -> ResponseResult<()> {
+    let chat_id = update.chat.id.0;
+    let is_present = update.new_chat_member.is_present();
+
+    if update.chat.is_private() {
+        let mut user = match storage.get_user(chat_id).await {
+            Ok(user) => user.unwrap_or_else(|| UserSettings::new(chat_id)),
+            Err(e) => {
+                error!("Не удалось обновить статус чата ID: {}: {}", chat_id, e);
+                return Ok(());
+            }
+        };
+        if user.active != is_present {
+            user.active = is_present;
+            storage.save_user_or_log(user).await;
+
+            if is_present {
+                info!("Пользователь ID: {} разблокировал бота", chat_id);
+            } else {
+                info!("Пользователь ID: {} заблокировал бота, уведомления приостановлены", chat_id);
+            }
+        }
+    } else if is_present {
+        let title = update.chat.title().unwrap_or("без названия");
+        match storage.get_user(chat_id).await {
+            Ok(Some(mut chat)) => {
+                if !chat.active {
+                    chat.active = true;
+                    storage.save_user_or_log(chat).await;
+                }
+            }
+            Ok(None) => {
+                storage.save_user_or_log(UserSettings::new(chat_id)).await;
+                info!("Бот добавлен в группу \"{}\" (ID: {}), группа зарегистрирована", title, chat_id);
+            }
+            Err(e) => error!("Не удалось зарегистрировать группу ID: {}: {}", chat_id, e),
+        }
+    } else {
+        match storage.delete_user(chat_id).await {
+            Ok(()) => info!("Бот удален из группы ID: {}, настройки группы удалены", chat_id),
+            Err(e) => warn!("Бот удален из группы ID: {}, но настройки группы не удалены: {}", chat_id, e),
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_start_message(bot: &Bot, msg: &Message, storage: &dyn UserStorage, config: &Config) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    
+    // Получаем или создаем настройки пользователя
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    
+    let mut changed = false;
+
+    // Принудительно устанавливаем стандартный режим при команде /start
+    if user.cute_mode {
+        user.cute_mode = false;
+        changed = true;
+    }
+
+    // Формат дат и времени по языку Telegram, пока пользователь не выбрал его сам
+    if user.locale.is_none() {
+        let detected = msg.from()
+            .and_then(|u| u.language_code.as_deref())
+            .and_then(Locale::from_language_code);
+        if detected.is_some() {
+            user.locale = detected;
+            changed = true;
+        }
+    }
+
+    // Запоминаем первый запуск, чтобы напомнить о незавершенной настройке
+    if user.started_at.is_none() {
+        user.started_at = Some(chrono::Utc::now());
+        changed = true;
+    }
+
+    if changed {
+        storage.save_user_or_log(user).await;
+    }
+    
+    // Всегда отправляем стандартное сообщение при /start
+    let standard_text = match &config.branding.start_text {
+        Some(text) => text.clone(),
+        None => templates::render("start", &[("bot_name", &escape_markdown_v2(&config.branding.bot_name))]),
+    };
+
+    // Отправляем приветственное сообщение
+    bot.send_message(msg.chat.id, standard_text)
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+    
+    // Отправляем дополнительное сообщение с подсказкой
+    bot.send_message(
+        msg.chat.id,
+        templates::text("start.hint")
+    ).await?;
+    
+    Ok(())
+}
+
+async fn send_help(bot: &Bot, msg: &Message, storage: &dyn UserStorage) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    
+    // Получаем настройки пользователя
+    // Если хранилище не ответило, показываем обычную справку
+    let user = storage.get_user(user_id).await.ok().flatten();
+    let cute_mode = user.map(|u| u.cute_mode).unwrap_or(false);
+    
+    // Текст справки в зависимости от режима
+    let help_text = if cute_mode {
+        templates::text("help.cute")
+    } else {
+        templates::text("help")
+    };
+
+    bot.send_message(msg.chat.id, help_text)
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+async fn set_city(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &weather::WeatherClient,
+    config: &Config,
+    city_arg: &str,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let username = msg.from()
+        .and_then(|user| user.username.clone())
+        .unwrap_or_else(|| format!("ID: {}", user_id));
+    
+    // Если аргумент пустой, показываем клавиатуру выбора города
+    if city_arg.trim().is_empty() {
+        info!("Пользователь @{} запросил список городов", username);
+        // Без недавних городов меню все равно полезно, поэтому сбой чтения не мешает его показать
+        let user = storage.get_user(user_id).await.ok().flatten();
+        let language = msg.from().and_then(|u| u.language_code.as_deref());
+        bot.send_message(
+            msg.chat.id, 
+            templates::text("city.choose")
+        )
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .reply_markup(get_city_keyboard(&config.branding, user.as_ref(), language))
+        .await?;
+        return Ok(());
+    }
+    
+    // Специальная обработка для колбэка "manual"
+    if city_arg.trim() == "manual" {
+        bot.send_message(
+            msg.chat.id, 
+            "✏️ Пожалуйста, введите название вашего города после команды, например:\n/city Москва"
+        ).await?;
+        return Ok(());
+    }
+
+    if city_arg.trim().chars().count() > utils::MAX_CITY_LENGTH {
+        bot.send_message(msg.chat.id, too_long_message(utils::MAX_CITY_LENGTH))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    // Недопустимое название не сохраняем и не повторяем в ответе
+    let Some(city) = moderation::clean_input(city_arg, &config.blocklist) else {
+        warn!("Пользователь @{} ввел недопустимое название города", username);
+        bot.send_message(msg.chat.id, templates::text("input.blocked"))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+    // От ввода могли остаться одни ссылки и упоминания
+    if city.is_empty() {
+        bot.send_message(msg.chat.id, templates::text("city.empty"))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    // Сохраняем флаг cute_mode перед сохранением пользователя
+    let is_cute_mode = user.cute_mode;
+    
+    user.set_city(&city);
+    user.remember_city(&city);
+    let timezone_notice = timezone::infer_for_city(weather_client, &mut user, &city).await;
+    if !save_or_apologize(bot, msg.chat.id, storage, user).await? {
+        return Ok(());
+    }
+    
+    info!("Пользователь @{} успешно установил город: {}", username, city);
+
+    // Формируем сообщение в зависимости от режима
+    let message = if is_cute_mode {
+        templates::render("city.set_cute", &[("city", &escape_markdown_v2(&utils::echo(&city)))])
+    } else {
+        templates::render("city.set", &[("city", &escape_markdown_v2(&utils::echo(&city)))])
+    };
+
+    send_echo(bot, msg.chat.id, message).await?;
+    timezone::send_notice(bot, msg.chat.id, timezone_notice).await?;
+    
+    Ok(())
+}
+
+async fn set_time(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &weather::WeatherClient,
+    config: &Config,
+    time_arg: &str,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let username = msg.from()
+        .and_then(|user| user.username.clone())
+        .unwrap_or_else(|| format!("ID: {}", user_id));
+    
+    // Если аргумент пустой, показываем клавиатуру выбора времени
+    if time_arg.trim().is_empty() {
+        info!("Пользователь @{} запросил список времени", username);
+        bot.send_message(
+            msg.chat.id, 
+            templates::text("time.choose")
+        )
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .reply_markup(get_time_keyboard(&config.branding))
+        .await?;
+        return Ok(());
+    }
+
+    // Специальная обработка для колбэка "manual"
+    if time_arg.trim() == "manual" {
+        bot.send_message(
+            msg.chat.id, 
+            "✏️ Пожалуйста, введите время в формате ЧЧ:ММ после команды, например:\n/time 08:00"
+        ).await?;
+        return Ok(());
+    }
+    
+    // Окно доставки вместо точного времени: /time утром
+    if let Some(window) = DeliveryWindow::parse(time_arg) {
+        let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+            return Ok(());
+        };
+        let first_time = user.notification_time.is_none();
+        let message = apply_delivery_window(&mut user, window, config);
+        let preview = first_time.then(|| user.clone());
+        if !save_or_apologize(bot, msg.chat.id, storage, user).await? {
+            return Ok(());
+        }
+        info!("Пользователь @{} выбрал окно доставки: {}", username, window.key());
+
+        bot.send_message(msg.chat.id, message)
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        if let Some(user) = preview {
+            send_notification_preview(bot, msg.chat.id, weather_client, config, &user).await?;
+        }
+        return Ok(());
+    }
+
+    // Проверяем формат времени: принимаем 8:00, 08.00, 0800 и т.п., сохраняем как ЧЧ:ММ
+    let Some(time) = utils::normalize_time(time_arg) else {
+        info!("Пользователь @{} указал некорректный формат времени: {}", username, time_arg);
+        bot.send_message(
+            msg.chat.id, 
+            "⚠️ Некорректный формат времени\\. Используйте формат HH:MM, например: 08:00"
+        ).await?;
+        return Ok(());
+    };
+
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    // Сообщение в зависимости от режима
+    let (time, message) = confirm_notification_time(time, user.cute_mode, config);
+
+    let first_time = user.notification_time.is_none();
+    user.notification_time = Some(time.clone());
+    user.delivery_window = None;
+    let preview = first_time.then(|| user.clone());
+    if !save_or_apologize(bot, msg.chat.id, storage, user).await? {
+        return Ok(());
+    }
+    
+    info!("Пользователь @{} успешно установил время уведомлений: {}", username, time);
+
+    bot.send_message(msg.chat.id, message)
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+    if let Some(user) = preview {
+        send_notification_preview(bot, msg.chat.id, weather_client, config, &user).await?;
+    }
+    
+    Ok(())
+}
+
+// Время выбрано впервые: сразу показываем, как будет выглядеть ежедневное уведомление
+// с текущей погодой, чтобы стиль и разделы можно было поменять до первой рассылки
+async fn send_notification_preview(
+    bot: &Bot,
+    chat_id: ChatId,
+    weather_client: &weather::WeatherClient,
+    config: &Config,
+    user: &UserSettings,
+) -> ResponseResult<()> {
+    match scheduler::preview_notification(weather_client, config, user).await {
+        Some(Ok(messages)) => {
+            let text = templates::render("time.preview", &[("message", &messages.join("\n\n"))]);
+            for part in all_cities::split_text(&text) {
+                bot.send_message(chat_id, part)
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .await?;
+            }
+        }
+        // Пример необязателен: время уже сохранено, а погоду пользователь увидит утром
+        Some(Err(e)) => warn!("Не удалось собрать пример уведомления для пользователя ID: {}: {}", chat_id, e),
+        None => {}
+    }
+    Ok(())
+}
+
+async fn send_current_weather(
+    bot: &Bot, 
+    msg: &Message, 
+    storage: &dyn UserStorage, 
+    weather_client: &weather::WeatherClient,
+    args: &str,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let username = msg.from()
+        .and_then(|user| user.username.clone())
+        .unwrap_or_else(|| format!("ID: {}", user_id));
+
+    if all_cities::is_all_cities(args) {
+        return all_cities::handle_all_cities(bot, msg, storage, weather_client).await;
+    }
+
+    // Демо-город работает без настройки профиля и не попадает в список недавних
+    if demo::is_demo_city(args) {
+        let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+            return Ok(());
+        };
+        let weather = weather_client
+            .get_weather(demo::CITY, FormatOptions::for_user(&user))
+            .await
+            .unwrap_or_default();
+        bot.send_message(msg.chat.id, templates::render("weather.header", &[
+            ("city", &escape_markdown_v2(demo::CITY)),
+            ("weather", &escape_markdown_v2(&weather)),
+        ]))
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+        return Ok(());
+    }
+
+    send_weather_to_chat(bot, msg.chat.id, &username, storage, weather_client).await
+}
+
+// Текущая погода во всех городах прогноза одним сообщением с кнопками быстрого переключения на недавние города
+async fn send_weather_to_chat(
+    bot: &Bot,
+    chat_id: ChatId,
+    username: &str,
+    storage: &dyn UserStorage,
+    weather_client: &weather::WeatherClient,
+) -> ResponseResult<()> {
+    let user_id = chat_id.0;
+    
+    // Получаем настройки пользователя
+    let user = match storage.get_user(user_id).await {
+        Ok(user) => user,
+        Err(e) => {
+            warn!("Не удалось загрузить настройки пользователя ID: {}: {}", user_id, e);
+            bot.send_message(chat_id, templates::text("storage.read_failed"))
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            return Ok(());
+        }
+    };
+    
+    if let Some(mut user_data) = user {
+        match user_data.city().map(str::to_string) {
+            Some(city) => {
+                bot.send_chat_action(chat_id, teloxide::types::ChatAction::Typing).await?;
+                
+                info!("Запрашиваю погоду для пользователя @{}, города: {}", username, user_data.cities.join(", "));
+                
+                // По блоку на каждый город прогноза; город без погоды отмечаем строкой,
+                // а ошибкой отвечаем, только если погоду не удалось получить ни для одного
+                let mut blocks = Vec::new();
+                let mut fetched = 0;
+                let mut first_error = None;
+                for city in &user_data.cities {
+                    match weather_client.get_weather(city, FormatOptions::for_user(&user_data)).await {
+                        Ok(weather) => {
+                            fetched += 1;
+                            let key = if user_data.cute_mode { "weather.header_cute" } else { "weather.header" };
+                            blocks.push(templates::render(key, &[
+                                ("city", &escape_markdown_v2(&utils::echo(city))),
+                                ("weather", &escape_markdown_v2(&weather)),
+                            ]));
+                        }
+                        Err(e) => {
+                            error!("Ошибка получения погоды для пользователя @{}, город {}: {}", username, city, e);
+                            blocks.push(templates::render("weather.all_failed", &[
+                                ("city", &escape_markdown_v2(&utils::echo(city))),
+                                ("error", &escape_markdown_v2(&e.to_string())),
+                            ]));
+                            first_error.get_or_insert(e);
+                        }
+                    }
+                }
+
+                match first_error {
+                    Some(e) if fetched == 0 => {
+                        error_throttle::send_error(
+                            bot,
+                            chat_id,
+                            templates::render("weather.error", &[("error", &escape_markdown_v2(&e.to_string()))])
+                        )
+                        .await?;
+                    }
+                    _ => {
+                        info!("Успешно получена погода для пользователя @{}", username);
+                        
+                        // Запрошенный город поднимается наверх списка недавних
+                        user_data.remember_city(&city);
+                        let keyboard = get_recent_cities_keyboard(&user_data);
+                        storage.save_user_or_log(user_data).await;
+
+                        // Много городов с подробным отчетом не помещаются в одно сообщение;
+                        // клавиатура недавних городов - под последним
+                        let mut messages = all_cities::split_messages(blocks);
+                        let last = messages.pop().unwrap_or_default();
+                        for message in messages {
+                            bot.send_message(chat_id, message)
+                                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                                .await?;
+                        }
+                        let mut request = bot.send_message(chat_id, last)
+                            .parse_mode(teloxide::types::ParseMode::MarkdownV2);
+                        if let Some(keyboard) = keyboard {
+                            request = request.reply_markup(keyboard);
+                        }
+                        request.await?;
+                    }
+                }
+            }
+            None => {
+                info!("Пользователь @{} запросил погоду без установленного города", username);
+                bot.send_message(
+                    chat_id, 
+                    templates::text("city.missing")
+                )
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            }
+        }
+    } else {
+        info!("Пользователь @{} запросил погоду без настройки профиля", username);
+        bot.send_message(
+            chat_id, 
+            templates::text("setup.required")
+        )
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+async fn send_weekly_forecast(
+    bot: &Bot, 
+    msg: &Message, 
+    storage: &dyn UserStorage, 
+    weather_client: &weather::WeatherClient,
+    args: &str
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let username = msg.from()
+        .and_then(|user| user.username.clone())
+        .unwrap_or_else(|| format!("ID: {}", user_id));
+
+    let Some(layout) = ForecastLayout::parse(args) else {
+        bot.send_message(msg.chat.id, templates::text("forecast.usage"))
+            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+    
+    // Получаем настройки пользователя
+    let user = match storage.get_user(user_id).await {
+        Ok(user) => user,
+        Err(e) => {
+            warn!("Не удалось загрузить настройки пользователя ID: {}: {}", user_id, e);
+            bot.send_message(msg.chat.id, templates::text("storage.read_failed"))
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            return Ok(());
+        }
+    };
+    
+    if let Some(user_data) = user {
+        match user_data.city() {
+            Some(city) => {
+                bot.send_chat_action(msg.chat.id, teloxide::types::ChatAction::Typing).await?;
+                
+                info!("Запрашиваю прогноз на неделю для пользователя @{}, город: {}", username, city);
+                
+                match weather_client.get_weekly_forecast(city, layout, FormatOptions::for_user(&user_data)).await {
+                    Ok(forecast) => {
+                        info!("Успешно получен прогноз на неделю для пользователя @{}", username);
+                        
+                        // Экранируем специальные символы для MarkdownV2
+                        let city_escaped = escape_markdown_v2(&utils::echo(city));
+                        let forecast_escaped = match layout {
+                            ForecastLayout::Text => escape_markdown_v2(&forecast),
+                            // Внутри блока кода экранировать нужно только ` и \, в таблице их нет
+                            ForecastLayout::Table => format!("```\n{}\n```", forecast),
+                        };
+                        
+                        // Формируем сообщение в зависимости от режима
+                        let message = if user_data.cute_mode {
+                            // Милый режим
+                            templates::render("forecast.header_cute", &[("city", &city_escaped), ("forecast", &forecast_escaped)])
+                        } else {
+                            // Стандартный режим
+                            templates::render("forecast.header", &[("city", &city_escaped), ("forecast", &forecast_escaped)])
+                        };
+                        
+                        bot.send_message(msg.chat.id, message)
+                            .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                            .await?;
+                    }
+                    Err(e) => {
+                        error!("Ошибка получения прогноза на неделю для пользователя @{}: {}", username, e);
+                        error_throttle::send_error(
+                            bot,
+                            msg.chat.id,
+                            templates::render("forecast.error", &[("error", &escape_markdown_v2(&e.to_string()))])
+                        )
+                        .await?;
+                    }
+                }
+            }
+            None => {
+                info!("Пользователь @{} запросил прогноз на неделю без установленного города", username);
+                bot.send_message(
+                    msg.chat.id, 
+                    templates::text("city.missing")
+                )
+                .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                .await?;
+            }
+        }
+    } else {
+        info!("Пользователь @{} запросил прогноз на неделю без настройки профиля", username);
+        bot.send_message(
+            msg.chat.id, 
+            templates::text("setup.required")
+        )
+        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+        .await?;
+    }
+    
+    Ok(())
+}
+
+// Округляет выбранное время до шага расписания и формирует подтверждение для пользователя
+fn confirm_notification_time(time: String, is_cute_mode: bool, config: &Config) -> (String, String) {
+    let rounded = utils::parse_time(&time)
+        .map(|parsed| utils::format_time(utils::round_time(parsed, config.schedule_granularity)))
+        .unwrap_or_else(|| time.clone());
+
+    let mut message = if is_cute_mode {
+        templates::render("time.set_cute", &[("time", &escape_markdown_v2(&rounded))])
+    } else {
+        templates::render("time.set", &[("time", &escape_markdown_v2(&rounded))])
+    };
+    if rounded != time {
+        message.push_str("\n\n");
+        message.push_str(&templates::render("time.rounded", &[
+            ("step", &config.schedule_granularity.to_string()),
+            ("time", &escape_markdown_v2(&time)),
+        ]));
+    }
+
+    (rounded, message)
+}
+
+// Включает окно доставки: время внутри окна выбирается по ID пользователя и хранится
+// в notification_time, чтобы остальной код видел обычное время уведомлений
+fn apply_delivery_window(user: &mut UserSettings, window: DeliveryWindow, config: &Config) -> String {
+    let time = utils::format_time(window.slot_for(user.user_id, config.schedule_granularity));
+    user.delivery_window = Some(window);
+    user.notification_time = Some(time.clone());
+    user.state = None;
+
+    templates::render("time.window", &[
+        ("window", &escape_markdown_v2(window.label())),
+        ("time", &escape_markdown_v2(&time)),
+    ])
+}
+
+// Обработчик колбэков от инлайн-клавиатуры
+async fn process_callback_query(
+    bot: Bot,
+    q: CallbackQuery,
+    answer: &CallbackAnswer,
+    storage: Arc<dyn UserStorage>,
+    weather_client: weather::WeatherClient,
+    config: Arc<Config>,
+    outbox: Arc<Outbox>,
+) -> ResponseResult<()> {
+    // Получаем ID пользователя
+    if let Some(chat_id) = q.message.as_ref().map(|msg| msg.chat.id) {
+        let user_id = chat_id.0;
+        
+        if let Some(data) = q.data {
+            if data == reengagement::UNSUBSCRIBE_CALLBACK {
+                let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                    return Ok(());
+                };
+                user.reengagement_opt_out = true;
+                if !save_or_apologize(&bot, chat_id, &*storage, user).await? {
+                    return Ok(());
+                }
+
+                answer.toast("🔕 Напоминания отключены").await?;
+                let text = "🔕 Хорошо, больше не буду напоминать о себе\\. Прогнозы по расписанию продолжат приходить\\.";
+                utils::edit_or_resend(&bot, chat_id, q.message.as_ref().map(|msg| msg.id), text.to_string()).await?;
+
+                info!("Пользователь ID: {} отписался от сообщений «мы скучали»", user_id);
+                return Ok(());
+            }
+
+            if data == onboarding::RESUME_CITY_CALLBACK || data == onboarding::RESUME_TIME_CALLBACK {
+                // Продолжение настройки из напоминания: показываем меню выбора
+                answer.ack().await?;
+
+                if data == onboarding::RESUME_CITY_CALLBACK {
+                    // Без недавних городов меню все равно полезно, поэтому сбой чтения не мешает его показать
+                    let user = storage.get_user(user_id).await.ok().flatten();
+                    bot.send_message(
+                        chat_id,
+                        templates::text("city.choose")
+                    )
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .reply_markup(get_city_keyboard(&config.branding, user.as_ref(), q.from.language_code.as_deref()))
+                    .await?;
+                } else {
+                    bot.send_message(
+                        chat_id,
+                        templates::text("time.choose")
+                    )
+                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
+                    .reply_markup(get_time_keyboard(&config.branding))
+                    .await?;
+                }
+
+                info!("Пользователь ID: {} продолжил настройку из напоминания", user_id);
+                return Ok(());
+            }
+
+            if let Some(city) = data.strip_prefix(SWITCH_CITY_PREFIX) {
+                // Быстрое переключение на недавний город из кнопок под погодой
+                let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                    return Ok(());
+                };
+                user.set_city(city);
+                user.state = None;
+                let timezone_notice = timezone::infer_for_city(&weather_client, &mut user, city).await;
+                if !save_or_apologize(&bot, chat_id, &*storage, user).await? {
+                    return Ok(());
+                }
+
+                answer.toast(format!("🏙️ Город: {}", city)).await?;
+
+                info!("Пользователь ID: {} переключился на недавний город: {}", user_id, city);
+                send_weather_to_chat(&bot, chat_id, &format!("ID: {}", user_id), &*storage, &weather_client).await?;
+                timezone::send_notice(&bot, chat_id, timezone_notice).await?;
+                return Ok(());
+            }
+
+            if data.starts_with("city_") {
+                if data == "city_manual" {
+                    // Пользователь выбрал ручной ввод города
+                    // Устанавливаем состояние ожидания ввода города
+                    let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                        return Ok(());
+                    };
+                    
+                    user.state = Some("waiting_for_city".to_string());
+                    storage.save_user_or_log(user).await;
+                    
+                    answer.ack().await?;
+                    
+                    utils::edit_or_resend(&bot, chat_id, q.message.as_ref().map(|msg| msg.id), templates::text("city.manual")).await?;
+                    
+                    return Ok(());
+                }
+                
+                // Обрабатываем выбор города из меню
+                let city = data.replace("city_", "");
+                
+                // Получаем или создаем настройки пользователя
+                let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                    return Ok(());
+                };
+                
+                let is_cute_mode = user.cute_mode;
+                user.set_city(&city);
+                user.remember_city(&city);
+                user.state = None; // Сбрасываем состояние, если оно было
+                let timezone_notice = timezone::infer_for_city(&weather_client, &mut user, &city).await;
+                if !save_or_apologize(&bot, chat_id, &*storage, user).await? {
+                    return Ok(());
+                }
+                
+                // Формируем сообщение
+                let message = if is_cute_mode {
+                    templates::render("city.set_cute", &[("city", &escape_markdown_v2(&utils::echo(&city)))])
+                } else {
+                    templates::render("city.set", &[("city", &escape_markdown_v2(&utils::echo(&city)))])
+                };
+                
+                // Отвечаем на колбэк
+                answer.toast("Город сохранен ✅").await?;
+                
+                // Редактируем сообщение с инлайн-клавиатурой
+                utils::edit_or_resend(&bot, chat_id, q.message.as_ref().map(|msg| msg.id), message).await?;
+                
+                info!("Пользователь ID: {} выбрал город: {} через меню", user_id, city);
+                timezone::send_notice(&bot, chat_id, timezone_notice).await?;
+            } else if let Some(key) = data.strip_prefix(sections::CALLBACK_PREFIX) {
+                sections::handle_toggle(&bot, answer, q.message.as_ref(), &*storage, key).await?;
+            } else if let Some(action) = data.strip_prefix(transfer::CALLBACK_PREFIX) {
+                transfer::handle_callback(&bot, q.message.as_ref(), &*storage, action).await?;
+            } else if let Some(action) = data.strip_prefix(household::CALLBACK_PREFIX) {
+                household::handle_callback(&bot, q.message.as_ref(), &*storage, action).await?;
+            } else if let Some(action) = data.strip_prefix(privacy::CALLBACK_PREFIX) {
+                privacy::handle_callback(&bot, q.message.as_ref(), &*storage, outbox.history(), action).await?;
+            } else if let Some(action) = data.strip_prefix(timezone::CALLBACK_PREFIX) {
+                timezone::handle_callback(&bot, q.message.as_ref(), &*storage, action).await?;
+            } else if let Some(action) = data.strip_prefix(delete_me::CALLBACK_PREFIX) {
+                delete_me::handle_callback(&bot, q.message.as_ref(), &*storage, &outbox, action).await?;
+            } else if let Some(window) = data.strip_prefix("window_").and_then(DeliveryWindow::parse) {
+                let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                    return Ok(());
+                };
+                let message = apply_delivery_window(&mut user, window, &config);
+                if !save_or_apologize(&bot, chat_id, &*storage, user).await? {
+                    return Ok(());
+                }
+
+                answer.toast("Окно доставки сохранено ✅").await?;
+                utils::edit_or_resend(&bot, chat_id, q.message.as_ref().map(|msg| msg.id), message).await?;
+
+                info!("Пользователь ID: {} выбрал окно доставки: {}", user_id, window.key());
+            } else if data.starts_with("time_") {
+                if data == "time_manual" {
+                    // Пользователь выбрал ручной ввод времени
+                    // Устанавливаем состояние ожидания ввода времени
+                    let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                        return Ok(());
+                    };
+                    
+                    user.state = Some("waiting_for_time".to_string());
+                    storage.save_user_or_log(user).await;
+                    
+                    answer.ack().await?;
+                    
+                    utils::edit_or_resend(&bot, chat_id, q.message.as_ref().map(|msg| msg.id), templates::text("time.manual")).await?;
+                    
+                    return Ok(());
+                }
+                
+                // Обрабатываем выбор времени из меню
+                let time = data.replace("time_", "");
+                
+                // Получаем или создаем настройки пользователя
+                let Some(mut user) = load_or_apologize(&bot, chat_id, &*storage, user_id).await? else {
+                    return Ok(());
+                };
+                
+                // Формируем сообщение
+                let (time, message) = confirm_notification_time(time, user.cute_mode, &config);
+
+                let first_time = user.notification_time.is_none();
+                user.notification_time = Some(time.clone());
+                user.delivery_window = None;
+                user.state = None; // Сбрасываем состояние, если оно было
+                let preview = first_time.then(|| user.clone());
+                if !save_or_apologize(&bot, chat_id, &*storage, user).await? {
+                    return Ok(());
+                }
+                
+                // Отвечаем на колбэк
+                answer.toast(format!("⏰ Время уведомлений: {}", time)).await?;
+                
+                // Редактируем сообщение с инлайн-клавиатурой
+                utils::edit_or_resend(&bot, chat_id, q.message.as_ref().map(|msg| msg.id), message).await?;
+                
+                info!("Пользователь ID: {} выбрал время: {} через меню", user_id, time);
+                if let Some(user) = preview {
+                    send_notification_preview(&bot, chat_id, &weather_client, &config, &user).await?;
+                }
+            } else {
+                // Кнопка от старой версии бота или с неизвестными данными
+                answer.alert("Эта кнопка больше не работает. Вызови команду заново").await?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+// Кнопки под погодой для переключения на другие недавние города пользователя
+fn get_recent_cities_keyboard(user: &UserSettings) -> Option<InlineKeyboardMarkup> {
+    let buttons: Vec<InlineKeyboardButton> = user.recent_cities
+        .iter()
+        .filter(|city| user.city() != Some(city.as_str()))
+        .filter(|city| format!("{}{}", SWITCH_CITY_PREFIX, city).len() <= 64)
+        .map(|city| InlineKeyboardButton::callback(format!("🔁 {}", city), format!("{}{}", SWITCH_CITY_PREFIX, city)))
+        .collect();
+
+    if buttons.is_empty() {
+        return None;
+    }
+
+    Some(InlineKeyboardMarkup::new(buttons.chunks(2).map(|row| row.to_vec())))
+}
+
+// Клавиатура быстрого выбора города: сверху недавние города пользователя,
+// затем список из настроек развертывания для его языка
+fn get_city_keyboard(branding: &Branding, user: Option<&UserSettings>, language: Option<&str>) -> InlineKeyboardMarkup {
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
+    let quick_cities = branding.cities_for(language);
+
+    // Данные колбэка ограничены 64 байтами, слишком длинные названия не показываем кнопкой
+    let recent: Vec<&String> = user
+        .map(|u| u.recent_cities.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|city| format!("city_{}", city).len() <= 64)
+        .collect();
+
+    for chunk in recent.chunks(branding.city_keyboard_columns) {
+        let row = chunk.iter()
+            .map(|city| InlineKeyboardButton::callback(format!("🕘 {}", city), format!("city_{}", city)))
+            .collect();
+        keyboard.push(row);
+    }
+    
+    for chunk in quick_cities.chunks(branding.city_keyboard_columns) {
+        let row = chunk.iter()
+            .map(|city| {
+                InlineKeyboardButton::callback(city.to_string(), format!("city_{}", city))
+            })
+            .collect();
+        keyboard.push(row);
+    }
+    
+    // Добавляем напоминание о ручном вводе
+    keyboard.push(vec![
+        InlineKeyboardButton::callback("Ввести город вручную".to_string(), "city_manual".to_string())
+    ]);
+    
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+// Получение клавиатуры для выбора времени: ряды вариантов задаются в настройках
+fn get_time_keyboard(branding: &Branding) -> InlineKeyboardMarkup {
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = branding.time_options
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|time| InlineKeyboardButton::callback(time.clone(), format!("time_{}", time)))
+                .collect()
+        })
+        .collect();
+    
+    // Окна доставки для тех, кому не важна точная минута
+    keyboard.push(
+        DeliveryWindow::ALL
+            .iter()
+            .map(|window| InlineKeyboardButton::callback(window.button_label(), format!("window_{}", window.key())))
+            .collect(),
+    );
+
+    // Добавляем напоминание о ручном вводе
+    keyboard.push(vec![
+        InlineKeyboardButton::callback("Ввести время вручную".to_string(), "time_manual".to_string())
+    ]);
+    
+    InlineKeyboardMarkup::new(keyboard)
+}
@@ -0,0 +1,121 @@
+use crate::weather::{city_time, OpenWeatherResponse};
+use chrono::NaiveDate;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::Mutex;
+
+// Сколько последних дней храним по каждому городу
+const MAX_DAYS_PER_CITY: usize = 400;
+
+// Погода в городе за один день по всем замерам, которые получил бот
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayRecord {
+    // Дата по местному времени города
+    pub date: NaiveDate,
+    pub temp_min: f32,
+    pub temp_max: f32,
+    pub temp_sum: f32,
+    pub samples: u32,
+    // Сколько раз встречалась каждая категория погоды (Rain, Clear, Snow...)
+    pub conditions: BTreeMap<String, u32>,
+}
+
+impl DayRecord {
+    fn new(date: NaiveDate) -> Self {
+        DayRecord {
+            date,
+            temp_min: f32::INFINITY,
+            temp_max: f32::NEG_INFINITY,
+            temp_sum: 0.0,
+            samples: 0,
+            conditions: BTreeMap::new(),
+        }
+    }
+
+    pub fn temp_avg(&self) -> f32 {
+        self.temp_sum / self.samples.max(1) as f32
+    }
+
+    // Преобладающая за день погода; при равенстве - первая по алфавиту
+    pub fn dominant_condition(&self) -> Option<&str> {
+        self.conditions
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| **count)
+            .map(|(condition, _)| condition.as_str())
+    }
+}
+
+// История погоды по городам в отдельном JSON-файле: из нее берутся заметки
+// вроде «третий день подряд без осадков»
+pub struct WeatherHistory {
+    cities: Mutex<HashMap<String, Vec<DayRecord>>>,
+    file_path: String,
+}
+
+impl WeatherHistory {
+    pub fn new(path: &str) -> Self {
+        let cities = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(cities) => Some(cities),
+                Err(e) => {
+                    error!("Ошибка чтения истории погоды {}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        WeatherHistory {
+            cities: Mutex::new(cities),
+            file_path: path.to_string(),
+        }
+    }
+
+    // Учитывает замер текущей погоды в истории города
+    pub fn record(&self, city: &str, data: &OpenWeatherResponse) {
+        let date = city_time(data.dt, data.timezone).date_naive();
+        let mut cities = self.cities.lock().unwrap();
+        let days = cities.entry(city_key(city)).or_default();
+
+        if days.last().is_none_or(|day| day.date < date) {
+            days.push(DayRecord::new(date));
+        }
+        let Some(day) = days.iter_mut().rev().find(|day| day.date == date) else {
+            return;
+        };
+        day.temp_min = day.temp_min.min(data.main.temp);
+        day.temp_max = day.temp_max.max(data.main.temp);
+        day.temp_sum += data.main.temp;
+        day.samples += 1;
+        if let Some(weather) = data.weather.first() {
+            *day.conditions.entry(weather.main.clone()).or_insert(0) += 1;
+        }
+
+        let excess = days.len().saturating_sub(MAX_DAYS_PER_CITY);
+        days.drain(..excess);
+        self.save(&cities);
+    }
+
+    // Дни города от старых к новым
+    pub fn days(&self, city: &str) -> Vec<DayRecord> {
+        self.cities.lock().unwrap().get(&city_key(city)).cloned().unwrap_or_default()
+    }
+
+    fn save(&self, cities: &HashMap<String, Vec<DayRecord>>) {
+        match serde_json::to_string(cities) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.file_path, json) {
+                    error!("Ошибка сохранения истории погоды: {}", e);
+                }
+            }
+            Err(e) => error!("Ошибка сериализации истории погоды: {}", e),
+        }
+    }
+}
+
+fn city_key(city: &str) -> String {
+    city.trim().to_lowercase()
+}
@@ -0,0 +1,245 @@
+use crate::storage::{HouseholdMember, UserSettings, UserStorage};
+use crate::{templates, utils};
+use crate::escape_markdown_v2;
+use crate::handlers::send_echo;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use teloxide::prelude::*;
+use teloxide::types::{Chat, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+// Префикс данных кнопок ответа на приглашение
+pub const CALLBACK_PREFIX: &str = "household_";
+// Параметр /start в ссылке-приглашении: t.me/<бот>?start=household-<код>
+pub const START_PREFIX: &str = "household-";
+// Сколько действует приглашение
+const INVITE_LIFETIME_HOURS: i64 = 24;
+// Сколько чатов можно добавить в одну семью
+const MAX_MEMBERS: usize = 10;
+
+// Приглашение, ожидающее подтверждения: owner - пользователь, чей прогноз будет рассылаться
+struct PendingInvite {
+    owner: i64,
+    expires_at: DateTime<Utc>,
+}
+
+// Приглашения живут только в памяти: после перезапуска бота ссылку нужно получить заново
+static INVITES: LazyLock<Mutex<HashMap<String, PendingInvite>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Обработка /household: список семьи, invite - ссылка-приглашение,
+// remove <ID> - исключить участника, leave - выйти из чужой семьи
+pub async fn handle_household_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let mut parts = args.split_whitespace();
+    let action = parts.next().unwrap_or("").to_lowercase();
+
+    let response = match action.as_str() {
+        "invite" | "пригласить" => {
+            let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+                return Ok(());
+            };
+            if user.household.members.len() >= MAX_MEMBERS {
+                templates::render("household.full", &[("max", &MAX_MEMBERS.to_string())])
+            } else {
+                let code = create_invite(user_id);
+                let me = bot.get_me().await?;
+                let link = match &me.user.username {
+                    Some(username) => format!("https://t.me/{}?start={}{}", username, START_PREFIX, code),
+                    None => format!("/start {}{}", START_PREFIX, code),
+                };
+                info!("Пользователь ID: {} создал приглашение в семью", user_id);
+                templates::render("household.invite", &[
+                    ("link", &escape_markdown_v2(&link)),
+                    ("hours", &INVITE_LIFETIME_HOURS.to_string()),
+                ])
+            }
+        }
+        "remove" | "удалить" => {
+            let chat_id = parts.next().and_then(|id| id.parse::<i64>().ok());
+            let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+                return Ok(());
+            };
+            match chat_id.filter(|chat_id| user.household.contains(*chat_id)) {
+                Some(chat_id) => {
+                    user.household.members.retain(|member| member.chat_id != chat_id);
+                    let saved = storage.save_user(user).await;
+                    if saved.is_ok() {
+                        info!("Пользователь ID: {} исключил из семьи чат {}", user_id, chat_id);
+                        let _ = bot.send_message(ChatId(chat_id), templates::text("household.removed_member"))
+                            .parse_mode(ParseMode::MarkdownV2)
+                            .await;
+                    }
+                    confirm_saved(saved, templates::text("household.removed"))
+                }
+                None => templates::text("household.not_member"),
+            }
+        }
+        "leave" | "выйти" => {
+            let owners: Vec<UserSettings> = match storage.get_all_users().await {
+                Ok(users) => users.into_iter().filter(|owner| owner.household.contains(user_id)).collect(),
+                Err(e) => {
+                    warn!("Не удалось найти семьи чата {}: {}", user_id, e);
+                    send_echo(bot, msg.chat.id, templates::text("storage.read_failed")).await?;
+                    return Ok(());
+                }
+            };
+            if owners.is_empty() {
+                templates::text("household.no_membership")
+            } else {
+                let mut saved = Ok(());
+                for mut owner in owners {
+                    owner.household.members.retain(|member| member.chat_id != user_id);
+                    let owner_id = owner.user_id;
+                    if let Err(e) = storage.save_user(owner).await {
+                        saved = Err(e);
+                        continue;
+                    }
+                    info!("Чат {} вышел из семьи пользователя ID: {}", user_id, owner_id);
+                    let _ = send_echo(bot, ChatId(owner_id), templates::render("household.member_left", &[
+                        ("name", &escape_markdown_v2(&utils::echo(&chat_name(&msg.chat)))),
+                    ]))
+                    .await;
+                }
+                confirm_saved(saved, templates::text("household.left"))
+            }
+        }
+        _ => {
+            let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+                return Ok(());
+            };
+            let members = if user.household.members.is_empty() {
+                "пока никого".to_string()
+            } else {
+                user.household
+                    .members
+                    .iter()
+                    .map(|member| format!("• {} (ID {})", utils::echo(&member.name), member.chat_id))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            templates::render("household.usage", &[("members", &escape_markdown_v2(&members))])
+        }
+    };
+
+    send_echo(bot, msg.chat.id, response).await?;
+    Ok(())
+}
+
+// Открыта ссылка-приглашение: просим подтвердить вступление
+pub async fn handle_invite_link(bot: &Bot, msg: &Message, storage: &dyn UserStorage, code: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let code = code.trim().to_uppercase();
+
+    let response = match find_invite(&code) {
+        None => templates::text("household.invalid"),
+        Some(owner_id) if owner_id == user_id => templates::text("household.self"),
+        Some(owner_id) => {
+            let Some(owner) = load_or_apologize(bot, msg.chat.id, storage, owner_id).await? else {
+                return Ok(());
+            };
+            let keyboard = InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::callback("✅ Присоединиться", format!("{}join_{}", CALLBACK_PREFIX, code)),
+                InlineKeyboardButton::callback("❌ Отказаться", format!("{}decline_{}", CALLBACK_PREFIX, code)),
+            ]]);
+            send_echo(bot, msg.chat.id, templates::render("household.confirm", &[
+                ("city", &escape_markdown_v2(&utils::echo(owner.city().unwrap_or("не выбран")))),
+                ("time", &escape_markdown_v2(owner.notification_time.as_deref().unwrap_or("не выбрано"))),
+            ]))
+            .reply_markup(keyboard)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Ответ на приглашение: join - вступить, decline - отказаться
+pub async fn handle_callback(
+    bot: &Bot,
+    message: Option<&Message>,
+    storage: &dyn UserStorage,
+    action: &str,
+) -> ResponseResult<()> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let user_id = message.chat.id.0;
+    let Some((action, code)) = action.split_once('_') else {
+        return Ok(());
+    };
+
+    let text = match (action, find_invite(code)) {
+        (_, None) => templates::text("household.invalid"),
+        ("join", Some(owner_id)) if owner_id != user_id => {
+            let Some(mut owner) = load_or_apologize(bot, message.chat.id, storage, owner_id).await? else {
+                return Ok(());
+            };
+            if owner.household.members.len() >= MAX_MEMBERS {
+                templates::render("household.full", &[("max", &MAX_MEMBERS.to_string())])
+            } else {
+                let name = chat_name(&message.chat);
+                let saved = if owner.household.contains(user_id) {
+                    Ok(())
+                } else {
+                    owner.household.members.push(HouseholdMember { chat_id: user_id, name: name.clone() });
+                    storage.save_user(owner).await
+                };
+                // Если вступление не сохранилось, приглашение остается в силе: можно нажать еще раз
+                if saved.is_ok() {
+                    INVITES.lock().unwrap().remove(code);
+                    info!("Чат {} присоединился к семье пользователя ID: {}", user_id, owner_id);
+
+                    send_echo(bot, ChatId(owner_id), templates::render("household.member_joined", &[
+                        ("name", &escape_markdown_v2(&utils::echo(&name))),
+                    ]))
+                    .await?;
+                }
+                confirm_saved(saved, templates::text("household.joined"))
+            }
+        }
+        ("decline", Some(_)) => templates::text("household.declined"),
+        _ => return Ok(()),
+    };
+
+    utils::edit_or_resend(bot, message.chat.id, Some(message.id), text).await
+}
+
+// Чаты, которым дублируется уведомление пользователя
+pub fn recipients(user: &UserSettings) -> Vec<i64> {
+    user.household.members.iter().map(|member| member.chat_id).collect()
+}
+
+fn create_invite(owner_id: i64) -> String {
+    let code = utils::random_code();
+    let mut invites = INVITES.lock().unwrap();
+    let now = Utc::now();
+    invites.retain(|_, invite| invite.expires_at > now);
+    invites.insert(code.clone(), PendingInvite {
+        owner: owner_id,
+        expires_at: now + Duration::hours(INVITE_LIFETIME_HOURS),
+    });
+    code
+}
+
+// Владелец действующего приглашения
+fn find_invite(code: &str) -> Option<i64> {
+    let mut invites = INVITES.lock().unwrap();
+    let now = Utc::now();
+    invites.retain(|_, invite| invite.expires_at > now);
+    invites.get(code).map(|invite| invite.owner)
+}
+
+// Как показывать участника владельцу: имя, название группы или ID чата
+fn chat_name(chat: &Chat) -> String {
+    chat.first_name()
+        .or_else(|| chat.title())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("ID {}", chat.id.0))
+}
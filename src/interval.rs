@@ -0,0 +1,104 @@
+use crate::storage::{IntervalSchedule, UserSettings, UserStorage};
+use crate::templates;
+use crate::timezone;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{DateTime, Local, NaiveTime};
+use log::info;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Чаще, чем раз в час, прогноз все равно не меняется
+const MIN_EVERY_HOURS: u32 = 1;
+// Реже раза в 12 часов достаточно обычного времени уведомлений
+const MAX_EVERY_HOURS: u32 = 12;
+
+// Обработка /every: "каждые 3 часа с 09 до 18" (или коротко /every 3 9 18), /every off - выключить
+pub async fn handle_every_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let args = args.trim();
+
+    let response = if args.is_empty() {
+        match user.interval_schedule {
+            Some(schedule) => render_schedule("every.status", schedule),
+            None => templates::text("every.usage"),
+        }
+    } else if matches!(args.to_lowercase().as_str(), "off" | "выкл" | "стоп") {
+        if user.interval_schedule.take().is_some() {
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} выключил прогноз по интервалу", user_id);
+            confirm_saved(saved, templates::text("every.off"))
+        } else {
+            templates::text("every.off")
+        }
+    } else {
+        match parse_schedule(args) {
+            Ok(schedule) => {
+                user.interval_schedule = Some(schedule);
+                let saved = storage.save_user(user).await;
+                info!(
+                    "Пользователь ID: {} включил прогноз каждые {} ч с {} до {}",
+                    user_id, schedule.every_hours, schedule.from_hour, schedule.until_hour
+                );
+                confirm_saved(saved, render_schedule("every.set", schedule))
+            }
+            Err(e) => templates::render("every.invalid", &[("error", &crate::escape_markdown_v2(&e))]),
+        }
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Разбор "каждые 3 часа с 09 до 18", "3ч 9-18", "3 9 18": три числа - интервал, начало и конец
+fn parse_schedule(text: &str) -> Result<IntervalSchedule, String> {
+    let numbers: Vec<u32> = text
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<u32>().map_err(|_| format!("некорректное число {}", part)))
+        .collect::<Result<_, _>>()?;
+    let [every_hours, from_hour, until_hour] = numbers[..] else {
+        return Err("нужны интервал и часы начала и конца, например: каждые 3 часа с 09 до 18".to_string());
+    };
+
+    if !(MIN_EVERY_HOURS..=MAX_EVERY_HOURS).contains(&every_hours) {
+        return Err(format!("интервал должен быть от {} до {} часов", MIN_EVERY_HOURS, MAX_EVERY_HOURS));
+    }
+    if from_hour > 23 || until_hour > 23 {
+        return Err("часы указываются от 0 до 23".to_string());
+    }
+    if from_hour >= until_hour {
+        return Err("час начала должен быть раньше часа окончания".to_string());
+    }
+    Ok(IntervalSchedule { every_hours, from_hour, until_hour })
+}
+
+// Шаблон с интервалом, рабочими часами и списком времени отправки
+fn render_schedule(key: &str, schedule: IntervalSchedule) -> String {
+    let hours = schedule
+        .hours()
+        .map(|hour| format!("{:02}:00", hour))
+        .collect::<Vec<_>>()
+        .join(", ");
+    templates::render(key, &[
+        ("every", &schedule.every_hours.to_string()),
+        ("range", &format!("{:02}:00–{:02}:00", schedule.from_hour, schedule.until_hour)),
+        ("hours", &hours),
+    ])
+}
+
+// Пользователи, которым в этот слот положен прогноз по интервалу, с городом уведомлений.
+// Тем, у кого на этот же слот приходится обычное уведомление, второй раз не отправляем
+pub fn due_users(users: &[UserSettings], slot: NaiveTime, regular: &[i64], at: DateTime<Local>) -> Vec<(UserSettings, String)> {
+    users
+        .iter()
+        .filter(|user| user.active && !regular.contains(&user.user_id))
+        .filter(|user| user.interval_schedule.is_some_and(|schedule| schedule.is_due(timezone::to_user_time(user, slot))))
+        .filter_map(|user| user.notification_city(timezone::user_date(user, at)).map(|city| (user.clone(), city)))
+        .collect()
+}
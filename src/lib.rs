@@ -0,0 +1,78 @@
+// Ядро погодного бота: хранилище пользователей, сервис погоды, планировщик уведомлений
+// и обработчики Telegram. Бинарный файл только разбирает аргументы и собирает App;
+// так же логику можно встроить в другой бот или написать свой интерфейс
+
+pub mod weather;
+pub mod storage;
+pub mod scheduler;
+pub mod config;
+pub mod metrics;
+mod admin;
+mod all_cities;
+mod cities;
+mod alerts;
+pub mod alert_rules;
+pub mod reporting;
+pub mod scenario;
+mod onboarding;
+mod reengagement;
+pub mod activity;
+mod retention;
+pub mod templates;
+mod travel;
+mod transfer;
+mod household;
+mod small_talk;
+mod moderation;
+mod callback_answer;
+mod capabilities;
+mod confidence;
+mod interval;
+mod demo;
+mod history;
+mod trends;
+mod records;
+mod stats;
+mod forecast_updates;
+mod fsck;
+mod user_import;
+pub mod cli;
+mod error_throttle;
+mod utils;
+mod smart_time;
+mod broadcast_report;
+mod outbox;
+mod delivery_history;
+mod update_offset;
+mod sections;
+mod settings_file;
+mod data_export;
+mod delete_me;
+mod backup;
+mod storage_lock;
+mod encryption;
+mod privacy;
+pub mod formatter;
+mod card;
+mod chart;
+mod night_mode;
+mod schema_watch;
+mod sanity;
+mod severity;
+mod webapp;
+mod transliteration;
+mod timezone;
+mod locale;
+mod api_keys;
+pub mod app;
+pub mod handlers;
+pub mod plugins;
+pub mod daily_extras;
+
+pub use app::{App, AppError};
+pub use config::Config;
+pub use handlers::{build_handler, escape_markdown_v2};
+pub use plugins::{CommandPlugin, PluginContext, PluginFuture, PluginRegistry};
+pub use scheduler::start_scheduler;
+pub use storage::{JsonStorage, MemoryStorage, PgStorage, RedisStorage, SqliteStorage, UserSettings, UserStorage};
+pub use weather::WeatherClient;
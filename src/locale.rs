@@ -0,0 +1,172 @@
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{Datelike, NaiveDate, Weekday};
+use log::info;
+use serde::{Deserialize, Serialize};
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Региональный формат дат и времени в отчетах: порядок дня и месяца, 12- или 24-часовое время
+// и названия дней недели. Тексты бота остаются на русском
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Locale {
+    #[default]
+    Ru,
+    EnUs,
+    EnGb,
+    De,
+    Fr,
+}
+
+const WEEKDAYS_RU: [&str; 7] = ["Понедельник", "Вторник", "Среда", "Четверг", "Пятница", "Суббота", "Воскресенье"];
+const WEEKDAYS_SHORT_RU: [&str; 7] = ["Пн", "Вт", "Ср", "Чт", "Пт", "Сб", "Вс"];
+const WEEKDAYS_EN: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const WEEKDAYS_SHORT_EN: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const WEEKDAYS_DE: [&str; 7] = ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"];
+const WEEKDAYS_SHORT_DE: [&str; 7] = ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"];
+const WEEKDAYS_FR: [&str; 7] = ["Lundi", "Mardi", "Mercredi", "Jeudi", "Vendredi", "Samedi", "Dimanche"];
+const WEEKDAYS_SHORT_FR: [&str; 7] = ["Lun", "Mar", "Mer", "Jeu", "Ven", "Sam", "Dim"];
+
+impl Locale {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().replace('_', "-").as_str() {
+            "ru" | "ru-ru" | "русский" => Some(Locale::Ru),
+            "en-us" | "us" => Some(Locale::EnUs),
+            "en" | "en-gb" | "gb" | "uk" => Some(Locale::EnGb),
+            "de" | "de-de" => Some(Locale::De),
+            "fr" | "fr-fr" => Some(Locale::Fr),
+            _ => None,
+        }
+    }
+
+    // Формат по языку интерфейса Telegram ("en", "en-US", "de"); неизвестные языки - None
+    pub fn from_language_code(code: &str) -> Option<Self> {
+        match code.to_lowercase().as_str() {
+            "en" | "en-us" => Some(Locale::EnUs),
+            "en-gb" => Some(Locale::EnGb),
+            code => Locale::parse(code.split('-').next().unwrap_or(code)),
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::Ru => "ru",
+            Locale::EnUs => "en-us",
+            Locale::EnGb => "en-gb",
+            Locale::De => "de",
+            Locale::Fr => "fr",
+        }
+    }
+
+    pub fn weekday_name(self, weekday: Weekday) -> &'static str {
+        let index = weekday.num_days_from_monday() as usize;
+        match self {
+            Locale::Ru => WEEKDAYS_RU[index],
+            Locale::EnUs | Locale::EnGb => WEEKDAYS_EN[index],
+            Locale::De => WEEKDAYS_DE[index],
+            Locale::Fr => WEEKDAYS_FR[index],
+        }
+    }
+
+    // "Сегодня" и "Завтра" вместо дня недели для ближайших дней прогноза; дальше - None
+    pub fn relative_day_name(self, days_from_today: i64) -> Option<&'static str> {
+        let names = match self {
+            Locale::Ru => ["Сегодня", "Завтра"],
+            Locale::EnUs | Locale::EnGb => ["Today", "Tomorrow"],
+            Locale::De => ["Heute", "Morgen"],
+            Locale::Fr => ["Aujourd'hui", "Demain"],
+        };
+        usize::try_from(days_from_today).ok().and_then(|index| names.get(index).copied())
+    }
+
+    pub fn weekday_short_name(self, weekday: Weekday) -> &'static str {
+        let index = weekday.num_days_from_monday() as usize;
+        match self {
+            Locale::Ru => WEEKDAYS_SHORT_RU[index],
+            Locale::EnUs | Locale::EnGb => WEEKDAYS_SHORT_EN[index],
+            Locale::De => WEEKDAYS_SHORT_DE[index],
+            Locale::Fr => WEEKDAYS_SHORT_FR[index],
+        }
+    }
+
+    // День и месяц: 16.10 (ru, de), 16/10 (en-gb, fr), 10/16 (en-us)
+    pub fn short_date(self, date: NaiveDate) -> String {
+        match self {
+            Locale::Ru | Locale::De => date.format("%d.%m").to_string(),
+            Locale::EnGb | Locale::Fr => date.format("%d/%m").to_string(),
+            Locale::EnUs => date.format("%m/%d").to_string(),
+        }
+    }
+
+    // Номер формы слова после числа в массиве [одна, несколько, много].
+    // По-русски: 1 день, 21 день; 2 дня, 34 дня; 5 дней, 11–14 дней, 112 дней.
+    // В английском и немецком единственное число только у 1, во французском - у 0 и 1
+    pub fn plural_form(self, n: i64) -> usize {
+        let n = n.unsigned_abs();
+        match self {
+            Locale::Ru => match (n % 10, n % 100) {
+                (_, 11..=14) => 2,
+                (1, _) => 0,
+                (2..=4, _) => 1,
+                _ => 2,
+            },
+            Locale::EnUs | Locale::EnGb | Locale::De => usize::from(n != 1) * 2,
+            Locale::Fr => usize::from(n > 1) * 2,
+        }
+    }
+
+    // Время: 07:05 или 7:05 AM для en-us
+    pub fn time(self, hour: u32, minute: u32) -> String {
+        match self {
+            Locale::EnUs => {
+                let suffix = if hour < 12 { "AM" } else { "PM" };
+                let hour = match hour % 12 {
+                    0 => 12,
+                    hour => hour,
+                };
+                format!("{}:{:02} {}", hour, minute, suffix)
+            }
+            _ => format!("{:02}:{:02}", hour, minute),
+        }
+    }
+}
+
+// Обработка /locale [ru|en-us|en-gb|de|fr]: формат дат и времени в отчетах
+pub async fn handle_locale_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    let response = match Locale::parse(args) {
+        Some(locale) => {
+            user.locale = Some(locale);
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} выбрал формат дат {}", user_id, locale.code());
+            confirm_saved(saved, templates::render("locale.set", &[("locale", locale.code()), ("example", &example(locale))]))
+        }
+        None => {
+            let locale = user.locale.unwrap_or_default();
+            templates::render("locale.usage", &[("locale", locale.code()), ("example", &example(locale))])
+        }
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Пример даты и времени в выбранном формате (уже экранирован для MarkdownV2)
+fn example(locale: Locale) -> String {
+    let date = NaiveDate::from_ymd_opt(2024, 10, 16).unwrap_or_default();
+    crate::escape_markdown_v2(&format!(
+        "{}, {}, {}",
+        locale.weekday_name(date.weekday()),
+        locale.short_date(date),
+        locale.time(19, 30)
+    ))
+}
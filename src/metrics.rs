@@ -0,0 +1,188 @@
+use chrono::{Duration, Local, NaiveDate};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+
+// Сколько дней храним дневные агрегаты задержки доставки
+const LATENCY_HISTORY_DAYS: usize = 14;
+// Сколько самых медленных доставок показываем в отчете
+const SLOWEST_DELIVERIES_IN_REPORT: usize = 5;
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+// Глобальный реестр метрик бота
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+pub struct Metrics {
+    counters: Mutex<BTreeMap<&'static str, u64>>,
+    latency: Mutex<LatencyTracker>,
+}
+
+// Одна доставка: кому и сколько миллисекунд прошло от слота до отправки
+struct LatencySample {
+    user_id: i64,
+    millis: u64,
+}
+
+// Агрегат задержек за один день
+struct DailyLatency {
+    day: NaiveDate,
+    count: usize,
+    p50: u64,
+    p95: u64,
+    max: u64,
+}
+
+struct LatencyTracker {
+    day: NaiveDate,
+    samples: Vec<LatencySample>,
+    history: VecDeque<DailyLatency>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Metrics {
+            counters: Mutex::new(BTreeMap::new()),
+            latency: Mutex::new(LatencyTracker {
+                day: Local::now().date_naive(),
+                samples: Vec::new(),
+                history: VecDeque::new(),
+            }),
+        }
+    }
+
+    // Увеличивает счетчик на единицу
+    pub fn increment(&self, name: &'static str) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.entry(name).or_insert(0) += 1;
+    }
+
+    // Текущее значение счетчика
+    pub fn get(&self, name: &str) -> u64 {
+        self.counters.lock().unwrap().get(name).copied().unwrap_or(0)
+    }
+
+    // Запоминает время от запланированного слота до успешной отправки уведомления
+    pub fn record_delivery_latency(&self, user_id: i64, latency: Duration) {
+        let millis = latency.num_milliseconds().max(0) as u64;
+        let mut tracker = self.latency.lock().unwrap();
+        tracker.roll_over(Local::now().date_naive());
+        tracker.samples.push(LatencySample { user_id, millis });
+    }
+
+    // Забывает замеры доставки пользователя (пользователь удалил свои данные).
+    // Дневные агрегаты обезличены и остаются
+    pub fn forget_user(&self, user_id: i64) {
+        self.latency.lock().unwrap().samples.retain(|sample| sample.user_id != user_id);
+    }
+
+    // Текстовый отчет о задержках доставки для команды /admin stats latency
+    pub fn latency_report(&self) -> String {
+        let mut tracker = self.latency.lock().unwrap();
+        tracker.roll_over(Local::now().date_naive());
+
+        let mut report = String::from("📈 Задержка доставки уведомлений (от слота до отправки)\n\n");
+
+        match tracker.today() {
+            Some(today) => {
+                report.push_str(&format!("Сегодня: {}\n", format_daily_latency(&today)));
+
+                let mut slowest: Vec<&LatencySample> = tracker.samples.iter().collect();
+                slowest.sort_by_key(|s| std::cmp::Reverse(s.millis));
+                report.push_str("\nСамые медленные доставки сегодня:\n");
+                for sample in slowest.iter().take(SLOWEST_DELIVERIES_IN_REPORT) {
+                    report.push_str(&format!("• {}: {}\n", sample.user_id, format_millis(sample.millis)));
+                }
+            }
+            None => report.push_str("Сегодня уведомления еще не отправлялись\n"),
+        }
+
+        if !tracker.history.is_empty() {
+            report.push_str("\nПо дням:\n");
+            for day in tracker.history.iter().rev() {
+                report.push_str(&format!("{}\n", format_daily_latency(day)));
+            }
+        }
+
+        report
+    }
+
+    // Метрики в текстовом формате Prometheus для команды /admin metrics
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        for (name, value) in self.counters.lock().unwrap().iter() {
+            output.push_str(&format!("# TYPE ferrisbot_{} counter\n", name));
+            output.push_str(&format!("ferrisbot_{} {}\n", name, value));
+        }
+
+        let mut tracker = self.latency.lock().unwrap();
+        tracker.roll_over(Local::now().date_naive());
+        if let Some(today) = tracker.today() {
+            output.push_str("# TYPE ferrisbot_delivery_latency_ms summary\n");
+            output.push_str(&format!("ferrisbot_delivery_latency_ms{{quantile=\"0.5\"}} {}\n", today.p50));
+            output.push_str(&format!("ferrisbot_delivery_latency_ms{{quantile=\"0.95\"}} {}\n", today.p95));
+            output.push_str(&format!("ferrisbot_delivery_latency_ms_count {}\n", today.count));
+        }
+
+        output
+    }
+}
+
+impl LatencyTracker {
+    // При смене дня сворачивает накопленные замеры в дневной агрегат
+    fn roll_over(&mut self, current_day: NaiveDate) {
+        if current_day == self.day {
+            return;
+        }
+
+        if let Some(summary) = self.today() {
+            self.history.push_back(summary);
+            while self.history.len() > LATENCY_HISTORY_DAYS {
+                self.history.pop_front();
+            }
+        }
+
+        self.day = current_day;
+        self.samples.clear();
+    }
+
+    fn today(&self) -> Option<DailyLatency> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut values: Vec<u64> = self.samples.iter().map(|s| s.millis).collect();
+        values.sort_unstable();
+
+        Some(DailyLatency {
+            day: self.day,
+            count: values.len(),
+            p50: percentile(&values, 50),
+            p95: percentile(&values, 95),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+// Перцентиль по методу ближайшего ранга для отсортированного непустого списка
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn format_daily_latency(day: &DailyLatency) -> String {
+    format!(
+        "{}: {} увед., p50 {}, p95 {}, макс {}",
+        day.day.format("%d.%m"),
+        day.count,
+        format_millis(day.p50),
+        format_millis(day.p95),
+        format_millis(day.max)
+    )
+}
+
+fn format_millis(millis: u64) -> String {
+    format!("{:.1} с", millis as f64 / 1000.0)
+}
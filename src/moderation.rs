@@ -0,0 +1,78 @@
+// Проверка свободного ввода (названия городов и т.п.) перед сохранением:
+// бот не должен повторять в своих сообщениях оскорбления и мат
+
+// Корни слов, которые блокируются по умолчанию; BLOCKLIST_FILE заменяет этот список своим
+pub const DEFAULT_BLOCKLIST: &[&str] = &[
+    "хуй", "хуе", "хуя", "пизд", "ебат", "ебан", "ебал", "еблан", "уеб", "бляд", "блять",
+    "сука", "суки", "мудак", "мудил", "пидор", "пидар", "гандон", "залуп", "шлюх",
+    "fuck", "shit", "nigger", "faggot",
+];
+
+// Латинские буквы и цифры, похожие на русские буквы: "xуй" и "3алуп" ловятся как обычные
+const LOOKALIKES: &[(char, char)] = &[
+    ('a', 'а'), ('b', 'в'), ('c', 'с'), ('e', 'е'), ('h', 'н'), ('k', 'к'), ('m', 'м'),
+    ('o', 'о'), ('p', 'р'), ('t', 'т'), ('x', 'х'), ('y', 'у'), ('0', 'о'), ('3', 'з'),
+    ('@', 'а'), ('6', 'б'),
+];
+
+// Окончания адресов сайтов, которые вставляют вместе с названием города
+const LINK_SUFFIXES: &[&str] = &[".com", ".ru", ".net", ".org", ".me", ".io", ".рф"];
+
+// Убирает управляющие символы, лишние пробелы, ссылки и упоминания: в сохраненном
+// городе они ломают разметку ответов и включают предпросмотр страниц
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<String>()
+        .split_whitespace()
+        .filter(|word| !is_link_or_mention(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_link_or_mention(word: &str) -> bool {
+    let word = word.to_lowercase();
+    let bare = word.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '/');
+    (word.starts_with('@') && word.len() > 1)
+        || word.contains("://")
+        || word.starts_with("www.")
+        || word.starts_with("t.me/")
+        || word.starts_with("telegram.me/")
+        || LINK_SUFFIXES.iter().any(|suffix| bare.ends_with(suffix) || bare.contains(&format!("{}/", suffix)))
+}
+
+// Есть ли в тексте слово из списка; пробелы, знаки и замена букв похожими не помогают обойти проверку
+pub fn is_blocked(text: &str, blocklist: &[String]) -> bool {
+    let letters: String = text
+        .to_lowercase()
+        .replace('ё', "е")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '@')
+        .collect();
+    let cyrillic: String = letters
+        .chars()
+        .map(|c| LOOKALIKES.iter().find(|(from, _)| *from == c).map_or(c, |(_, to)| *to))
+        .collect();
+
+    blocklist
+        .iter()
+        .map(|word| word.to_lowercase().replace('ё', "е"))
+        .filter(|word| !word.is_empty())
+        .any(|word| letters.contains(&word) || cyrillic.contains(&word))
+}
+
+// Очищенный текст, если его можно сохранять и показывать, иначе None
+pub fn clean_input(text: &str, blocklist: &[String]) -> Option<String> {
+    let text = sanitize(text);
+    (!is_blocked(&text, blocklist)).then_some(text)
+}
+
+// Разбор файла со списком: по слову в строке, строки с # - комментарии
+pub fn parse_blocklist(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
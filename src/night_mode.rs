@@ -0,0 +1,50 @@
+use crate::storage::{UserSettings, UserStorage};
+use crate::templates;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{NaiveTime, Timelike};
+use log::info;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// С этого часа уведомление рассказывает о завтрашнем дне: сегодняшний уже почти закончился
+pub const NIGHT_MODE_HOUR: u32 = 20;
+
+// Обработка /nightmode on|off: прогноз на завтра в поздних уведомлениях
+pub async fn handle_night_mode_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    let enabled = match args.trim().to_lowercase().as_str() {
+        "on" | "вкл" => Some(true),
+        "off" | "выкл" => Some(false),
+        _ => None,
+    };
+
+    let hour = NIGHT_MODE_HOUR.to_string();
+    let response = match enabled {
+        Some(enabled) => {
+            user.night_mode = enabled;
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} {} ночной режим", user_id, if enabled { "включил" } else { "выключил" });
+
+            confirm_saved(saved, templates::render(if enabled { "night.on" } else { "night.off" }, &[("hour", &hour)]))
+        }
+        None => templates::render("night.usage", &[
+            ("status", if user.night_mode { "включен" } else { "выключен" }),
+            ("hour", &hour),
+        ]),
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Присылать ли в этот слот прогноз на завтра вместо сегодняшней погоды
+pub fn shows_tomorrow(user: &UserSettings, slot: NaiveTime) -> bool {
+    user.night_mode && slot.hour() >= NIGHT_MODE_HOUR
+}
@@ -0,0 +1,78 @@
+use crate::storage::{UserSettings, UserStorage};
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{error, info};
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use tokio::time::{self, Duration};
+
+// Через сколько часов после /start напоминаем о незавершенной настройке
+const REMINDER_DELAY_HOURS: i64 = 24;
+// Как часто проверяем, кому пора отправить напоминание
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+// Данные кнопок для продолжения настройки
+pub const RESUME_CITY_CALLBACK: &str = "onboarding_city";
+pub const RESUME_TIME_CALLBACK: &str = "onboarding_time";
+
+// Фоновая задача: один раз напоминает пользователям, которые запустили бота,
+// но за сутки так и не указали город или время уведомлений
+pub async fn start_onboarding_reminders(bot: Bot, storage: Arc<dyn UserStorage>) {
+    info!("Запуск проверки незавершенных настроек");
+    let mut interval = time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let deadline = Utc::now() - ChronoDuration::hours(REMINDER_DELAY_HOURS);
+        let users = storage.all_users_or_log().await;
+
+        for mut user in users.into_iter().filter(|u| needs_reminder(u, deadline)) {
+            let message = reminder_text(&user);
+            let keyboard = resume_keyboard(&user);
+
+            match bot.send_message(ChatId(user.user_id), message).reply_markup(keyboard).await {
+                Ok(_) => info!("Отправлено напоминание о настройке пользователю ID: {}", user.user_id),
+                Err(e) => error!("Не удалось отправить напоминание о настройке пользователю {}: {}", user.user_id, e),
+            }
+
+            // Отмечаем даже при ошибке отправки: напоминание никогда не повторяется
+            user.onboarding_reminder_sent = true;
+            storage.save_user_or_log(user).await;
+        }
+    }
+}
+
+fn needs_reminder(user: &UserSettings, deadline: chrono::DateTime<Utc>) -> bool {
+    let setup_incomplete = user.cities.is_empty() || user.notification_time.is_none();
+    let started_long_ago = user.started_at.map(|t| t <= deadline).unwrap_or(false);
+
+    user.active && setup_incomplete && started_long_ago && !user.onboarding_reminder_sent
+}
+
+fn reminder_text(user: &UserSettings) -> String {
+    let missing = match (user.cities.is_empty(), user.notification_time.is_none()) {
+        (true, true) => "город и время уведомлений",
+        (true, false) => "город",
+        _ => "время уведомлений",
+    };
+
+    format!(
+        "👋 Похоже, вы не закончили настройку: осталось указать {}.\n\n\
+        Без этого я не смогу присылать ежедневный прогноз погоды. Продолжим?",
+        missing
+    )
+}
+
+fn resume_keyboard(user: &UserSettings) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+
+    if user.cities.is_empty() {
+        row.push(InlineKeyboardButton::callback("🏙️ Выбрать город".to_string(), RESUME_CITY_CALLBACK.to_string()));
+    }
+    if user.notification_time.is_none() {
+        row.push(InlineKeyboardButton::callback("⏰ Выбрать время".to_string(), RESUME_TIME_CALLBACK.to_string()));
+    }
+
+    InlineKeyboardMarkup::new(vec![row])
+}
@@ -0,0 +1,595 @@
+use crate::broadcast_report::{self, BroadcastSummary, DeliveryOutcome};
+use crate::delivery_history::{DeliveryHistory, NotificationKind};
+use crate::encryption::{self, StorageCipher};
+use crate::metrics::metrics;
+use crate::storage::{self, UserStorage};
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+use teloxide::RequestError;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+
+// Как часто отправитель забирает сообщения из очереди
+const DRAIN_INTERVAL: Duration = Duration::from_secs(1);
+// Как часто очередь и история доставки записываются на диск. Между записями изменения
+// копятся в памяти: переписывать файл на каждое сообщение рассылки слишком дорого
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+// Сколько сообщений отправляем за один проход: держимся ниже лимита Telegram в 30 сообщений в секунду
+const MESSAGES_PER_TICK: usize = 25;
+// Пауза перед первым повтором, дальше она удваивается
+const INITIAL_BACKOFF_SECS: i64 = 60;
+// Больше пауза не растет
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+// После стольких неудачных попыток сообщение уходит в список недоставленных
+const MAX_ATTEMPTS: u32 = 8;
+// Сколько недоставленных сообщений храним; самые старые сверх этого выбрасываются
+const MAX_DEAD_LETTERS: usize = 1000;
+// Сколько недоставленных показывает /admin outbox dead
+const DEAD_LETTERS_SHOWN: usize = 10;
+// Об успешной отправке на уровне info пишем раз на столько сообщений, остальные - в debug
+const SENT_LOG_SAMPLE: u64 = 100;
+
+static SENT_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// Готовое уведомление в формате MarkdownV2, ожидающее отправки
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub id: u64,
+    pub chat_id: i64,
+    pub text: String,
+    // Рассылка, к сводке которой относится сообщение
+    pub broadcast: Option<String>,
+    // Запланированный слот, от которого считаем задержку доставки
+    pub slot: DateTime<Utc>,
+    // Вид уведомления для истории доставки; у сообщений, поставленных до ее появления, не задан
+    #[serde(default)]
+    pub kind: Option<NotificationKind>,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+// Сводка рассылки, которая еще не доставлена целиком
+struct PendingBroadcast {
+    summary: BroadcastSummary,
+    // Отправлять сводку администраторам, даже если ошибок не было
+    always_notify: bool,
+    // Планировщик еще формирует сообщения рассылки
+    generating: bool,
+}
+
+// Исходящие уведомления: планировщик только формирует сообщения и кладет их сюда,
+// а отправитель доставляет их с ограничением скорости и повторами.
+// Очередь сохраняется в JSON-файл, поэтому рассылка продолжается после перезапуска
+pub struct Outbox {
+    messages: Mutex<Vec<OutboxMessage>>,
+    // Недоставленные после MAX_ATTEMPTS попыток: ждут, пока администратор отправит их заново
+    dead_letters: Mutex<Vec<OutboxMessage>>,
+    // До какого момента Telegram просил не отправлять сообщения (ответ 429 с retry_after)
+    paused_until: std::sync::Mutex<Option<DateTime<Utc>>>,
+    broadcasts: Mutex<HashMap<String, PendingBroadcast>>,
+    // None - очередь живет только в памяти (STORAGE_BACKEND=memory)
+    file_path: Option<String>,
+    // Очередь изменилась с последней записи в файл
+    dirty: AtomicBool,
+    cipher: Option<StorageCipher>,
+    history: DeliveryHistory,
+}
+
+// Временные ошибки, после которых есть смысл повторить отправку.
+// Блокировка бота, удаленный чат или ошибка разметки повтором не исправятся
+pub fn is_retryable(e: &RequestError) -> bool {
+    matches!(e, RequestError::Network(_) | RequestError::RetryAfter(_) | RequestError::Io(_))
+}
+
+impl Outbox {
+    // path - файл очереди, history - история доставки, загруженная из хранилища,
+    // key - STORAGE_KEY: с ним очередь пишется зашифрованной, как и файл настроек.
+    // Ошибка означает, что зашифрованную очередь нечем или не удалось расшифровать
+    pub fn new(path: &str, history: DeliveryHistory, key: Option<&str>) -> Result<Self, String> {
+        let cipher = key.map(StorageCipher::new).transpose()?;
+        let messages = load_messages(path, cipher.as_ref())?;
+        let dead_letters = load_messages(&dead_letters_path(path), cipher.as_ref())?;
+
+        if !messages.is_empty() {
+            info!("В очереди исходящих осталось с прошлого запуска: {}", messages.len());
+        }
+        if !dead_letters.is_empty() {
+            warn!("Недоставленных уведомлений ждут повторной отправки администратором: {}", dead_letters.len());
+        }
+
+        Ok(Outbox {
+            messages: Mutex::new(messages),
+            dead_letters: Mutex::new(dead_letters),
+            paused_until: std::sync::Mutex::new(None),
+            broadcasts: Mutex::new(HashMap::new()),
+            file_path: Some(path.to_string()),
+            dirty: AtomicBool::new(false),
+            cipher,
+            history,
+        })
+    }
+
+    pub fn in_memory(history: DeliveryHistory) -> Self {
+        Outbox {
+            messages: Mutex::new(Vec::new()),
+            dead_letters: Mutex::new(Vec::new()),
+            paused_until: std::sync::Mutex::new(None),
+            broadcasts: Mutex::new(HashMap::new()),
+            file_path: None,
+            dirty: AtomicBool::new(false),
+            cipher: None,
+            history,
+        }
+    }
+
+    pub fn history(&self) -> &DeliveryHistory {
+        &self.history
+    }
+
+    // Начинает сводку рассылки; она будет отправлена, когда доставят все ее сообщения
+    pub async fn begin_broadcast(&self, label: &str, always_notify: bool) {
+        self.broadcasts.lock().await.insert(
+            label.to_string(),
+            PendingBroadcast { summary: BroadcastSummary::new(label), always_notify, generating: true },
+        );
+    }
+
+    // Все сообщения рассылки сформированы: сводку можно подводить, как только их доставят
+    pub async fn end_broadcast(&self, label: &str) {
+        if let Some(pending) = self.broadcasts.lock().await.get_mut(label) {
+            pending.generating = false;
+        }
+    }
+
+    // Учитывает в сводке сообщение, которое не удалось даже сформировать
+    pub async fn record_failure(&self, label: &str, kind: &str) {
+        if let Some(pending) = self.broadcasts.lock().await.get_mut(label) {
+            pending.summary.record(DeliveryOutcome::Failed(kind.to_string()));
+        }
+    }
+
+    // Кладет готовое сообщение в очередь и отмечает его в истории доставки
+    pub async fn enqueue(&self, chat_id: i64, text: String, broadcast: Option<&str>, slot: DateTime<Local>, kind: NotificationKind) {
+        let mut messages = self.messages.lock().await;
+        // Номер не должен совпасть с недоставленным: по нему администратор отправляет его заново
+        let dead_max = self.dead_letters.lock().await.iter().map(|message| message.id).max().unwrap_or(0);
+        let id = messages.iter().map(|message| message.id).max().unwrap_or(0).max(dead_max) + 1;
+        messages.push(OutboxMessage {
+            id,
+            chat_id,
+            text,
+            broadcast: broadcast.map(str::to_string),
+            slot: slot.with_timezone(&Utc),
+            kind: Some(kind),
+            attempts: 0,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+        });
+        self.mark_dirty();
+        self.history.record_queued(chat_id, kind, slot);
+        metrics().increment("outbox_enqueued_total");
+    }
+
+    // Убирает из очереди и из недоставленных все сообщения для чата (пользователь удалил свои данные)
+    pub async fn cancel_chat(&self, chat_id: i64) -> usize {
+        let mut cancelled = 0;
+        for list in [&self.messages, &self.dead_letters] {
+            let mut messages = list.lock().await;
+            let before = messages.len();
+            messages.retain(|message| message.chat_id != chat_id);
+            cancelled += before - messages.len();
+        }
+        if cancelled > 0 {
+            self.mark_dirty();
+        }
+        cancelled
+    }
+
+    pub async fn len(&self) -> usize {
+        self.messages.lock().await.len()
+    }
+
+    // Краткое состояние очереди для /admin
+    pub async fn status(&self) -> String {
+        let dead_letters = self.dead_letters.lock().await.len();
+        let messages = self.messages.lock().await;
+        let retrying = messages.iter().filter(|message| message.attempts > 0).count();
+        let mut text = format!(
+            "📮 Очередь исходящих: {}\n\
+            Из них ждут повтора: {}\n\
+            Поставлено с запуска: {}\n\
+            Доставлено с повторной попытки: {}\n\
+            Недоставлено после {} попыток: {} (/admin outbox dead)\n\
+            Выброшено из переполненного списка недоставленных: {}",
+            messages.len(),
+            retrying,
+            metrics().get("outbox_enqueued_total"),
+            metrics().get("outbox_retried_total"),
+            MAX_ATTEMPTS,
+            dead_letters,
+            metrics().get("outbox_dropped_total")
+        );
+        if let Some(until) = self.paused_until() {
+            text.push_str(&format!("\nОтправка приостановлена по просьбе Telegram до {} UTC", until.format("%H:%M:%S")));
+        }
+        if let Some(message) = messages.iter().filter(|message| message.attempts > 0).min_by_key(|message| message.next_attempt_at) {
+            text.push_str(&format!(
+                "\nСледующий повтор: {} UTC ({})",
+                message.next_attempt_at.format("%d.%m %H:%M:%S"),
+                message.last_error.as_deref().unwrap_or("-")
+            ));
+        }
+        text
+    }
+
+    // Недоставленные сообщения для /admin outbox dead, последние сверху
+    pub async fn dead_letters_report(&self) -> String {
+        let dead_letters = self.dead_letters.lock().await;
+        if dead_letters.is_empty() {
+            return "Недоставленных уведомлений нет".to_string();
+        }
+        let mut text = format!("📭 Недоставленные уведомления: {}\n", dead_letters.len());
+        for message in dead_letters.iter().rev().take(DEAD_LETTERS_SHOWN) {
+            text.push_str(&format!(
+                "\n#{} чат {}, слот {} UTC: {}",
+                message.id,
+                message.chat_id,
+                message.slot.format("%d.%m %H:%M"),
+                message.last_error.as_deref().unwrap_or("-")
+            ));
+        }
+        text.push_str("\n\n/admin outbox replay - отправить все заново, /admin outbox replay <номер> - одно");
+        text
+    }
+
+    // Возвращает недоставленные сообщения в очередь (все или одно по номеру) с новым счетчиком попыток.
+    // Возвращает, сколько сообщений поставлено заново
+    pub async fn replay(&self, id: Option<u64>) -> usize {
+        let replayed: Vec<OutboxMessage> = {
+            let mut dead_letters = self.dead_letters.lock().await;
+            let (replayed, kept) = dead_letters.drain(..).partition(|message| id.is_none_or(|id| message.id == id));
+            *dead_letters = kept;
+            replayed
+        };
+        let count = replayed.len();
+        let now = Utc::now();
+        let mut messages = self.messages.lock().await;
+        for mut message in replayed {
+            message.attempts = 0;
+            message.next_attempt_at = now;
+            messages.push(message);
+        }
+        if count > 0 {
+            self.mark_dirty();
+        }
+        count
+    }
+
+    // Сообщение исчерпало попытки: оно остается в списке недоставленных до решения администратора
+    async fn dead_letter(&self, message: OutboxMessage) {
+        let mut dead_letters = self.dead_letters.lock().await;
+        dead_letters.push(message);
+        if dead_letters.len() > MAX_DEAD_LETTERS {
+            let overflow = dead_letters.len() - MAX_DEAD_LETTERS;
+            for _ in dead_letters.drain(..overflow) {
+                metrics().increment("outbox_dropped_total");
+            }
+        }
+        metrics().increment("outbox_dead_letters_total");
+        self.mark_dirty();
+    }
+
+    // Telegram ограничил частоту отправки: лимит общий для бота, поэтому ждут все сообщения
+    fn pause(&self, wait: Duration) {
+        let until = Utc::now() + ChronoDuration::from_std(wait).unwrap_or(ChronoDuration::seconds(MAX_BACKOFF_SECS));
+        let mut paused_until = self.paused_until.lock().unwrap();
+        *paused_until = Some(paused_until.map_or(until, |current| current.max(until)));
+    }
+
+    fn paused_until(&self) -> Option<DateTime<Utc>> {
+        let mut paused_until = self.paused_until.lock().unwrap();
+        if paused_until.is_some_and(|until| until <= Utc::now()) {
+            *paused_until = None;
+        }
+        *paused_until
+    }
+
+    // Забирает до limit сообщений, время отправки которых наступило, в порядке постановки
+    async fn take_due(&self, limit: usize) -> Vec<OutboxMessage> {
+        if self.paused_until().is_some() {
+            return Vec::new();
+        }
+        let now = Utc::now();
+        let mut messages = self.messages.lock().await;
+        messages.sort_by_key(|message| message.id);
+
+        let mut due = Vec::new();
+        let mut pending = Vec::with_capacity(messages.len());
+        for message in messages.drain(..) {
+            if due.len() < limit && message.next_attempt_at <= now {
+                due.push(message);
+            } else {
+                pending.push(message);
+            }
+        }
+        *messages = pending;
+        if !due.is_empty() {
+            self.mark_dirty();
+        }
+        due
+    }
+
+    async fn requeue(&self, message: OutboxMessage) {
+        let mut messages = self.messages.lock().await;
+        messages.push(message);
+        self.mark_dirty();
+    }
+
+    async fn record_outcome(&self, broadcast: Option<&str>, outcome: DeliveryOutcome) {
+        let Some(label) = broadcast else {
+            return;
+        };
+        if let Some(pending) = self.broadcasts.lock().await.get_mut(label) {
+            pending.summary.record(outcome);
+        }
+    }
+
+    // Рассылки, все сообщения которых уже обработаны
+    async fn take_finished_broadcasts(&self) -> Vec<PendingBroadcast> {
+        let messages = self.messages.lock().await;
+        let mut broadcasts = self.broadcasts.lock().await;
+        let finished: Vec<String> = broadcasts
+            .iter()
+            .filter(|(label, pending)| {
+                !pending.generating
+                    && !messages.iter().any(|message| message.broadcast.as_deref() == Some(label.as_str()))
+            })
+            .map(|(label, _)| label.clone())
+            .collect();
+        finished.iter().filter_map(|label| broadcasts.remove(label)).collect()
+    }
+
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    // Записывает изменившуюся очередь в файл, а историю доставки - в хранилище.
+    // Очередь пишется первой: если запись истории не удастся, после перезапуска
+    // уведомление в худшем случае придет дважды, но не потеряется
+    pub async fn flush(&self, storage: &dyn UserStorage) {
+        if let Some(file_path) = &self.file_path {
+            if self.dirty.swap(false, Ordering::SeqCst) {
+                let snapshot = self.messages.lock().await.clone();
+                let dead_letters = self.dead_letters.lock().await.clone();
+                let saved = save_messages(file_path, &snapshot, self.cipher.as_ref())
+                    .and_then(|_| save_messages(&dead_letters_path(file_path), &dead_letters, self.cipher.as_ref()));
+                if let Err(e) = saved {
+                    error!("Ошибка сохранения очереди исходящих: {}", e);
+                    self.mark_dirty();
+                }
+            }
+        }
+        self.history.flush(storage).await;
+    }
+}
+
+// Недоставленные сообщения лежат рядом с очередью: outbox.json.dead
+fn dead_letters_path(path: &str) -> String {
+    format!("{}.dead", path)
+}
+
+// Сообщения из файла очереди; отсутствующий файл - пустой список. Ошибка означает,
+// что зашифрованный файл нечем или не удалось расшифровать
+fn load_messages(path: &str, cipher: Option<&StorageCipher>) -> Result<Vec<OutboxMessage>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => encryption::open_content(&content, cipher, path)?,
+        Err(_) => return Ok(Vec::new()),
+    };
+    match serde_json::from_str(&content) {
+        Ok(messages) => Ok(messages),
+        Err(e) => {
+            // Копия нужна, чтобы первая же запись не затерла недоставленные сообщения
+            error!("Ошибка чтения очереди исходящих {}: {}", path, e);
+            storage::backup_broken_file(path);
+            Ok(Vec::new())
+        }
+    }
+}
+
+// Когда повторить отправку: при ограничении частоты - не раньше, чем просит Telegram,
+// при остальных временных ошибках - с паузой, удваивающейся с каждой попыткой
+fn retry_delay(e: &RequestError, attempts: u32) -> ChronoDuration {
+    match e {
+        RequestError::RetryAfter(wait) => {
+            ChronoDuration::from_std(*wait).unwrap_or(ChronoDuration::seconds(MAX_BACKOFF_SECS)) + ChronoDuration::seconds(1)
+        }
+        _ => ChronoDuration::seconds((INITIAL_BACKOFF_SECS << attempts.min(16)).min(MAX_BACKOFF_SECS)),
+    }
+}
+
+// Пишем во временный файл и переименовываем, чтобы сбой не оставил очередь недописанной
+fn save_messages(file_path: &str, messages: &[OutboxMessage], cipher: Option<&StorageCipher>) -> Result<(), String> {
+    let mut json = serde_json::to_string(messages).map_err(|e| e.to_string())?;
+    if let Some(cipher) = cipher {
+        json = cipher.seal(&json)?;
+    }
+    let tmp_path = format!("{}.tmp", file_path);
+    fs::write(&tmp_path, json)
+        .and_then(|_| fs::rename(&tmp_path, file_path))
+        .map_err(|e| e.to_string())
+}
+
+// Фоновая задача: раз в FLUSH_INTERVAL записывает накопившиеся изменения очереди и истории доставки
+pub async fn start_flush(outbox: Arc<Outbox>, storage: Arc<dyn UserStorage>) {
+    let mut interval = time::interval(FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        outbox.flush(&*storage).await;
+    }
+}
+
+// Фоновая задача: доставляет сообщения из очереди и отправляет администраторам сводки завершенных рассылок
+pub async fn start_sender(bot: Bot, outbox: Arc<Outbox>, admin_ids: Vec<i64>) {
+    info!("Отправитель исходящих уведомлений запущен");
+    let mut interval = time::interval(DRAIN_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for message in outbox.take_due(MESSAGES_PER_TICK).await {
+            // После ответа 429 остаток пачки ждет вместе с очередью, а не получает тот же отказ
+            if outbox.paused_until().is_some() {
+                outbox.requeue(message).await;
+                continue;
+            }
+            send_message(&bot, &outbox, message).await;
+        }
+
+        for pending in outbox.take_finished_broadcasts().await {
+            // О рассылках без проблем сообщаем, только если об этом просили, иначе сводка приходила бы каждую минуту
+            let notify_admins = pending.always_notify || pending.summary.has_failures();
+            broadcast_report::finish(&bot, &admin_ids, pending.summary, notify_admins).await;
+        }
+    }
+}
+
+async fn send_message(bot: &Bot, outbox: &Outbox, mut message: OutboxMessage) {
+    match bot.send_message(ChatId(message.chat_id), message.text.clone())
+        .parse_mode(ParseMode::MarkdownV2)
+        .await
+    {
+        Ok(_) => {
+            let sent = SENT_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+            if sent % SENT_LOG_SAMPLE == 1 {
+                info!("Уведомление успешно отправлено пользователю ID: {} (всего отправлено: {})", message.chat_id, sent);
+            } else {
+                debug!("Уведомление успешно отправлено пользователю ID: {}", message.chat_id);
+            }
+            metrics().increment("notifications_sent_total");
+            if message.attempts > 0 {
+                metrics().increment("outbox_retried_total");
+            }
+            metrics().record_delivery_latency(message.chat_id, Utc::now() - message.slot);
+            if let Some(kind) = message.kind {
+                outbox.history.record_result(message.chat_id, kind, message.slot, Ok(()));
+            }
+            outbox.record_outcome(message.broadcast.as_deref(), DeliveryOutcome::Sent).await;
+        }
+        // Ограничение частоты - не вина сообщения, попыткой оно не считается
+        Err(RequestError::RetryAfter(wait)) => {
+            let delay = retry_delay(&RequestError::RetryAfter(wait), message.attempts);
+            warn!(
+                "Telegram ограничил частоту отправки, уведомление пользователю {} и остальная очередь ждут {} с",
+                message.chat_id,
+                delay.num_seconds()
+            );
+            outbox.pause(wait);
+            message.next_attempt_at = Utc::now() + delay;
+            message.last_error = Some(RequestError::RetryAfter(wait).to_string());
+            outbox.requeue(message).await;
+        }
+        Err(e) if is_retryable(&e) && message.attempts + 1 < MAX_ATTEMPTS => {
+            let delay = retry_delay(&e, message.attempts);
+            warn!(
+                "Не удалось отправить уведомление пользователю {} ({}), следующая попытка через {} с",
+                message.chat_id,
+                e,
+                delay.num_seconds()
+            );
+            message.attempts += 1;
+            message.next_attempt_at = Utc::now() + delay;
+            message.last_error = Some(e.to_string());
+            outbox.requeue(message).await;
+        }
+        Err(e) => {
+            error!("Не удалось отправить уведомление пользователю {}: {}", message.chat_id, e);
+            metrics().increment("notifications_failed_total");
+            if let Some(kind) = message.kind {
+                outbox.history.record_result(message.chat_id, kind, message.slot, Err(e.to_string()));
+            }
+            outbox.record_outcome(message.broadcast.as_deref(), DeliveryOutcome::from_request_error(&e)).await;
+            // Временная ошибка, не прошедшая за все попытки, ждет администратора, а не пропадает
+            if is_retryable(&e) {
+                message.attempts += 1;
+                message.last_error = Some(e.to_string());
+                outbox.dead_letter(message).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn retry_delay_waits_as_long_as_telegram_asks() {
+        let delay = retry_delay(&RequestError::RetryAfter(Duration::from_secs(37)), 0);
+        assert!(delay >= ChronoDuration::seconds(37));
+        assert!(delay < ChronoDuration::seconds(INITIAL_BACKOFF_SECS));
+
+        let io = || RequestError::Io(std::io::Error::other("connection reset"));
+        assert_eq!(retry_delay(&io(), 0), ChronoDuration::seconds(INITIAL_BACKOFF_SECS));
+        assert_eq!(retry_delay(&io(), 2), ChronoDuration::seconds(INITIAL_BACKOFF_SECS * 4));
+        assert_eq!(retry_delay(&io(), MAX_ATTEMPTS), ChronoDuration::seconds(MAX_BACKOFF_SECS));
+    }
+
+    #[tokio::test]
+    async fn retry_after_pauses_the_whole_queue() {
+        let storage = MemoryStorage::new();
+        let outbox = Outbox::in_memory(DeliveryHistory::load(&storage).await);
+        outbox.enqueue(1, "text".to_string(), None, Local::now(), NotificationKind::Daily).await;
+
+        outbox.pause(Duration::from_secs(30));
+        assert!(outbox.take_due(MESSAGES_PER_TICK).await.is_empty());
+        assert_eq!(outbox.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn dead_letters_survive_restart_and_can_be_replayed() {
+        let dir = std::env::temp_dir().join(format!("ferrisbot-outbox-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("outbox.json").to_string_lossy().into_owned();
+        let storage = MemoryStorage::new();
+
+        let outbox = Outbox::new(&path, DeliveryHistory::load(&storage).await, None).unwrap();
+        outbox.enqueue(1, "first".to_string(), None, Local::now(), NotificationKind::Daily).await;
+        outbox.enqueue(2, "second".to_string(), None, Local::now(), NotificationKind::Daily).await;
+        for mut message in outbox.take_due(MESSAGES_PER_TICK).await {
+            message.attempts = MAX_ATTEMPTS;
+            message.last_error = Some("network error".to_string());
+            outbox.dead_letter(message).await;
+        }
+        outbox.flush(&storage).await;
+
+        // Как после перезапуска: очередь пуста, недоставленные прочитаны из файла
+        let outbox = Outbox::new(&path, DeliveryHistory::load(&storage).await, None).unwrap();
+        assert_eq!(outbox.len().await, 0);
+        assert!(outbox.dead_letters_report().await.contains("network error"));
+
+        // Новое сообщение не получает номер недоставленного
+        outbox.enqueue(3, "third".to_string(), None, Local::now(), NotificationKind::Daily).await;
+        assert_eq!(outbox.take_due(MESSAGES_PER_TICK).await[0].id, 3);
+
+        assert_eq!(outbox.replay(Some(42)).await, 0);
+        assert_eq!(outbox.replay(Some(2)).await, 1);
+        let replayed = outbox.take_due(MESSAGES_PER_TICK).await;
+        assert_eq!(replayed.len(), 1);
+        assert_eq!((replayed[0].chat_id, replayed[0].attempts), (2, 0));
+
+        assert_eq!(outbox.replay(None).await, 1);
+        assert_eq!(outbox.len().await, 1);
+        assert_eq!(outbox.dead_letters_report().await, "Недоставленных уведомлений нет");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
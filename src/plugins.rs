@@ -0,0 +1,95 @@
+use crate::config::Config;
+use crate::handlers::Command;
+use crate::storage::UserStorage;
+use crate::weather::WeatherClient;
+use log::{info, warn};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::BotCommand;
+use teloxide::utils::command::BotCommands;
+
+// Результат обработчика команды плагина
+pub type PluginFuture<'a> = Pin<Box<dyn Future<Output = ResponseResult<()>> + Send + 'a>>;
+
+// То, что доступно обработчику команды плагина
+pub struct PluginContext<'a> {
+    pub bot: &'a Bot,
+    pub msg: &'a Message,
+    pub storage: &'a dyn UserStorage,
+    pub weather_client: &'a WeatherClient,
+    pub config: &'a Config,
+}
+
+// Дополнительная команда бота (курсы валют, гороскоп и т.п.), подключаемая при сборке.
+// Команда попадает в меню бота, а сообщения с ней - в handle
+pub trait CommandPlugin: Send + Sync {
+    // Имя команды без косой черты, например "rates"
+    fn command(&self) -> &str;
+    // Описание для меню команд
+    fn description(&self) -> &str;
+    // Обработка команды; args - текст после имени команды
+    fn handle<'a>(&'a self, ctx: PluginContext<'a>, args: &'a str) -> PluginFuture<'a>;
+}
+
+// Подключенные плагины; передается в App::with_plugins
+#[derive(Clone, Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Arc<dyn CommandPlugin>>,
+}
+
+// Команда плагина, найденная в сообщении
+#[derive(Clone)]
+pub struct PluginCall {
+    pub plugin: Arc<dyn CommandPlugin>,
+    pub args: String,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Подключает плагин. Команды с именами встроенных или уже подключенных пропускаются:
+    // встроенная команда все равно перехватила бы сообщение раньше
+    pub fn register(mut self, plugin: impl CommandPlugin + 'static) -> Self {
+        let name = plugin.command().trim_start_matches('/').to_lowercase();
+        let builtin = Command::bot_commands()
+            .iter()
+            .any(|command| command.command.trim_start_matches('/') == name);
+        if builtin || self.find(&name).is_some() {
+            warn!("Команда плагина /{} уже занята, плагин не подключен", name);
+            return self;
+        }
+        info!("Подключен плагин с командой /{}", name);
+        self.plugins.push(Arc::new(plugin));
+        self
+    }
+
+    // Команды плагинов для меню бота
+    pub fn bot_commands(&self) -> Vec<BotCommand> {
+        self.plugins
+            .iter()
+            .map(|plugin| BotCommand::new(plugin.command().trim_start_matches('/'), plugin.description()))
+            .collect()
+    }
+
+    fn find(&self, name: &str) -> Option<&Arc<dyn CommandPlugin>> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.command().trim_start_matches('/').eq_ignore_ascii_case(name))
+    }
+
+    // Ищет в тексте сообщения команду плагина: "/rates usd" или "/rates@bot usd"
+    pub(crate) fn parse(&self, msg: &Message) -> Option<PluginCall> {
+        let text = msg.text()?.trim();
+        let rest = text.strip_prefix('/')?;
+        let (command, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let name = command.split('@').next().unwrap_or(command);
+        self.find(name).map(|plugin| PluginCall {
+            plugin: Arc::clone(plugin),
+            args: args.trim().to_string(),
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use crate::delivery_history::DeliveryHistory;
+use crate::handlers::load_or_apologize;
+use crate::storage::UserStorage;
+use crate::{data_export, delete_me, escape_markdown_v2, templates};
+use serde_json::Value;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+// Префикс данных кнопок под сводкой
+pub const CALLBACK_PREFIX: &str = "privacy_";
+
+// Пояснения к полям записи пользователя в том порядке, в котором их показываем.
+// Поле без пояснения все равно попадет в сводку под своим именем - список полей
+// берется из самой записи, а не из этой таблицы
+const FIELDS: &[(&str, &str)] = &[
+    ("user_id", "ID чата в Telegram"),
+    ("cities", "города прогноза"),
+    ("recent_cities", "недавние города"),
+    ("travel", "город и дата окончания поездки"),
+    ("notification_time", "время уведомлений"),
+    ("extra_times", "дополнительное время уведомлений"),
+    ("delivery_window", "окно доставки"),
+    ("interval_schedule", "прогноз по интервалу"),
+    ("smart_time", "ранний прогноз при непогоде"),
+    ("night_mode", "вечерний прогноз на завтра"),
+    ("forecast_updates", "подписка на изменения прогноза"),
+    ("cute_mode", "милый режим"),
+    ("daily_extras", "цитата или гороскоп дня"),
+    ("report_sections", "разделы отчета"),
+    ("report_style", "стиль отчета"),
+    ("temperature_precision", "точность температуры"),
+    ("units", "единицы измерения"),
+    ("locale", "формат дат и времени"),
+    ("utc_offset", "часовой пояс города"),
+    ("household", "участники семьи: ID и имена чатов"),
+    ("forecast_snapshot", "прогноз из последнего утреннего уведомления"),
+    ("early_sent_on", "день, когда прогноз ушел заранее"),
+    ("state", "какой ввод бот сейчас ждет"),
+    ("last_input", "номер последнего ответа на запрос ввода"),
+    ("active", "не заблокирован ли бот"),
+    ("started_at", "время первого /start"),
+    ("created_at", "когда появилась запись"),
+    ("last_seen", "время последнего сообщения боту"),
+    ("commands_used", "число отправленных команд"),
+    ("onboarding_reminder_sent", "отправлялось ли напоминание о настройке"),
+    ("reengagement_opt_out", "отписка от сообщений «мы скучали»"),
+    ("last_reengagement_at", "когда последний раз писали «мы скучали»"),
+    ("retention_warning_at", "когда предупреждали об удалении неактивных данных"),
+    ("version", "версия формата записи"),
+];
+
+// Обработка /privacy: что бот хранит о пользователе, и кнопки выгрузки и удаления
+pub async fn handle_privacy_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    history: &DeliveryHistory,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let record = match serde_json::to_value(&user) {
+        Ok(Value::Object(record)) => record,
+        _ => return Ok(()),
+    };
+
+    let mut lines: Vec<String> = FIELDS
+        .iter()
+        .filter_map(|(field, description)| record.get(*field).map(|value| describe(description, value)))
+        .collect();
+    // Новые поля, для которых еще не написали пояснение
+    for (field, value) in record.iter().filter(|(field, _)| !FIELDS.iter().any(|(known, _)| known == field)) {
+        lines.push(describe(field, value));
+    }
+    // История доставки хранится отдельно от записи
+    let deliveries = serde_json::to_value(history.recent(user_id, usize::MAX)).unwrap_or_default();
+    lines.push(describe("история доставки уведомлений (/history)", &deliveries));
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("📦 Выгрузить мои данные", format!("{}export", CALLBACK_PREFIX)),
+        InlineKeyboardButton::callback("🗑 Удалить мои данные", format!("{}delete", CALLBACK_PREFIX)),
+    ]]);
+    bot.send_message(msg.chat.id, templates::render("privacy.summary", &[
+        ("fields", &escape_markdown_v2(&lines.join("\n"))),
+    ]))
+    .parse_mode(ParseMode::MarkdownV2)
+    .reply_markup(keyboard)
+    .await?;
+    Ok(())
+}
+
+// Кнопки под сводкой ведут туда же, куда команды /export и /delete_me
+pub async fn handle_callback(
+    bot: &Bot,
+    message: Option<&Message>,
+    storage: &dyn UserStorage,
+    history: &DeliveryHistory,
+    action: &str,
+) -> ResponseResult<()> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    match action {
+        "export" => data_export::handle_export_command(bot, message, storage, history).await,
+        "delete" => delete_me::handle_delete_me_command(bot, message).await,
+        _ => Ok(()),
+    }
+}
+
+// Строка сводки: пустые поля отмечаем, чтобы было видно, что сейчас не хранится ничего
+fn describe(description: &str, value: &Value) -> String {
+    let empty = match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    };
+    if empty {
+        format!("• {} - пусто", description)
+    } else {
+        format!("• {}", description)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::handlers::load_or_apologize;
+use crate::locale::Locale;
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::trends;
+use crate::utils;
+use crate::weather::WeatherClient;
+use crate::escape_markdown_v2;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Обработка /records: самый теплый и самый холодный день в городе пользователя
+// за все время, пока бот получает по нему погоду
+pub async fn handle_records_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let Some(city) = user.city().map(str::to_string) else {
+        bot.send_message(msg.chat.id, templates::text("city.missing"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+
+    let days = weather_client.history().map(|history| history.days(&city)).unwrap_or_default();
+    let city_name = escape_markdown_v2(&utils::echo(&city));
+    let response = match trends::records(&days) {
+        Some(records) => {
+            let locale = user.locale.unwrap_or_default();
+            let precision = user.temperature_precision;
+            templates::render("records.report", &[
+                ("city", &city_name),
+                ("max", &escape_markdown_v2(&precision.celsius(records.warmest.temp_max))),
+                ("max_date", &escape_markdown_v2(&locale.short_date(records.warmest.date))),
+                ("min", &escape_markdown_v2(&precision.celsius(records.coldest.temp_min))),
+                ("min_date", &escape_markdown_v2(&locale.short_date(records.coldest.date))),
+                ("since", &escape_markdown_v2(&locale.short_date(records.since))),
+                // Шаблон на русском: locale пользователя задает только формат дат, не язык
+                ("days", &utils::count(Locale::Ru, records.days as i64, ["день", "дня", "дней"])),
+            ])
+        }
+        None => templates::render("records.empty", &[("city", &city_name)]),
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use crate::storage::{UserSettings, UserStorage};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use tokio::sync::RwLock;
+use tokio::time::{self, Duration};
+
+// Через сколько дней без активности пользователь считается «заскучавшим»
+const DORMANT_AFTER_DAYS: i64 = 30;
+// Не чаще одного письма в этот период
+const CAMPAIGN_PERIOD_DAYS: i64 = 30;
+// Как часто проверяем, кому пора написать
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Данные кнопки отписки от напоминаний
+pub const UNSUBSCRIBE_CALLBACK: &str = "reengage_unsubscribe";
+
+const DEFAULT_TEMPLATE: &str = "🌤 Мы скучали! Давно не виделись, а погода тем временем меняется каждый день.\n\n\
+Загляните за свежим прогнозом: /weather";
+
+// Настройки кампании, которыми управляет администратор через /admin reengage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReengagementSettings {
+    pub enabled: bool,
+    // Текст сообщения; {city} заменяется на город пользователя
+    pub template: String,
+}
+
+impl Default for ReengagementSettings {
+    fn default() -> Self {
+        ReengagementSettings {
+            enabled: true,
+            template: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+// Хранилище настроек кампании в отдельном JSON-файле
+pub struct ReengagementStore {
+    settings: RwLock<ReengagementSettings>,
+    file_path: String,
+}
+
+impl ReengagementStore {
+    pub fn new(path: &str) -> Self {
+        let settings = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(settings) => Some(settings),
+                Err(e) => {
+                    error!("Ошибка чтения настроек кампании {}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        ReengagementStore {
+            settings: RwLock::new(settings),
+            file_path: path.to_string(),
+        }
+    }
+
+    pub async fn get(&self) -> ReengagementSettings {
+        self.settings.read().await.clone()
+    }
+
+    pub async fn set_template(&self, template: &str) {
+        let mut settings = self.settings.write().await;
+        settings.template = template.to_string();
+        self.save(&settings);
+    }
+
+    pub async fn set_enabled(&self, enabled: bool) {
+        let mut settings = self.settings.write().await;
+        settings.enabled = enabled;
+        self.save(&settings);
+    }
+
+    fn save(&self, settings: &ReengagementSettings) {
+        match serde_json::to_string_pretty(settings) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.file_path, json) {
+                    error!("Ошибка сохранения настроек кампании: {}", e);
+                }
+            }
+            Err(e) => error!("Ошибка сериализации настроек кампании: {}", e),
+        }
+    }
+}
+
+// Фоновая задача: раз в месяц пишет пользователям, которые давно не заходили
+pub async fn start_reengagement_campaign(bot: Bot, storage: Arc<dyn UserStorage>, store: Arc<ReengagementStore>) {
+    info!("Запуск кампании возврата неактивных пользователей");
+    let mut interval = time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        run_campaign(&bot, &*storage, &store).await;
+    }
+}
+
+// Один проход кампании; возвращает число отправленных сообщений
+pub async fn run_campaign(bot: &Bot, storage: &dyn UserStorage, store: &ReengagementStore) -> usize {
+    let settings = store.get().await;
+    if !settings.enabled {
+        return 0;
+    }
+
+    let now = Utc::now();
+    let mut sent = 0;
+
+    for mut user in storage.all_users_or_log().await.into_iter().filter(|u| is_dormant(u, now)) {
+        let text = settings.template.replace("{city}", user.city().unwrap_or("вашем городе"));
+
+        match bot.send_message(ChatId(user.user_id), text).reply_markup(unsubscribe_keyboard()).await {
+            Ok(_) => {
+                info!("Отправлено сообщение «мы скучали» пользователю ID: {}", user.user_id);
+                sent += 1;
+            }
+            Err(e) => error!("Не удалось отправить сообщение «мы скучали» пользователю {}: {}", user.user_id, e),
+        }
+
+        user.last_reengagement_at = Some(now);
+        storage.save_user_or_log(user).await;
+    }
+
+    sent
+}
+
+fn is_dormant(user: &UserSettings, now: DateTime<Utc>) -> bool {
+    // Для записей без last_seen ориентируемся на дату появления пользователя
+    let inactive_long = user
+        .last_seen
+        .or(user.created_at)
+        .map(|seen| now - seen >= ChronoDuration::days(DORMANT_AFTER_DAYS))
+        .unwrap_or(false);
+    let not_recently_contacted = user
+        .last_reengagement_at
+        .map(|sent| now - sent >= ChronoDuration::days(CAMPAIGN_PERIOD_DAYS))
+        .unwrap_or(true);
+
+    user.active && user.user_id > 0 && !user.reengagement_opt_out && inactive_long && not_recently_contacted
+}
+
+fn unsubscribe_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "🔕 Больше не присылать".to_string(),
+        UNSUBSCRIBE_CALLBACK.to_string(),
+    )]])
+}
@@ -0,0 +1,256 @@
+use crate::config::secret_var;
+use crate::locale::Locale;
+use crate::utils;
+use chrono::Utc;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pretty_env_logger::env_logger;
+use reqwest::{Client, Url};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+// Одинаковые ошибки отправляем оператору не чаще, чем раз в этот интервал
+const REPEAT_REPORT_INTERVAL: Duration = Duration::from_secs(300);
+
+// Куда отправлять отчеты об ошибках
+enum ReportTarget {
+    // Произвольный webhook: POST с JSON (поле text совместимо со Slack/Mattermost)
+    Webhook(String),
+    // Sentry: событие отправляется через store API, адрес и ключ берутся из DSN
+    Sentry { store_url: String, auth_header: String },
+}
+
+struct ErrorReport {
+    target: String,
+    message: String,
+}
+
+// Логгер-обертка над pretty_env_logger: пишет все как обычно,
+// а записи уровня error дополнительно пересылает в систему отчетов об ошибках
+struct ReportingLogger {
+    // Под блокировкой, чтобы уровень можно было сменить на лету (/admin loglevel)
+    inner: RwLock<env_logger::Logger>,
+    sender: Option<mpsc::UnboundedSender<ErrorReport>>,
+}
+
+// Установленный логгер: через него меняется уровень логирования без перезапуска
+static LOGGER: OnceLock<&'static ReportingLogger> = OnceLock::new();
+
+impl Log for ReportingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.read().unwrap().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.read().unwrap().log(record);
+
+        // Ошибки самого модуля отчетов не пересылаем, чтобы не зациклиться
+        if record.level() == Level::Error && record.target() != module_path!() {
+            if let Some(sender) = &self.sender {
+                let _ = sender.send(ErrorReport {
+                    target: record.target().to_string(),
+                    message: record.args().to_string(),
+                });
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.read().unwrap().flush();
+    }
+}
+
+// Инициализирует логирование и, если задан ERROR_WEBHOOK_URL или SENTRY_DSN,
+// пересылку ошибок и паник оператору. Должна вызываться внутри tokio runtime.
+pub fn init_logging() {
+    let inner = build_logger(None);
+    let max_level = inner.filter();
+
+    let targets = report_targets_from_env();
+    let sender = if targets.is_empty() {
+        None
+    } else {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_reporter(receiver, targets));
+        Some(sender)
+    };
+    let reporting_enabled = sender.is_some();
+
+    let logger: &'static ReportingLogger = Box::leak(Box::new(ReportingLogger { inner: RwLock::new(inner), sender }));
+    log::set_logger(logger).expect("Логгер уже инициализирован");
+    log::set_max_level(max_level);
+    let _ = LOGGER.set(logger);
+
+    install_panic_hook();
+
+    if reporting_enabled {
+        log::info!("Отправка отчетов об ошибках включена");
+    }
+}
+
+// Логгер с фильтрами из RUST_LOG; level заменяет общий уровень, уровни отдельных модулей остаются
+fn build_logger(level: Option<LevelFilter>) -> env_logger::Logger {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if let Ok(filters) = std::env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    if let Some(level) = level {
+        builder.filter_level(level);
+    }
+    builder.build()
+}
+
+// Меняет уровень логирования без перезапуска бота; None - вернуть уровень из RUST_LOG.
+// Возвращает самый подробный уровень, который теперь попадает в лог
+pub fn set_log_level(level: Option<LevelFilter>) -> Result<LevelFilter, String> {
+    let logger = LOGGER.get().ok_or("логирование не инициализировано")?;
+    let inner = build_logger(level);
+    let max_level = inner.filter();
+    *logger.inner.write().unwrap() = inner;
+    log::set_max_level(max_level);
+    Ok(max_level)
+}
+
+fn report_targets_from_env() -> Vec<ReportTarget> {
+    let mut targets = Vec::new();
+
+    if let Some(url) = secret_var("ERROR_WEBHOOK_URL") {
+        targets.push(ReportTarget::Webhook(url));
+    }
+
+    if let Some(dsn) = secret_var("SENTRY_DSN") {
+        match parse_sentry_dsn(&dsn) {
+            Some(target) => targets.push(target),
+            None => eprintln!("Некорректный SENTRY_DSN, отчеты в Sentry отключены"),
+        }
+    }
+
+    targets
+}
+
+// DSN вида https://<ключ>@<хост>/<id проекта>
+fn parse_sentry_dsn(dsn: &str) -> Option<ReportTarget> {
+    let url = Url::parse(dsn).ok()?;
+    let key = url.username();
+    let host = url.host_str()?;
+    let project_id = url.path().trim_matches('/');
+
+    if key.is_empty() || project_id.is_empty() {
+        return None;
+    }
+
+    let host_with_port = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+
+    Some(ReportTarget::Sentry {
+        store_url: format!("{}://{}/api/{}/store/", url.scheme(), host_with_port, project_id),
+        auth_header: format!(
+            "Sentry sentry_version=7, sentry_key={}, sentry_client=ferrisbot/{}",
+            key,
+            env!("CARGO_PKG_VERSION")
+        ),
+    })
+}
+
+// Паники логируем как ошибки, чтобы они попадали в отчеты, а затем
+// передаем управление стандартному обработчику
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "неизвестно".to_string());
+        let payload = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "без описания".to_string());
+
+        log::error!("Паника в {}: {}", location, payload);
+        default_hook(info);
+    }));
+}
+
+async fn run_reporter(mut receiver: mpsc::UnboundedReceiver<ErrorReport>, targets: Vec<ReportTarget>) {
+    let client = Client::new();
+    // Когда последний раз отправляли ошибку с таким текстом и сколько повторов пропустили
+    let mut recent: HashMap<String, (Instant, u32)> = HashMap::new();
+
+    while let Some(report) = receiver.recv().await {
+        let key = format!("{}: {}", report.target, report.message);
+
+        let suppressed = match recent.get_mut(&key) {
+            Some((last_sent, suppressed)) if last_sent.elapsed() < REPEAT_REPORT_INTERVAL => {
+                *suppressed += 1;
+                continue;
+            }
+            Some((last_sent, suppressed)) => {
+                let count = *suppressed;
+                *last_sent = Instant::now();
+                *suppressed = 0;
+                count
+            }
+            None => {
+                recent.insert(key, (Instant::now(), 0));
+                0
+            }
+        };
+
+        // Не даем карте бесконечно расти при большом разнообразии ошибок
+        recent.retain(|_, (last_sent, _)| last_sent.elapsed() < REPEAT_REPORT_INTERVAL * 2);
+
+        let message = if suppressed > 0 {
+            format!("{} (повторялась еще {})", report.message, utils::count(Locale::Ru, suppressed as i64, ["раз", "раза", "раз"]))
+        } else {
+            report.message.clone()
+        };
+
+        for target in &targets {
+            send_report(&client, target, &report.target, &message).await;
+        }
+    }
+}
+
+async fn send_report(client: &Client, target: &ReportTarget, source: &str, message: &str) {
+    let result = match target {
+        ReportTarget::Webhook(url) => {
+            let body = json!({
+                "text": format!("🚨 FerrisBot [{}]: {}", source, message),
+                "level": "error",
+                "target": source,
+                "message": message,
+                "timestamp": Utc::now().to_rfc3339(),
+            });
+            client.post(url).json(&body).send().await
+        }
+        ReportTarget::Sentry { store_url, auth_header } => {
+            let body = json!({
+                "timestamp": Utc::now().timestamp(),
+                "level": "error",
+                "logger": source,
+                "platform": "other",
+                "message": { "formatted": message },
+            });
+            client
+                .post(store_url)
+                .header("X-Sentry-Auth", auth_header)
+                .json(&body)
+                .send()
+                .await
+        }
+    };
+
+    match result {
+        Ok(response) if !response.status().is_success() => {
+            log::warn!("Сервис отчетов об ошибках вернул статус {}", response.status());
+        }
+        Err(e) => log::warn!("Не удалось отправить отчет об ошибке: {}", e),
+        Ok(_) => {}
+    }
+}
@@ -0,0 +1,93 @@
+use crate::activity;
+use crate::config::Config;
+use crate::locale::Locale;
+use crate::metrics::metrics;
+use crate::storage::{UserSettings, UserStorage};
+use crate::{templates, utils};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{error, info};
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+use tokio::time::{self, Duration};
+
+// Сколько дней после предупреждения ждем, прежде чем удалить данные
+const WARNING_PERIOD_DAYS: i64 = 7;
+// Как часто проверяем хранилище на устаревшие записи
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+// Фоновая задача: удаляет данные пользователей, которые не заходили дольше
+// RETENTION_MONTHS месяцев, предварительно предупредив их
+pub async fn start_retention_job(bot: Bot, storage: Arc<dyn UserStorage>, config: Arc<Config>) {
+    let months = match config.retention_months {
+        Some(months) => months,
+        None => {
+            info!("Автоматическое удаление неактивных пользователей отключено");
+            return std::future::pending().await;
+        }
+    };
+
+    info!("Запуск очистки данных пользователей, неактивных более {} мес.", months);
+    let mut interval = time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        run_retention(&bot, &*storage, months).await;
+    }
+}
+
+// Один проход очистки: предупреждает тех, кому пора, и удаляет предупрежденных
+async fn run_retention(bot: &Bot, storage: &dyn UserStorage, months: u32) {
+    let now = Utc::now();
+    let inactive_since = now - ChronoDuration::days(30 * months as i64);
+    let mut warned = 0;
+    let mut pruned = 0;
+
+    // Активность, еще не записанная в хранилище, тоже считается: вернувшегося пользователя не удаляем
+    activity::flush(storage).await;
+    for mut user in storage.all_users_or_log().await {
+        let last_activity = match last_activity(&user) {
+            Some(time) if time <= inactive_since => time,
+            _ => continue,
+        };
+
+        // Предупреждение действительно, только если после него пользователь так и не появился
+        match user.retention_warning_at.filter(|warned_at| *warned_at > last_activity) {
+            None => {
+                let text = templates::render("retention.warning", &[
+                    ("months", &months.to_string()),
+                    ("period", &utils::count(Locale::Ru, WARNING_PERIOD_DAYS, ["день", "дня", "дней"])),
+                ]);
+                if let Err(e) = bot.send_message(ChatId(user.user_id), text).parse_mode(ParseMode::MarkdownV2).await {
+                    error!("Не удалось предупредить пользователя {} об удалении данных: {}", user.user_id, e);
+                }
+
+                user.retention_warning_at = Some(now);
+                storage.save_user_or_log(user).await;
+                warned += 1;
+            }
+            Some(warned_at) if now - warned_at >= ChronoDuration::days(WARNING_PERIOD_DAYS) => {
+                info!("Удаляем данные неактивного пользователя ID: {}", user.user_id);
+                if let Err(e) = storage.delete_user(user.user_id).await {
+                    error!("Не удалось удалить данные пользователя {}: {}", user.user_id, e);
+                    continue;
+                }
+                metrics().increment("users_pruned_total");
+                pruned += 1;
+            }
+            Some(_) => {}
+        }
+    }
+
+    if warned > 0 || pruned > 0 {
+        info!("Очистка данных: предупреждено {}, удалено {}", warned, pruned);
+    }
+}
+
+// Последняя известная активность; групповые чаты не учитываются
+fn last_activity(user: &UserSettings) -> Option<DateTime<Utc>> {
+    if user.user_id < 0 {
+        return None;
+    }
+    user.last_seen.or(user.created_at)
+}
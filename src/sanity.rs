@@ -0,0 +1,61 @@
+use crate::weather::{ForecastResponse, MainInfo, OpenWeatherResponse, WeatherInfo};
+
+// Температура за пределами этих значений на Земле не наблюдалась: такой ответ поврежден
+const MIN_TEMP: f32 = -90.0;
+const MAX_TEMP: f32 = 60.0;
+// Данные о текущей погоде не должны быть старше этого
+const MAX_CURRENT_AGE_SECS: i64 = 6 * 3600;
+// Допустимое расхождение часов сервиса и бота
+const MAX_CLOCK_SKEW_SECS: i64 = 3600;
+// Первая запись прогноза должна относиться к ближайшим часам
+const MAX_FORECAST_START_SECS: i64 = 6 * 3600;
+
+// Проверка правдоподобия текущей погоды; now - текущее время (Unix)
+pub fn check_current(data: &OpenWeatherResponse, now: i64) -> Result<(), String> {
+    check_weather(&data.weather)?;
+    check_main(&data.main)?;
+    if !(0.0..150.0).contains(&data.wind.speed) {
+        return Err(format!("скорость ветра {} м/с", data.wind.speed));
+    }
+    if data.dt < now - MAX_CURRENT_AGE_SECS || data.dt > now + MAX_CLOCK_SKEW_SECS {
+        return Err(format!("время измерения {} при текущем {}", data.dt, now));
+    }
+    Ok(())
+}
+
+// Проверка правдоподобия прогноза: каждая запись и время начала прогноза
+pub fn check_forecast(forecast: &ForecastResponse, now: i64) -> Result<(), String> {
+    let first = forecast.list.first().ok_or_else(|| "пустой прогноз".to_string())?;
+    if first.dt < now - MAX_CURRENT_AGE_SECS || first.dt > now + MAX_FORECAST_START_SECS {
+        return Err(format!("прогноз начинается с {} при текущем {}", first.dt, now));
+    }
+    for item in &forecast.list {
+        check_weather(&item.weather).map_err(|e| format!("{}: {}", item.dt_txt, e))?;
+        check_main(&item.main).map_err(|e| format!("{}: {}", item.dt_txt, e))?;
+    }
+    Ok(())
+}
+
+fn check_weather(weather: &[WeatherInfo]) -> Result<(), String> {
+    if weather.is_empty() {
+        return Err("нет описания погоды".to_string());
+    }
+    Ok(())
+}
+
+fn check_main(main: &MainInfo) -> Result<(), String> {
+    for (name, temp) in [
+        ("температура", main.temp),
+        ("ощущаемая температура", main.feels_like),
+        ("минимальная температура", main.temp_min),
+        ("максимальная температура", main.temp_max),
+    ] {
+        if !(MIN_TEMP..=MAX_TEMP).contains(&temp) {
+            return Err(format!("{} {}°C", name, temp));
+        }
+    }
+    if !(0.0..=100.0).contains(&main.humidity) {
+        return Err(format!("влажность {}%", main.humidity));
+    }
+    Ok(())
+}
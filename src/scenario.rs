@@ -0,0 +1,209 @@
+use crate::config::Config;
+use crate::reengagement::ReengagementStore;
+use crate::delivery_history::DeliveryHistory;
+use crate::outbox::Outbox;
+use crate::plugins::PluginRegistry;
+use crate::storage::{JsonStorage, UserStorage};
+use crate::weather::WeatherClient;
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::Me;
+
+// Сценарий e2e-проверки: последовательность действий одного пользователя
+// и ожидаемое состояние его настроек в хранилище после каждого шага
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    // Чат пользователя в тестовом окружении Telegram, куда бот будет отвечать
+    user_id: i64,
+    // Отдельный файл хранилища, чтобы сценарий не трогал users.json
+    #[serde(default = "default_storage_path")]
+    storage_path: String,
+    steps: Vec<ScenarioStep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioStep {
+    // Текст сообщения или команды от пользователя
+    send: Option<String>,
+    // Данные нажатой кнопки инлайн-клавиатуры (например, "city_Москва")
+    callback: Option<String>,
+    // Ожидаемые значения полей UserSettings после шага
+    #[serde(default)]
+    expect: Map<String, Value>,
+}
+
+fn default_storage_path() -> String {
+    "scenario_users.json".to_string()
+}
+
+// Прогоняет сценарий через дерево обработчиков бота. Возвращает true, если все ожидания выполнены
+pub async fn run_scenario(
+    path: &str,
+    bot: Bot,
+    weather_client: WeatherClient,
+    config: Arc<Config>,
+    plugins: Arc<PluginRegistry>,
+) -> bool {
+    let scenario = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str::<Scenario>(&content).map_err(|e| e.to_string()))
+    {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            error!("Не удалось загрузить сценарий {}: {}", path, e);
+            return false;
+        }
+    };
+
+    let me = match bot.get_me().await {
+        Ok(me) => me,
+        Err(e) => {
+            error!("Не удалось получить информацию о боте: {}", e);
+            return false;
+        }
+    };
+
+    // Каждый прогон начинается с чистого хранилища
+    let _ = std::fs::remove_file(&scenario.storage_path);
+    let storage: Arc<dyn UserStorage> = match JsonStorage::new(&scenario.storage_path, None).await {
+        Ok(storage) => Arc::new(storage),
+        Err(e) => {
+            error!("Не удалось открыть хранилище сценария: {}", e);
+            return false;
+        }
+    };
+    let reengagement_store = Arc::new(ReengagementStore::new("scenario_reengagement.json"));
+    let outbox = match Outbox::new("scenario_outbox.json", DeliveryHistory::load(&*storage).await, None) {
+        Ok(outbox) => Arc::new(outbox),
+        Err(e) => {
+            error!("Не удалось открыть очередь сценария: {}", e);
+            return false;
+        }
+    };
+    let handler = crate::build_handler();
+
+    info!("Запуск сценария {}: {} шагов", path, scenario.steps.len());
+    let mut passed = true;
+
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let step_number = index + 1;
+        let update_id = step_number as i32;
+
+        let update = match build_update(&bot, &me, scenario.user_id, update_id, step).await {
+            Ok(update) => update,
+            Err(e) => {
+                error!("Шаг {}: не удалось сформировать обновление: {}", step_number, e);
+                passed = false;
+                continue;
+            }
+        };
+
+        let deps = dptree::deps![
+            bot.clone(),
+            Arc::clone(&storage),
+            weather_client.clone(),
+            Arc::clone(&config),
+            Arc::clone(&reengagement_store),
+            Arc::clone(&outbox),
+            Arc::clone(&plugins),
+            me.clone(),
+            update
+        ];
+
+        match handler.dispatch(deps).await {
+            ControlFlow::Break(Ok(())) => info!("Шаг {}: обработан", step_number),
+            // Ошибки Bot API (например, ответ на синтетический колбэк) не считаем провалом шага,
+            // результат проверяется по состоянию хранилища
+            ControlFlow::Break(Err(e)) => warn!("Шаг {}: обработчик вернул ошибку: {}", step_number, e),
+            ControlFlow::Continue(_) => warn!("Шаг {}: ни один обработчик не принял обновление", step_number),
+        }
+
+        if !check_expectations(&*storage, scenario.user_id, step_number, &step.expect).await {
+            passed = false;
+        }
+    }
+
+    if passed {
+        info!("Сценарий {} пройден", path);
+    } else {
+        error!("Сценарий {} не пройден", path);
+    }
+    passed
+}
+
+async fn build_update(bot: &Bot, me: &Me, user_id: i64, update_id: i32, step: &ScenarioStep) -> Result<Update, String> {
+    let from = json!({
+        "id": user_id,
+        "is_bot": false,
+        "first_name": "Scenario",
+        "username": "scenario_runner",
+    });
+
+    let update = match (&step.send, &step.callback) {
+        (Some(text), None) => json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": Utc::now().timestamp(),
+                "chat": { "id": user_id, "type": "private", "first_name": "Scenario" },
+                "from": from,
+                "text": text,
+            }
+        }),
+        (None, Some(data)) => {
+            // Колбэк должен ссылаться на настоящее сообщение бота, иначе его нельзя отредактировать
+            let message = bot
+                .send_message(ChatId(user_id), format!("Сценарий: нажатие кнопки {} (@{})", data, me.username()))
+                .await
+                .map_err(|e| e.to_string())?;
+
+            json!({
+                "update_id": update_id,
+                "callback_query": {
+                    "id": format!("scenario-{}", update_id),
+                    "from": from,
+                    "message": serde_json::to_value(&message).map_err(|e| e.to_string())?,
+                    "chat_instance": "scenario",
+                    "data": data,
+                }
+            })
+        }
+        _ => return Err("в шаге должно быть ровно одно из полей send или callback".to_string()),
+    };
+
+    // Разбираем через текст: при разборе из Value вложенные поля Update не распознаются
+    serde_json::from_str(&update.to_string()).map_err(|e| e.to_string())
+}
+
+async fn check_expectations(storage: &dyn UserStorage, user_id: i64, step_number: usize, expect: &Map<String, Value>) -> bool {
+    if expect.is_empty() {
+        return true;
+    }
+
+    let actual = match storage.get_user(user_id).await {
+        Ok(Some(user)) => serde_json::to_value(user).unwrap_or(Value::Null),
+        Ok(None) => Value::Null,
+        Err(e) => {
+            error!("Шаг {}: {}", step_number, e);
+            return false;
+        }
+    };
+
+    let mut ok = true;
+    for (field, expected) in expect {
+        let value = actual.get(field).unwrap_or(&Value::Null);
+        if value != expected {
+            error!(
+                "Шаг {}: поле {} = {}, ожидалось {}",
+                step_number, field, value, expected
+            );
+            ok = false;
+        }
+    }
+    ok
+}
@@ -1,14 +1,34 @@
-use teloxide::types::ChatId;
+use teloxide::types::{ChatId, ParseMode};
 use teloxide::Bot;
-use super::storage::JsonStorage;
+use super::storage::{UserSettings, UserStorage};
 use super::weather::WeatherClient;
-use chrono::{Local, Datelike, Weekday, Timelike};
-use tokio::time::{sleep, Duration};
+use super::metrics::metrics;
+use super::travel;
+use super::forecast_updates;
+use super::error_throttle;
+use super::utils;
+use super::smart_time;
+use super::night_mode;
+use super::config::Config;
+use super::outbox::Outbox;
+use super::delivery_history::NotificationKind;
+use super::formatter::{FormatOptions, ReportStyle};
+use super::daily_extras;
+use super::household;
+use super::interval;
+use super::alert_rules;
+use super::timezone;
+use super::locale::Locale;
+use super::all_cities;
+use chrono::{DateTime, Local, Datelike, NaiveTime, TimeZone, Weekday, Timelike};
+use futures::stream::{self, StreamExt};
+use tokio::time::{sleep, Duration, Instant};
+use std::collections::HashMap;
 use std::sync::Arc;
-use teloxide::payloads::SendMessageSetters;
 use teloxide::prelude::Requester;
+use teloxide::payloads::SendMessageSetters;
 use rand::Rng;
-use log::{info, error, warn};
+use log::{debug, info, error, warn};
 
 // Вспомогательная функция для экранирования специальных символов Markdown
 fn escape_markdown_v2(text: &str) -> String {
@@ -33,11 +53,74 @@ fn escape_markdown_v2(text: &str) -> String {
     result
 }
 
-pub async fn start_scheduler(bot: Bot, storage: Arc<JsonStorage>, weather_client: WeatherClient) {
+// Пауза перед первым перезапуском упавшего планировщика
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(5);
+// Максимальная пауза между перезапусками
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(300);
+// Если планировщик проработал дольше этого времени, сбой считаем разовым и сбрасываем паузу
+const STABLE_RUN_DURATION: Duration = Duration::from_secs(600);
+// Сколько сообщений /admin simulate формирует по-настоящему: каждое стоит запроса к OpenWeather
+const SIMULATE_COMPOSE_LIMIT: usize = 30;
+// Сколько уведомлений формируется одновременно: каждое - запросы к OpenWeather,
+// поэтому рассылка на тысячи пользователей не должна открывать тысячи соединений сразу
+const GENERATION_CONCURRENCY: usize = 16;
+// Насколько далеко в прошлое планировщик догоняет пропущенные слоты
+const MAX_CATCH_UP_MINUTES: i64 = 30;
+
+// Запускает планировщик и перезапускает его с нарастающей паузой,
+// если цикл проверки расписания аварийно завершился (например, из-за паники)
+pub async fn start_scheduler(
+    bot: Bot,
+    storage: Arc<dyn UserStorage>,
+    weather_client: WeatherClient,
+    config: Arc<Config>,
+    outbox: Arc<Outbox>,
+) {
+    let mut backoff = INITIAL_RESTART_BACKOFF;
+
+    loop {
+        let started_at = Instant::now();
+        let task = tokio::spawn(run_scheduler(
+            bot.clone(),
+            Arc::clone(&storage),
+            weather_client.clone(),
+            Arc::clone(&config),
+            Arc::clone(&outbox),
+        ));
+
+        match task.await {
+            Ok(()) => warn!("Цикл планировщика неожиданно завершился"),
+            Err(e) if e.is_panic() => {
+                error!("Планировщик аварийно завершился из-за паники");
+                metrics().increment("scheduler_restarts_total");
+            }
+            Err(e) => error!("Задача планировщика прервана: {}", e),
+        }
+
+        if started_at.elapsed() > STABLE_RUN_DURATION {
+            backoff = INITIAL_RESTART_BACKOFF;
+        }
+
+        warn!("Перезапуск планировщика через {} с", backoff.as_secs());
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+    }
+}
+
+async fn run_scheduler(
+    bot: Bot,
+    storage: Arc<dyn UserStorage>,
+    weather_client: WeatherClient,
+    config: Arc<Config>,
+    outbox: Arc<Outbox>,
+) {
     info!("Планировщик уведомлений запущен. Проверка расписания будет выполняться каждую минуту");
     
     // Счетчик для отслеживания времени между проверками webhook
     let mut webhook_check_counter = 0;
+    // Последний обработанный слот: если проверка затянулась или процесс подвис,
+    // следующая обработает все пропущенные минуты, а не только текущую
+    let mut last_slot: Option<DateTime<Local>> = None;
     
     loop {
         // Удаляем webhook только раз в 15 минут, чтобы уменьшить количество запросов
@@ -62,116 +145,635 @@ pub async fn start_scheduler(bot: Bot, storage: Arc<JsonStorage>, weather_client
         }
         
         let now = Local::now();
-        let now_time = now.format("%H:%M").to_string();
-        let today = now.weekday();
-        
-        info!("Проверка расписания уведомлений [{}]", now_time);
+        // Начало текущей минуты - запланированный слот, от которого считаем задержку доставки
+        let current_slot = now.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(now);
+
+        // Возвращаем домашний город тем, у кого закончилась поездка
+        travel::expire_travel(&bot, &*storage, now).await;
         
         // Получаем всех пользователей из хранилища
-        let users = storage.get_all_users().await;
-        info!("Всего пользователей в базе: {}", users.len());
+        let users = storage.all_users_or_log().await;
 
-        // Проверяем, не настало ли время для массовой рассылки (12:00 или 18:00)
-        let hours = now.hour();
-        let minutes = now.minute();
-        let is_mass_notification_time = (hours == 12 || hours == 18) && minutes == 0;
-        
-        info!("Текущее время: {}, массовая рассылка: {}", now_time, is_mass_notification_time);
-        
-        if is_mass_notification_time {
-            info!("Время массовой рассылки [{}]. Отправляем уведомления всем пользователям.", now_time);
-            
-            // Дополнительно удаляем webhook перед массовой рассылкой
-            // и добавляем обработку ошибок
-            match bot.delete_webhook().await {
-                Ok(_) => {
-                    info!("Webhook успешно удален перед массовой рассылкой");
-                },
-                Err(e) => {
-                    if e.to_string().contains("network error") {
-                        warn!("Временная сетевая ошибка при удалении webhook перед массовой рассылкой");
-                    } else {
-                        error!("Ошибка при удалении webhook перед массовой рассылкой: {}", e);
-                    }
+        for slot in slots_to_process(last_slot, current_slot) {
+            run_slot(&bot, &*storage, &weather_client, &config, &outbox, &users, slot).await;
+        }
+        last_slot = Some(current_slot);
+
+        // Ждем начала следующей минуты: пауза отсчитывается от слота, а не от конца проверки
+        let next_slot = current_slot + chrono::Duration::minutes(1);
+        let wait = (next_slot - Local::now()).to_std().unwrap_or_default();
+        sleep(wait).await;
+    }
+}
+
+// Слоты после last и до current включительно, но не больше MAX_CATCH_UP_MINUTES:
+// уведомления, опоздавшие сильнее, уже не нужны. При первой проверке - только текущий слот
+fn slots_to_process(last: Option<DateTime<Local>>, current: DateTime<Local>) -> Vec<DateTime<Local>> {
+    let minute = chrono::Duration::minutes(1);
+    let Some(last) = last else {
+        return vec![current];
+    };
+    let earliest = current - chrono::Duration::minutes(MAX_CATCH_UP_MINUTES - 1);
+    if last + minute < earliest {
+        warn!(
+            "Планировщик пропустил слоты с {} по {}: они уже не будут обработаны",
+            (last + minute).format("%H:%M"),
+            (earliest - minute).format("%H:%M")
+        );
+    }
+    let mut slot = (last + minute).max(earliest);
+    let mut slots = Vec::new();
+    while slot <= current {
+        slots.push(slot);
+        slot += minute;
+    }
+    if slots.len() > 1 {
+        warn!("Планировщик догоняет пропущенные слоты: {}", slots.len() - 1);
+    }
+    slots
+}
+
+// Все уведомления одного слота расписания (минуты)
+async fn run_slot(
+    bot: &Bot,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    config: &Config,
+    outbox: &Outbox,
+    users: &[UserSettings],
+    slot: DateTime<Local>,
+) {
+    // Шаг расписания в минутах: уведомления уходят только на границах шага
+    let granularity = config.schedule_granularity;
+    let now_time = slot.format("%H:%M").to_string();
+    debug!("Проверка расписания уведомлений [{}]", now_time);
+
+    // Проверяем, не настало ли время для массовой рассылки (12:00 или 18:00)
+    let hours = slot.hour();
+    let minutes = slot.minute();
+    let is_mass_notification_time = (hours == 12 || hours == 18) && minutes == 0;
+
+    if is_mass_notification_time {
+        info!("Время массовой рассылки [{}]. Отправляем уведомления всем пользователям.", now_time);
+
+        // Дополнительно удаляем webhook перед массовой рассылкой
+        // и добавляем обработку ошибок
+        match bot.delete_webhook().await {
+            Ok(_) => {
+                info!("Webhook успешно удален перед массовой рассылкой");
+            },
+            Err(e) => {
+                if e.to_string().contains("network error") {
+                    warn!("Временная сетевая ошибка при удалении webhook перед массовой рассылкой");
+                } else {
+                    error!("Ошибка при удалении webhook перед массовой рассылкой: {}", e);
                 }
             }
-            
-            send_mass_notifications(&bot, &users, &weather_client, &now_time, today).await;
         }
 
-        // Обычная проверка индивидуальных уведомлений
-        for user in users {
-            if let Some(scheduled_time) = &user.notification_time {
-                if scheduled_time == &now_time {
-                    if let Some(city) = &user.city {
-                        info!("Отправка уведомления пользователю ID: {}, город: {}", user.user_id, city);
-                        
-                        // Получаем погоду
-                        match weather_client.get_weather(city).await {
-                            Ok(weather_text) => {
-                                // Формируем сообщение в зависимости от режима бота
-                                let message = if user.cute_mode {
-                                    // Милый режим: с приветствием и милыми сообщениями
-                                    // Получаем приветствие и дополнительные сообщения
-                                    let greeting = get_greeting(today);
-                                    let cute_message = get_cute_message();
-                                    let good_day_wish = get_good_day_wish();
-                                    
-                                    // Формируем полное сообщение с экранированием
-                                    format!("{}\n\n🌦 *Погода в {}*\n\n{}\n\n{}\n\n{}", 
-                                        escape_markdown_v2(&greeting), 
-                                        escape_markdown_v2(city), 
-                                        escape_markdown_v2(&weather_text), 
-                                        escape_markdown_v2(&cute_message), 
-                                        escape_markdown_v2(&good_day_wish))
-                                } else {
-                                    // Стандартный режим: только погода
-                                    format!("🌅 *Утренний прогноз погоды*\n\n🌦 *Погода в {}*\n\n{}", 
-                                        escape_markdown_v2(city), 
-                                        escape_markdown_v2(&weather_text))
-                                };
-                                
-                                // Отправляем сообщение
-                                if let Err(e) = bot.send_message(ChatId(user.user_id), message)
-                                    .parse_mode(teloxide::types::ParseMode::MarkdownV2)
-                                    .await 
-                                {
-                                    error!("Не удалось отправить уведомление пользователю {}: {}", user.user_id, e);
-                                } else {
-                                    info!("Уведомление успешно отправлено пользователю ID: {}", user.user_id);
-                                }
-                            }
-                            Err(e) => {
-                                warn!("Ошибка получения погоды для пользователя {}: {}", user.user_id, e);
-                                
-                                // Отправляем уведомление об ошибке
-                                let error_message = if user.cute_mode {
-                                    format!("Доброе утро\\! К сожалению, не удалось получить данные о погоде: {}", 
-                                        escape_markdown_v2(&e.to_string()))
-                                } else {
-                                    format!("❌ *Ошибка*: Не удалось получить данные о погоде: {}", 
-                                        escape_markdown_v2(&e.to_string()))
-                                };
-                                
-                                if let Err(e) = bot.send_message(
-                                    ChatId(user.user_id),
-                                    error_message
-                                ).parse_mode(teloxide::types::ParseMode::MarkdownV2).await {
-                                    error!("Не удалось отправить уведомление об ошибке пользователю {}: {}", user.user_id, e);
-                                }
-                            }
-                        }
-                    } else {
-                        warn!("У пользователя ID: {} не установлен город", user.user_id);
-                    }
+        let label = format!("массовая рассылка {}", slot.format("%d.%m %H:%M"));
+        outbox.begin_broadcast(&label, true).await;
+        queue_mass_notifications(users, weather_client, outbox, &label, slot).await;
+        outbox.end_broadcast(&label).await;
+    }
+
+    if now_time == alert_rules::CHECK_TIME && !config.alert_rules.is_empty() {
+        info!("Проверка правил предупреждений оператора: {}", config.alert_rules.len());
+        alert_rules::check_rules(bot, storage, weather_client, &config.alert_rules).await;
+    }
+
+    if now_time == forecast_updates::CHECK_TIME {
+        info!("Сверка утренних прогнозов со свежими данными");
+        forecast_updates::check_forecast_changes(bot, storage, weather_client).await;
+    }
+
+    // Обычная проверка индивидуальных уведомлений: берем только тех, чей слот наступил
+    let current_slot = NaiveTime::from_hms_opt(hours, minutes, 0).unwrap_or_default();
+    let mut slots = group_by_slot(users, granularity);
+    let label = format!("уведомления {}", slot.format("%d.%m %H:%M"));
+    outbox.begin_broadcast(&label, false).await;
+    let (due, skipped) = select_due(slots.remove(&current_slot).unwrap_or_default(), current_slot, slot);
+    let mut tick = TickSummary::default();
+    for (user_id, reason) in skipped {
+        if reason == SkipReason::NoCity {
+            debug!("У пользователя ID: {} не установлен город", user_id);
+            tick.without_city += 1;
+        }
+    }
+    let regular: Vec<i64> = due.iter().map(|(user, _, _)| user.user_id).collect();
+    let extra = extra_due(users, current_slot, granularity, &regular, slot);
+    let mut jobs = Vec::new();
+    for (user, cities, tomorrow) in due.into_iter().chain(extra) {
+        debug!("Подготовка уведомления пользователю ID: {}, города: {}", user.user_id, cities.join(", "));
+        jobs.push(NotificationJob { user, cities, tomorrow, kind: NotificationKind::Daily });
+        tick.regular += 1;
+    }
+
+    // Прогноз каждые N часов в рабочее время - всегда о текущей погоде
+    for (user, city) in interval::due_users(users, current_slot, &regular, slot) {
+        debug!("Подготовка уведомления по интервалу пользователю ID: {}, город: {}", user.user_id, city);
+        jobs.push(NotificationJob { user, cities: vec![city], tomorrow: false, kind: NotificationKind::Interval });
+        tick.interval += 1;
+    }
+
+    // Умное время: за час до слота проверяем погоду и при непогоде отправляем прогноз сразу
+    if !smart_time::is_quiet(current_slot) {
+        let ahead_slot = current_slot + chrono::Duration::minutes(smart_time::ADVANCE_MINUTES);
+        let ahead_at = slot + chrono::Duration::minutes(smart_time::ADVANCE_MINUTES);
+        let early: Vec<NotificationJob> = stream::iter(slots.remove(&ahead_slot).unwrap_or_default())
+            .map(|user| early_job(bot, storage, weather_client, user, ahead_slot, ahead_at))
+            .buffer_unordered(GENERATION_CONCURRENCY)
+            .filter_map(|job| async move { job })
+            .collect()
+            .await;
+        tick.early = early.len();
+        jobs.extend(early);
+    }
+
+    // Формируем уведомления параллельно, но не больше GENERATION_CONCURRENCY сразу
+    stream::iter(jobs)
+        .for_each_concurrent(GENERATION_CONCURRENCY, |job| {
+            queue_notification(bot, storage, weather_client, outbox, &label, job, slot)
+        })
+        .await;
+
+    // Сводку по рассылке подведет отправитель, когда доставит все ее сообщения
+    outbox.end_broadcast(&label).await;
+
+    tick.log(&now_time, users.len(), is_mass_notification_time);
+}
+
+// Умное время для одного пользователя: при непогоде в слоте через час сообщает об этом сразу
+// и возвращает уведомление, которое нужно отправить заранее
+async fn early_job(
+    bot: &Bot,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    mut user: UserSettings,
+    ahead_slot: NaiveTime,
+    ahead_at: DateTime<Local>,
+) -> Option<NotificationJob> {
+    // День того уведомления, которое отправляем заранее, - по часам пользователя
+    let today = timezone::user_date(&user, ahead_at);
+    if !user.active || !user.smart_time || user.early_sent_on == Some(today) {
+        return None;
+    }
+    let city = user.notification_city(today)?;
+    let reason = smart_time::early_reason(weather_client, user.user_id, &city).await?;
+
+    debug!("Умное время: отправляем прогноз пользователю ID: {} заранее ({})", user.user_id, reason);
+    user.early_sent_on = Some(today);
+    storage.save_user_or_log(user.clone()).await;
+
+    smart_time::send_early_notice(bot, user.user_id, &reason).await;
+    let tomorrow = night_mode::shows_tomorrow(&user, timezone::to_user_time(&user, ahead_slot));
+    // Непогоду проверяем по основному городу, а прогноз приходит по всем городам, как обычно
+    let cities = user.notification_cities(today);
+    Some(NotificationJob { user, cities, tomorrow, kind: NotificationKind::Early })
+}
+
+// Итоги одной проверки расписания. Подробности по каждому пользователю пишутся на уровне debug,
+// а на info - одна строка за минуту, и только если в эту минуту что-то отправлялось
+#[derive(Default)]
+struct TickSummary {
+    regular: usize,
+    interval: usize,
+    early: usize,
+    without_city: usize,
+}
+
+impl TickSummary {
+    fn log(&self, now_time: &str, users: usize, mass: bool) {
+        let queued = self.regular + self.interval + self.early;
+        let line = format!(
+            "Расписание [{}]: пользователей {}, в очередь {} (по времени {}, по интервалу {}, заранее {}), без города {}{}",
+            now_time,
+            users,
+            queued,
+            self.regular,
+            self.interval,
+            self.early,
+            self.without_city,
+            if mass { ", массовая рассылка" } else { "" }
+        );
+        if queued > 0 || mass {
+            info!("{}", line);
+        } else {
+            debug!("{}", line);
+        }
+    }
+}
+
+// Уведомление одному пользователю в текущем слоте. tomorrow - прислать прогноз на завтра
+struct NotificationJob {
+    user: UserSettings,
+    cities: Vec<String>,
+    tomorrow: bool,
+    kind: NotificationKind,
+}
+
+// Формирует уведомление в отдельной задаче, чтобы паника при обработке одного пользователя
+// не останавливала рассылку остальным, кладет его в очередь исходящих
+// и при необходимости запоминает утренний прогноз по основному городу.
+// Уведомление, уже поставленное в очередь в этом слоте (бот перезапустили в ту же минуту), не повторяется
+async fn queue_notification(
+    bot: &Bot,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    outbox: &Outbox,
+    label: &str,
+    job: NotificationJob,
+    slot: DateTime<Local>,
+) {
+    let NotificationJob { user, cities, tomorrow, kind } = job;
+    let user_id = user.user_id;
+    if outbox.history().was_queued(user_id, kind, slot) {
+        debug!("Уведомление пользователю ID: {} за этот слот уже поставлено в очередь", user_id);
+        return;
+    }
+    let household = household::recipients(&user);
+    let wants_updates = user.forecast_updates && slot.format("%H:%M").to_string().as_str() < forecast_updates::CHECK_TIME;
+    let snapshot_city = cities.first().cloned();
+    let job = tokio::spawn(build_scheduled_notification(
+        bot.clone(),
+        user,
+        cities,
+        weather_client.clone(),
+        slot.weekday(),
+        tomorrow,
+    ));
+    match job.await {
+        Ok(Some(messages)) => {
+            // Тот же прогноз получают участники семьи пользователя
+            for chat_id in household.into_iter().chain(std::iter::once(user_id)) {
+                for message in &messages {
+                    outbox.enqueue(chat_id, message.clone(), Some(label), slot, kind).await;
                 }
             }
         }
+        Ok(None) => outbox.record_failure(label, "не удалось получить погоду").await,
+        Err(e) => {
+            error!("Сбой при формировании уведомления пользователю {}: {}", user_id, e);
+            metrics().increment("notification_job_panics_total");
+            outbox.record_failure(label, "сбой при формировании уведомления").await;
+        }
+    }
+
+    // Запоминаем утренний прогноз, чтобы днем сообщить, если он изменится
+    if let Some(city) = snapshot_city.filter(|_| wants_updates) {
+        forecast_updates::save_snapshot(storage, weather_client, user_id, &city).await;
+    }
+}
+
+// Раскладывает пользователей по слотам расписания. Время, сохраненное до смены шага,
+// округляется до ближайшего слота, чтобы такие пользователи не остались без уведомлений
+fn group_by_slot(users: &[UserSettings], granularity: u32) -> HashMap<NaiveTime, Vec<UserSettings>> {
+    let mut slots: HashMap<NaiveTime, Vec<UserSettings>> = HashMap::new();
+    for user in users {
+        if let Some(slot) = user_slot(user, granularity) {
+            slots.entry(timezone::to_server_time(user, slot)).or_default().push(user.clone());
+        }
+    }
+    slots
+}
+
+// Почему пользователь слота не получит уведомление
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SkipReason {
+    // Пользователь заблокировал бота
+    Inactive,
+    // Умное время уже прислало сегодняшний прогноз заранее
+    SentEarly,
+    NoCity,
+}
+
+impl SkipReason {
+    fn label(self) -> &'static str {
+        match self {
+            SkipReason::Inactive => "заблокировал бота",
+            SkipReason::SentEarly => "прогноз уже отправлен заранее",
+            SkipReason::NoCity => "не установлен город",
+        }
+    }
+}
+
+// Получатель уведомления: пользователь, города и прогноз на завтра вместо текущей погоды
+type Recipient = (UserSettings, Vec<String>, bool);
+
+// Пользователи, у которых на этот слот приходится дополнительное время уведомлений.
+// Тем, кому в этот слот уже уходит основное уведомление, второй раз не отправляем
+fn extra_due(users: &[UserSettings], slot: NaiveTime, granularity: u32, regular: &[i64], at: DateTime<Local>) -> Vec<Recipient> {
+    users
+        .iter()
+        .filter(|user| user.active && !regular.contains(&user.user_id))
+        .filter(|user| {
+            user.extra_times
+                .iter()
+                .filter_map(|time| utils::parse_time(time))
+                .any(|time| timezone::to_server_time(user, utils::round_time(time, granularity)) == slot)
+        })
+        .filter_map(|user| {
+            let cities = user.notification_cities(timezone::user_date(user, at));
+            let tomorrow = night_mode::shows_tomorrow(user, timezone::to_user_time(user, slot));
+            (!cities.is_empty()).then(|| (user.clone(), cities, tomorrow))
+        })
+        .collect()
+}
+
+// Кому из пользователей слота уходит уведомление. Остальные - во втором списке с причиной.
+// at - момент слота: "сегодня" для отметки умного времени и поездки считается по часам пользователя
+fn select_due(users: Vec<UserSettings>, slot: NaiveTime, at: DateTime<Local>) -> (Vec<Recipient>, Vec<(i64, SkipReason)>) {
+    let mut due = Vec::new();
+    let mut skipped = Vec::new();
+    for user in users {
+        let today = timezone::user_date(&user, at);
+        if !user.active {
+            skipped.push((user.user_id, SkipReason::Inactive));
+        } else if user.early_sent_on == Some(today) {
+            skipped.push((user.user_id, SkipReason::SentEarly));
+        } else if user.notification_cities(today).is_empty() {
+            skipped.push((user.user_id, SkipReason::NoCity));
+        } else {
+            let tomorrow = night_mode::shows_tomorrow(&user, timezone::to_user_time(&user, slot));
+            let cities = user.notification_cities(today);
+            due.push((user, cities, tomorrow));
+        }
+    }
+    (due, skipped)
+}
+
+// Симуляция для /admin simulate: кому и какие уведомления ушли бы сегодня в указанное время.
+// Сообщения формируются по-настоящему (с запросами погоды), но ничего не отправляется
+pub async fn simulate(storage: &dyn UserStorage, weather_client: &WeatherClient, config: &Config, time: NaiveTime) -> String {
+    let granularity = config.schedule_granularity;
+    let slot = utils::round_time(time, granularity);
+    let now = Local::now();
+    let users = match storage.get_all_users().await {
+        Ok(users) => users,
+        Err(e) => return format!("❌ Не удалось получить список пользователей: {}", e),
+    };
+    let mut slots = group_by_slot(&users, granularity);
+
+    let mut text = format!("🔬 Симуляция рассылки на {} (ничего не отправляется)", utils::format_time(slot));
+    if slot != time {
+        text.push_str(&format!("\nВремя округлено до шага расписания {} мин", granularity));
+    }
+    if (slot.hour() == 12 || slot.hour() == 18) && slot.minute() == 0 {
+        let mass = users.iter().filter(|user| user.active && !user.cities.is_empty()).count();
+        text.push_str(&format!("\n📢 В это время также массовая рассылка: {} получателей", mass));
+    }
+
+    // Момент симулируемого слота сегодня по часам бота
+    let at = Local.from_local_datetime(&now.date_naive().and_time(slot)).single().unwrap_or(now);
+    let (mut due, skipped) = select_due(slots.remove(&slot).unwrap_or_default(), slot, at);
+    let regular: Vec<i64> = due.iter().map(|(user, _, _)| user.user_id).collect();
+    due.extend(extra_due(&users, slot, granularity, &regular, at));
+    text.push_str(&format!("\n\nПолучателей: {}", due.len()));
+
+    let mut total_size = 0;
+    for (index, (user, cities, tomorrow)) in due.iter().enumerate() {
+        if index == SIMULATE_COMPOSE_LIMIT {
+            text.push_str(&format!("\n…и еще {} (сообщения не формировались)", due.len() - index));
+            break;
+        }
+        let kind = if *tomorrow { ", на завтра" } else { "" };
+        let city = cities.join(", ");
+        match compose_scheduled_notification(user, cities, weather_client, now.weekday(), *tomorrow).await {
+            Ok(messages) => {
+                let size: usize = messages.iter().map(|message| message.chars().count()).sum();
+                total_size += size;
+                let parts = if messages.len() > 1 { format!(", сообщений: {}", messages.len()) } else { String::new() };
+                text.push_str(&format!("\n• {} - {}{} - {} симв.{}", user.user_id, city, kind, size, parts));
+            }
+            Err(e) => text.push_str(&format!("\n• {} - {}{} - ошибка: {}", user.user_id, city, kind, e)),
+        }
+    }
+    if total_size > 0 {
+        text.push_str(&format!("\nОбщий объем: {} симв.", total_size));
+    }
+
+    if !skipped.is_empty() {
+        text.push_str(&format!("\n\nПропущено: {}", skipped.len()));
+        for (user_id, reason) in &skipped {
+            text.push_str(&format!("\n• {} - {}", user_id, reason.label()));
+        }
+    }
+
+    // Умное время в этот момент проверяет погоду для слота на час позже
+    let ahead_slot = slot + chrono::Duration::minutes(smart_time::ADVANCE_MINUTES);
+    if !smart_time::is_quiet(slot) {
+        let smart = slots
+            .get(&ahead_slot)
+            .map(|users| users.iter().filter(|user| user.active && user.smart_time).count())
+            .unwrap_or(0);
+        if smart > 0 {
+            text.push_str(&format!(
+                "\n\n🧠 Умное время проверит непогоду для {} слота {}",
+                utils::count(Locale::Ru, smart as i64, ["пользователя", "пользователей", "пользователей"]),
+                utils::format_time(ahead_slot)
+            ));
+        }
+    }
+
+    text
+}
+
+// Слот расписания пользователя: из окна доставки или округленное время уведомлений
+fn user_slot(user: &UserSettings, granularity: u32) -> Option<NaiveTime> {
+    match user.delivery_window {
+        Some(window) => Some(window.slot_for(user.user_id, granularity)),
+        None => user.notification_time.as_deref().and_then(utils::parse_time).map(|time| utils::round_time(time, granularity)),
+    }
+}
+
+// Тестовая отправка для /admin testsend: формирует уведомление так же, как ближайшая рассылка,
+// и сразу отправляет его пользователю с пометкой о тесте. Возвращает отчет для администратора
+pub async fn send_test_notification(
+    bot: &Bot,
+    weather_client: &WeatherClient,
+    config: &Config,
+    user: UserSettings,
+) -> String {
+    let user_id = user.user_id;
+    let now = Local::now();
+    let Some(slot) = user_slot(&user, config.schedule_granularity) else {
+        return format!("❌ У пользователя {} не задано время уведомлений", user_id);
+    };
+    // Поездка кончается по дате пользователя, а не по часам бота
+    let cities = user.notification_cities(timezone::user_date(&user, now));
+    if cities.is_empty() {
+        return format!("❌ У пользователя {} не установлен город", user_id);
+    }
+    let city = cities.join(", ");
+    let tomorrow = night_mode::shows_tomorrow(&user, slot);
+    let active = user.active;
+
+    info!("Тестовая отправка уведомления пользователю ID: {}", user_id);
+    let Some(messages) = build_scheduled_notification(bot.clone(), user, cities, weather_client.clone(), now.weekday(), tomorrow).await else {
+        return format!("❌ Не удалось получить погоду для {}, пользователю отправлено сообщение об ошибке", city);
+    };
+
+    let details = format!(
+        "слот {}, города {}, {}{}",
+        utils::format_time(slot),
+        city,
+        if tomorrow { "прогноз на завтра" } else { "текущая погода" },
+        if active { "" } else { ", пользователь помечен как заблокировавший бота" }
+    );
+    // Пометка о тесте - отдельным сообщением, чтобы не сдвинуть первое за лимит Telegram
+    let test_mark = "🧪 _Тестовая отправка_".to_string();
+    for message in std::iter::once(test_mark).chain(messages) {
+        if let Err(e) = bot.send_message(ChatId(user_id), message).parse_mode(ParseMode::MarkdownV2).await {
+            return format!("❌ Telegram не принял уведомление для {} ({}): {}", user_id, details, e);
+        }
+    }
+    format!("✅ Тестовое уведомление отправлено пользователю {} ({})", user_id, details)
+}
+
+// Пример ежедневного уведомления по текущей погоде, собранный так же, как настоящая рассылка,
+// но без отправки: сообщения, на которые оно делится. None - время или город еще не выбраны
+pub async fn preview_notification(
+    weather_client: &WeatherClient,
+    config: &Config,
+    user: &UserSettings,
+) -> Option<Result<Vec<String>, String>> {
+    let now = Local::now();
+    let slot = user_slot(user, config.schedule_granularity)?;
+    let cities = user.notification_cities(timezone::user_date(user, now));
+    if cities.is_empty() {
+        return None;
+    }
+    let tomorrow = night_mode::shows_tomorrow(user, slot);
+    Some(compose_scheduled_notification(user, &cities, weather_client, now.weekday(), tomorrow).await)
+}
+
+// Формирование ежедневного уведомления одному пользователю. None - погоду получить не удалось,
+// пользователю уже отправлено сообщение об ошибке. tomorrow - вместо текущей погоды прогноз на завтра
+async fn build_scheduled_notification(
+    bot: Bot,
+    user: UserSettings,
+    cities: Vec<String>,
+    weather_client: WeatherClient,
+    today: Weekday,
+    tomorrow: bool,
+) -> Option<Vec<String>> {
+    match compose_scheduled_notification(&user, &cities, &weather_client, today, tomorrow).await {
+        Ok(messages) => Some(messages),
+        Err(e) => {
+            warn!("Ошибка получения погоды для пользователя {}: {}", user.user_id, e);
+            
+            // Отправляем уведомление об ошибке
+            let error_message = if user.cute_mode && tomorrow {
+                format!("Добрый вечер\\! К сожалению, не удалось получить прогноз на завтра: {}", 
+                    escape_markdown_v2(&e.to_string()))
+            } else if user.cute_mode {
+                format!("Доброе утро\\! К сожалению, не удалось получить данные о погоде: {}", 
+                    escape_markdown_v2(&e.to_string()))
+            } else {
+                format!("❌ *Ошибка*: Не удалось получить данные о погоде: {}", 
+                    escape_markdown_v2(&e.to_string()))
+            };
+            
+            if let Err(e) = error_throttle::send_error(&bot, ChatId(user.user_id), error_message).await {
+                error!("Не удалось отправить уведомление об ошибке пользователю {}: {}", user.user_id, e);
+            }
+            None
+        }
+    }
+}
+
+// Текст ежедневного уведомления без отправки: по блоку на каждый город под одним приветствием.
+// Город, для которого погоду получить не удалось, пропускается; Err - не удалось ни для одного.
+// Текст длиннее лимита Telegram (много городов с подробным отчетом) делится на несколько сообщений
+async fn compose_scheduled_notification(
+    user: &UserSettings,
+    cities: &[String],
+    weather_client: &WeatherClient,
+    today: Weekday,
+    tomorrow: bool,
+) -> Result<Vec<String>, String> {
+    // Получаем погоду
+    let mut reports = Vec::new();
+    let mut first_error = None;
+    for city in cities {
+        let weather_text = if tomorrow {
+            weather_client.get_tomorrow_forecast(city, FormatOptions::for_user(user)).await
+        } else {
+            weather_client.get_weather(city, FormatOptions::for_user(user)).await
+        };
+        match weather_text {
+            Ok(weather_text) => reports.push((city.as_str(), weather_text)),
+            Err(e) => {
+                warn!("Не удалось получить погоду для {} (пользователь {}): {}", city, user.user_id, e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if reports.is_empty() {
+        return Err(first_error.unwrap_or_else(|| "не выбран город".to_string()));
+    }
+
+    // Блоки городов с заголовком вида "🌦 *Погода в <город>*"
+    let city_blocks = |icon: &str, title: &str| {
+        reports
+            .iter()
+            .map(|(city, weather_text)| format!("{} *{} {}*\n\n{}", icon, title, escape_markdown_v2(city), escape_markdown_v2(weather_text)))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+
+    // Формируем сообщение в зависимости от режима бота
+    let message = if user.report_style == ReportStyle::Compact {
+        // Компактный отчет: без приветствий, чтобы погода была видна прямо в превью уведомления
+        let lines: Vec<String> = reports.iter().map(|(city, weather_text)| format!("{}: {}", city, weather_text)).collect();
+        escape_markdown_v2(&lines.join("\n"))
+    } else if user.cute_mode && tomorrow {
+        // Поздним вечером: вечернее приветствие и прогноз на завтра
+        format!("{}\n\n{}\n\n{}", 
+            escape_markdown_v2(&get_evening_greeting(today)), 
+            city_blocks("🌙", "Прогноз на завтра в"), 
+            escape_markdown_v2(&get_cute_message()))
+    } else if user.cute_mode {
+        // Милый режим: с приветствием и милыми сообщениями
+        // Получаем приветствие и дополнительные сообщения
+        let greeting = get_greeting(today);
+        let cute_message = get_cute_message();
+        let good_day_wish = get_good_day_wish();
         
-        // Ждем минуту перед следующей проверкой
-        info!("Следующая проверка расписания через 1 минуту");
-        sleep(Duration::from_secs(60)).await;
+        // Формируем полное сообщение с экранированием
+        let mut message = format!("{}\n\n{}\n\n{}\n\n{}", 
+            escape_markdown_v2(&greeting), 
+            city_blocks("🌦", "Погода в"), 
+            escape_markdown_v2(&cute_message), 
+            escape_markdown_v2(&good_day_wish));
+        // Цитата или гороскоп дня, если пользователь их включил
+        if let Some(extra) = daily_extras::line_for(user, Local::now().date_naive()) {
+            message.push_str(&format!("\n\n{}", escape_markdown_v2(&extra)));
+        }
+        message
+    } else if tomorrow {
+        format!("🌙 *Прогноз на завтра*\n\n{}", city_blocks("🌦", "Погода в"))
+    } else {
+        // Стандартный режим: только погода
+        format!("🌅 *Утренний прогноз погоды*\n\n{}", city_blocks("🌦", "Погода в"))
+    };
+
+    // О резкой перемене погоды предупреждаем первыми строками утреннего отчета
+    if !tomorrow && user.report_style == ReportStyle::Normal {
+        let mut anomalies = Vec::new();
+        for (city, _) in &reports {
+            match weather_client.get_forecast_anomaly(city).await {
+                Ok(Some(anomaly)) => anomalies.push(format!("*{}*", escape_markdown_v2(&anomaly))),
+                Ok(None) => {}
+                Err(e) => warn!("Не удалось сравнить прогноз с историей для {}: {}", city, e),
+            }
+        }
+        if !anomalies.is_empty() {
+            return Ok(all_cities::split_text(&format!("{}\n\n{}", anomalies.join("\n"), message)));
+        }
     }
+
+    Ok(all_cities::split_text(&message))
 }
 
 // Приветствие с учетом дня недели
@@ -225,67 +827,98 @@ fn get_good_day_wish() -> String {
     wishes[index].to_string()
 }
 
-// Функция для отправки уведомлений всем пользователям
-async fn send_mass_notifications(
-    bot: &Bot, 
-    users: &Vec<super::storage::UserSettings>, 
+// Формирует дневное или вечернее уведомление всем пользователям и кладет его в очередь исходящих
+async fn queue_mass_notifications(
+    users: &[UserSettings], 
     weather_client: &WeatherClient,
-    time: &str,
-    day: Weekday
+    outbox: &Outbox,
+    label: &str,
+    slot: DateTime<Local>,
 ) {
-    for user in users {
-        if let Some(city) = &user.city {
-            info!("Отправка массового уведомления пользователю ID: {}, город: {}", user.user_id, city);
-            
-            // Получаем погоду
-            match weather_client.get_weather(city).await {
-                Ok(weather_text) => {
-                    // Получаем сообщение в соответствии с режимом пользователя
-                    let message = if user.cute_mode {
-                        // Милый режим: приветствие и милые сообщения
-                        let greeting = if time == "12:00" {
-                            get_noon_greeting(day)
-                        } else {
-                            get_evening_greeting(day)
-                        };
-                        
-                        // Получаем милое сообщение
-                        let cute_message = get_cute_message();
-                        
-                        // Формируем полное сообщение с экранированием
-                        format!("{}\n\n🌦 *Погода в {}*\n\n{}\n\n{}", 
-                            escape_markdown_v2(&greeting), 
-                            escape_markdown_v2(city), 
-                            escape_markdown_v2(&weather_text), 
-                            escape_markdown_v2(&cute_message))
-                    } else {
-                        // Стандартный режим: только погода
-                        let greeting = if time == "12:00" {
-                            "🕛 *Дневной прогноз погоды*".to_string()
-                        } else {
-                            "🌆 *Вечерний прогноз погоды*".to_string()
-                        };
-                        
-                        format!("{}\n\n🌦 *Погода в {}*\n\n{}", 
-                            greeting, 
-                            escape_markdown_v2(city), 
-                            escape_markdown_v2(&weather_text))
-                    };
-                    
-                    // Отправляем сообщение
-                    if let Err(e) = bot.send_message(ChatId(user.user_id), message)
-                        .parse_mode(teloxide::types::ParseMode::MarkdownV2)
-                        .await 
-                    {
-                        error!("Не удалось отправить массовое уведомление пользователю {}: {}", user.user_id, e);
-                    } else {
-                        info!("Массовое уведомление успешно отправлено пользователю ID: {}", user.user_id);
-                    }
-                }
+    let is_noon = slot.hour() == 12;
+    let day = slot.weekday();
+
+    let recipients = users
+        .iter()
+        .filter(|u| u.active && !outbox.history().was_queued(u.user_id, NotificationKind::Mass, slot))
+        .filter_map(|user| user.notification_city(timezone::user_date(user, slot)).map(|city| (user, city)));
+
+    // Формируем параллельно, но не больше GENERATION_CONCURRENCY сразу
+    stream::iter(recipients)
+        .for_each_concurrent(GENERATION_CONCURRENCY, |(user, city)| async move {
+            debug!("Подготовка массового уведомления пользователю ID: {}, город: {}", user.user_id, city);
+
+            // Паника при обработке одного пользователя не должна прерывать рассылку
+            let job = tokio::spawn(build_mass_notification(
+                user.clone(),
+                city,
+                weather_client.clone(),
+                is_noon,
+                day,
+            ));
+            match job.await {
+                Ok(Some(message)) => outbox.enqueue(user.user_id, message, Some(label), slot, NotificationKind::Mass).await,
+                Ok(None) => outbox.record_failure(label, "не удалось получить погоду").await,
                 Err(e) => {
-                    warn!("Ошибка получения погоды для пользователя {}: {}", user.user_id, e);
+                    error!("Сбой при формировании массового уведомления пользователю {}: {}", user.user_id, e);
+                    metrics().increment("notification_job_panics_total");
+                    outbox.record_failure(label, "сбой при формировании уведомления").await;
                 }
             }
+        })
+        .await;
+}
+
+// Формирование дневного или вечернего уведомления одному пользователю
+async fn build_mass_notification(
+    user: UserSettings,
+    city: String,
+    weather_client: WeatherClient,
+    is_noon: bool,
+    day: Weekday,
+) -> Option<String> {
+    // Получаем погоду
+    match weather_client.get_weather(&city, FormatOptions::for_user(&user)).await {
+        Ok(weather_text) => {
+            // Получаем сообщение в соответствии с режимом пользователя
+            let message = if user.report_style == ReportStyle::Compact {
+                escape_markdown_v2(&format!("{}: {}", city, weather_text))
+            } else if user.cute_mode {
+                // Милый режим: приветствие и милые сообщения
+                let greeting = if is_noon {
+                    get_noon_greeting(day)
+                } else {
+                    get_evening_greeting(day)
+                };
+                
+                // Получаем милое сообщение
+                let cute_message = get_cute_message();
+                
+                // Формируем полное сообщение с экранированием
+                format!("{}\n\n🌦 *Погода в {}*\n\n{}\n\n{}", 
+                    escape_markdown_v2(&greeting), 
+                    escape_markdown_v2(&city), 
+                    escape_markdown_v2(&weather_text), 
+                    escape_markdown_v2(&cute_message))
+            } else {
+                // Стандартный режим: только погода
+                let greeting = if is_noon {
+                    "🕛 *Дневной прогноз погоды*".to_string()
+                } else {
+                    "🌆 *Вечерний прогноз погоды*".to_string()
+                };
+                
+                format!("{}\n\n🌦 *Погода в {}*\n\n{}", 
+                    greeting, 
+                    escape_markdown_v2(&city), 
+                    escape_markdown_v2(&weather_text))
+            };
+
+            Some(message)
+        }
+        Err(e) => {
+            warn!("Ошибка получения погоды для пользователя {}: {}", user.user_id, e);
+            None
         }
     }
 }
@@ -314,4 +947,50 @@ fn get_evening_greeting(day: Weekday) -> String {
         Weekday::Sat => "*Добрый вечер\\!* 🎭\nНадеюсь, суббота была наполнена приятными событиями\\!".to_string(),
         Weekday::Sun => "*Спокойного вечера\\!* 🌠\nВпереди новая неделя\\! Время настроиться на продуктивный лад\\!".to_string(),
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+
+    fn user(user_id: i64, offset_hours: i32, early_sent_on: Option<NaiveDate>) -> UserSettings {
+        let mut user = UserSettings::new(user_id);
+        user.set_city("Москва");
+        user.utc_offset = Some(offset_hours * 3600);
+        user.early_sent_on = early_sent_on;
+        user
+    }
+
+    #[test]
+    fn early_notice_is_checked_against_user_date() {
+        // 22:30 UTC 15 января. Умное время уже отправило прогноз на 16-е
+        let at = Utc.with_ymd_and_hms(2025, 1, 15, 22, 30, 0).unwrap().with_timezone(&Local);
+        let sent_on = NaiveDate::from_ymd_opt(2025, 1, 16);
+        let slot = at.time();
+        let users = vec![user(1, 3, sent_on), user(2, 0, sent_on), user(3, 0, None)];
+
+        let (due, skipped) = select_due(users, slot, at);
+        // У пользователя UTC+3 уже 16-е - прогноз за этот день ушел заранее
+        assert_eq!(skipped, vec![(1, SkipReason::SentEarly)]);
+        // По UTC еще 15-е: отметка о 16-м сегодняшнее уведомление не отменяет
+        let due: Vec<i64> = due.iter().map(|(user, _, _)| user.user_id).collect();
+        assert_eq!(due, vec![2, 3]);
+    }
+
+    #[test]
+    fn catches_up_missed_slots_within_limit() {
+        let current = Local.with_ymd_and_hms(2025, 1, 15, 8, 0, 0).unwrap();
+        let minutes_ago = |minutes| current - chrono::Duration::minutes(minutes);
+
+        assert_eq!(slots_to_process(None, current), vec![current]);
+        assert!(slots_to_process(Some(current), current).is_empty());
+        // Проверка затянулась на три минуты: обрабатываем 07:58, 07:59 и 08:00
+        assert_eq!(slots_to_process(Some(minutes_ago(3)), current), vec![minutes_ago(2), minutes_ago(1), current]);
+
+        // После долгого простоя - только последние MAX_CATCH_UP_MINUTES слотов
+        let slots = slots_to_process(Some(minutes_ago(120)), current);
+        assert_eq!(slots.len() as i64, MAX_CATCH_UP_MINUTES);
+        assert_eq!(slots.last(), Some(&current));
+        assert_eq!(slots[0], minutes_ago(MAX_CATCH_UP_MINUTES - 1));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::metrics::metrics;
+use chrono::{DateTime, Local};
+use log::{error, warn};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{LazyLock, Mutex};
+
+// Ответ какого запроса OpenWeather разбираем
+#[derive(Debug, Clone, Copy)]
+pub enum Endpoint {
+    Current,
+    Forecast,
+}
+
+// Поля, которые OpenWeather присылает сейчас (используемые и сознательно пропускаемые).
+// Массивы обозначены как "weather[]": поля элементов - "weather[].id"
+const CURRENT_KNOWN: &[&str] = &[
+    "coord", "coord.lon", "coord.lat",
+    "weather", "weather[].id", "weather[].main", "weather[].description", "weather[].icon",
+    "base",
+    "main", "main.temp", "main.feels_like", "main.temp_min", "main.temp_max", "main.pressure",
+    "main.humidity", "main.sea_level", "main.grnd_level",
+    "visibility",
+    "wind", "wind.speed", "wind.deg", "wind.gust",
+    "rain", "rain.1h", "rain.3h",
+    "snow", "snow.1h", "snow.3h",
+    "clouds", "clouds.all",
+    "dt",
+    "sys", "sys.type", "sys.id", "sys.message", "sys.country", "sys.sunrise", "sys.sunset",
+    "timezone", "id", "name", "cod",
+];
+
+const FORECAST_KNOWN: &[&str] = &[
+    "cod", "message", "cnt",
+    "list", "list[].dt", "list[].dt_txt", "list[].visibility", "list[].pop",
+    "list[].main", "list[].main.temp", "list[].main.feels_like", "list[].main.temp_min",
+    "list[].main.temp_max", "list[].main.pressure", "list[].main.sea_level",
+    "list[].main.grnd_level", "list[].main.humidity", "list[].main.temp_kf",
+    "list[].weather", "list[].weather[].id", "list[].weather[].main",
+    "list[].weather[].description", "list[].weather[].icon",
+    "list[].clouds", "list[].clouds.all",
+    "list[].wind", "list[].wind.speed", "list[].wind.deg", "list[].wind.gust",
+    "list[].rain", "list[].rain.3h",
+    "list[].snow", "list[].snow.3h",
+    "list[].sys", "list[].sys.pod",
+    "city", "city.id", "city.name", "city.coord", "city.coord.lat", "city.coord.lon",
+    "city.country", "city.population", "city.timezone", "city.sunrise", "city.sunset",
+];
+
+// Поля, которые мы читаем с запасным значением: если они пропадут, ответ разберется,
+// но пользователь увидит нули или пропуски вместо данных
+const CURRENT_EXPECTED: &[&str] = &["visibility"];
+const FORECAST_EXPECTED: &[&str] = &["list[].wind"];
+
+impl Endpoint {
+    fn name(self) -> &'static str {
+        match self {
+            Endpoint::Current => "weather",
+            Endpoint::Forecast => "forecast",
+        }
+    }
+
+    fn known(self) -> &'static [&'static str] {
+        match self {
+            Endpoint::Current => CURRENT_KNOWN,
+            Endpoint::Forecast => FORECAST_KNOWN,
+        }
+    }
+
+    fn expected(self) -> &'static [&'static str] {
+        match self {
+            Endpoint::Current => CURRENT_EXPECTED,
+            Endpoint::Forecast => FORECAST_EXPECTED,
+        }
+    }
+}
+
+// Накопленные с запуска признаки изменения формата ответов
+#[derive(Default)]
+struct DriftStats {
+    // "forecast: list[].foo" -> сколько ответов содержали это поле
+    unknown: BTreeMap<String, u64>,
+    // Поля с запасным значением, которых не оказалось в ответе
+    missing: BTreeMap<String, u64>,
+    parse_errors: u64,
+    last_parse_error: Option<(DateTime<Local>, String)>,
+}
+
+static DRIFT: LazyLock<Mutex<DriftStats>> = LazyLock::new(|| Mutex::new(DriftStats::default()));
+
+// Разбирает ответ OpenWeather и отмечает поля, которых мы не знаем или которых не хватило.
+// Каждое новое расхождение пишется в лог один раз, дальше только считается
+pub fn parse<T: DeserializeOwned>(endpoint: Endpoint, body: &str) -> Result<T, String> {
+    let value: Value = serde_json::from_str(body).map_err(|e| record_parse_error(endpoint, e.to_string()))?;
+
+    let mut paths = BTreeSet::new();
+    collect_paths(&value, "", &mut paths);
+
+    {
+        let mut drift = DRIFT.lock().unwrap();
+        for path in paths.iter().filter(|path| !endpoint.known().contains(&path.as_str())) {
+            let key = format!("{}: {}", endpoint.name(), path);
+            let count = drift.unknown.entry(key.clone()).or_insert(0);
+            if *count == 0 {
+                warn!("Новое поле в ответе OpenWeather {}", key);
+            }
+            *count += 1;
+            metrics().increment("weather_schema_unknown_fields_total");
+        }
+        for path in endpoint.expected().iter().filter(|path| !paths.contains(**path)) {
+            let key = format!("{}: {}", endpoint.name(), path);
+            let count = drift.missing.entry(key.clone()).or_insert(0);
+            if *count == 0 {
+                warn!("В ответе OpenWeather нет поля {}, используется значение по умолчанию", key);
+            }
+            *count += 1;
+            metrics().increment("weather_schema_missing_fields_total");
+        }
+    }
+
+    serde_json::from_value(value).map_err(|e| record_parse_error(endpoint, e.to_string()))
+}
+
+fn record_parse_error(endpoint: Endpoint, e: String) -> String {
+    error!("Ответ OpenWeather {} не разобран: {}", endpoint.name(), e);
+    metrics().increment("weather_parse_errors_total");
+    let mut drift = DRIFT.lock().unwrap();
+    drift.parse_errors += 1;
+    drift.last_parse_error = Some((Local::now(), format!("{}: {}", endpoint.name(), e)));
+    e
+}
+
+// Собирает пути всех полей JSON: "main.temp", "weather[].icon"
+fn collect_paths(value: &Value, prefix: &str, paths: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                collect_paths(child, &path, paths);
+                paths.insert(path);
+            }
+        }
+        Value::Array(items) => {
+            let path = format!("{}[]", prefix);
+            for item in items {
+                collect_paths(item, &path, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Отчет для /admin schema
+pub fn report() -> String {
+    let drift = DRIFT.lock().unwrap();
+    if drift.unknown.is_empty() && drift.missing.is_empty() && drift.parse_errors == 0 {
+        return "🧬 Формат ответов OpenWeather не менялся с момента запуска".to_string();
+    }
+
+    let mut text = String::from("🧬 Изменения формата ответов OpenWeather с момента запуска");
+    if !drift.unknown.is_empty() {
+        text.push_str("\n\nНовые поля:");
+        for (path, count) in &drift.unknown {
+            text.push_str(&format!("\n• {} - {}", path, count));
+        }
+    }
+    if !drift.missing.is_empty() {
+        text.push_str("\n\nПропавшие поля (использовано значение по умолчанию):");
+        for (path, count) in &drift.missing {
+            text.push_str(&format!("\n• {} - {}", path, count));
+        }
+    }
+    text.push_str(&format!("\n\nОшибок разбора: {}", drift.parse_errors));
+    if let Some((at, e)) = &drift.last_parse_error {
+        text.push_str(&format!("\nПоследняя ({}): {}", at.format("%d.%m %H:%M"), e));
+    }
+    text
+}
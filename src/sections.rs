@@ -0,0 +1,156 @@
+use crate::callback_answer::CallbackAnswer;
+use crate::formatter::{ReportStyle, TemperaturePrecision};
+use crate::storage::{UserSettings, UserStorage};
+use crate::templates;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use bitflags::bitflags;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+// Префикс данных кнопок переключения разделов
+pub const CALLBACK_PREFIX: &str = "section_";
+
+bitflags! {
+    // Разделы отчета о погоде, которые пользователь хочет видеть
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct ReportSections: u32 {
+        const RECOMMENDATIONS = 1;
+        const SUN = 1 << 1;
+        const WIND = 1 << 2;
+        const HUMIDITY = 1 << 3;
+        const DAILY_TEMPS = 1 << 4;
+    }
+}
+
+impl Default for ReportSections {
+    fn default() -> Self {
+        ReportSections::all()
+    }
+}
+
+// Ключ для данных кнопки и название раздела для пользователя
+pub(crate) const SECTIONS: [(ReportSections, &str, &str); 5] = [
+    (ReportSections::RECOMMENDATIONS, "recommendations", "Рекомендации по одежде"),
+    (ReportSections::SUN, "sun", "Восход и закат"),
+    (ReportSections::WIND, "wind", "Ветер"),
+    (ReportSections::HUMIDITY, "humidity", "Влажность"),
+    (ReportSections::DAILY_TEMPS, "daily_temps", "Температура по времени суток"),
+];
+
+// Обработка /sections: показывает разделы отчета с переключателями
+pub async fn handle_sections_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let sections = user.report_sections;
+
+    bot.send_message(msg.chat.id, templates::text("sections.choose"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(sections_keyboard(sections))
+        .await?;
+    Ok(())
+}
+
+// Нажатие на кнопку раздела: включает или выключает его и обновляет клавиатуру
+pub async fn handle_toggle(
+    bot: &Bot,
+    answer: &CallbackAnswer,
+    message: Option<&Message>,
+    storage: &dyn UserStorage,
+    key: &str,
+) -> ResponseResult<()> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let user_id = message.chat.id.0;
+    let Some((section, _, label)) = SECTIONS.iter().find(|(_, section_key, _)| *section_key == key) else {
+        return Ok(());
+    };
+
+    let mut user = match storage.get_user(user_id).await {
+        Ok(user) => user.unwrap_or_else(|| UserSettings::new(user_id)),
+        Err(e) => {
+            warn!("Не удалось загрузить разделы отчета пользователя ID: {}: {}", user_id, e);
+            answer.alert("💾 Не удалось загрузить твои настройки, попробуй позже").await?;
+            return Ok(());
+        }
+    };
+    user.report_sections.toggle(*section);
+    let sections = user.report_sections;
+    if let Err(e) = storage.save_user(user).await {
+        warn!("Не удалось сохранить разделы отчета пользователя ID: {}: {}", user_id, e);
+        answer.alert("💾 Не удалось сохранить изменения, попробуй позже").await?;
+        return Ok(());
+    }
+    info!("Пользователь ID: {} переключил раздел отчета {}: {:?}", user_id, key, sections);
+
+    let state = if sections.contains(*section) { "включен" } else { "выключен" };
+    answer.toast(format!("Раздел «{}» {}", label, state)).await?;
+    bot.edit_message_reply_markup(message.chat.id, message.id)
+        .reply_markup(sections_keyboard(sections))
+        .await?;
+    Ok(())
+}
+
+// Обработка /style: обычный или компактный отчет
+pub async fn handle_style_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    let response = match ReportStyle::parse(args) {
+        Some(style) => {
+            user.report_style = style;
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} выбрал стиль отчета {:?}", user_id, style);
+
+            confirm_saved(saved, templates::render("style.set", &[("style", style.label())]))
+        }
+        None => templates::render("style.usage", &[("style", user.report_style.label())]),
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Обработка /precision whole|tenths: целые градусы или с десятыми
+pub async fn handle_precision_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    let response = match TemperaturePrecision::parse(args) {
+        Some(precision) => {
+            user.temperature_precision = precision;
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} выбрал точность температуры {:?}", user_id, precision);
+
+            confirm_saved(saved, templates::render("precision.set", &[("precision", precision.label())]))
+        }
+        None => templates::render("precision.usage", &[("precision", user.temperature_precision.label())]),
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+fn sections_keyboard(sections: ReportSections) -> InlineKeyboardMarkup {
+    let rows = SECTIONS.iter().map(|(section, key, label)| {
+        let mark = if sections.contains(*section) { "✅" } else { "⬜" };
+        vec![InlineKeyboardButton::callback(
+            format!("{} {}", mark, label),
+            format!("{}{}", CALLBACK_PREFIX, key),
+        )]
+    });
+    InlineKeyboardMarkup::new(rows)
+}
@@ -0,0 +1,137 @@
+use crate::config::Config;
+use crate::moderation;
+use crate::storage::{self, UserSettings, UserStorage};
+use crate::templates;
+use crate::weather::WeatherClient;
+use crate::{timezone, utils};
+use crate::escape_markdown_v2;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use teloxide::prelude::*;
+use teloxide::types::{InputFile, ParseMode};
+
+// Метка формата файла: по ней отличаем файл настроек от случайного JSON
+const FORMAT: &str = "ferrisbot-settings";
+const VERSION: u32 = 1;
+// Файл настроек занимает пару килобайт; больший - точно не наш
+const MAX_FILE_SIZE: u32 = 64 * 1024;
+
+// Файл с личными настройками: только то, что пользователь выбрал сам,
+// без ID, состояния диалога и служебных отметок. При чтении настройки сначала
+// берутся как есть и проходят миграции схемы записи: файл мог выгрузить старый бот
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsFile<S = UserSettings> {
+    format: String,
+    version: u32,
+    settings: S,
+}
+
+// /settings export - прислать файл настроек, /settings import - ответом на файл применить его
+pub async fn handle_settings_file_command(
+    bot: &Bot,
+    msg: &Message,
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    config: &Config,
+    action: &str,
+) -> ResponseResult<()> {
+    match action {
+        "export" => export(bot, msg, storage).await,
+        _ => import(bot, msg, storage, weather_client, config).await,
+    }
+}
+
+async fn export(bot: &Bot, msg: &Message, storage: &dyn UserStorage) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    let mut settings = UserSettings::new(user_id);
+    settings.copy_preferences_from(&user);
+
+    let file = SettingsFile { format: FORMAT.to_string(), version: VERSION, settings };
+    let json = match serde_json::to_vec_pretty(&file) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("Не удалось сериализовать настройки пользователя {}: {}", user_id, e);
+            return Ok(());
+        }
+    };
+
+    bot.send_document(msg.chat.id, InputFile::memory(json).file_name("ferrisbot-settings.json"))
+        .caption(templates::text("settings.exported"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    info!("Пользователь ID: {} выгрузил файл настроек", user_id);
+    Ok(())
+}
+
+async fn import(bot: &Bot, msg: &Message, storage: &dyn UserStorage, weather_client: &WeatherClient, config: &Config) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(document) = msg.reply_to_message().and_then(|reply| reply.document()) else {
+        bot.send_message(msg.chat.id, templates::text("settings.import_usage"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+
+    let result = if document.file.size > MAX_FILE_SIZE {
+        Err("файл слишком большой для файла настроек".to_string())
+    } else {
+        match utils::download_text(bot, &document.file.id).await {
+            Ok(content) => parse(&content, &config.blocklist),
+            Err(e) => Err(format!("не удалось скачать файл: {}", e)),
+        }
+    };
+
+    let response = match result {
+        Ok(imported) => {
+            let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+                return Ok(());
+            };
+            user.copy_preferences_from(&imported);
+            // Файл старого бота мог прийти без пояса города: берем его по основному городу
+            if let Some(city) = imported.city().filter(|_| imported.utc_offset.is_none()) {
+                timezone::infer_silently(weather_client, &mut user, city).await;
+            }
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} применил файл настроек", user_id);
+            confirm_saved(saved, templates::text("settings.imported"))
+        }
+        Err(e) => {
+            info!("Пользователь ID: {} прислал некорректный файл настроек: {}", user_id, e);
+            templates::render("settings.import_failed", &[("error", &escape_markdown_v2(&e))])
+        }
+    };
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Проверяет файл настроек: формат, версию и города (как при вводе через /city)
+fn parse(content: &str, blocklist: &[String]) -> Result<UserSettings, String> {
+    let file: SettingsFile<Value> = serde_json::from_str(content).map_err(|_| "это не файл настроек бота".to_string())?;
+    if file.format != FORMAT {
+        return Err("это не файл настроек бота".to_string());
+    }
+    if file.version > VERSION {
+        return Err(format!("файл из более новой версии бота (версия {})", file.version));
+    }
+
+    let settings = storage::migrate_record(file.settings).map_err(|_| "это не файл настроек бота".to_string())?;
+    let cities = settings
+        .cities
+        .iter()
+        .chain(&settings.recent_cities)
+        .chain(settings.travel.iter().map(|travel| &travel.city));
+    for city in cities {
+        if city.chars().count() > utils::MAX_CITY_LENGTH || moderation::is_blocked(city, blocklist) {
+            return Err(format!("недопустимый город: {}", utils::echo(city)));
+        }
+    }
+    Ok(settings)
+}
@@ -0,0 +1,199 @@
+use crate::weather::{humidex, OpenWeatherResponse, STRONG_WIND_SPEED};
+
+// С этой суммы баллов день считаем опасным: отчет дополняется предупреждениями
+const SEVERE_SCORE: u32 = 3;
+// Порывы, опасные для деревьев и рекламных конструкций, м/с
+const DANGEROUS_GUST_SPEED: f32 = 20.0;
+// Сильный дождь или снег, мм за час
+const HEAVY_PRECIPITATION: f32 = 5.0;
+
+// Насколько погода заслуживает внимания: сумма баллов и причины, по которым они начислены
+#[derive(Debug, Clone, Default)]
+pub struct Severity {
+    pub score: u32,
+    pub warnings: Vec<String>,
+}
+
+impl Severity {
+    // Опасный день: отчет расширяется разделом предупреждений
+    pub fn is_severe(&self) -> bool {
+        self.score >= SEVERE_SCORE
+    }
+
+    // Ничем не примечательный день: отчет можно сократить
+    pub fn is_calm(&self) -> bool {
+        self.score == 0
+    }
+
+    fn add(&mut self, points: u32, warning: String) {
+        self.score += points;
+        self.warnings.push(warning);
+    }
+}
+
+// Оценка текущей погоды: мороз и жара, ветер, гроза, осадки, туман и духота
+pub fn assess(data: &OpenWeatherResponse) -> Severity {
+    let mut severity = Severity::default();
+    let temp = data.main.temp;
+
+    match temp {
+        t if t <= -25.0 => severity.add(3, format!("Сильный мороз: {:.0}°C", t)),
+        t if t <= -15.0 => severity.add(2, format!("Мороз: {:.0}°C", t)),
+        t if t >= 35.0 => severity.add(3, format!("Сильная жара: {:.0}°C", t)),
+        t if t >= 30.0 => severity.add(2, format!("Жара: {:.0}°C", t)),
+        _ => {}
+    }
+
+    if data.wind.speed >= STRONG_WIND_SPEED {
+        severity.add(2, format!("Сильный ветер: {:.0} м/с", data.wind.speed));
+    }
+    if let Some(gust) = data.wind.gust.filter(|gust| *gust >= DANGEROUS_GUST_SPEED) {
+        severity.add(2, format!("Опасные порывы ветра до {:.0} м/с", gust));
+    }
+
+    for weather in &data.weather {
+        match weather.main.as_str() {
+            "Thunderstorm" => severity.add(3, "Гроза".to_string()),
+            "Tornado" | "Squall" => severity.add(3, "Шквал".to_string()),
+            "Rain" | "Snow" | "Drizzle" => severity.score += 1,
+            _ => {}
+        }
+    }
+
+    let hourly = [&data.rain, &data.snow]
+        .iter()
+        .filter_map(|info| info.as_ref())
+        .map(|info| info.one_hour.unwrap_or(info.three_hours / 3.0))
+        .sum::<f32>();
+    if hourly >= HEAVY_PRECIPITATION {
+        severity.add(2, format!("Сильные осадки: {:.1} мм за час", hourly));
+    }
+
+    if let Some(visibility) = data.visibility.filter(|visibility| *visibility < 1000) {
+        severity.add(2, format!("Плохая видимость: {} м", visibility));
+    }
+
+    // Жару уже учли по температуре, духоту добавляем только при теплой погоде
+    if temp < 30.0 && humidex(temp, data.main.humidity) >= 40.0 {
+        severity.add(2, "Сильная духота".to_string());
+    }
+
+    severity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo;
+    use crate::weather::PrecipitationInfo;
+
+    // Спокойная погода демо-режима: +14°C, ветер 4 м/с, порывы 7 м/с, видимость 10 км
+    fn calm() -> OpenWeatherResponse {
+        demo::current_weather(1_736_942_400)
+    }
+
+    fn with_temp(temp: f32) -> OpenWeatherResponse {
+        let mut data = calm();
+        data.main.temp = temp;
+        data.main.humidity = 20.0;
+        data
+    }
+
+    #[test]
+    fn calm_day_scores_zero() {
+        let severity = assess(&calm());
+        assert_eq!(severity.score, 0);
+        assert!(severity.warnings.is_empty());
+        assert!(severity.is_calm());
+        assert!(!severity.is_severe());
+    }
+
+    #[test]
+    fn temperature_thresholds() {
+        let cases = [
+            (-30.0, 3),
+            (-25.0, 3),
+            (-24.9, 2),
+            (-15.0, 2),
+            (-14.9, 0),
+            (29.9, 0),
+            (30.0, 2),
+            (34.9, 2),
+            (35.0, 3),
+        ];
+        for (temp, score) in cases {
+            assert_eq!(assess(&with_temp(temp)).score, score, "{}°C", temp);
+        }
+    }
+
+    #[test]
+    fn wind_gust_and_visibility_thresholds() {
+        let score = |change: &dyn Fn(&mut OpenWeatherResponse)| {
+            let mut data = calm();
+            change(&mut data);
+            assess(&data).score
+        };
+        assert_eq!(score(&|data| data.wind.speed = STRONG_WIND_SPEED - 0.1), 0);
+        assert_eq!(score(&|data| data.wind.speed = STRONG_WIND_SPEED), 2);
+        assert_eq!(score(&|data| data.wind.gust = Some(DANGEROUS_GUST_SPEED - 0.1)), 0);
+        assert_eq!(score(&|data| data.wind.gust = Some(DANGEROUS_GUST_SPEED)), 2);
+        assert_eq!(score(&|data| data.visibility = Some(1000)), 0);
+        assert_eq!(score(&|data| data.visibility = Some(999)), 2);
+        assert_eq!(score(&|data| data.visibility = None), 0);
+    }
+
+    #[test]
+    fn weather_kinds_and_precipitation() {
+        for (main, score) in [("Clouds", 0), ("Rain", 1), ("Snow", 1), ("Drizzle", 1), ("Thunderstorm", 3), ("Squall", 3), ("Tornado", 3)] {
+            let mut data = calm();
+            data.weather[0].main = main.to_string();
+            assert_eq!(assess(&data).score, score, "{}", main);
+        }
+
+        let precipitation = |one_hour: Option<f32>, three_hours: f32| {
+            let mut data = calm();
+            data.rain = Some(PrecipitationInfo { one_hour, three_hours });
+            assess(&data).score
+        };
+        assert_eq!(precipitation(Some(4.9), 0.0), 0);
+        assert_eq!(precipitation(Some(HEAVY_PRECIPITATION), 0.0), 2);
+        // Без часового значения берется треть трехчасового
+        assert_eq!(precipitation(None, 14.9), 0);
+        assert_eq!(precipitation(None, 15.0), 2);
+    }
+
+    #[test]
+    fn humidex_counts_only_below_heat() {
+        let mut stuffy = with_temp(29.0);
+        stuffy.main.humidity = 90.0;
+        assert_eq!(assess(&stuffy).score, 2);
+        assert_eq!(assess(&stuffy).warnings, vec!["Сильная духота".to_string()]);
+
+        // При жаре духота не добавляет баллов сверх температуры
+        let mut hot = with_temp(36.0);
+        hot.main.humidity = 90.0;
+        assert_eq!(assess(&hot).score, 3);
+    }
+
+    #[test]
+    fn severity_levels_are_ordered() {
+        let mut rainy = calm();
+        rainy.weather[0].main = "Rain".to_string();
+        let mut frosty = rainy.clone();
+        frosty.main.temp = -20.0;
+        frosty.main.humidity = 20.0;
+        let mut storm = frosty.clone();
+        storm.weather[0].main = "Thunderstorm".to_string();
+        storm.wind.speed = 18.0;
+
+        let levels: Vec<Severity> = [calm(), rainy, frosty, storm].iter().map(assess).collect();
+        let scores: Vec<u32> = levels.iter().map(|severity| severity.score).collect();
+        assert_eq!(scores, vec![0, 1, 3, 7]);
+        assert_eq!(levels.iter().map(Severity::is_calm).collect::<Vec<_>>(), vec![true, false, false, false]);
+        assert_eq!(levels.iter().map(Severity::is_severe).collect::<Vec<_>>(), vec![false, false, true, true]);
+        // Дождь баллы добавляет, но отдельного предупреждения не дает
+        assert!(levels[1].warnings.is_empty());
+        // Предупреждения идут в порядке проверок: температура, ветер, явления
+        assert_eq!(levels[3].warnings, vec!["Мороз: -20°C", "Сильный ветер: 18 м/с", "Гроза"]);
+    }
+}
@@ -0,0 +1,34 @@
+// Короткие ответы на бытовые фразы в милом режиме, чтобы обычное сообщение
+// не упиралось в "я понимаю только команды"
+
+// Намерение: шаблон ответа и фразы, по которым оно узнается
+const INTENTS: &[(&str, &[&str])] = &[
+    ("smalltalk.thanks", &["спасибо", "спс", "благодарю", "thanks", "thank you"]),
+    ("smalltalk.morning", &["доброе утро", "утречко", "good morning"]),
+    ("smalltalk.night", &["спокойной ночи", "доброй ночи", "good night"]),
+    ("smalltalk.how_are_you", &["как дела", "как ты", "как поживаешь", "how are you"]),
+    ("smalltalk.hello", &["привет", "приветик", "здравствуй", "hello", "hi"]),
+];
+
+// Фразы длиннее этого считаем содержательными и не пытаемся на них отвечать
+const MAX_WORDS: usize = 6;
+
+// Ключ шаблона ответа на фразу или None, если фраза не похожа на бытовую
+pub fn match_intent(text: &str) -> Option<&'static str> {
+    let normalized: String = text
+        .to_lowercase()
+        .replace('ё', "е")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if words.is_empty() || words.len() > MAX_WORDS {
+        return None;
+    }
+    let phrase = format!(" {} ", words.join(" "));
+
+    INTENTS
+        .iter()
+        .find(|(_, phrases)| phrases.iter().any(|p| phrase.contains(&format!(" {} ", p))))
+        .map(|(key, _)| *key)
+}
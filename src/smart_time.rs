@@ -0,0 +1,75 @@
+use crate::storage::UserStorage;
+use crate::templates;
+use crate::weather::WeatherClient;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{NaiveTime, Timelike};
+use log::{error, info, warn};
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// За сколько минут до обычного времени проверяем погоду и при непогоде отправляем прогноз сразу
+pub const ADVANCE_MINUTES: i64 = 60;
+// На сколько часов вперед смотрим прогноз
+const LOOKAHEAD_HOURS: i64 = 12;
+// Тихие часы: в это время прогноз заранее не отправляем, пользователь получит его в свое время
+const QUIET_HOURS_START: u32 = 23;
+const QUIET_HOURS_END: u32 = 7;
+
+// Обработка /smarttime on|off: режим «умное время»
+pub async fn handle_smart_time_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+
+    let enabled = match args.trim().to_lowercase().as_str() {
+        "on" | "вкл" => Some(true),
+        "off" | "выкл" => Some(false),
+        _ => None,
+    };
+
+    let response = match enabled {
+        Some(enabled) => {
+            user.smart_time = enabled;
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} {} умное время", user_id, if enabled { "включил" } else { "выключил" });
+
+            confirm_saved(saved, templates::text(if enabled { "smart.on" } else { "smart.off" }))
+        }
+        None => templates::render("smart.usage", &[
+            ("status", if user.smart_time { "включено" } else { "выключено" }),
+        ]),
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+pub fn is_quiet(time: NaiveTime) -> bool {
+    time.hour() >= QUIET_HOURS_START || time.hour() < QUIET_HOURS_END
+}
+
+// Причина отправить прогноз раньше (например, "дождь"), None - погода спокойная или данных нет
+pub async fn early_reason(weather_client: &WeatherClient, user_id: i64, city: &str) -> Option<String> {
+    match weather_client.get_upcoming_hazard(city, LOOKAHEAD_HOURS).await {
+        Ok(reason) => reason,
+        Err(e) => {
+            warn!("Не удалось проверить погоду для умного времени пользователя {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+// Предупреждает, почему прогноз пришел раньше обычного
+pub async fn send_early_notice(bot: &Bot, user_id: i64, reason: &str) {
+    let message = templates::render("smart.early", &[("reason", &crate::escape_markdown_v2(reason))]);
+    if let Err(e) = bot.send_message(ChatId(user_id), message)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await
+    {
+        error!("Не удалось отправить пользователю {} пояснение к раннему прогнозу: {}", user_id, e);
+    }
+}
@@ -0,0 +1,42 @@
+// Простая статистика по рядам температур
+
+// Среднее значение; None - значений нет
+pub fn mean(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f32>() / values.len() as f32)
+}
+
+// Отклонение значения от среднего ряда: положительное - значение выше среднего
+pub fn deviation(value: f32, baseline: &[f32]) -> Option<f32> {
+    mean(baseline).map(|average| value - average)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_series_has_no_mean() {
+        assert_eq!(mean(&[]), None);
+        assert_eq!(deviation(5.0, &[]), None);
+    }
+
+    #[test]
+    fn single_value_is_its_own_mean() {
+        assert_eq!(mean(&[-3.5]), Some(-3.5));
+        assert_eq!(deviation(-3.5, &[-3.5]), Some(0.0));
+        assert_eq!(deviation(2.0, &[-3.5]), Some(5.5));
+    }
+
+    #[test]
+    fn aggregates_over_series() {
+        let week = [10.0, 12.0, 8.0, 14.0, 6.0, 11.0, 9.0];
+        assert_eq!(mean(&week), Some(10.0));
+        // Ниже среднего - отрицательное отклонение, выше - положительное
+        assert_eq!(deviation(-2.0, &week), Some(-12.0));
+        assert_eq!(deviation(18.0, &week), Some(8.0));
+        assert_eq!(mean(&[-5.0, 5.0]), Some(0.0));
+    }
+}
@@ -1,101 +1,1212 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::io::ErrorKind;
-use log::error;
+use log::{error, warn};
 use log::info;
+use crate::activity::Activity;
+use crate::alerts;
+use crate::delivery_history::DeliveryRecord;
+use crate::encryption::{self, StorageCipher};
+use crate::metrics::metrics;
+use crate::formatter::{ReportStyle, TemperaturePrecision, Units};
+use crate::locale::Locale;
+use crate::sections::ReportSections;
+use crate::weather::DayOutlook;
+
+mod postgres;
+mod redis;
+mod sqlite;
+
+pub use postgres::PgStorage;
+pub use redis::RedisStorage;
+pub use sqlite::SqliteStorage;
+
+// Сколько последних городов пользователя запоминаем для быстрого выбора
+pub const RECENT_CITIES_LIMIT: usize = 5;
+// Сколько городов может быть в одном прогнозе
+pub const CITIES_LIMIT: usize = 5;
+// Сколько дополнительных времен уведомлений можно задать кроме основного
+pub const EXTRA_TIMES_LIMIT: usize = 3;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSettings {
     pub user_id: i64,
-    pub city: Option<String>,
+    #[serde(default)]
+    pub cities: Vec<String>, // Города прогноза, первый - основной
     pub notification_time: Option<String>,
+    #[serde(default)]
+    pub extra_times: Vec<String>, // Дополнительное время уведомлений (ЧЧ:ММ), кроме основного
     pub cute_mode: bool, // Флаг указывающий использует ли пользователь "милый режим"
     pub state: Option<String>, // Добавляем поле для хранения состояния пользователя
+    #[serde(default)]
+    pub last_input: Option<LastInput>, // Сообщение, которым пользователь ответил на запрос ввода
+    #[serde(default = "default_active")]
+    pub active: bool, // false, если пользователь заблокировал бота
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>, // Когда пользователь впервые выполнил /start
+    #[serde(default)]
+    pub onboarding_reminder_sent: bool, // Напоминание о незавершенной настройке уже отправлялось
+    #[serde(default)]
+    pub last_seen: Option<DateTime<Utc>>, // Последнее взаимодействие пользователя с ботом
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>, // Когда пользователь появился в хранилище
+    #[serde(default)]
+    pub commands_used: u64, // Сколько команд пользователь отправил боту
+    #[serde(default)]
+    pub reengagement_opt_out: bool, // Пользователь отписался от сообщений «мы скучали»
+    #[serde(default)]
+    pub last_reengagement_at: Option<DateTime<Utc>>, // Когда последний раз отправляли «мы скучали»
+    #[serde(default)]
+    pub retention_warning_at: Option<DateTime<Utc>>, // Когда предупредили о скором удалении данных
+    #[serde(default)]
+    pub recent_cities: Vec<String>, // Последние города пользователя, самый свежий первым
+    #[serde(default)]
+    pub travel: Option<TravelOverride>, // Временный город уведомлений на время поездки
+    #[serde(default)]
+    pub forecast_updates: bool, // Подписка на сообщения «прогноз обновился»
+    #[serde(default)]
+    pub forecast_snapshot: Option<DayOutlook>, // Прогноз, выданный в утреннем уведомлении
+    #[serde(default)]
+    pub delivery_window: Option<DeliveryWindow>, // Окно доставки вместо точного времени
+    #[serde(default)]
+    pub smart_time: bool, // Присылать прогноз раньше, если ожидается непогода
+    #[serde(default)]
+    pub early_sent_on: Option<NaiveDate>, // День, когда прогноз уже отправлен заранее
+    #[serde(default)]
+    pub report_sections: ReportSections, // Какие разделы показывать в отчете о погоде
+    #[serde(default)]
+    pub report_style: ReportStyle, // Обычный или компактный отчет
+    #[serde(default = "default_night_mode")]
+    pub night_mode: bool, // Поздним вечером присылать прогноз на завтра
+    #[serde(default)]
+    pub daily_extras: bool, // Цитата или гороскоп в утреннем сообщении милого режима
+    #[serde(default)]
+    pub locale: Option<Locale>, // Формат дат и времени в отчетах; None - русский
+    #[serde(default)]
+    pub temperature_precision: TemperaturePrecision, // Целые градусы или с десятыми
+    #[serde(default)]
+    pub units: Units, // °C и м/с или °F и мили в час
+    #[serde(default)]
+    pub household: Household, // Семья: чаты, которым дублируется прогноз этого пользователя
+    #[serde(default)]
+    pub interval_schedule: Option<IntervalSchedule>, // Прогноз каждые N часов в рабочее время
+    #[serde(default)]
+    pub utc_offset: Option<i32>, // Сдвиг часового пояса города от UTC в секундах; None - время по часам бота
+    #[serde(default = "legacy_version")]
+    pub version: u32, // Версия схемы записи, см. migrate_record
+}
+
+fn default_active() -> bool {
+    true
+}
+
+// Записи без поля version сохранены до его появления
+fn legacy_version() -> u32 {
+    1
+}
+
+fn default_night_mode() -> bool {
+    true
+}
+
+// Последний ответ пользователя на запрос ввода города или времени.
+// Нужен, чтобы при редактировании этого сообщения повторно применить исправленное значение
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastInput {
+    pub message_id: i32,
+    pub state: String,
+}
+
+// Состояние диалога: какой ввод бот ждет от пользователя и последний ответ на запрос.
+// Серверные хранилища держат его отдельно от настроек: оно меняется на каждый шаг диалога
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DialogueState {
+    pub state: Option<String>,
+    pub last_input: Option<LastInput>,
+}
+
+impl DialogueState {
+    pub fn is_empty(&self) -> bool {
+        self.state.is_none() && self.last_input.is_none()
+    }
+}
+
+// Запись пользователя без состояния диалога и само состояние - для хранения по отдельности
+pub fn split_dialogue(user: &UserSettings) -> Result<(Value, DialogueState), String> {
+    let mut settings = serde_json::to_value(user).map_err(|e| e.to_string())?;
+    if let Value::Object(record) = &mut settings {
+        record.remove("state");
+        record.remove("last_input");
+    }
+    let dialogue = DialogueState { state: user.state.clone(), last_input: user.last_input.clone() };
+    Ok((settings, dialogue))
+}
+
+// Обратное к split_dialogue: запись приводится к текущей схеме, затем получает состояние диалога
+pub fn merge_dialogue(settings: Value, dialogue: DialogueState) -> Result<UserSettings, String> {
+    let mut user = migrate_record(settings)?;
+    user.state = dialogue.state;
+    user.last_input = dialogue.last_input;
+    Ok(user)
+}
+
+// Поездка: уведомления приходят для другого города до указанной даты включительно
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TravelOverride {
+    pub city: String,
+    pub until: NaiveDate,
+}
+
+// Семья: прогноз владельца по его расписанию получают и все подтвердившие приглашение участники
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Household {
+    pub members: Vec<HouseholdMember>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HouseholdMember {
+    pub chat_id: i64,
+    // Имя из Telegram на момент вступления, чтобы владелец видел, кто в списке
+    pub name: String,
+}
+
+impl Household {
+    pub fn contains(&self, chat_id: i64) -> bool {
+        self.members.iter().any(|member| member.chat_id == chat_id)
+    }
+}
+
+// Расписание "каждые N часов с from_hour до until_hour" в дополнение к обычному времени
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IntervalSchedule {
+    pub every_hours: u32,
+    pub from_hour: u32,
+    pub until_hour: u32,
+}
+
+impl IntervalSchedule {
+    // Часы, в которые уходит прогноз: from_hour, from_hour + N, ... не позже until_hour
+    pub fn hours(&self) -> impl Iterator<Item = u32> {
+        (self.from_hour..=self.until_hour).step_by(self.every_hours.max(1) as usize)
+    }
+
+    pub fn is_due(&self, slot: NaiveTime) -> bool {
+        slot.minute() == 0 && self.hours().any(|hour| hour == slot.hour())
+    }
+}
+
+// Окно доставки: минуту отправки внутри окна бот выбирает сам, чтобы рассылка не собиралась в :00
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryWindow {
+    Morning,
+    Day,
+    Evening,
+}
+
+impl DeliveryWindow {
+    pub const ALL: [DeliveryWindow; 3] = [DeliveryWindow::Morning, DeliveryWindow::Day, DeliveryWindow::Evening];
+
+    // Ключ для данных колбэка
+    pub fn key(self) -> &'static str {
+        match self {
+            DeliveryWindow::Morning => "morning",
+            DeliveryWindow::Day => "day",
+            DeliveryWindow::Evening => "evening",
+        }
+    }
+
+    // Принимает ключ колбэка или слово пользователя: "утром", "днём", "вечером"
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "morning" | "утром" | "утро" => Some(DeliveryWindow::Morning),
+            "day" | "днем" | "днём" | "день" => Some(DeliveryWindow::Day),
+            "evening" | "вечером" | "вечер" => Some(DeliveryWindow::Evening),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DeliveryWindow::Morning => "утром (7–9)",
+            DeliveryWindow::Day => "днём (12–14)",
+            DeliveryWindow::Evening => "вечером (18–21)",
+        }
+    }
+
+    pub fn button_label(self) -> &'static str {
+        match self {
+            DeliveryWindow::Morning => "🌅 Утром (7–9)",
+            DeliveryWindow::Day => "☀️ Днём (12–14)",
+            DeliveryWindow::Evening => "🌆 Вечером (18–21)",
+        }
+    }
+
+    // Начало и конец окна в минутах от полуночи
+    fn bounds(self) -> (u32, u32) {
+        match self {
+            DeliveryWindow::Morning => (7 * 60, 9 * 60),
+            DeliveryWindow::Day => (12 * 60, 14 * 60),
+            DeliveryWindow::Evening => (18 * 60, 21 * 60),
+        }
+    }
+
+    // Время отправки пользователю внутри окна: зависит только от ID, поэтому постоянно изо дня в день,
+    // а пользователи равномерно распределяются по слотам с шагом расписания
+    pub fn slot_for(self, user_id: i64, granularity: u32) -> NaiveTime {
+        let (start, end) = self.bounds();
+        let step = granularity.max(1);
+        let slots = ((end - start) / step).max(1) as u64;
+        let hash = (user_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+        let minutes = start + (hash % slots) as u32 * step;
+        NaiveTime::from_hms_opt(minutes / 60, minutes % 60, 0).unwrap_or_default()
+    }
+}
+
+impl UserSettings {
+    // Настройки нового пользователя: город и время не заданы, стандартный режим
+    pub fn new(user_id: i64) -> Self {
+        UserSettings {
+            user_id,
+            cities: Vec::new(),
+            notification_time: None,
+            extra_times: Vec::new(),
+            cute_mode: false,
+            state: None,
+            last_input: None,
+            active: true,
+            started_at: None,
+            onboarding_reminder_sent: false,
+            last_seen: None,
+            created_at: Some(Utc::now()),
+            commands_used: 0,
+            reengagement_opt_out: false,
+            last_reengagement_at: None,
+            retention_warning_at: None,
+            recent_cities: Vec::new(),
+            travel: None,
+            forecast_updates: false,
+            forecast_snapshot: None,
+            delivery_window: None,
+            smart_time: false,
+            early_sent_on: None,
+            report_sections: ReportSections::default(),
+            report_style: ReportStyle::default(),
+            night_mode: true,
+            daily_extras: false,
+            locale: None,
+            temperature_precision: TemperaturePrecision::default(),
+            units: Units::default(),
+            household: Household::default(),
+            interval_schedule: None,
+            utc_offset: None,
+            version: SCHEMA_VERSION,
+        }
+    }
+
+    // Основной город: по нему идут /forecast, снимок прогноза и часовой пояс
+    pub fn city(&self) -> Option<&str> {
+        self.cities.first().map(String::as_str)
+    }
+
+    // Заменяет основной город (/city); дополнительные города прогноза остаются после него
+    pub fn set_city(&mut self, city: &str) {
+        if !self.cities.is_empty() {
+            self.cities.remove(0);
+        }
+        self.make_main_city(city);
+    }
+
+    // Ставит город первым; прежний основной становится дополнительным.
+    // Если список уже заполнен, последний город вытесняется
+    pub fn make_main_city(&mut self, city: &str) {
+        let city_lower = city.to_lowercase();
+        self.cities.retain(|c| c.to_lowercase() != city_lower);
+        self.cities.insert(0, city.to_string());
+        self.cities.truncate(CITIES_LIMIT);
+    }
+
+    // Добавляет город в конец списка; false - город уже есть или список заполнен
+    pub fn add_city(&mut self, city: &str) -> bool {
+        let city_lower = city.to_lowercase();
+        if self.cities.len() >= CITIES_LIMIT || self.cities.iter().any(|c| c.to_lowercase() == city_lower) {
+            return false;
+        }
+        self.cities.push(city.to_string());
+        true
+    }
+
+    // Убирает город из списка; false - такого города нет
+    pub fn remove_city(&mut self, city: &str) -> bool {
+        let city_lower = city.to_lowercase();
+        let before = self.cities.len();
+        self.cities.retain(|c| c.to_lowercase() != city_lower);
+        self.cities.len() != before
+    }
+
+    // Город для уведомлений: на время поездки - город поездки, иначе основной
+    pub fn notification_city(&self, today: NaiveDate) -> Option<String> {
+        match &self.travel {
+            Some(travel) if today <= travel.until => Some(travel.city.clone()),
+            _ => self.city().map(str::to_string),
+        }
+    }
+
+    // Города ежедневного уведомления: на время поездки - только город поездки, иначе все города прогноза
+    pub fn notification_cities(&self, today: NaiveDate) -> Vec<String> {
+        match &self.travel {
+            Some(travel) if today <= travel.until => vec![travel.city.clone()],
+            _ => self.cities.clone(),
+        }
+    }
+
+    // Копирует пользовательские настройки из другого аккаунта; ID, состояние диалога
+    // и служебные отметки (активность, напоминания, удаление данных) остаются своими
+    pub fn copy_preferences_from(&mut self, other: &UserSettings) {
+        self.cities = other.cities.clone();
+        self.notification_time = other.notification_time.clone();
+        self.extra_times = other.extra_times.clone();
+        self.cute_mode = other.cute_mode;
+        self.recent_cities = other.recent_cities.clone();
+        self.travel = other.travel.clone();
+        self.forecast_updates = other.forecast_updates;
+        self.delivery_window = other.delivery_window;
+        self.smart_time = other.smart_time;
+        self.report_sections = other.report_sections;
+        self.report_style = other.report_style;
+        self.night_mode = other.night_mode;
+        self.daily_extras = other.daily_extras;
+        self.locale = other.locale;
+        self.temperature_precision = other.temperature_precision;
+        self.units = other.units;
+        self.interval_schedule = other.interval_schedule;
+        self.utc_offset = other.utc_offset;
+    }
+
+    // Запоминает город в начале списка недавних, без повторов
+    pub fn remember_city(&mut self, city: &str) {
+        let city_lower = city.to_lowercase();
+        self.recent_cities.retain(|c| c.to_lowercase() != city_lower);
+        self.recent_cities.insert(0, city.to_string());
+        self.recent_cities.truncate(RECENT_CITIES_LIMIT);
+    }
+}
+
+// Текущая версия схемы записи пользователя
+pub const SCHEMA_VERSION: u32 = 3;
+
+// Шаги миграции: шаг с индексом i переводит запись из версии i + 1 в i + 2.
+// Новое переименование или перенос поля - новый шаг в конце и SCHEMA_VERSION на единицу больше
+const MIGRATIONS: [fn(&mut Map<String, Value>); SCHEMA_VERSION as usize - 1] = [migrate_v1_to_v2, migrate_v2_to_v3];
+
+// v1 -> v2: в первых версиях бота и во вручную собранных файлах нет обязательного cute_mode,
+// а ID иногда записан строкой - раньше из-за одной такой записи отбрасывался весь файл
+fn migrate_v1_to_v2(record: &mut Map<String, Value>) {
+    record.entry("cute_mode").or_insert(Value::Bool(false));
+    let id_from_text = record.get("user_id").and_then(Value::as_str).and_then(|id| id.trim().parse::<i64>().ok());
+    if let Some(id) = id_from_text {
+        record.insert("user_id".to_string(), Value::from(id));
+    }
+}
+
+// v2 -> v3: один город city стал списком cities, прежний город - основной (первый)
+fn migrate_v2_to_v3(record: &mut Map<String, Value>) {
+    let city = record.remove("city");
+    if record.contains_key("cities") {
+        return;
+    }
+    let cities = match city {
+        Some(Value::String(city)) if !city.trim().is_empty() => vec![Value::String(city)],
+        _ => Vec::new(),
+    };
+    record.insert("cities".to_string(), Value::Array(cities));
+}
+
+// Приводит запись из файла к текущей схеме и разбирает ее
+pub fn migrate_record(value: Value) -> Result<UserSettings, String> {
+    let Value::Object(mut record) = value else {
+        return Err("запись не является объектом".to_string());
+    };
+
+    let version = record_version(&record);
+    if version > SCHEMA_VERSION {
+        // Файл от более новой версии бота: незнакомые поля пропадут при следующей записи
+        warn!("Запись версии схемы {} новее поддерживаемой ({}), читаем как есть", version, SCHEMA_VERSION);
+    } else {
+        for migration in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+            migration(&mut record);
+        }
+        record.insert("version".to_string(), Value::from(SCHEMA_VERSION));
+    }
+
+    serde_json::from_value(Value::Object(record)).map_err(|e| e.to_string())
+}
+
+// Формат файла хранилища определяется расширением: .jsonl - по записи на строку, иначе JSON-массив
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageFormat {
+    Json,
+    Jsonl,
+}
+
+impl StorageFormat {
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".jsonl") {
+            StorageFormat::Jsonl
+        } else {
+            StorageFormat::Json
+        }
+    }
+}
+
+// Строка JSONL-файла и журнала изменений: новая версия настроек пользователя или отметка об удалении
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum JsonlRecord {
+    Deleted { deleted_user_id: i64 },
+    User(Box<UserSettings>),
+}
+
+// Минимальное число лишних строк в JSONL-файле, после которого он переписывается начисто
+const JSONL_COMPACTION_SLACK: usize = 500;
+
+// Сколько сбоев записи подряд терпим, прежде чем звать оператора: единичный сбой
+// (например, файл на мгновение занят) не стоит тревоги, повторяющиеся - почти наверняка диск
+const WRITE_FAILURES_BEFORE_ALERT: usize = 3;
+
+// Сбой хранилища. Read - данные не удалось прочитать: действовать по настройкам
+// по умолчанию нельзя, иначе следующее сохранение затрет настоящую запись.
+// Write - изменение не удалось записать: при перезапуске бота оно потеряется
+#[derive(Debug, Clone)]
+pub enum StorageError {
+    Read(String),
+    Write(String),
+}
+
+// Конструкторы заодно учитывают сбой в метриках, чтобы любое хранилище попадало в /admin metrics
+impl StorageError {
+    pub fn read(e: impl std::fmt::Display) -> Self {
+        metrics().increment("storage_read_errors_total");
+        StorageError::Read(e.to_string())
+    }
+
+    pub fn write(e: impl std::fmt::Display) -> Self {
+        metrics().increment("storage_write_errors_total");
+        StorageError::Write(e.to_string())
+    }
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::Read(e) => write!(f, "не удалось прочитать данные: {}", e),
+            StorageError::Write(e) => write!(f, "не удалось записать изменения: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+// Результат операции хранилища
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// Хранилище настроек пользователей. Обработчики и планировщик работают с ним через
+// Arc<dyn UserStorage>, поэтому другое хранилище подключается без изменений в них
+pub trait UserStorage: Send + Sync {
+    fn get_user(&self, user_id: i64) -> StorageFuture<'_, Result<Option<UserSettings>, StorageError>>;
+
+    // Создает или заменяет запись пользователя. При ошибке запись в памяти уже обновлена,
+    // но после перезапуска изменение пропадет - об этом стоит сказать пользователю
+    fn save_user(&self, user: UserSettings) -> StorageFuture<'_, Result<(), StorageError>>;
+
+    fn get_all_users(&self) -> StorageFuture<'_, Result<Vec<UserSettings>, StorageError>>;
+
+    fn delete_user(&self, user_id: i64) -> StorageFuture<'_, Result<(), StorageError>>;
+
+    // Записывает накопленную активность пользователей (последнее взаимодействие, число команд),
+    // при необходимости создавая записи о них
+    fn record_activity(&self, activity: Vec<Activity>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            for entry in activity {
+                let mut user = self.get_user(entry.user_id).await?.unwrap_or_else(|| UserSettings::new(entry.user_id));
+                entry.apply(&mut user);
+                self.save_user(user).await?;
+            }
+            Ok(())
+        })
+    }
+
+    // История доставки запланированных уведомлений всех чатов (см. delivery_history).
+    // Хранилище без нее (память) возвращает пустую историю
+    fn load_deliveries(&self) -> StorageFuture<'_, Result<Vec<DeliveryRecord>, StorageError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    // Заменяет историю доставки перечисленных чатов; пустой список удаляет историю чата
+    fn save_deliveries(&self, chats: Vec<(i64, Vec<DeliveryRecord>)>) -> StorageFuture<'_, Result<(), StorageError>> {
+        let _ = chats;
+        Box::pin(async { Ok(()) })
+    }
+
+    // Сохранение, о сбое которого некому сообщить (фоновые задачи, служебные отметки):
+    // ошибка только пишется в лог, а при повторах хранилище само позовет оператора
+    fn save_user_or_log(&self, user: UserSettings) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            let user_id = user.user_id;
+            if let Err(e) = self.save_user(user).await {
+                warn!("Изменение настроек пользователя ID: {} не сохранено: {}", user_id, e);
+            }
+        })
+    }
+
+    // Все записи для фоновой задачи: при сбое чтения проход пропускается (ошибка пишется в лог),
+    // следующий проход попробует снова
+    fn all_users_or_log(&self) -> StorageFuture<'_, Vec<UserSettings>> {
+        Box::pin(async move {
+            self.get_all_users().await.unwrap_or_else(|e| {
+                error!("Не удалось получить список пользователей: {}", e);
+                Vec::new()
+            })
+        })
+    }
+
+    // Заменяет все записи разом (используется при исправлении хранилища)
+    fn replace_all(&self, users: Vec<UserSettings>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            for old in self.get_all_users().await? {
+                if !users.iter().any(|user| user.user_id == old.user_id) {
+                    self.delete_user(old.user_id).await?;
+                }
+            }
+            for user in users {
+                self.save_user(user).await?;
+            }
+            Ok(())
+        })
+    }
 }
 
 #[derive(Clone)]
 pub struct JsonStorage {
     pub data: Arc<RwLock<Vec<UserSettings>>>,
     file_path: String,
+    format: StorageFormat,
+    // Сколько строк в JSONL-файле сейчас (вместе с устаревшими версиями записей)
+    jsonl_lines: Arc<AtomicUsize>,
+    // Сбои записи подряд, см. WRITE_FAILURES_BEFORE_ALERT
+    write_failures: Arc<AtomicUsize>,
+    // Задан STORAGE_KEY: файл и журнал пишутся зашифрованными
+    cipher: Option<Arc<StorageCipher>>,
+    // История доставки уведомлений, лежит рядом с основным файлом (см. deliveries_path)
+    deliveries: Arc<RwLock<Vec<DeliveryRecord>>>,
 }
 
 impl JsonStorage {
-    pub async fn new(path: &str) -> Self {
-        // Создаем хранилище и пытаемся загрузить существующие данные
-        let data = match fs::read_to_string(path) {
-            Ok(content) => {
-                if content.trim().is_empty() {
-                    // Файл пустой, начинаем с пустого списка
-                    info!("Файл данных пустой, создан новый список пользователей");
-                    Vec::new()
-                } else {
-                    match serde_json::from_str::<Vec<UserSettings>>(&content) {
-                        Ok(users) => users,
-                        Err(e) => {
-                            error!("Ошибка десериализации данных: {}", e);
-                            // Создаем резервную копию проблемного файла
-                            let backup_path = format!("{}.backup", path);
-                            if let Err(copy_err) = fs::copy(path, &backup_path) {
-                                error!("Не удалось создать резервную копию: {}", copy_err);
-                            } else {
-                                info!("Создана резервная копия поврежденного файла данных: {}", backup_path);
-                            }
-                            Vec::new()
-                        }
+    // key - значение STORAGE_KEY. Ошибка означает, что зашифрованный файл нечем
+    // или не удалось расшифровать: работать с пустым списком вместо него нельзя
+    pub async fn new(path: &str, key: Option<&str>) -> Result<Self, String> {
+        let format = StorageFormat::from_path(path);
+        let cipher = key.map(StorageCipher::new).transpose()?;
+        let (mut data, lines) = load_users(path, format, cipher.as_ref())?;
+
+        // Изменения, которые не успели попасть в основной файл до сбоя
+        let journal = journal_path(path);
+        let replayed = if format == StorageFormat::Json {
+            replay_journal(&journal, &mut data, cipher.as_ref())?
+        } else {
+            0
+        };
+
+        let deliveries = load_deliveries(&deliveries_path(path), cipher.as_ref())?;
+
+        let storage = JsonStorage {
+            data: Arc::new(RwLock::new(data)),
+            file_path: path.to_string(),
+            format,
+            jsonl_lines: Arc::new(AtomicUsize::new(lines)),
+            write_failures: Arc::new(AtomicUsize::new(0)),
+            cipher: cipher.map(Arc::new),
+            deliveries: Arc::new(RwLock::new(deliveries)),
+        };
+
+        if replayed > 0 {
+            info!("Восстановлено изменений из журнала {}: {}", journal, replayed);
+            let data = storage.data.read().await;
+            if storage.save_to_file(&data).await.is_ok() {
+                storage.clear_journal();
+            }
+        }
+
+        // После загрузки сразу убираем из журнала устаревшие версии записей
+        if format == StorageFormat::Jsonl {
+            let data = storage.data.read().await;
+            if lines > data.len() {
+                // Не вышло - останутся лишние строки, данные от этого не страдают
+                let _ = storage.compact_jsonl(&data).await;
+            }
+        }
+
+        Ok(storage)
+    }
+
+    // Строка для записи в файл: с ключом - зашифрованная, без него - как есть
+    fn seal(&self, line: String) -> Result<String, StorageError> {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&line).map_err(|e| {
+                error!("Ошибка шифрования данных: {}", e);
+                StorageError::write(e)
+            }),
+            None => Ok(line),
+        }
+    }
+
+    // JSON-массив переписывается целиком, в JSONL дописывается одна строка с изменением
+    async fn persist(&self, data: &[UserSettings], record: JsonlRecord) -> Result<(), StorageError> {
+        match self.format {
+            StorageFormat::Json => {
+                // Сначала фиксируем изменение в журнале: если запись основного файла
+                // прервется, при следующем запуске изменение будет восстановлено
+                let journaled = self.append_journal(&record);
+                let result = self.track_write(self.save_to_file(data).await);
+                match result {
+                    Ok(()) => {
+                        self.clear_journal();
+                        Ok(())
                     }
+                    // Изменение уже в журнале и не потеряется, но о сбоях основного файла оператор узнает
+                    Err(_) if journaled => Ok(()),
+                    Err(e) => Err(e),
                 }
             }
-            Err(e) if e.kind() == ErrorKind::NotFound => {
-                // Файл не найден, начинаем с пустого списка
-                info!("Файл данных не найден, создан новый файл: {}", path);
-                Vec::new()
-            }
+            StorageFormat::Jsonl => self.track_write(self.append_jsonl(data, &record).await),
+        }
+    }
+
+    // Считает сбои записи подряд и после нескольких зовет оператора
+    fn track_write(&self, result: Result<(), StorageError>) -> Result<(), StorageError> {
+        match &result {
+            Ok(()) => self.write_failures.store(0, Ordering::SeqCst),
             Err(e) => {
-                error!("Ошибка чтения файла: {}", e);
-                Vec::new()
+                let failures = self.write_failures.fetch_add(1, Ordering::SeqCst) + 1;
+                if failures >= WRITE_FAILURES_BEFORE_ALERT {
+                    alerts::critical(
+                        "storage_write",
+                        format!("{} сбоев записи подряд в {}: {}", failures, self.file_path, e),
+                    );
+                }
             }
-        };
+        }
+        result
+    }
+    
+    // Записывает JSON-массив через временный файл, чтобы основной файл не остался недописанным
+    async fn save_to_file(&self, data: &[UserSettings]) -> Result<(), StorageError> {
+        let json = serde_json::to_string_pretty(data).map_err(|e| {
+            error!("Ошибка сериализации данных: {}", e);
+            StorageError::write(e)
+        })?;
+        let json = self.seal(json)?;
+        let tmp_path = format!("{}.tmp", self.file_path);
+        fs::write(&tmp_path, json)
+            .and_then(|_| fs::rename(&tmp_path, &self.file_path))
+            .map_err(|e| {
+                error!("Ошибка сохранения данных в файл: {}", e);
+                StorageError::write(e)
+            })
+    }
 
-        JsonStorage {
-            data: Arc::new(RwLock::new(data)),
-            file_path: path.to_string(),
+    // История доставки переписывается целиком через временный файл. Вызывается пачкой
+    // изменений раз в несколько секунд, а не на каждое уведомление
+    async fn save_deliveries_file(&self, records: &[DeliveryRecord]) -> Result<(), StorageError> {
+        let json = serde_json::to_string(records).map_err(|e| {
+            error!("Ошибка сериализации истории доставки: {}", e);
+            StorageError::write(e)
+        })?;
+        let json = self.seal(json)?;
+        let path = deliveries_path(&self.file_path);
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, json)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| {
+                error!("Ошибка сохранения истории доставки: {}", e);
+                StorageError::write(e)
+            })
+    }
+
+    // Возвращает, удалось ли записать изменение в журнал
+    fn append_journal(&self, record: &JsonlRecord) -> bool {
+        let result = serde_json::to_string(record)
+            .map_err(std::io::Error::other)
+            .and_then(|line| self.seal(line).map_err(std::io::Error::other))
+            .and_then(|line| {
+                let mut file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(journal_path(&self.file_path))?;
+                writeln!(file, "{}", line)?;
+                file.sync_data()
+            });
+
+        if let Err(e) = &result {
+            error!("Не удалось записать изменение в журнал: {}", e);
         }
+        result.is_ok()
     }
 
-    pub async fn get_user(&self, user_id: i64) -> Option<UserSettings> {
-        let data = self.data.read().await;
-        data.iter().find(|user| user.user_id == user_id).cloned()
+    fn clear_journal(&self) {
+        match fs::remove_file(journal_path(&self.file_path)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => error!("Не удалось очистить журнал изменений: {}", e),
+        }
     }
 
-    pub async fn save_user(&self, user: UserSettings) {
-        let mut data = self.data.write().await;
-        if let Some(pos) = data.iter().position(|u| u.user_id == user.user_id) {
-            data[pos] = user;
-        } else {
-            data.push(user);
+    async fn append_jsonl(&self, data: &[UserSettings], record: &JsonlRecord) -> Result<(), StorageError> {
+        let line = serde_json::to_string(record).map_err(|e| {
+            error!("Ошибка сериализации данных: {}", e);
+            StorageError::write(e)
+        })?;
+        let line = self.seal(line)?;
+
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| {
+                error!("Ошибка сохранения данных в файл: {}", e);
+                StorageError::write(e)
+            })?;
+
+        // Когда устаревших строк становится заметно больше, чем пользователей, сжимаем файл.
+        // Изменение уже записано, так что неудачное сжатие на результат не влияет
+        let lines = self.jsonl_lines.fetch_add(1, Ordering::SeqCst) + 1;
+        if lines > data.len() * 2 + JSONL_COMPACTION_SLACK {
+            let _ = self.compact_jsonl(data).await;
+        }
+        Ok(())
+    }
+
+    // Переписывает JSONL-файл, оставляя по одной строке на пользователя.
+    // Пишем во временный файл и переименовываем, чтобы сбой не оставил файл наполовину записанным
+    async fn compact_jsonl(&self, data: &[UserSettings]) -> Result<(), StorageError> {
+        let mut content = String::new();
+        for user in data {
+            let line = serde_json::to_string(user).map_err(|e| {
+                error!("Ошибка сериализации данных: {}", e);
+                StorageError::write(e)
+            })?;
+            content.push_str(&self.seal(line)?);
+            content.push('\n');
         }
-        
-        // Сохраняем обновленные данные в файл
-        self.save_to_file(&data).await;
+
+        let tmp_path = format!("{}.tmp", self.file_path);
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.file_path))
+            .map_err(|e| {
+                error!("Не удалось сжать файл данных {}: {}", self.file_path, e);
+                StorageError::write(e)
+            })?;
+
+        self.jsonl_lines.store(data.len(), Ordering::SeqCst);
+        info!("Файл данных {} сжат до {} записей", self.file_path, data.len());
+        Ok(())
     }
+}
 
-    pub async fn get_all_users(&self) -> Vec<UserSettings> {
-        let data = self.data.read().await;
-        data.clone()
+impl UserStorage for JsonStorage {
+    fn get_user(&self, user_id: i64) -> StorageFuture<'_, Result<Option<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let data = self.data.read().await;
+            Ok(data.iter().find(|user| user.user_id == user_id).cloned())
+        })
     }
-    
-    async fn save_to_file(&self, data: &[UserSettings]) {
-        match serde_json::to_string_pretty(data) {
-            Ok(json) => {
-                if let Err(e) = fs::write(&self.file_path, json) {
-                    error!("Ошибка сохранения данных в файл: {}", e);
+
+    fn save_user(&self, user: UserSettings) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut data = self.data.write().await;
+            let record = JsonlRecord::User(Box::new(user.clone()));
+            if let Some(pos) = data.iter().position(|u| u.user_id == user.user_id) {
+                data[pos] = user;
+            } else {
+                data.push(user);
+            }
+
+            // Сохраняем обновленные данные в файл
+            self.persist(&data, record).await
+        })
+    }
+
+    fn get_all_users(&self) -> StorageFuture<'_, Result<Vec<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let data = self.data.read().await;
+            Ok(data.clone())
+        })
+    }
+
+    fn delete_user(&self, user_id: i64) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut data = self.data.write().await;
+            let before = data.len();
+            data.retain(|u| u.user_id != user_id);
+
+            if data.len() == before {
+                return Ok(());
+            }
+            self.persist(&data, JsonlRecord::Deleted { deleted_user_id: user_id }).await
+        })
+    }
+
+    // Вся пачка под одной блокировкой: параллельные обновления не затрут счетчик команд,
+    // а JSON-файл переписывается один раз
+    fn record_activity(&self, activity: Vec<Activity>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut data = self.data.write().await;
+            let mut changed = Vec::with_capacity(activity.len());
+            for entry in &activity {
+                let pos = match data.iter().position(|u| u.user_id == entry.user_id) {
+                    Some(pos) => pos,
+                    None => {
+                        data.push(UserSettings::new(entry.user_id));
+                        data.len() - 1
+                    }
+                };
+                entry.apply(&mut data[pos]);
+                changed.push(pos);
+            }
+
+            match self.format {
+                StorageFormat::Json => self.track_write(self.save_to_file(&data).await).inspect(|_| self.clear_journal()),
+                StorageFormat::Jsonl => {
+                    for pos in changed {
+                        let record = JsonlRecord::User(Box::new(data[pos].clone()));
+                        self.track_write(self.append_jsonl(&data, &record).await)?;
+                    }
+                    Ok(())
                 }
             }
-            Err(e) => {
-                error!("Ошибка сериализации данных: {}", e);
+        })
+    }
+
+    fn load_deliveries(&self) -> StorageFuture<'_, Result<Vec<DeliveryRecord>, StorageError>> {
+        Box::pin(async move { Ok(self.deliveries.read().await.clone()) })
+    }
+
+    fn save_deliveries(&self, chats: Vec<(i64, Vec<DeliveryRecord>)>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut deliveries = self.deliveries.write().await;
+            for (chat_id, records) in chats {
+                deliveries.retain(|record| record.chat_id != chat_id);
+                deliveries.extend(records);
             }
+            deliveries.sort_by_key(|record| record.at);
+            let result = self.save_deliveries_file(&deliveries).await;
+            self.track_write(result)
+        })
+    }
+
+    // Файл переписывается один раз, а не по записи на пользователя
+    fn replace_all(&self, users: Vec<UserSettings>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut data = self.data.write().await;
+            *data = users;
+
+            let result = match self.format {
+                StorageFormat::Json => self.save_to_file(&data).await.inspect(|_| self.clear_journal()),
+                StorageFormat::Jsonl => self.compact_jsonl(&data).await,
+            };
+            self.track_write(result)
+        })
+    }
+}
+
+// Хранилище только в памяти: настройки живут до перезапуска бота и никуда не записываются.
+// Для тестов, демо-развертываний и режима «ничего не сохраняем» (STORAGE_BACKEND=memory)
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    data: Arc<RwLock<Vec<UserSettings>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Хранилище с заранее заданными пользователями, например для тестов
+    pub fn with_users(users: Vec<UserSettings>) -> Self {
+        MemoryStorage { data: Arc::new(RwLock::new(users)) }
+    }
+}
+
+impl UserStorage for MemoryStorage {
+    fn get_user(&self, user_id: i64) -> StorageFuture<'_, Result<Option<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let data = self.data.read().await;
+            Ok(data.iter().find(|user| user.user_id == user_id).cloned())
+        })
+    }
+
+    fn save_user(&self, user: UserSettings) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut data = self.data.write().await;
+            match data.iter_mut().find(|u| u.user_id == user.user_id) {
+                Some(existing) => *existing = user,
+                None => data.push(user),
+            }
+            Ok(())
+        })
+    }
+
+    fn get_all_users(&self) -> StorageFuture<'_, Result<Vec<UserSettings>, StorageError>> {
+        Box::pin(async move { Ok(self.data.read().await.clone()) })
+    }
+
+    fn delete_user(&self, user_id: i64) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            self.data.write().await.retain(|u| u.user_id != user_id);
+            Ok(())
+        })
+    }
+}
+
+fn journal_path(path: &str) -> String {
+    format!("{}.journal", path)
+}
+
+fn deliveries_path(path: &str) -> String {
+    format!("{}.deliveries", path)
+}
+
+// Загружает историю доставки. Поврежденный файл сохраняется копией: история начнется
+// заново, но прежние записи можно будет разобрать вручную
+fn load_deliveries(path: &str, cipher: Option<&StorageCipher>) -> Result<Vec<DeliveryRecord>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => encryption::open_content(&content, cipher, path)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("не удалось прочитать историю доставки {}: {}", path, e)),
+    };
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str(&content) {
+        Ok(records) => Ok(records),
+        Err(e) => {
+            error!("История доставки {} повреждена, начинаем с пустой: {}", path, e);
+            backup_broken_file(path);
+            Ok(Vec::new())
+        }
+    }
+}
+
+// Применяет к загруженным данным изменения из журнала; возвращает их число
+fn replay_journal(journal: &str, data: &mut Vec<UserSettings>, cipher: Option<&StorageCipher>) -> Result<usize, String> {
+    let content = match fs::read_to_string(journal) {
+        Ok(content) => encryption::open_content(&content, cipher, journal)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => {
+            error!("Не удалось прочитать журнал изменений {}: {}", journal, e);
+            return Ok(0);
         }
+    };
+
+    let (changes, count) = parse_records(&content);
+    for change in changes {
+        apply_record(data, change);
+    }
+    Ok(count)
+}
+
+// Загружает пользователей из файла; вторым значением возвращает число строк JSONL-файла
+fn load_users(path: &str, format: StorageFormat, cipher: Option<&StorageCipher>) -> Result<(Vec<UserSettings>, usize), String> {
+    // Создаем хранилище и пытаемся загрузить существующие данные
+    let loaded = match fs::read_to_string(path) {
+        Ok(content) => {
+            let content = encryption::open_content(&content, cipher, path)?;
+            if content.trim().is_empty() {
+                // Файл пустой, начинаем с пустого списка
+                info!("Файл данных пустой, создан новый список пользователей");
+                (Vec::new(), 0)
+            } else if format == StorageFormat::Jsonl {
+                parse_jsonl(&content)
+            } else {
+                match serde_json::from_str::<Vec<Value>>(&content) {
+                    Ok(records) => {
+                        let total = records.len();
+                        let outdated = records
+                            .iter()
+                            .filter_map(Value::as_object)
+                            .filter(|record| record_version(record) < SCHEMA_VERSION)
+                            .count();
+                        let users: Vec<UserSettings> = records
+                            .into_iter()
+                            .enumerate()
+                            .filter_map(|(index, record)| match migrate_record(record) {
+                                Ok(user) => Some(user),
+                                Err(e) => {
+                                    error!("Пропущена запись {}: {}", index + 1, e);
+                                    None
+                                }
+                            })
+                            .collect();
+                        if outdated > 0 {
+                            info!("Записей обновлено до версии схемы {}: {}", SCHEMA_VERSION, outdated);
+                        }
+                        // Нечитаемые записи при следующем сохранении пропадут из файла, поэтому сохраняем оригинал
+                        if users.len() < total {
+                            backup_broken_file(path);
+                        }
+                        (users, 0)
+                    }
+                    Err(e) => {
+                        error!("Ошибка десериализации данных: {}", e);
+                        backup_broken_file(path);
+                        (Vec::new(), 0)
+                    }
+                }
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // Файл не найден, начинаем с пустого списка
+            info!("Файл данных не найден, создан новый файл: {}", path);
+            (Vec::new(), 0)
+        }
+        Err(e) => {
+            error!("Ошибка чтения файла: {}", e);
+            (Vec::new(), 0)
+        }
+    };
+    Ok(loaded)
+}
+
+fn record_version(record: &Map<String, Value>) -> u32 {
+    record.get("version").and_then(Value::as_u64).map_or(legacy_version(), |v| v as u32)
+}
+
+// Создаем резервную копию проблемного файла
+pub fn backup_broken_file(path: &str) {
+    let backup_path = format!("{}.backup", path);
+    if let Err(copy_err) = fs::copy(path, &backup_path) {
+        error!("Не удалось создать резервную копию: {}", copy_err);
+    } else {
+        info!("Создана резервная копия поврежденного файла данных: {}", backup_path);
+    }
+}
+
+// Проигрывает JSONL-файл по порядку: последняя версия записи побеждает, удаления убирают пользователя
+fn parse_jsonl(content: &str) -> (Vec<UserSettings>, usize) {
+    let mut users: Vec<UserSettings> = Vec::new();
+    let (records, lines) = parse_records(content);
+    for record in records {
+        apply_record(&mut users, record);
+    }
+    (users, lines)
+}
+
+// Разбирает строки с изменениями; вторым значением возвращает число непустых строк
+fn parse_records(content: &str) -> (Vec<JsonlRecord>, usize) {
+    let mut records = Vec::new();
+    let mut lines = 0;
+
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        lines += 1;
+
+        match parse_record(line) {
+            Ok(record) => records.push(record),
+            // Недописанная последняя строка после сбоя не должна ломать загрузку остальных
+            Err(e) => error!("Пропущена поврежденная строка {}: {}", number + 1, e),
+        }
+    }
+
+    (records, lines)
+}
+
+// Строка журнала: отметка об удалении или запись пользователя, приведенная к текущей схеме
+fn parse_record(line: &str) -> Result<JsonlRecord, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    if let Some(deleted_user_id) = value.get("deleted_user_id").and_then(Value::as_i64) {
+        return Ok(JsonlRecord::Deleted { deleted_user_id });
+    }
+    migrate_record(value).map(|user| JsonlRecord::User(Box::new(user)))
+}
+
+fn apply_record(users: &mut Vec<UserSettings>, record: JsonlRecord) {
+    match record {
+        JsonlRecord::User(user) => {
+            let user = *user;
+            match users.iter().position(|u| u.user_id == user.user_id) {
+                Some(pos) => users[pos] = user,
+                None => users.push(user),
+            }
+        }
+        JsonlRecord::Deleted { deleted_user_id } => users.retain(|u| u.user_id != deleted_user_id),
+    }
+}
+
+// Файл базы SQLite, а не JSON: для переноса между хранилищами
+pub fn is_sqlite_path(path: &str) -> bool {
+    [".db", ".sqlite", ".sqlite3"].iter().any(|extension| path.ends_with(extension))
+}
+
+// Адрес базы PostgreSQL вместо пути к файлу
+pub fn is_postgres_url(path: &str) -> bool {
+    path.starts_with("postgres://") || path.starts_with("postgresql://")
+}
+
+async fn open_file(path: &str, key: Option<&str>) -> Result<Box<dyn UserStorage>, String> {
+    if is_postgres_url(path) {
+        Ok(Box::new(PgStorage::connect(path).await?))
+    } else if is_sqlite_path(path) {
+        Ok(Box::new(SqliteStorage::open(path, key).await?))
+    } else {
+        Ok(Box::new(JsonStorage::new(path, key).await?))
+    }
+}
+
+// Переносит пользователей и историю доставки из одного хранилища в другое, формат определяется
+// расширением: .json, .jsonl или база SQLite (.db, .sqlite); вместо файла можно указать адрес
+// PostgreSQL. Например, cargo run -- migrate-storage users.json users.db.
+// С заданным STORAGE_KEY оба файловых хранилища зашифрованы
+pub async fn migrate(from: &str, to: &str, key: Option<&str>) -> Result<usize, String> {
+    if !is_postgres_url(from) && !std::path::Path::new(from).exists() {
+        return Err(format!("файл {} не найден", from));
+    }
+    if !is_postgres_url(to) && std::path::Path::new(to).exists() {
+        return Err(format!("файл {} уже существует, удалите его или выберите другое имя", to));
+    }
+
+    // Загружаем через хранилище, чтобы учесть незавершенные изменения из журнала
+    let source = open_file(from, key).await?;
+    let users = source.get_all_users().await.map_err(|e| e.to_string())?;
+    let deliveries = source.load_deliveries().await.map_err(|e| e.to_string())?;
+
+    let target = open_file(to, key).await?;
+    // Перенос заменяет все записи, поэтому в базу с пользователями не пишем
+    if !target.get_all_users().await.map_err(|e| e.to_string())?.is_empty() {
+        return Err("в базе PostgreSQL уже есть пользователи, перенос возможен только в пустую базу".to_string());
+    }
+    let count = users.len();
+    target.replace_all(users).await.map_err(|e| e.to_string())?;
+    // История доставки переезжает вместе с настройками
+    let mut chats: Vec<(i64, Vec<DeliveryRecord>)> = Vec::new();
+    for record in deliveries {
+        match chats.iter_mut().find(|(chat_id, _)| *chat_id == record.chat_id) {
+            Some((_, records)) => records.push(record),
+            None => chats.push((record.chat_id, vec![record])),
+        }
+    }
+    if !chats.is_empty() {
+        target.save_deliveries(chats).await.map_err(|e| e.to_string())?;
     }
+    Ok(count)
 }
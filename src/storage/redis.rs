@@ -0,0 +1,363 @@
+use super::{merge_dialogue, split_dialogue, DialogueState, LastInput, StorageError, StorageFuture, UserSettings, UserStorage};
+use crate::activity::Activity;
+use crate::delivery_history::DeliveryRecord;
+use log::{error, warn};
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, AsyncConnectionConfig, Pipeline};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Сколько ждать подключения к серверу и ответа на команду
+const REDIS_TIMEOUT: Duration = Duration::from_secs(10);
+// Сколько ключей читать одной командой MGET
+const MGET_CHUNK: usize = 500;
+// Сколько раз повторять запись активности, если другой экземпляр бота изменил те же записи
+const ACTIVITY_ATTEMPTS: usize = 5;
+
+// Состояние диалога в ключе с TTL: state и last_input, как в записи пользователя
+#[derive(Serialize, Deserialize)]
+struct StoredState {
+    state: Option<String>,
+    last_input: Option<LastInput>,
+}
+
+// Хранилище в Redis (REDIS_URL). Все ключи пользователя лежат под {prefix}:user:{id}:
+// настройки - в самом ключе (JSON), состояние диалога - в {prefix}:user:{id}:state с TTL,
+// история доставки чата - в {prefix}:user:{id}:deliveries. Списки пользователей и чатов
+// с историей - множества {prefix}:users и {prefix}:delivery_chats.
+// Незавершенный диалог Redis забывает сам, настройки хранятся бессрочно. STORAGE_KEY здесь не применяется
+#[derive(Clone)]
+pub struct RedisStorage {
+    // Одно мультиплексированное соединение на процесс, переподключается само
+    conn: ConnectionManager,
+    // Для WATCH нужно свое соединение: отслеживание ключей привязано к соединению
+    client: redis::Client,
+    prefix: String,
+    state_ttl: Duration,
+}
+
+impl RedisStorage {
+    // Подключается по адресу вида redis://:password@host:6379/0 (rediss:// - с TLS)
+    pub async fn connect(url: &str, prefix: &str, state_ttl: Duration) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| format!("некорректный REDIS_URL: {}", e))?;
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(REDIS_TIMEOUT)
+            .set_response_timeout(REDIS_TIMEOUT);
+        let conn = ConnectionManager::new_with_config(client.clone(), config)
+            .await
+            .map_err(|e| format!("не удалось подключиться к Redis: {}", e))?;
+        Ok(RedisStorage { conn, client, prefix: prefix.to_string(), state_ttl })
+    }
+
+    fn user_key(&self, user_id: i64) -> String {
+        format!("{}:user:{}", self.prefix, user_id)
+    }
+
+    fn state_key(&self, user_id: i64) -> String {
+        format!("{}:user:{}:state", self.prefix, user_id)
+    }
+
+    fn deliveries_key(&self, chat_id: i64) -> String {
+        format!("{}:user:{}:deliveries", self.prefix, chat_id)
+    }
+
+    fn users_key(&self) -> String {
+        format!("{}:users", self.prefix)
+    }
+
+    fn delivery_chats_key(&self) -> String {
+        format!("{}:delivery_chats", self.prefix)
+    }
+
+    // Команды записи пользователя в транзакцию вызывающего: настройки, состояние диалога
+    // с TTL (пустое удаляется) и отметка в списке пользователей
+    fn queue_user(&self, pipe: &mut Pipeline, user: &UserSettings) -> Result<(), String> {
+        let (settings, dialogue) = split_dialogue(user)?;
+        pipe.set(self.user_key(user.user_id), settings.to_string())
+            .sadd(self.users_key(), user.user_id);
+        if dialogue.is_empty() {
+            pipe.del(self.state_key(user.user_id));
+        } else {
+            let state = StoredState { state: dialogue.state, last_input: dialogue.last_input };
+            let state = serde_json::to_string(&state).map_err(|e| e.to_string())?;
+            pipe.set_ex(self.state_key(user.user_id), state, self.state_ttl.as_secs());
+        }
+        Ok(())
+    }
+
+    fn queue_delete(&self, pipe: &mut Pipeline, user_id: i64) {
+        pipe.del(&[self.user_key(user_id), self.state_key(user_id)]).srem(self.users_key(), user_id);
+    }
+
+    async fn user_ids(&self) -> Result<Vec<i64>, String> {
+        let mut ids: Vec<i64> = self.conn.clone().smembers(self.users_key()).await.map_err(|e| e.to_string())?;
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    // Значения ключей по порядку, отсутствующие - None
+    async fn mget(&self, keys: Vec<String>) -> Result<Vec<Option<String>>, String> {
+        let mut values = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MGET_CHUNK) {
+            let part: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(chunk)
+                .query_async(&mut self.conn.clone())
+                .await
+                .map_err(|e| e.to_string())?;
+            values.extend(part);
+        }
+        Ok(values)
+    }
+
+    // Одна попытка записать всю пачку активности: ключи настроек отслеживаются WATCH, и если
+    // другой экземпляр бота изменил их между чтением и записью, EXEC ничего не применяет.
+    // Возвращает false, если попытку нужно повторить
+    async fn try_record_activity(&self, conn: &mut redis::aio::MultiplexedConnection, activity: &[Activity]) -> Result<bool, String> {
+        let keys: Vec<String> = activity.iter().map(|entry| self.user_key(entry.user_id)).collect();
+        redis::cmd("WATCH").arg(&keys).query_async::<()>(conn).await.map_err(|e| e.to_string())?;
+        let values: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(conn).await.map_err(|e| e.to_string())?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (entry, value) in activity.iter().zip(values) {
+            let mut user = match value.as_deref().map(|text| decode_user(entry.user_id, text, None)).transpose() {
+                Ok(user) => user.unwrap_or_else(|| UserSettings::new(entry.user_id)),
+                // Нечитаемую запись не затираем записью по умолчанию
+                Err(e) => {
+                    error!("Активность пользователя {} не записана: {}", entry.user_id, e);
+                    continue;
+                }
+            };
+            entry.apply(&mut user);
+            // Пишется только ключ настроек: состояние диалога и его TTL не трогаем
+            let (settings, _) = split_dialogue(&user)?;
+            pipe.set(self.user_key(entry.user_id), settings.to_string()).ignore()
+                .sadd(self.users_key(), entry.user_id).ignore();
+        }
+        let applied: Option<()> = pipe.query_async(conn).await.map_err(|e| e.to_string())?;
+        Ok(applied.is_some())
+    }
+}
+
+// Настройки и состояние из ключей в запись; запись приводится к текущей схеме, как при чтении файла
+fn decode_user(user_id: i64, settings: &str, state: Option<&str>) -> Result<UserSettings, String> {
+    let settings = serde_json::from_str(settings).map_err(|e| e.to_string())?;
+    let dialogue = match state {
+        Some(state) => {
+            let state: StoredState = serde_json::from_str(state).map_err(|e| e.to_string())?;
+            DialogueState { state: state.state, last_input: state.last_input }
+        }
+        None => DialogueState::default(),
+    };
+    merge_dialogue(settings, dialogue).map_err(|e| format!("запись пользователя {}: {}", user_id, e))
+}
+
+impl UserStorage for RedisStorage {
+    fn get_user(&self, user_id: i64) -> StorageFuture<'_, Result<Option<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let values = self
+                .mget(vec![self.user_key(user_id), self.state_key(user_id)])
+                .await
+                .map_err(StorageError::read)?;
+            match values.as_slice() {
+                [Some(settings), state] => decode_user(user_id, settings, state.as_deref()).map(Some).map_err(StorageError::read),
+                _ => Ok(None),
+            }
+        })
+    }
+
+    fn save_user(&self, user: UserSettings) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            self.queue_user(&mut pipe, &user).map_err(StorageError::write)?;
+            pipe.query_async::<()>(&mut self.conn.clone()).await.map_err(|e| {
+                error!("Ошибка сохранения пользователя {} в Redis: {}", user.user_id, e);
+                StorageError::write(e)
+            })
+        })
+    }
+
+    // Нечитаемая запись пропускается, как и при загрузке файла: остальные пользователи важнее
+    fn get_all_users(&self) -> StorageFuture<'_, Result<Vec<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let ids = self.user_ids().await.map_err(StorageError::read)?;
+            let keys = ids.iter().flat_map(|&user_id| [self.user_key(user_id), self.state_key(user_id)]).collect();
+            let values = self.mget(keys).await.map_err(StorageError::read)?;
+            Ok(ids
+                .iter()
+                .zip(values.chunks(2))
+                .filter_map(|(&user_id, pair)| match pair {
+                    [Some(settings), state] => decode_user(user_id, settings, state.as_deref())
+                        .inspect_err(|e| error!("Пропущена {}", e))
+                        .ok(),
+                    // Пользователь удален между чтением списка и записей
+                    _ => None,
+                })
+                .collect())
+        })
+    }
+
+    fn delete_user(&self, user_id: i64) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            self.queue_delete(&mut pipe, user_id);
+            pipe.query_async::<()>(&mut self.conn.clone()).await.map_err(StorageError::write)
+        })
+    }
+
+    // Вся пачка активности - одна транзакция WATCH/MULTI: одновременное сохранение настроек
+    // другим экземпляром бота не затирается, а при сбое пачка не применяется ни для кого
+    fn record_activity(&self, activity: Vec<Activity>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            if activity.is_empty() {
+                return Ok(());
+            }
+            let config = AsyncConnectionConfig::new()
+                .set_connection_timeout(REDIS_TIMEOUT)
+                .set_response_timeout(REDIS_TIMEOUT);
+            let mut conn = self
+                .client
+                .get_multiplexed_async_connection_with_config(&config)
+                .await
+                .map_err(StorageError::write)?;
+            for attempt in 1..=ACTIVITY_ATTEMPTS {
+                if self.try_record_activity(&mut conn, &activity).await.map_err(StorageError::write)? {
+                    return Ok(());
+                }
+                warn!("Записи пользователей изменились во время записи активности, попытка {}", attempt);
+            }
+            Err(StorageError::write("записи пользователей все время меняются другим экземпляром бота"))
+        })
+    }
+
+    fn load_deliveries(&self) -> StorageFuture<'_, Result<Vec<DeliveryRecord>, StorageError>> {
+        Box::pin(async move {
+            let mut chats: Vec<i64> = self.conn.clone().smembers(self.delivery_chats_key()).await.map_err(StorageError::read)?;
+            chats.sort_unstable();
+            let keys = chats.iter().map(|&chat_id| self.deliveries_key(chat_id)).collect();
+            let values = self.mget(keys).await.map_err(StorageError::read)?;
+            Ok(values
+                .iter()
+                .flatten()
+                .filter_map(|text| {
+                    serde_json::from_str::<Vec<DeliveryRecord>>(text)
+                        .inspect_err(|e| error!("Пропущена история доставки чата: {}", e))
+                        .ok()
+                })
+                .flatten()
+                .collect())
+        })
+    }
+
+    fn save_deliveries(&self, chats: Vec<(i64, Vec<DeliveryRecord>)>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for (chat_id, records) in chats {
+                if records.is_empty() {
+                    pipe.del(self.deliveries_key(chat_id)).srem(self.delivery_chats_key(), chat_id);
+                    continue;
+                }
+                let json = serde_json::to_string(&records).map_err(StorageError::write)?;
+                pipe.set(self.deliveries_key(chat_id), json).sadd(self.delivery_chats_key(), chat_id);
+            }
+            pipe.query_async::<()>(&mut self.conn.clone()).await.map_err(|e| {
+                error!("Ошибка сохранения истории доставки в Redis: {}", e);
+                StorageError::write(e)
+            })
+        })
+    }
+
+    // Все записи заменяются одной транзакцией MULTI: при сбое остаются прежние
+    fn replace_all(&self, users: Vec<UserSettings>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let old_ids = self.user_ids().await.map_err(StorageError::write)?;
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for user_id in old_ids {
+                self.queue_delete(&mut pipe, user_id);
+            }
+            for user in &users {
+                self.queue_user(&mut pipe, user).map_err(StorageError::write)?;
+            }
+            pipe.query_async::<()>(&mut self.conn.clone()).await.map_err(StorageError::write)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery_history::{DeliveryStatus, NotificationKind};
+    use chrono::Utc;
+
+    fn delivery(chat_id: i64) -> DeliveryRecord {
+        DeliveryRecord {
+            chat_id,
+            kind: NotificationKind::Daily,
+            slot: Utc::now(),
+            status: DeliveryStatus::Sent,
+            at: Utc::now(),
+            error: None,
+        }
+    }
+
+    // Нужен сервер Redis, запуск: TEST_REDIS_URL=redis://localhost:6379/15 cargo test -- --ignored.
+    // Ключи теста лежат под своим префиксом и удаляются в конце
+    #[tokio::test]
+    #[ignore = "нужен сервер Redis в TEST_REDIS_URL"]
+    async fn stores_users_states_and_history() {
+        let url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL не задан");
+        let prefix = format!("ferrisbot-test-{}", std::process::id());
+        let storage = RedisStorage::connect(&url, &prefix, Duration::from_secs(60)).await.unwrap();
+
+        let mut user = UserSettings::new(1);
+        user.set_city("Казань");
+        user.state = Some("waiting_for_city".to_string());
+        user.last_input = Some(LastInput { message_id: 7, state: "waiting_for_city".to_string() });
+        storage.save_user(user).await.unwrap();
+        storage.save_user(UserSettings::new(2)).await.unwrap();
+        storage.delete_user(2).await.unwrap();
+        let activity = Activity { user_id: 3, last_seen: Utc::now(), commands: 2 };
+        storage.record_activity(vec![activity]).await.unwrap();
+        // Активность пользователя посреди диалога меняет только счетчики: город и состояние остаются
+        let activity = Activity { user_id: 1, last_seen: Utc::now(), commands: 1 };
+        storage.record_activity(vec![activity]).await.unwrap();
+        storage.save_deliveries(vec![(1, vec![delivery(1), delivery(1)]), (3, vec![delivery(3)])]).await.unwrap();
+        storage.save_deliveries(vec![(3, Vec::new())]).await.unwrap();
+
+        let users = storage.get_all_users().await.unwrap();
+        assert_eq!(users.iter().map(|user| user.user_id).collect::<Vec<_>>(), vec![1, 3]);
+        let user = storage.get_user(1).await.unwrap().unwrap();
+        assert_eq!(user.city(), Some("Казань"));
+        assert_eq!(user.state.as_deref(), Some("waiting_for_city"));
+        assert_eq!(user.last_input.as_ref().map(|input| input.message_id), Some(7));
+        assert_eq!(user.commands_used, 1);
+        assert_eq!(storage.get_user(3).await.unwrap().unwrap().commands_used, 2);
+        assert_eq!(storage.load_deliveries().await.unwrap().len(), 2);
+
+        // Состояние диалога живет не дольше TTL, настройки - бессрочно
+        let mut conn = storage.conn.clone();
+        let state_ttl: i64 = conn.ttl(storage.state_key(1)).await.unwrap();
+        assert!((1..=60).contains(&state_ttl));
+        let settings_ttl: i64 = conn.ttl(storage.user_key(1)).await.unwrap();
+        assert_eq!(settings_ttl, -1);
+
+        // Истекшее состояние - просто диалог без ожидания ввода
+        let _: () = conn.del(storage.state_key(1)).await.unwrap();
+        let user = storage.get_user(1).await.unwrap().unwrap();
+        assert!(user.state.is_none() && user.last_input.is_none());
+        assert_eq!(user.city(), Some("Казань"));
+
+        storage.replace_all(vec![UserSettings::new(5)]).await.unwrap();
+        assert_eq!(storage.get_all_users().await.unwrap().len(), 1);
+        assert!(storage.get_user(1).await.unwrap().is_none());
+
+        storage.replace_all(Vec::new()).await.unwrap();
+        storage.save_deliveries(vec![(1, Vec::new())]).await.unwrap();
+        let left: Vec<String> = conn.keys(format!("{}:*", prefix)).await.unwrap();
+        assert!(left.is_empty(), "остались ключи {:?}", left);
+    }
+}
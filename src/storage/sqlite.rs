@@ -0,0 +1,383 @@
+use super::{migrate_record, StorageError, StorageFuture, UserSettings, UserStorage};
+use crate::activity::Activity;
+use crate::delivery_history::DeliveryRecord;
+use crate::encryption::{self, StorageCipher};
+use chrono::Utc;
+use log::{error, info};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Схема базы по версиям: номер примененной версии хранится в PRAGMA user_version,
+// при открытии базы применяются только новые шаги. Шаги не меняются задним числом, только добавляются
+const MIGRATIONS: &[&str] = &[
+    // 1: настройки пользователя - запись UserSettings в JSON, история доставки - по строке на запись
+    "CREATE TABLE users (
+        user_id INTEGER PRIMARY KEY,
+        settings TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE deliveries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        chat_id INTEGER NOT NULL,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX deliveries_chat_id ON deliveries (chat_id);",
+];
+
+// Сколько ждать, пока другой процесс (например, команда users) отпустит базу
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Хранилище в SQLite (STORAGE_BACKEND=sqlite). Каждое сохранение - одна транзакция
+// с одной записью, а не перезапись всего файла, поэтому подходит для большой базы.
+// С заданным STORAGE_KEY настройки и история доставки шифруются так же, как в файловом хранилище
+#[derive(Clone)]
+pub struct SqliteStorage {
+    // rusqlite синхронный: запросы выполняются в spawn_blocking, соединение одно на процесс
+    conn: Arc<Mutex<Connection>>,
+    path: String,
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl SqliteStorage {
+    // Открывает или создает базу и приводит схему к последней версии.
+    // key - значение STORAGE_KEY, как у JsonStorage
+    pub async fn open(path: &str, key: Option<&str>) -> Result<Self, String> {
+        let cipher = key.map(StorageCipher::new).transpose()?;
+        let db_path = path.to_string();
+        let conn = tokio::task::spawn_blocking(move || open_connection(&db_path))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("не удалось открыть базу SQLite {}: {}", path, e))?;
+
+        let storage = SqliteStorage {
+            conn: Arc::new(Mutex::new(conn)),
+            path: path.to_string(),
+            cipher: cipher.map(Arc::new),
+        };
+        storage.check_key().await?;
+        Ok(storage)
+    }
+
+    // Ключ проверяем по первой записи: с неверным ключом или без него все записи оказались бы
+    // нечитаемыми, и бот работал бы так, будто пользователей нет
+    async fn check_key(&self) -> Result<(), String> {
+        let first = self
+            .run(|conn| {
+                conn.query_row("SELECT user_id, settings FROM users LIMIT 1", [], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+                    .optional()
+            })
+            .await?;
+        match first {
+            Some((user_id, text)) if encryption::is_sealed(&text) => decode_user(&text, self.cipher.as_deref(), user_id)
+                .map(|_| ())
+                .map_err(|e| format!("{}: {}", self.path, e)),
+            _ => Ok(()),
+        }
+    }
+
+    // Копия базы одним файлом (VACUUM INTO): согласованная, даже если бот в это время пишет
+    pub async fn backup_to(&self, target: &str) -> Result<(), String> {
+        let target = target.to_string();
+        self.run(move |conn| conn.execute("VACUUM INTO ?1", params![target]).map(|_| ()))
+            .await
+            .map_err(|e| format!("не удалось скопировать базу {}: {}", self.path, e))
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut conn).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    // JSON записи для столбца: с ключом - зашифрованный
+    fn seal(&self, value: &impl serde::Serialize) -> Result<String, StorageError> {
+        let json = serde_json::to_string(value).map_err(|e| {
+            error!("Ошибка сериализации данных: {}", e);
+            StorageError::write(e)
+        })?;
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&json).map_err(|e| {
+                error!("Ошибка шифрования данных: {}", e);
+                StorageError::write(e)
+            }),
+            None => Ok(json),
+        }
+    }
+}
+
+fn open_connection(path: &str) -> rusqlite::Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // WAL: чтение не ждет записи, а запись переживает сбой посреди транзакции
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    migrate_schema(&mut conn)?;
+    Ok(conn)
+}
+
+fn migrate_schema(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+        info!("Схема базы SQLite обновлена до версии {}", index + 1);
+    }
+    Ok(())
+}
+
+// Текст столбца в значение: зашифрованный расшифровывается, запись пользователя
+// приводится к текущей схеме так же, как при чтении файла
+fn open_value(text: &str, cipher: Option<&StorageCipher>, label: &str) -> Result<Value, String> {
+    let text = encryption::open_content(text, cipher, label)?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+fn decode_user(text: &str, cipher: Option<&StorageCipher>, user_id: i64) -> Result<UserSettings, String> {
+    let label = format!("запись пользователя {}", user_id);
+    open_value(text, cipher, &label).and_then(migrate_record)
+}
+
+fn upsert_user(tx: &Transaction, user_id: i64, settings: &str) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO users (user_id, settings, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT (user_id) DO UPDATE SET settings = excluded.settings, updated_at = excluded.updated_at",
+        params![user_id, settings, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+impl UserStorage for SqliteStorage {
+    fn get_user(&self, user_id: i64) -> StorageFuture<'_, Result<Option<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let text = self
+                .run(move |conn| {
+                    conn.query_row("SELECT settings FROM users WHERE user_id = ?1", params![user_id], |row| row.get::<_, String>(0))
+                        .optional()
+                })
+                .await
+                .map_err(StorageError::read)?;
+            text.map(|text| decode_user(&text, self.cipher.as_deref(), user_id))
+                .transpose()
+                .map_err(StorageError::read)
+        })
+    }
+
+    fn save_user(&self, user: UserSettings) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let settings = self.seal(&user)?;
+            let user_id = user.user_id;
+            self.run(move |conn| {
+                let tx = conn.transaction()?;
+                upsert_user(&tx, user_id, &settings)?;
+                tx.commit()
+            })
+            .await
+            .map_err(|e| {
+                error!("Ошибка сохранения пользователя {} в SQLite: {}", user_id, e);
+                StorageError::write(e)
+            })
+        })
+    }
+
+    // Нечитаемая запись пропускается, как и при загрузке файла: остальные пользователи важнее
+    fn get_all_users(&self) -> StorageFuture<'_, Result<Vec<UserSettings>, StorageError>> {
+        Box::pin(async move {
+            let rows = self
+                .run(|conn| {
+                    let mut statement = conn.prepare("SELECT user_id, settings FROM users ORDER BY user_id")?;
+                    let rows = statement
+                        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+                    Ok(rows)
+                })
+                .await
+                .map_err(StorageError::read)?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|(user_id, text)| match decode_user(&text, self.cipher.as_deref(), user_id) {
+                    Ok(user) => Some(user),
+                    Err(e) => {
+                        error!("Пропущена запись пользователя {}: {}", user_id, e);
+                        None
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn delete_user(&self, user_id: i64) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            self.run(move |conn| conn.execute("DELETE FROM users WHERE user_id = ?1", params![user_id]).map(|_| ()))
+                .await
+                .map_err(StorageError::write)
+        })
+    }
+
+    // Вся пачка активности - одна транзакция
+    fn record_activity(&self, activity: Vec<Activity>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let cipher = self.cipher.clone();
+            let storage = self.clone();
+            self.run(move |conn| {
+                let tx = conn.transaction()?;
+                for entry in activity {
+                    let text: Option<String> = tx
+                        .query_row("SELECT settings FROM users WHERE user_id = ?1", params![entry.user_id], |row| row.get(0))
+                        .optional()?;
+                    let mut user = match text {
+                        Some(text) => match decode_user(&text, cipher.as_deref(), entry.user_id) {
+                            Ok(user) => user,
+                            // Нечитаемую запись не затираем записью по умолчанию
+                            Err(e) => {
+                                error!("Активность пользователя {} не записана: {}", entry.user_id, e);
+                                continue;
+                            }
+                        },
+                        None => UserSettings::new(entry.user_id),
+                    };
+                    entry.apply(&mut user);
+                    let settings = storage.seal(&user).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                    upsert_user(&tx, user.user_id, &settings)?;
+                }
+                tx.commit()
+            })
+            .await
+            .map_err(StorageError::write)
+        })
+    }
+
+    fn load_deliveries(&self) -> StorageFuture<'_, Result<Vec<DeliveryRecord>, StorageError>> {
+        Box::pin(async move {
+            let rows = self
+                .run(|conn| {
+                    let mut statement = conn.prepare("SELECT record FROM deliveries ORDER BY id")?;
+                    let rows = statement.query_map([], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+                    Ok(rows)
+                })
+                .await
+                .map_err(StorageError::read)?;
+            Ok(rows
+                .iter()
+                .filter_map(|text| {
+                    open_value(text, self.cipher.as_deref(), "история доставки")
+                        .and_then(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+                        .inspect_err(|e| error!("Пропущена запись истории доставки: {}", e))
+                        .ok()
+                })
+                .collect())
+        })
+    }
+
+    fn save_deliveries(&self, chats: Vec<(i64, Vec<DeliveryRecord>)>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let mut rows = Vec::with_capacity(chats.len());
+            for (chat_id, records) in chats {
+                let sealed = records.iter().map(|record| self.seal(record)).collect::<Result<Vec<_>, _>>()?;
+                rows.push((chat_id, sealed));
+            }
+            self.run(move |conn| {
+                let tx = conn.transaction()?;
+                for (chat_id, records) in rows {
+                    tx.execute("DELETE FROM deliveries WHERE chat_id = ?1", params![chat_id])?;
+                    for record in records {
+                        tx.execute("INSERT INTO deliveries (chat_id, record) VALUES (?1, ?2)", params![chat_id, record])?;
+                    }
+                }
+                tx.commit()
+            })
+            .await
+            .map_err(|e| {
+                error!("Ошибка сохранения истории доставки в SQLite: {}", e);
+                StorageError::write(e)
+            })
+        })
+    }
+
+    // Все записи заменяются в одной транзакции: при сбое остаются прежние
+    fn replace_all(&self, users: Vec<UserSettings>) -> StorageFuture<'_, Result<(), StorageError>> {
+        Box::pin(async move {
+            let rows = users
+                .iter()
+                .map(|user| self.seal(user).map(|settings| (user.user_id, settings)))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.run(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute("DELETE FROM users", [])?;
+                for (user_id, settings) in rows {
+                    upsert_user(&tx, user_id, &settings)?;
+                }
+                tx.commit()
+            })
+            .await
+            .map_err(StorageError::write)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery_history::{DeliveryStatus, NotificationKind};
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn delivery(chat_id: i64) -> DeliveryRecord {
+        DeliveryRecord {
+            chat_id,
+            kind: NotificationKind::Daily,
+            slot: Utc::now(),
+            status: DeliveryStatus::Sent,
+            at: Utc::now(),
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_users_activity_and_history() {
+        let dir = std::env::temp_dir().join(format!("ferrisbot-sqlite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("users.db").to_string_lossy().into_owned();
+
+        let storage = SqliteStorage::open(&path, Some(KEY)).await.unwrap();
+        let mut user = UserSettings::new(1);
+        user.set_city("Казань");
+        storage.save_user(user).await.unwrap();
+        storage.save_user(UserSettings::new(2)).await.unwrap();
+        storage.delete_user(2).await.unwrap();
+        let activity = Activity { user_id: 3, last_seen: Utc::now(), commands: 2 };
+        storage.record_activity(vec![activity]).await.unwrap();
+        storage.save_deliveries(vec![(1, vec![delivery(1), delivery(1)]), (3, vec![delivery(3)])]).await.unwrap();
+        storage.save_deliveries(vec![(3, Vec::new())]).await.unwrap();
+
+        // Заново открытая база с тем же ключом видит все изменения
+        let storage = SqliteStorage::open(&path, Some(KEY)).await.unwrap();
+        let users = storage.get_all_users().await.unwrap();
+        assert_eq!(users.iter().map(|user| user.user_id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(storage.get_user(1).await.unwrap().unwrap().city(), Some("Казань"));
+        assert_eq!(storage.get_user(3).await.unwrap().unwrap().commands_used, 2);
+        let deliveries = storage.load_deliveries().await.unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert!(deliveries.iter().all(|record| record.chat_id == 1));
+
+        // Без ключа или с чужим ключом база не открывается, а не выглядит пустой
+        assert!(SqliteStorage::open(&path, None).await.is_err());
+        assert!(SqliteStorage::open(&path, Some(&"ff".repeat(32))).await.is_err());
+
+        storage.replace_all(vec![UserSettings::new(5)]).await.unwrap();
+        assert_eq!(storage.get_all_users().await.unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+
+// Блокировка файла хранилища на время работы бота. Бот держит данные в памяти и при следующей
+// записи затер бы правки офлайн-команд CLI, поэтому бот и команды, меняющие хранилище, не работают
+// с одним файлом одновременно. Блокировку держит операционная система: после падения процесса
+// она снимается сама, а оставшийся файл *.lock ничего не блокирует
+pub struct StorageLock {
+    _file: File,
+}
+
+fn lock_path(storage_path: &str) -> String {
+    format!("{}.lock", storage_path)
+}
+
+// Захватывает хранилище; owner - кто его держит, записывается в файл для сообщения об ошибке
+pub fn acquire(storage_path: &str, owner: &str) -> Result<StorageLock, String> {
+    let path = lock_path(storage_path);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| format!("не удалось открыть файл блокировки {}: {}", path, e))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = std::fs::read_to_string(&path).unwrap_or_default();
+            let holder = holder.trim();
+            return Err(format!(
+                "хранилище {} сейчас занято{}. Остановите бота перед правкой хранилища из командной строки",
+                storage_path,
+                if holder.is_empty() { String::new() } else { format!(" ({})", holder) }
+            ));
+        }
+        Err(TryLockError::Error(e)) => return Err(format!("не удалось заблокировать {}: {}", path, e)),
+    }
+
+    let _ = file.set_len(0).and_then(|_| write!(file, "{}, PID {}", owner, std::process::id()));
+    Ok(StorageLock { _file: file })
+}
@@ -0,0 +1,338 @@
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{LazyLock, RwLock};
+use std::time::SystemTime;
+use tokio::time::{self, Duration};
+
+// Как часто проверяем каталог шаблонов на изменения
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+// Встроенные тексты сообщений (MarkdownV2). Любой из них можно переопределить
+// в файлах *.toml каталога шаблонов, например:
+//   city.set = "🌆 Готово, город {city} сохранен"
+// Подстановки вида {city} заменяются значениями при отправке
+const DEFAULTS: &[(&str, &str)] = &[
+    ("start", "📱 *Добро пожаловать в {bot_name}\\!*\n\n\
+        Я твой персональный бот\\-помощник с погодой\\! \
+        Каждое утро я буду отправлять тебе актуальный прогноз погоды в указанное время\\.\n\n\
+        *Что я умею:*\n\
+        • 🌦️ Отправлять ежедневный прогноз погоды в твоем городе\n\
+        • 🕒 Автоматически присылать прогноз в указанное время\n\
+        • 🔍 Предоставлять прогноз по запросу в любое время\n\n\
+        *Для начала работы:*\n\
+        1️⃣ Сначала установи свой город командой /city\n\
+        2️⃣ Затем установи время уведомлений: /time\n\
+        3️⃣ Готово\\! Бот будет присылать прогноз погоды по расписанию\n\n\
+        *Важно:* При вводе команд /city и /time можно выбрать вариант из меню или ввести значение вручную\\.\n\n\
+        *Другие команды:*\n\
+        /weather \\- получить текущий прогноз погоды\n\
+        /forecast \\- получить прогноз погоды на неделю\n\
+        /help \\- показать список всех команд"),
+    ("start.hint", "👉 Пожалуйста, начните с установки вашего города командой /city"),
+    ("help", "🌟 *Доступные команды:*\n\n\
+        /start \\- начать работу с ботом\n\
+        /help \\- показать это сообщение\n\
+        /city \\- выбрать город из списка или ввести вручную\n\
+        /cities \\- несколько городов в одном прогнозе\n\
+        /time \\- выбрать время уведомлений из списка или ввести вручную\n\
+        /weather \\- узнать текущую погоду, /weather all \\- во всех сохраненных городах\n\
+        /forecast \\- получить прогноз погоды на неделю, /forecast table \\- таблицей\n\
+        /card \\- текущая погода картинкой\n\
+        /comparechart \\- температура в недавних городах на одном графике\n\
+        /records \\- самый теплый и самый холодный день в городе\n\
+        /travel \\- временно сменить город на время поездки\n\
+        /updates \\- сообщать, если прогноз на день изменился\n\
+        /smarttime \\- присылать прогноз раньше, если ожидается непогода\n\
+        /every \\- прогноз каждые N часов в рабочее время\n\
+        /nightmode \\- вечером присылать прогноз на завтра\n\
+        /sections \\- выбрать разделы отчета о погоде\n\
+        /style \\- обычный или компактный отчет о погоде\n\
+        /precision \\- целые градусы или с десятыми\n\
+        /locale \\- формат дат и времени в отчетах\n\
+        /settings \\- все настройки на одном экране\n\
+        /privacy \\- что бот хранит о тебе\n\
+        /export \\- прислать все, что бот хранит о тебе, JSON\\-файлом\n\
+        /delete\\_me \\- удалить все, что бот хранит о тебе\n\
+        /history \\- последние уведомления и доставлены ли они\n\
+        /transfer \\- перенести настройки в другой аккаунт Telegram\n\
+        /household \\- общий утренний прогноз для семьи\n\n\
+        *Совет:* Команды /city и /time без параметров покажут интерактивное меню для выбора\\!"),
+    ("help.cute", "✨ *Доступные команды:*\n\n\
+        /start \\- начать работу с ботом\n\
+        /help \\- показать это сообщение\n\
+        /city \\- выбрать город из списка или ввести вручную\n\
+        /cities \\- несколько городов в одном прогнозе\n\
+        /time \\- выбрать время уведомлений из списка или ввести вручную\n\
+        /weather \\- узнать текущую погоду, /weather all \\- во всех сохраненных городах\n\
+        /forecast \\- получить прогноз погоды на неделю 💖, /forecast table \\- таблицей\n\
+        /card \\- текущая погода картинкой 🖼\n\
+        /comparechart \\- температура в недавних городах на одном графике 📈\n\
+        /records \\- самый теплый и самый холодный день в городе 🏆\n\
+        /travel \\- временно сменить город на время поездки ✈️\n\
+        /updates \\- сообщать, если прогноз на день изменился\n\
+        /smarttime \\- присылать прогноз раньше, если ожидается непогода\n\
+        /every \\- прогноз каждые N часов в рабочее время\n\
+        /nightmode \\- вечером присылать прогноз на завтра\n\
+        /sections \\- выбрать разделы отчета о погоде\n\
+        /style \\- обычный или компактный отчет о погоде\n\
+        /precision \\- целые градусы или с десятыми\n\
+        /locale \\- формат дат и времени в отчетах\n\
+        /settings \\- все настройки на одном экране\n\
+        /privacy \\- что бот хранит о тебе\n\
+        /export \\- прислать все, что бот хранит о тебе, JSON\\-файлом\n\
+        /delete\\_me \\- удалить все, что бот хранит о тебе\n\
+        /history \\- последние уведомления и доставлены ли они\n\
+        /transfer \\- перенести настройки в другой аккаунт Telegram\n\
+        /household \\- общий утренний прогноз для семьи\n\n\
+        *Совет:* Команды /city и /time без параметров покажут интерактивное меню для выбора\\!"),
+    ("city.choose", "🏙️ *Выберите город из списка или введите его вручную*\n\nДля ручного ввода используйте команду /city \\[название города\\]"),
+    ("city.manual", "🏙️ *Ввод города вручную*\n\nПожалуйста, напишите название вашего города\\.\n\nПримеры: *Москва*, *Санкт\\-Петербург*, *Новосибирск*"),
+    ("city.set", "🌆 *Город успешно установлен:* {city}\n\nВы можете:\n• Узнать текущую погоду с помощью /weather\n• Установить время для ежедневных уведомлений командой /time"),
+    ("city.set_cute", "🌆 *Город успешно установлен:* {city}\n\nТеперь ты можешь:\n• Узнать текущую погоду с помощью /weather\n• Установить время для ежедневных уведомлений командой /time"),
+    ("city.empty", "⚠️ *Название города не может быть пустым*\n\nПожалуйста, введите корректное название населенного пункта\\."),
+    ("provider.unsupported", "🚫 Источник погоды {provider} не дает данных для этой команды\\."),
+    ("input.blocked", "🚫 Такой текст я не могу сохранить\\. Пожалуйста, введите другое название\\."),
+    ("input.too_long", "✂️ Слишком длинный текст: можно не больше {max} символов\\. Попробуйте короче\\."),
+    ("cities.list", "🏙 *Города прогноза*\n\n{cities}\n\nВ /weather и ежедневном уведомлении погода по всем городам приходит одним сообщением\\.\n\n`/cities add Город` \\- добавить, `/cities remove Город` \\- убрать, `/cities main Город` \\- сделать основным"),
+    ("cities.empty", "🏙 Города прогноза еще не выбраны\\. Основной город задается командой /city, дополнительные \\- `/cities add Город`\\."),
+    ("cities.usage", "🏙 *Города прогноза*\n\n/cities \\- список\n`/cities add Город` \\- добавить\n`/cities remove Город` \\- убрать\n`/cities main Город` \\- сделать основным"),
+    ("cities.added", "✅ Город {city} добавлен в прогноз\\."),
+    ("cities.exists", "ℹ️ Город {city} уже есть в прогнозе\\."),
+    ("cities.full", "⚠️ В прогнозе уже {max} городов \\- больше не поместится\\. Сначала можно убрать один: `/cities remove Город`\\."),
+    ("cities.removed", "🗑 Город {city} убран из прогноза\\."),
+    ("cities.unknown", "⚠️ Города {city} нет в прогнозе\\."),
+    ("cities.main", "🏠 Основной город: {city}\\. По нему считаются /forecast и часовой пояс уведомлений\\."),
+    ("city.missing", "⚠️ *Город не установлен*\n\nПожалуйста, используй команду /city, чтобы установить город\\. Посмотреть, как выглядит отчет, можно командой `/weather Демо`\\."),
+    ("time.choose", "⏰ *Выберите время ежедневных уведомлений о погоде*\n\nДля ручного ввода используйте команду /time \\[ЧЧ:ММ\\] или /time утром\\|днём\\|вечером"),
+    ("time.manual", "⏰ *Ввод времени вручную*\n\nПожалуйста, напишите время в формате ЧЧ:ММ, например: *08:30*\n\nДопустимое время: от 00:00 до 23:59"),
+    ("time.set", "⏰ *Время уведомлений установлено:* {time}\n\nТеперь каждый день в это время вы будете получать актуальный прогноз погоды\\."),
+    ("time.set_cute", "⏰ *Время уведомлений установлено:* {time}\n\nТеперь каждый день в это время я буду отправлять тебе прогноз погоды и милое сообщение\\! 💖"),
+    ("time.rounded", "ℹ️ Уведомления отправляются с шагом {step} мин, поэтому вместо {time} выбрано ближайшее время\\."),
+    ("time.window", "⏰ *Уведомления будут приходить {window}*\n\nТочную минуту внутри окна я выбрал сам: {time}\\. Если важна точность, укажите время командой /time ЧЧ:ММ\\."),
+    ("time.preview", "👀 *Вот так будет выглядеть ваш прогноз:*\n\n{message}\n\nВид отчета можно поменять до первой рассылки: /style, /sections, /precision или все сразу в /settings\\."),
+    ("timezone.inferred", "🕒 Уведомления будут приходить по местному времени города: {offset}\\."),
+    ("timezone.ask", "🕒 *По какому времени присылать уведомления?*\n\nВ городе {city} часовой пояс {offset}, а время уведомлений {time} сейчас считается по {current}\\."),
+    ("timezone.set", "🕒 Уведомления в {time} будут приходить по местному времени: {offset}\\."),
+    ("timezone.kept", "🕒 Время уведомлений по\\-прежнему считается по {offset}\\."),
+    ("time.invalid", "⚠️ *Некорректный формат времени*\n\nПожалуйста, введите время в формате ЧЧ:ММ \\(например: 08:30\\)\\.\n\nДопустимое время: от 00:00 до 23:59"),
+    ("setup.required", "⚠️ *Требуется настройка*\n\nПожалуйста, настрой бота с помощью команды /city\\. Посмотреть, как выглядит отчет, можно командой `/weather Демо`\\."),
+    ("weather.header", "🌦️ *Погода в {city}*\n\n{weather}"),
+    ("weather.all_city", "🌦️ *{city}*\n\n{weather}"),
+    ("weather.all_failed", "⚠️ *{city}*: не удалось получить погоду \\({error}\\)"),
+    ("weather.all_footer", "⏱ Собрано за {seconds} с"),
+    ("weather.header_cute", "💖 *Специально для тебя, погода в {city}*\n\n{weather}"),
+    ("rules.alert", "⚠️ *Предупреждение: {city}*\n\n{warnings}"),
+    ("handler.timeout", "⏳ Сервис отвечает медленно, попробуй позже\\."),
+    ("storage.read_failed", "💾 Не удалось загрузить твои настройки, попробуй позже\\."),
+    ("storage.save_failed", "💾 Не удалось сохранить изменения, попробуй позже\\."),
+    ("weather.error", "❌ *Не удалось получить погоду:*\n{error}\n\nПроверь правильность названия города или попробуй позже\\."),
+    ("forecast.header", "🗓 *Прогноз погоды на неделю в {city}*\n\n{forecast}"),
+    ("forecast.header_cute", "✨ *Прогноз погоды на неделю в {city}*\n\nСпециально для тебя я подготовил\\(а\\) детальный прогноз:\n\n{forecast}"),
+    ("card.caption", "{emoji} *{city}*: {weather}"),
+    ("comparechart.few", "📈 *Сравнение городов*\n\nНа графике сравниваются недавние города, а их пока меньше двух\\. Выберите еще город командой /city, и можно будет сравнить\\."),
+    ("comparechart.caption", "📈 *Температура на сутки:* {cities}"),
+    ("comparechart.failed", "\n⚠️ Нет данных для: {cities}"),
+    ("forecast.usage", "🗓 *Прогноз на неделю*\n\n/forecast \\- по дням с описанием погоды\n/forecast table \\- таблицей, чтобы удобно сравнить дни"),
+    ("forecast.error", "❌ *Не удалось получить прогноз:*\n{error}\n\nПроверь правильность названия города или попробуй позже\\."),
+    ("cute.on", "💕 *Милый режим активирован\\!*\n\nТеперь бот будет отправлять тебе милые сообщения и пожелания\\. Твой персональный бот\\-помощник всегда рядом\\!"),
+    ("cute.off", "🔄 Стандартный режим активирован\\. Бот будет отправлять только информативные сообщения о погоде\\."),
+    ("smalltalk.thanks", "🥰 Всегда пожалуйста\\! Обращайся, я слежу за погодой для тебя 💖"),
+    ("smalltalk.morning", "☀️ Доброе утро\\! Хочешь узнать, что там за окном? Жми /weather 💕"),
+    ("smalltalk.night", "🌙 Сладких снов\\! Утром я расскажу, какая будет погода ✨"),
+    ("smalltalk.how_are_you", "😊 У меня все отлично, особенно когда ты пишешь\\! А погоду можно узнать через /weather 🌤"),
+    ("smalltalk.hello", "👋 Привет\\-привет\\! Рад\\(а\\) тебя видеть 💖 Погода ждет тебя по команде /weather"),
+    ("travel.usage", "✈️ *Режим поездки*\n\nУкажите город и дату возвращения, например:\n/travel Сочи 25\\.12\n/travel Казань 3d\n/travel Тула до понедельника\n\nДо этой даты ежедневные уведомления будут приходить для города поездки\\."),
+    ("travel.set", "✈️ *Хорошей поездки\\!*\n\nДо {until} включительно уведомления будут приходить для города {city}\\. Потом они снова будут приходить для {home}\\.\n\nОтменить раньше: /travel off"),
+    ("travel.status", "✈️ Сейчас действует режим поездки: {city} до {until}\\.\n\nОтменить: /travel off"),
+    ("travel.cancelled", "🏠 Режим поездки выключен, уведомления снова приходят для {home}\\."),
+    ("travel.ended", "🏠 *С возвращением\\!*\n\nПоездка закончилась, уведомления снова приходят для {home}\\."),
+    ("travel.invalid", "⚠️ *Не получилось включить режим поездки:* {error}\\.\n\nПример: /travel Сочи 25\\.12"),
+    ("updates.usage", "🔄 *Обновления прогноза* сейчас {status}\\.\n\nЕсли после утреннего уведомления прогноз на вторую половину дня заметно изменится, я пришлю короткое сообщение\\.\n\n/updates on \\- включить, /updates off \\- выключить"),
+    ("updates.on", "🔄 Готово\\! Если днем прогноз заметно изменится, я сообщу\\."),
+    ("updates.off", "🔕 Сообщения об обновлении прогноза выключены\\."),
+    ("updates.changed", "🔄 *Прогноз для {city} обновился*\n\n{changes}"),
+    ("errors.repeated", "🤐 Ошибка повторяется — не буду повторяться, попробую позже\\."),
+    ("smart.usage", "🧠 *Умное время* сейчас {status}\\.\n\nЕсли в ближайшие часы ожидаются осадки, гроза или сильный ветер, я пришлю прогноз на час раньше обычного, чтобы вы успели поменять планы\\. Ночью с 23:00 до 07:00 заранее не пишу\\.\n\n/smarttime on \\- включить, /smarttime off \\- выключить"),
+    ("smart.on", "🧠 Умное время включено\\! В непогоду прогноз придет на час раньше\\."),
+    ("smart.off", "⏰ Умное время выключено, прогноз будет приходить точно по расписанию\\."),
+    ("smart.early", "🧠 Присылаю прогноз на час раньше: ожидается {reason}\\."),
+    ("records.report", "📊 *Рекорды: {city}*\n\n🔥 Самый теплый день: {max_date}, до {max}\n🥶 Самый холодный день: {min_date}, до {min}\n\nПо данным бота с {since}, наблюдения за {days}"),
+    ("records.empty", "📊 По городу {city} пока нет истории\\. Рекорды появятся, когда бот несколько раз получит погоду для него\\."),
+    ("every.usage", "⏱ *Прогноз по интервалу*\n\nПрисылаю текущую погоду каждые N часов в заданные часы, в дополнение к обычному времени уведомлений\\.\n\nПример: `/every каждые 3 часа с 09 до 18` или коротко `/every 3 9 18`\n/every off \\- выключить"),
+    ("every.status", "⏱ *Прогноз по интервалу:* каждые {every} ч, {range}\n\nВремя отправки: {hours}\n\n/every off \\- выключить"),
+    ("every.set", "✅ Буду присылать погоду каждые {every} ч, {range}\n\nВремя отправки: {hours}"),
+    ("every.off", "⏱ Прогноз по интервалу выключен\\."),
+    ("every.invalid", "⚠️ Не получилось разобрать расписание: {error}\n\nПример: `/every каждые 3 часа с 09 до 18`"),
+    ("night.usage", "🌙 *Ночной режим* сейчас {status}\\.\n\nЕсли уведомление приходит в {hour}:00 или позже, в нем будет прогноз на завтра, а не погода уходящего дня\\.\n\n/nightmode on \\- включить, /nightmode off \\- выключить"),
+    ("night.on", "🌙 Готово\\! Уведомления после {hour}:00 будут рассказывать о завтрашнем дне\\."),
+    ("night.off", "☀️ Ночной режим выключен, в уведомлениях будет текущая погода в любое время\\."),
+    ("extras.usage", "💬 *Утренние дополнения* сейчас {status}\\.\n\nВ милом режиме к утреннему сообщению добавляется цитата или гороскоп дня\\.\n\n/extras on \\- включить, /extras off \\- выключить"),
+    ("extras.on", "💬 Готово\\! Утром в милом режиме добавлю цитату или гороскоп дня\\."),
+    ("extras.off", "💬 Утренние дополнения выключены\\."),
+    ("sections.choose", "🧩 *Разделы отчета о погоде*\n\nНажмите на раздел, чтобы включить или выключить его\\. Настройка действует и для /weather, и для ежедневных уведомлений\\."),
+    ("style.usage", "📝 *Стиль отчета* сейчас: {style}\\.\n\nКомпактный отчет умещается в одну строку, например `☀️ +21° 💨3м/с 💧40%`, и хорошо читается в уведомлениях на часах\\.\n\n/style compact \\- компактный, /style normal \\- обычный"),
+    ("locale.usage", "🗓 *Формат дат и времени* сейчас: {locale}, например: {example}\\.\n\n/locale ru, /locale en\\-us, /locale en\\-gb, /locale de или /locale fr"),
+    ("locale.set", "🗓 Готово\\! Формат дат и времени: {locale}, например: {example}\\."),
+    ("style.set", "📝 Готово\\! Стиль отчета: {style}\\."),
+    ("precision.usage", "🌡 *Точность температуры* сейчас: {precision}\\.\n\n/precision whole \\- целые градусы, например `+21°C`\n/precision tenths \\- с десятыми, например `21.3°C`"),
+    ("precision.set", "🌡 Готово\\! Температура в отчетах: {precision}\\."),
+    ("settings.open", "⚙️ *Настройки*\n\nГород, время уведомлений, разделы и оформление отчета \\- на одном экране\\. Нажмите кнопку ниже\\."),
+    ("settings.exported", "💾 Файл с твоими настройками\\. Чтобы восстановить их \\- здесь или в другом боте на FerrisBot, ответь на этот файл командой `/settings import`"),
+    ("settings.import_usage", "💾 Чтобы применить настройки, ответь на файл настроек командой `/settings import`\\. Получить файл: `/settings export`"),
+    ("settings.imported", "✅ Настройки из файла применены\\. Посмотреть их можно командой /settings"),
+    ("settings.import_failed", "⚠️ Не получилось применить файл: {error}"),
+    ("privacy.summary", "🔒 *Что бот хранит о тебе*\n\n{fields}\n\nЭти данные нужны только для прогнозов и уведомлений и никому не передаются\\. Погоду бот запрашивает у OpenWeather только по названию города\\."),
+    ("history.list", "📬 *Последние уведомления*\n\n{items}"),
+    ("history.empty", "📬 Запланированных уведомлений пока не было\\. Время уведомлений выбирается командой /time"),
+    ("export.sent", "📦 Все, что бот хранит о тебе: город, время уведомлений, режимы, история городов и служебные отметки\\. Файл только для просмотра, для переноса настроек есть `/settings export`"),
+    ("export.empty", "📦 Бот пока ничего не хранит о тебе: в файле только отметка времени выгрузки"),
+    ("delete_me.confirm", "🗑 *Удалить все твои данные?*\n\nБот забудет город, время уведомлений, режимы и историю городов, уведомления перестанут приходить\\. Отменить удаление будет нельзя\\. Сначала можно сохранить данные командой /export"),
+    ("delete_me.done", "🗑 Все твои данные удалены, уведомления больше не придут\\. Чтобы начать заново, отправь /start"),
+    ("delete_me.cancelled", "👌 Удаление отменено, все настройки на месте"),
+    ("delete_me.failed", "💾 Не удалось удалить данные, попробуй позже\\."),
+    ("retention.warning", "🗂 Больше {months} мес\\. от тебя не было ни одной команды\\. Через {period} я удалю твои настройки\\.\n\n\
+        Если хочешь их сохранить, просто отправь любую команду, например /weather"),
+    ("settings.unavailable", "⚙️ *Настройки*\n\nЭкран настроек в этом боте не подключен\\. Используйте команды /city, /time, /sections, /style, /precision и /locale\\."),
+    ("transfer.created", "🔑 *Перенос настроек*\n\nОткройте эту ссылку из нового аккаунта Telegram:\n{link}\n\nИли отправьте там команду `/transfer {code}`\\. Код действует {minutes} минут, перенос нужно будет подтвердить в обоих аккаунтах\\."),
+    ("transfer.confirm", "🔑 *Перенести настройки в этот аккаунт?*\n\nГород: {city}\nВремя уведомлений: {time}\n\nТекущие настройки этого аккаунта будут заменены\\."),
+    ("transfer.waiting", "⏳ Запрос отправлен\\. Подтвердите перенос в исходном аккаунте\\."),
+    ("transfer.request", "🔑 *Аккаунт {account} запросил ваши настройки*\n\nРазрешить скопировать город, время уведомлений и оформление отчетов? Если вы не создавали ссылку командой /transfer, нажмите «Запретить»\\."),
+    ("transfer.allowed", "✅ Настройки скопированы в другой аккаунт\\."),
+    ("transfer.done", "✅ *Настройки перенесены*\n\nГород: {city}\nВремя уведомлений: {time}"),
+    ("transfer.denied", "❌ Исходный аккаунт не разрешил перенос настроек\\."),
+    ("transfer.cancelled", "❌ Перенос настроек отменен\\."),
+    ("transfer.invalid", "⚠️ Код переноса не найден или истек\\. Получите новую ссылку командой /transfer в исходном аккаунте\\."),
+    ("transfer.taken", "⚠️ Перенос по этой ссылке уже подтвердил другой аккаунт\\. Попросите владельца настроек прислать новую ссылку командой /transfer\\."),
+    ("transfer.self", "⚠️ Эту ссылку нужно открыть из другого аккаунта Telegram\\."),
+    ("household.usage", "👨‍👩‍👧 *Семья*\n\nУчастники получают ваш утренний прогноз в то же время, что и вы:\n{members}\n\n`/household invite` \\- ссылка\\-приглашение\n`/household remove ID` \\- исключить участника\n`/household leave` \\- выйти из чужой семьи"),
+    ("household.invite", "👨‍👩‍👧 *Приглашение в семью*\n\nОтправьте эту ссылку члену семьи:\n{link}\n\nСсылка действует {hours} часа, вступление нужно подтвердить\\."),
+    ("household.confirm", "👨‍👩‍👧 *Присоединиться к семье?*\n\nВы будете получать прогноз вместе с пригласившим:\nГород: {city}\nВремя уведомлений: {time}"),
+    ("household.joined", "✅ Вы присоединились к семье\\. Выйти можно командой /household leave\\."),
+    ("household.declined", "❌ Приглашение отклонено\\."),
+    ("household.member_joined", "✅ {name} теперь в семье и будет получать ваш утренний прогноз\\."),
+    ("household.member_left", "👋 {name} больше не в семье\\."),
+    ("household.removed", "✅ Участник исключен из семьи\\."),
+    ("household.removed_member", "👋 Вас исключили из семьи, общий прогноз больше не придет\\."),
+    ("household.left", "✅ Вы вышли из семьи, общий прогноз больше не придет\\."),
+    ("household.not_member", "⚠️ Участник с таким ID не найден\\. Список участников: /household"),
+    ("household.no_membership", "⚠️ Вы не состоите ни в одной семье\\."),
+    ("household.full", "⚠️ В семье уже {max} участников\\. Исключите кого\\-нибудь командой /household remove ID\\."),
+    ("household.invalid", "⚠️ Приглашение не найдено или истекло\\. Попросите новую ссылку\\."),
+    ("household.self", "⚠️ Эту ссылку нужно переслать члену семьи\\."),
+    ("unknown", "Я понимаю только команды\\. Используйте /help для получения списка доступных команд\\."),
+];
+
+// Переопределения из каталога шаблонов
+static OVERRIDES: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// Текст сообщения по ключу: переопределенный оператором или встроенный
+pub fn text(key: &str) -> String {
+    if let Some(value) = OVERRIDES.read().unwrap().get(key) {
+        return value.clone();
+    }
+
+    match DEFAULTS.iter().find(|(name, _)| *name == key) {
+        Some((_, value)) => value.to_string(),
+        None => {
+            warn!("Неизвестный ключ шаблона: {}", key);
+            key.to_string()
+        }
+    }
+}
+
+// Текст сообщения с подстановкой значений вместо {name}
+pub fn render(key: &str, args: &[(&str, &str)]) -> String {
+    let mut result = text(key);
+    for (name, value) in args {
+        result = result.replace(&format!("{{{}}}", name), value);
+    }
+    result
+}
+
+// Загружает переопределения из всех *.toml файлов каталога
+pub fn load(dir: &str) {
+    let mut overrides = HashMap::new();
+
+    for path in template_files(dir) {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                error!("Не удалось прочитать файл шаблонов {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        match content.parse::<toml::Table>() {
+            Ok(table) => flatten("", &table, &mut overrides),
+            Err(e) => error!("Ошибка разбора файла шаблонов {}: {}", path.display(), e),
+        }
+    }
+
+    for key in overrides.keys() {
+        if !DEFAULTS.iter().any(|(name, _)| name == key) {
+            warn!("Шаблон {} не используется ботом", key);
+        }
+    }
+
+    if !overrides.is_empty() {
+        info!("Загружено переопределений шаблонов: {}", overrides.len());
+    }
+    *OVERRIDES.write().unwrap() = overrides;
+}
+
+// Фоновая задача: перечитывает шаблоны при изменении файлов в каталоге
+pub async fn watch_templates(dir: String) {
+    let mut last_state = directory_state(&dir);
+    let mut interval = time::interval(RELOAD_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let state = directory_state(&dir);
+        if state != last_state {
+            info!("Файлы шаблонов изменились, перечитываем {}", dir);
+            load(&dir);
+            last_state = state;
+        }
+    }
+}
+
+fn template_files(dir: &str) -> Vec<std::path::PathBuf> {
+    let mut files: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().map(|ext| ext == "toml").unwrap_or(false))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    // Порядок важен: при совпадении ключей побеждает файл, идущий позже по алфавиту
+    files.sort();
+    files
+}
+
+// Список файлов с временем изменения, по которому определяем, что пора перечитать шаблоны
+fn directory_state(dir: &str) -> Vec<(std::path::PathBuf, Option<SystemTime>)> {
+    template_files(dir)
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(Path::new(&path)).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+// Вложенные таблицы TOML превращаются в ключи через точку: [city] set = "..." -> city.set
+fn flatten(prefix: &str, table: &toml::Table, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let full_key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::String(text) => {
+                out.insert(full_key, text.clone());
+            }
+            toml::Value::Table(nested) => flatten(&full_key, nested, out),
+            _ => warn!("Шаблон {} должен быть строкой", full_key),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::handlers::{confirm_saved, load_or_apologize};
+use crate::storage::{UserSettings, UserStorage};
+use crate::weather::WeatherClient;
+use crate::{escape_markdown_v2, templates, utils};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
+use log::{info, warn};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+// Префикс данных кнопок выбора часового пояса
+pub const CALLBACK_PREFIX: &str = "tz_";
+
+// Сдвиг часов бота от UTC в секундах
+pub fn server_offset() -> i32 {
+    Local::now().offset().local_minus_utc()
+}
+
+// Сдвиг, по которому считается время уведомлений пользователя: без сохраненного - часы бота
+pub fn user_offset(user: &UserSettings) -> i32 {
+    user.utc_offset.unwrap_or_else(server_offset)
+}
+
+// Время пользователя по часам бота: по ним работает расписание
+pub fn to_server_time(user: &UserSettings, time: NaiveTime) -> NaiveTime {
+    shift(time, server_offset() - user_offset(user))
+}
+
+// Время по часам бота в часовом поясе пользователя
+pub fn to_user_time(user: &UserSettings, time: NaiveTime) -> NaiveTime {
+    shift(time, user_offset(user) - server_offset())
+}
+
+// Дата у пользователя в момент at: около полуночи она может отличаться от даты по часам бота
+pub fn user_date(user: &UserSettings, at: DateTime<Local>) -> NaiveDate {
+    (at.naive_utc() + Duration::seconds(user_offset(user) as i64)).date()
+}
+
+fn shift(time: NaiveTime, seconds: i32) -> NaiveTime {
+    time.overflowing_add_signed(Duration::seconds(seconds as i64)).0
+}
+
+// UTC+3, UTC+5:30, UTC-4
+pub fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    match minutes % 60 {
+        0 => format!("UTC{}{}", sign, minutes / 60),
+        rest => format!("UTC{}{}:{:02}", sign, minutes / 60, rest),
+    }
+}
+
+// Сообщение после смены города: пояснение или вопрос с кнопками
+pub struct TimezoneNotice {
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+}
+
+// Вызывается при смене города до сохранения записи: берет сдвиг города из ответа OpenWeather.
+// Если от сдвига ничего не меняется или время уведомлений еще не выбрано, сдвиг записывается сразу.
+// Если уже выбранное время пришлось бы считать по другим часам, спрашиваем пользователя
+pub async fn infer_for_city(weather_client: &WeatherClient, user: &mut UserSettings, city: &str) -> Option<TimezoneNotice> {
+    let offset = city_offset(weather_client, city).await?;
+    let current = user_offset(user);
+    let has_schedule = user.notification_time.is_some() || user.interval_schedule.is_some();
+
+    if offset != current && has_schedule {
+        let text = templates::render("timezone.ask", &[
+            ("city", &escape_markdown_v2(&utils::echo(city))),
+            ("offset", &escape_markdown_v2(&format_offset(offset))),
+            ("time", &escape_markdown_v2(user.notification_time.as_deref().unwrap_or("-"))),
+            ("current", &escape_markdown_v2(&format_offset(current))),
+        ]);
+        let keyboard = InlineKeyboardMarkup::new(vec![
+            vec![InlineKeyboardButton::callback(
+                format!("🌍 По местному ({})", format_offset(offset)),
+                format!("{}set_{}", CALLBACK_PREFIX, offset),
+            )],
+            vec![InlineKeyboardButton::callback(
+                format!("🕒 Как раньше ({})", format_offset(current)),
+                format!("{}keep", CALLBACK_PREFIX),
+            )],
+        ]);
+        return Some(TimezoneNotice { text, keyboard: Some(keyboard) });
+    }
+
+    user.utc_offset = Some(offset);
+    // Пояс совпадает с часами бота - пояснять нечего
+    if offset == server_offset() {
+        return None;
+    }
+    let text = templates::render("timezone.inferred", &[("offset", &escape_markdown_v2(&format_offset(offset)))]);
+    Some(TimezoneNotice { text, keyboard: None })
+}
+
+// Смена города там, где спросить пользователя нельзя (экран настроек, импорт, командная строка):
+// сдвиг города записывается сразу, и время уведомлений дальше считается по местным часам.
+// None - город не нашелся, пояс остается прежним
+pub async fn infer_silently(weather_client: &WeatherClient, user: &mut UserSettings, city: &str) -> Option<i32> {
+    let offset = city_offset(weather_client, city).await?;
+    user.utc_offset = Some(offset);
+    Some(offset)
+}
+
+// Сдвиг города от UTC из ответа OpenWeather
+pub async fn city_offset(weather_client: &WeatherClient, city: &str) -> Option<i32> {
+    match weather_client.fetch_current_weather(city).await {
+        Ok(weather) => Some(weather.timezone),
+        // Город мог не найтись - пояс останется прежним, о самом городе пользователь узнает из /weather
+        Err(e) => {
+            warn!("Не удалось определить часовой пояс города {}: {}", city, e);
+            None
+        }
+    }
+}
+
+pub async fn send_notice(bot: &Bot, chat_id: ChatId, notice: Option<TimezoneNotice>) -> ResponseResult<()> {
+    let Some(notice) = notice else {
+        return Ok(());
+    };
+    let request = bot.send_message(chat_id, notice.text).parse_mode(ParseMode::MarkdownV2);
+    match notice.keyboard {
+        Some(keyboard) => request.reply_markup(keyboard).await?,
+        None => request.await?,
+    };
+    Ok(())
+}
+
+// Ответ на вопрос о часовом поясе: set_<сдвиг> - считать время по местному, keep - оставить как было
+pub async fn handle_callback(
+    bot: &Bot,
+    message: Option<&Message>,
+    storage: &dyn UserStorage,
+    action: &str,
+) -> ResponseResult<()> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let chat_id = message.chat.id;
+
+    let text = if let Some(offset) = action.strip_prefix("set_").and_then(|offset| offset.parse::<i32>().ok()) {
+        let Some(mut user) = load_or_apologize(bot, chat_id, storage, chat_id.0).await? else {
+            return Ok(());
+        };
+        user.utc_offset = Some(offset);
+        let time = user.notification_time.clone().unwrap_or_else(|| "-".to_string());
+        let saved = storage.save_user(user).await;
+        if saved.is_ok() {
+            info!("Пользователь ID: {} выбрал часовой пояс {}", chat_id, format_offset(offset));
+        }
+        confirm_saved(saved, templates::render("timezone.set", &[
+            ("time", &escape_markdown_v2(&time)),
+            ("offset", &escape_markdown_v2(&format_offset(offset))),
+        ]))
+    } else if action == "keep" {
+        let Some(user) = load_or_apologize(bot, chat_id, storage, chat_id.0).await? else {
+            return Ok(());
+        };
+        templates::render("timezone.kept", &[("offset", &escape_markdown_v2(&format_offset(user_offset(&user))))])
+    } else {
+        return Ok(());
+    };
+
+    utils::edit_or_resend(bot, chat_id, Some(message.id), text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn user_with_offset(hours: i32) -> UserSettings {
+        let mut user = UserSettings::new(1);
+        user.utc_offset = Some(hours * 3600);
+        user
+    }
+
+    #[test]
+    fn user_date_follows_user_offset_around_midnight() {
+        // 22:30 UTC 15 января: в Москве уже 16-е, в Нью-Йорке еще 15-е
+        let at = Utc.with_ymd_and_hms(2025, 1, 15, 22, 30, 0).unwrap().with_timezone(&Local);
+        let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+        assert_eq!(user_date(&user_with_offset(3), at), date(16));
+        assert_eq!(user_date(&user_with_offset(0), at), date(15));
+        assert_eq!(user_date(&user_with_offset(-5), at), date(15));
+        assert_eq!(user_date(&user_with_offset(1), at), date(15));
+        assert_eq!(user_date(&user_with_offset(2), at), date(16));
+    }
+
+    #[test]
+    fn formats_offsets() {
+        assert_eq!(format_offset(3 * 3600), "UTC+3");
+        assert_eq!(format_offset(0), "UTC+0");
+        assert_eq!(format_offset(19800), "UTC+5:30");
+        assert_eq!(format_offset(-4 * 3600), "UTC-4");
+    }
+}
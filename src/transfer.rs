@@ -0,0 +1,242 @@
+use crate::storage::{UserSettings, UserStorage};
+use crate::{templates, utils};
+use crate::escape_markdown_v2;
+use crate::handlers::send_echo;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use teloxide::prelude::*;
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode};
+
+// Префикс данных кнопок подтверждения переноса
+pub const CALLBACK_PREFIX: &str = "transfer_";
+// Параметр /start в ссылке переноса: t.me/<бот>?start=transfer-<код>
+pub const START_PREFIX: &str = "transfer-";
+// Сколько действует код переноса
+const CODE_LIFETIME_MINUTES: i64 = 15;
+
+// Перенос, ожидающий подтверждения: from - аккаунт с настройками,
+// to - аккаунт, который открыл ссылку и подтвердил перенос
+struct PendingTransfer {
+    from: i64,
+    to: Option<i64>,
+    expires_at: DateTime<Utc>,
+}
+
+// Коды живут только в памяти: после перезапуска бота ссылку нужно получить заново
+static PENDING: LazyLock<Mutex<HashMap<String, PendingTransfer>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Обработка /transfer: без аргументов - новая ссылка, /transfer cancel - отменить ее,
+// /transfer <код> - принять настройки по коду, если ссылка не открывается
+pub async fn handle_transfer_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let args = args.trim();
+
+    if args.is_empty() {
+        let code = create_code(user_id);
+        let me = bot.get_me().await?;
+        let link = match &me.user.username {
+            Some(username) => format!("https://t.me/{}?start={}{}", username, START_PREFIX, code),
+            None => format!("/transfer {}", code),
+        };
+        info!("Пользователь ID: {} создал код переноса настроек", user_id);
+        bot.send_message(msg.chat.id, templates::render("transfer.created", &[
+            ("link", &escape_markdown_v2(&link)),
+            ("code", &code),
+            ("minutes", &CODE_LIFETIME_MINUTES.to_string()),
+        ]))
+        .parse_mode(ParseMode::MarkdownV2)
+        .disable_web_page_preview(true)
+        .await?;
+        return Ok(());
+    }
+
+    if matches!(args.to_lowercase().as_str(), "cancel" | "отмена") {
+        PENDING.lock().unwrap().retain(|_, transfer| transfer.from != user_id);
+        bot.send_message(msg.chat.id, templates::text("transfer.cancelled"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    handle_transfer_code(bot, msg, storage, args).await
+}
+
+// Аккаунт открыл ссылку или ввел код: показываем, что будет перенесено, и просим подтвердить
+pub async fn handle_transfer_code(bot: &Bot, msg: &Message, storage: &dyn UserStorage, code: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let code = code.trim().to_uppercase();
+
+    let from = {
+        let mut pending = PENDING.lock().unwrap();
+        remove_expired(&mut pending);
+        pending.get(&code).map(|transfer| transfer.from)
+    };
+    let response = match from {
+        None => templates::text("transfer.invalid"),
+        Some(from) if from == user_id => templates::text("transfer.self"),
+        Some(from) => {
+            let Some(source) = load_or_apologize(bot, msg.chat.id, storage, from).await? else {
+                return Ok(());
+            };
+            let keyboard = InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::callback("✅ Перенести", format!("{}accept_{}", CALLBACK_PREFIX, code)),
+                InlineKeyboardButton::callback("❌ Отмена", format!("{}decline_{}", CALLBACK_PREFIX, code)),
+            ]]);
+            send_echo(bot, msg.chat.id, render_with_summary("transfer.confirm", &source))
+                .reply_markup(keyboard)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    Ok(())
+}
+
+// Нажатия на кнопки: accept/decline - в новом аккаунте, allow/deny - в исходном
+pub async fn handle_callback(
+    bot: &Bot,
+    message: Option<&Message>,
+    storage: &dyn UserStorage,
+    action: &str,
+) -> ResponseResult<()> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let user_id = message.chat.id.0;
+    let Some((action, data)) = action.split_once('_') else {
+        return Ok(());
+    };
+    // В кнопках исходного аккаунта после кода стоит ID аккаунта, запросившего перенос:
+    // разрешение относится только к нему
+    let (code, requester) = match data.split_once('_') {
+        Some((code, requester)) => (code, requester.parse::<i64>().ok()),
+        None => (data, None),
+    };
+
+    let transfer = {
+        let mut pending = PENDING.lock().unwrap();
+        remove_expired(&mut pending);
+        pending.get(code).map(|transfer| (transfer.from, transfer.to))
+    };
+    let Some((from, to)) = transfer else {
+        return edit(bot, message, templates::text("transfer.invalid")).await;
+    };
+
+    match action {
+        // Ссылку уже подтвердил другой аккаунт: второй запрос исходному аккаунту не отправляем
+        "accept" if to.is_some_and(|to| to != user_id) => edit(bot, message, templates::text("transfer.taken")).await,
+        // Новый аккаунт подтвердил: спрашиваем разрешение у исходного
+        "accept" if from != user_id => {
+            if let Some(transfer) = PENDING.lock().unwrap().get_mut(code) {
+                transfer.to = Some(user_id);
+            }
+            let keyboard = InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::callback("✅ Разрешить", format!("{}allow_{}_{}", CALLBACK_PREFIX, code, user_id)),
+                InlineKeyboardButton::callback("❌ Запретить", format!("{}deny_{}_{}", CALLBACK_PREFIX, code, user_id)),
+            ]]);
+            let account = escape_markdown_v2(&describe_account(&message.chat));
+            bot.send_message(ChatId(from), templates::render("transfer.request", &[("account", &account)]))
+                .parse_mode(ParseMode::MarkdownV2)
+                .reply_markup(keyboard)
+                .await?;
+            edit(bot, message, templates::text("transfer.waiting")).await
+        }
+        "decline" if to.is_none_or(|to| to == user_id) => {
+            PENDING.lock().unwrap().remove(code);
+            edit(bot, message, templates::text("transfer.cancelled")).await
+        }
+        // Исходный аккаунт разрешил: копируем настройки и сообщаем обоим
+        "allow" if from == user_id => {
+            let Some(to) = to.filter(|to| Some(*to) == requester) else {
+                return edit(bot, message, templates::text("transfer.invalid")).await;
+            };
+            // Если настройки не прочитались, ссылка остается в силе: можно нажать еще раз
+            let (source, mut target) = match (storage.get_user(from).await, storage.get_user(to).await) {
+                (Ok(source), Ok(target)) => (
+                    source.unwrap_or_else(|| UserSettings::new(from)),
+                    target.unwrap_or_else(|| UserSettings::new(to)),
+                ),
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!("Не удалось загрузить настройки для переноса из ID: {} в ID: {}: {}", from, to, e);
+                    return edit(bot, message, templates::text("storage.read_failed")).await;
+                }
+            };
+            PENDING.lock().unwrap().remove(code);
+
+            target.copy_preferences_from(&source);
+            let saved = storage.save_user(target).await;
+            if saved.is_err() {
+                return edit(bot, message, confirm_saved(saved, templates::text("transfer.allowed"))).await;
+            }
+            info!("Настройки пользователя ID: {} перенесены в аккаунт ID: {}", from, to);
+
+            send_echo(bot, ChatId(to), render_with_summary("transfer.done", &source)).await?;
+            edit(bot, message, templates::text("transfer.allowed")).await
+        }
+        "deny" if from == user_id && to == requester => {
+            PENDING.lock().unwrap().remove(code);
+            if let Some(to) = to {
+                bot.send_message(ChatId(to), templates::text("transfer.denied"))
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await?;
+            }
+            edit(bot, message, templates::text("transfer.cancelled")).await
+        }
+        _ => {
+            warn!("Пользователь ID: {} нажал чужую кнопку переноса настроек: {}", user_id, action);
+            Ok(())
+        }
+    }
+}
+
+// Новый код переноса; прежние коды этого пользователя перестают действовать
+fn create_code(user_id: i64) -> String {
+    let code = utils::random_code();
+
+    let mut pending = PENDING.lock().unwrap();
+    remove_expired(&mut pending);
+    pending.retain(|_, transfer| transfer.from != user_id);
+    pending.insert(code.clone(), PendingTransfer {
+        from: user_id,
+        to: None,
+        expires_at: Utc::now() + Duration::minutes(CODE_LIFETIME_MINUTES),
+    });
+    code
+}
+
+fn remove_expired(pending: &mut HashMap<String, PendingTransfer>) {
+    let now = Utc::now();
+    pending.retain(|_, transfer| transfer.expires_at > now);
+}
+
+// Аккаунт, запросивший перенос, для вопроса исходному аккаунту: "@username", иначе имя и ID
+fn describe_account(chat: &teloxide::types::Chat) -> String {
+    if let Some(username) = chat.username() {
+        return format!("@{}", username);
+    }
+    let name: Vec<&str> = [chat.first_name(), chat.last_name()].into_iter().flatten().collect();
+    if name.is_empty() {
+        format!("ID: {}", chat.id)
+    } else {
+        format!("{} (ID: {})", name.join(" "), chat.id)
+    }
+}
+
+// Шаблон с городом и временем переносимых настроек
+fn render_with_summary(key: &str, user: &UserSettings) -> String {
+    templates::render(key, &[
+        ("city", &escape_markdown_v2(&utils::echo(user.city().unwrap_or("не выбран")))),
+        ("time", &escape_markdown_v2(user.notification_time.as_deref().unwrap_or("не выбрано"))),
+    ])
+}
+
+async fn edit(bot: &Bot, message: &Message, text: String) -> ResponseResult<()> {
+    utils::edit_or_resend(bot, message.chat.id, Some(message.id), text).await
+}
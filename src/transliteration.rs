@@ -0,0 +1,130 @@
+// Варианты написания города для поиска в OpenWeather: пользователи переключают раскладку
+// и пишут "Moskva" вместо "Москва" или "Лондон" вместо "London"
+
+// Привычные сокращения и написания, которые транслитерация не восстановит
+const ALIASES: &[(&str, &str)] = &[
+    ("spb", "Санкт-Петербург"),
+    ("piter", "Санкт-Петербург"),
+    ("питер", "Санкт-Петербург"),
+    ("спб", "Санкт-Петербург"),
+    ("sankt-peterburg", "Санкт-Петербург"),
+    ("sankt peterburg", "Санкт-Петербург"),
+    ("saint petersburg", "Санкт-Петербург"),
+    ("st petersburg", "Санкт-Петербург"),
+    ("st. petersburg", "Санкт-Петербург"),
+    ("msk", "Москва"),
+    ("мск", "Москва"),
+    ("ekaterinburg", "Екатеринбург"),
+    ("yekaterinburg", "Екатеринбург"),
+    ("екб", "Екатеринбург"),
+    ("nizhniy novgorod", "Нижний Новгород"),
+    ("nizhny novgorod", "Нижний Новгород"),
+    ("нью-йорк", "New York"),
+    ("нью йорк", "New York"),
+    ("париж", "Paris"),
+    ("лондон", "London"),
+    ("пекин", "Beijing"),
+];
+
+const CYRILLIC_TO_LATIN: &[(char, &str)] = &[
+    ('а', "a"), ('б', "b"), ('в', "v"), ('г', "g"), ('д', "d"), ('е', "e"), ('ё', "yo"),
+    ('ж', "zh"), ('з', "z"), ('и', "i"), ('й', "y"), ('к', "k"), ('л', "l"), ('м', "m"),
+    ('н', "n"), ('о', "o"), ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"),
+    ('ф', "f"), ('х', "kh"), ('ц', "ts"), ('ч', "ch"), ('ш', "sh"), ('щ', "shch"), ('ъ', ""),
+    ('ы', "y"), ('ь', ""), ('э', "e"), ('ю', "yu"), ('я', "ya"),
+];
+
+// Сначала более длинные сочетания: "shch" раньше "sh", "sh" раньше "s"
+const LATIN_TO_CYRILLIC: &[(&str, &str)] = &[
+    ("shch", "щ"), ("sch", "щ"), ("zh", "ж"), ("kh", "х"), ("ts", "ц"), ("ch", "ч"),
+    ("sh", "ш"), ("yu", "ю"), ("ju", "ю"), ("ya", "я"), ("ja", "я"), ("yo", "ё"),
+    ("ye", "е"), ("a", "а"), ("b", "б"), ("v", "в"), ("w", "в"), ("g", "г"), ("d", "д"),
+    ("e", "е"), ("z", "з"), ("i", "и"), ("j", "й"), ("k", "к"), ("l", "л"), ("m", "м"),
+    ("n", "н"), ("o", "о"), ("p", "п"), ("r", "р"), ("s", "с"), ("t", "т"), ("u", "у"),
+    ("f", "ф"), ("h", "х"), ("c", "к"), ("q", "к"), ("x", "кс"),
+];
+
+// Варианты названия для запроса: исходное, известное сокращение, транслитерация
+// в другую раскладку. Без повторов, исходное всегда первое
+pub fn candidates(city: &str) -> Vec<String> {
+    let city = city.trim();
+    let mut variants = vec![city.to_string()];
+    let lower = city.to_lowercase();
+
+    if let Some((_, name)) = ALIASES.iter().find(|(alias, _)| *alias == lower) {
+        push_unique(&mut variants, name.to_string());
+    }
+    if lower.chars().any(is_cyrillic) {
+        push_unique(&mut variants, to_latin(city));
+    } else if lower.chars().any(|c| c.is_ascii_alphabetic()) {
+        push_unique(&mut variants, to_cyrillic(city));
+    }
+    variants
+}
+
+fn push_unique(variants: &mut Vec<String>, variant: String) {
+    if !variant.is_empty() && !variants.iter().any(|known| known.to_lowercase() == variant.to_lowercase()) {
+        variants.push(variant);
+    }
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, 'а'..='я' | 'ё')
+}
+
+fn to_latin(text: &str) -> String {
+    capitalize_words(
+        &text
+            .to_lowercase()
+            .chars()
+            .map(|c| {
+                CYRILLIC_TO_LATIN
+                    .iter()
+                    .find(|(cyrillic, _)| *cyrillic == c)
+                    .map(|(_, latin)| latin.to_string())
+                    .unwrap_or_else(|| c.to_string())
+            })
+            .collect::<String>(),
+    )
+}
+
+fn to_cyrillic(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let mut result = String::new();
+    let mut rest = lower.as_str();
+    while let Some(c) = rest.chars().next() {
+        // "y" после гласной - это "й" (Tverskoy), иначе "ы" (Syktyvkar)
+        if c == 'y' && !["yu", "ya", "yo", "ye"].iter().any(|digraph| rest.starts_with(digraph)) {
+            let after_vowel = result.chars().last().is_some_and(|last| "аеёиоуыэюя".contains(last));
+            result.push(if after_vowel { 'й' } else { 'ы' });
+            rest = &rest[1..];
+            continue;
+        }
+        match LATIN_TO_CYRILLIC.iter().find(|(latin, _)| rest.starts_with(latin)) {
+            Some((latin, cyrillic)) => {
+                result.push_str(cyrillic);
+                rest = &rest[latin.len()..];
+            }
+            None => {
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    capitalize_words(&result)
+}
+
+// Каждое слово и часть через дефис с заглавной буквы: "нижний новгород" -> "Нижний Новгород"
+fn capitalize_words(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut start = true;
+    for c in text.chars() {
+        if start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.push(c);
+        }
+        start = c == ' ' || c == '-';
+    }
+    result
+}
@@ -0,0 +1,155 @@
+use crate::storage::{TravelOverride, UserSettings, UserStorage};
+use crate::config::Config;
+use crate::{moderation, templates, timezone};
+use crate::handlers::send_echo;
+use crate::utils;
+use crate::handlers::confirm_saved;
+use crate::handlers::load_or_apologize;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime};
+use log::{error, info};
+use teloxide::prelude::*;
+use teloxide::types::ParseMode;
+
+// Обработка /travel <город> [до] <дата>, /travel off и /travel без аргументов
+pub async fn handle_travel_command(bot: &Bot, msg: &Message, storage: &dyn UserStorage, config: &Config, args: &str) -> ResponseResult<()> {
+    let user_id = msg.chat.id.0;
+    let now = Local::now().naive_local();
+    let Some(mut user) = load_or_apologize(bot, msg.chat.id, storage, user_id).await? else {
+        return Ok(());
+    };
+    // Ссылки и упоминания, вставленные вместе с городом, отбрасываем
+    let args = moderation::sanitize(args);
+    let args = args.as_str();
+
+    let response = if args.is_empty() {
+        match &user.travel {
+            Some(travel) => templates::render("travel.status", &[
+                ("city", &escape(&travel.city)),
+                ("until", &escape(&format_date(travel.until))),
+            ]),
+            None => templates::text("travel.usage"),
+        }
+    } else if matches!(args.to_lowercase().as_str(), "off" | "стоп" | "отмена") {
+        let cancelled = templates::render("travel.cancelled", &[("home", &escape(home_city(&user)))]);
+        if user.travel.take().is_some() {
+            let saved = storage.save_user(user).await;
+            info!("Пользователь ID: {} отменил режим поездки", user_id);
+            confirm_saved(saved, cancelled)
+        } else {
+            cancelled
+        }
+    } else {
+        match parse_travel_args(args, now) {
+            Ok((city, _)) if moderation::is_blocked(&city, &config.blocklist) => templates::text("input.blocked"),
+            Ok((city, _)) if city.chars().count() > utils::MAX_CITY_LENGTH => {
+                templates::render("input.too_long", &[("max", &utils::MAX_CITY_LENGTH.to_string())])
+            }
+            Ok((city, until)) => {
+                user.travel = Some(TravelOverride { city: city.clone(), until });
+                let saved = storage.save_user(user.clone()).await;
+                info!("Пользователь ID: {} включил режим поездки: {} до {}", user_id, city, until);
+
+                confirm_saved(saved, templates::render("travel.set", &[
+                    ("city", &escape(&city)),
+                    ("until", &escape(&format_date(until))),
+                    ("home", &escape(home_city(&user))),
+                ]))
+            }
+            Err(e) => templates::render("travel.invalid", &[("error", &escape(&e))]),
+        }
+    };
+
+    send_echo(bot, msg.chat.id, response).await?;
+    Ok(())
+}
+
+// Завершает поездки, срок которых истек, и сообщает пользователю о возврате домашнего города
+pub async fn expire_travel(bot: &Bot, storage: &dyn UserStorage, now: DateTime<Local>) {
+    for mut user in storage.all_users_or_log().await {
+        // Последний день поездки кончается в полночь у пользователя, а не по часам бота
+        let today = timezone::user_date(&user, now);
+        let expired = user.travel.as_ref().map(|t| t.until < today).unwrap_or(false);
+        if !expired {
+            continue;
+        }
+
+        let travel = user.travel.take();
+        storage.save_user_or_log(user.clone()).await;
+        info!("Поездка пользователя ID: {} завершена: {:?}", user.user_id, travel.map(|t| t.city));
+
+        if !user.active {
+            continue;
+        }
+
+        let message = templates::render("travel.ended", &[("home", &escape(home_city(&user)))]);
+        if let Err(e) = bot.send_message(ChatId(user.user_id), message)
+            .parse_mode(ParseMode::MarkdownV2)
+            .await
+        {
+            error!("Не удалось сообщить пользователю {} о завершении поездки: {}", user.user_id, e);
+        }
+    }
+}
+
+// Разбирает "Сочи 25.12", "Нижний Новгород до 2025-01-10", "Казань 3d", "Сочи до понедельника":
+// последнее слово - дата или длительность поездки, перед ним необязательное "до"
+fn parse_travel_args(args: &str, now: NaiveDateTime) -> Result<(String, NaiveDate), String> {
+    let today = now.date();
+    let mut words: Vec<&str> = args.split_whitespace().collect();
+    let last = words.pop().ok_or("не указан город")?;
+    let has_until = words.last().map(|w| w.to_lowercase() == "до").unwrap_or(false);
+    if has_until {
+        words.pop();
+    }
+
+    let until = match parse_date(last, today) {
+        Some(date) => date,
+        None => {
+            let duration_text = if has_until { format!("до {}", last) } else { last.to_string() };
+            let duration = utils::parse_duration(&duration_text, now)
+                .map_err(|_| format!("не удалось разобрать дату или срок «{}», например: 25.12, 3d, до понедельника", last))?;
+            // Поездка длится до последнего полного дня перед окончанием срока
+            (now + duration - Duration::seconds(1)).date()
+        }
+    };
+
+    if words.is_empty() {
+        return Err("не указан город".to_string());
+    }
+    if until < today {
+        return Err("дата окончания поездки уже прошла".to_string());
+    }
+
+    Ok((words.join(" "), until))
+}
+
+// Дата в формате ДД.ММ.ГГГГ, ГГГГ-ММ-ДД или ДД.ММ (ближайшая такая дата, начиная с сегодня)
+fn parse_date(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%d.%m.%Y") {
+        return Some(date);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    let (day, month) = text.split_once('.')?;
+    let (day, month) = (day.parse().ok()?, month.parse().ok()?);
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+    if this_year >= today {
+        Some(this_year)
+    } else {
+        NaiveDate::from_ymd_opt(today.year() + 1, month, day)
+    }
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%d.%m.%Y").to_string()
+}
+
+fn home_city(user: &UserSettings) -> &str {
+    user.city().unwrap_or("домашнего города")
+}
+
+fn escape(text: &str) -> String {
+    crate::escape_markdown_v2(&utils::echo(text))
+}
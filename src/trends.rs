@@ -0,0 +1,127 @@
+use crate::history::DayRecord;
+use crate::stats;
+use chrono::{Duration, NaiveDate};
+
+// Серии короче этого не упоминаем: два дождливых дня подряд - обычное дело
+const MIN_STREAK_DAYS: usize = 3;
+// Рекорд месяца считаем только при достаточной истории за этот месяц
+const RECORD_WINDOW_DAYS: i64 = 30;
+const MIN_RECORD_DAYS: usize = 14;
+// Прогноз сравниваем со средней температурой за прошедшую неделю, если за нее есть хотя бы 5 дней
+const ANOMALY_WINDOW_DAYS: i64 = 7;
+const MIN_ANOMALY_DAYS: usize = 5;
+// С какого отклонения от средней за неделю перемену погоды считаем резкой, °C
+const ANOMALY_THRESHOLD: f32 = 8.0;
+
+// Погода дня для подсчета серий
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DayKind {
+    Rainy,
+    Snowy,
+    Dry,
+}
+
+impl DayKind {
+    fn of(day: &DayRecord) -> Option<Self> {
+        Some(match day.dominant_condition()? {
+            "Rain" | "Drizzle" | "Thunderstorm" => DayKind::Rainy,
+            "Snow" => DayKind::Snowy,
+            _ => DayKind::Dry,
+        })
+    }
+
+    fn describe(self, days: usize) -> String {
+        let ordinal = ordinal(days);
+        match self {
+            DayKind::Rainy => format!("🌧 {} дождливый день подряд", ordinal),
+            DayKind::Snowy => format!("🌨 {} снежный день подряд", ordinal),
+            DayKind::Dry => format!("🌤 {} день подряд без осадков", ordinal),
+        }
+    }
+}
+
+// Заметка о серии одинаковых дней, которая заканчивается последним днем истории.
+// Пропущенный день (бот не получал погоду) серию прерывает
+pub fn streak_note(days: &[DayRecord]) -> Option<String> {
+    let last = days.last()?;
+    let kind = DayKind::of(last)?;
+
+    let mut length = 1;
+    for pair in days.windows(2).rev() {
+        let consecutive = pair[0].date.succ_opt() == Some(pair[1].date);
+        if !consecutive || DayKind::of(&pair[0]) != Some(kind) {
+            break;
+        }
+        length += 1;
+    }
+
+    (length >= MIN_STREAK_DAYS).then(|| kind.describe(length))
+}
+
+// Заметка о рекорде месяца: последний день теплее или холоднее всех остальных за 30 дней
+pub fn record_note(days: &[DayRecord]) -> Option<String> {
+    let (last, previous) = days.split_last()?;
+    let window_start = last.date - Duration::days(RECORD_WINDOW_DAYS);
+    let month: Vec<&DayRecord> = previous.iter().filter(|day| day.date > window_start).collect();
+    if month.len() + 1 < MIN_RECORD_DAYS {
+        return None;
+    }
+
+    if month.iter().all(|day| last.temp_max > day.temp_max) {
+        Some("🔥 Самый теплый день за месяц по данным бота".to_string())
+    } else if month.iter().all(|day| last.temp_min < day.temp_min) {
+        Some("🥶 Самый холодный день за месяц по данным бота".to_string())
+    } else {
+        None
+    }
+}
+
+// Заметка о резкой перемене: средняя температура по прогнозу на сегодня сильно отличается
+// от средней за прошедшие 7 дней
+pub fn anomaly_note(days: &[DayRecord], today: NaiveDate, forecast_avg: f32) -> Option<String> {
+    let window_start = today - Duration::days(ANOMALY_WINDOW_DAYS);
+    let week: Vec<f32> = days
+        .iter()
+        .filter(|day| day.date >= window_start && day.date < today)
+        .map(DayRecord::temp_avg)
+        .collect();
+    if week.len() < MIN_ANOMALY_DAYS {
+        return None;
+    }
+
+    let deviation = stats::deviation(forecast_avg, &week)?;
+    if deviation.abs() < ANOMALY_THRESHOLD {
+        return None;
+    }
+    let degrees = deviation.abs().round() as i32;
+    Some(if deviation < 0.0 {
+        format!("🥶 Резкое похолодание на {}° по сравнению с прошлой неделей", degrees)
+    } else {
+        format!("🔥 Резкое потепление на {}° по сравнению с прошлой неделей", degrees)
+    })
+}
+
+// Рекорды города за все время наблюдений бота
+pub struct Records<'a> {
+    pub warmest: &'a DayRecord,
+    pub coldest: &'a DayRecord,
+    pub since: NaiveDate,
+    pub days: usize,
+}
+
+pub fn records(days: &[DayRecord]) -> Option<Records<'_>> {
+    Some(Records {
+        warmest: days.iter().max_by(|a, b| a.temp_max.total_cmp(&b.temp_max))?,
+        coldest: days.iter().min_by(|a, b| a.temp_min.total_cmp(&b.temp_min))?,
+        since: days.first()?.date,
+        days: days.len(),
+    })
+}
+
+// "Третий", "5-й": словами до десяти, дальше числом
+fn ordinal(n: usize) -> String {
+    const WORDS: [&str; 10] = [
+        "Первый", "Второй", "Третий", "Четвертый", "Пятый", "Шестой", "Седьмой", "Восьмой", "Девятый", "Десятый",
+    ];
+    WORDS.get(n.wrapping_sub(1)).map_or_else(|| format!("{}-й", n), |word| word.to_string())
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+// Telegram нумерует обновления по порядку, но после недели без обновлений начинает
+// нумерацию заново со случайного числа: такую старую отметку не учитываем
+const MAX_OFFSET_AGE_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SavedOffset {
+    last_update_id: i32,
+    saved_at: DateTime<Utc>,
+}
+
+// Номер последнего обработанного обновления Telegram, сохраняется между перезапусками.
+// После быстрого перезапуска Telegram повторно присылает неподтвержденные обновления,
+// и без этой отметки пользователь получил бы второй ответ на ту же команду
+pub struct UpdateOffset {
+    // Отметка прошлого запуска: обновления не новее нее уже обработаны
+    processed_before_start: Option<i32>,
+    last: Mutex<Option<i32>>,
+    file_path: String,
+}
+
+impl UpdateOffset {
+    pub fn new(path: &str) -> Self {
+        let saved = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| match serde_json::from_str::<SavedOffset>(&content) {
+                Ok(saved) => Some(saved),
+                Err(e) => {
+                    error!("Ошибка чтения номера последнего обновления {}: {}", path, e);
+                    None
+                }
+            })
+            .filter(|saved| Utc::now() - saved.saved_at < Duration::days(MAX_OFFSET_AGE_DAYS));
+
+        let processed_before_start = saved.map(|saved| saved.last_update_id);
+        if let Some(update_id) = processed_before_start {
+            info!("Обновления до {} включительно уже обработаны прошлым запуском", update_id);
+        }
+        UpdateOffset {
+            processed_before_start,
+            last: Mutex::new(processed_before_start),
+            file_path: path.to_string(),
+        }
+    }
+
+    // true - обновление еще не обрабатывалось. Сравниваем только с отметкой прошлого запуска:
+    // обновления разных чатов обрабатываются параллельно и могут прийти не по порядку
+    pub fn accept(&self, update_id: i32) -> bool {
+        if self.processed_before_start.is_some_and(|processed| update_id <= processed) {
+            info!("Пропущено обновление {}, обработанное до перезапуска", update_id);
+            return false;
+        }
+
+        let mut last = self.last.lock().unwrap();
+        if last.is_none_or(|last| update_id > last) {
+            *last = Some(update_id);
+            self.save(SavedOffset { last_update_id: update_id, saved_at: Utc::now() });
+        }
+        true
+    }
+
+    fn save(&self, offset: SavedOffset) {
+        match serde_json::to_string(&offset) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.file_path, json) {
+                    error!("Ошибка сохранения номера последнего обновления: {}", e);
+                }
+            }
+            Err(e) => error!("Ошибка сериализации номера последнего обновления: {}", e),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::storage::{self, UserSettings, UserStorage};
+use crate::timezone;
+use crate::weather::WeatherClient;
+use log::{error, info, warn};
+use std::collections::HashMap;
+
+// Что делать, если импортируемый пользователь уже есть в хранилище
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictStrategy {
+    // Существующая запись не меняется
+    Keep,
+    // Импортированная запись полностью заменяет существующую
+    Overwrite,
+    // У существующей записи заполняются только пустые город и время (по умолчанию)
+    Merge,
+}
+
+impl ConflictStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "keep" => Some(ConflictStrategy::Keep),
+            "overwrite" => Some(ConflictStrategy::Overwrite),
+            "merge" => Some(ConflictStrategy::Merge),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    // Записи, которые не удалось сохранить
+    pub failed: usize,
+}
+
+impl ImportSummary {
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "📥 Импорт завершен\n\nДобавлено: {}\nОбновлено: {}\nПропущено: {}",
+            self.added, self.updated, self.skipped
+        );
+        if self.failed > 0 {
+            text.push_str(&format!("\n⚠️ Не сохранено из-за ошибки записи: {}", self.failed));
+        }
+        text
+    }
+}
+
+// Разбирает файл экспорта по расширению: .csv, .jsonl или JSON-массив настроек
+pub fn parse_users(content: &str, file_name: &str) -> Result<Vec<UserSettings>, String> {
+    let file_name = file_name.to_lowercase();
+
+    if file_name.ends_with(".csv") {
+        parse_csv(content)
+    } else if file_name.ends_with(".jsonl") {
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| e.to_string())
+                    .and_then(storage::migrate_record)
+                    .map_err(|e| format!("строка {}: {}", index + 1, e))
+            })
+            .collect()
+    } else {
+        let records: Vec<serde_json::Value> =
+            serde_json::from_str(content).map_err(|e| format!("некорректный JSON: {}", e))?;
+        records
+            .into_iter()
+            .enumerate()
+            .map(|(index, record)| storage::migrate_record(record).map_err(|e| format!("запись {}: {}", index + 1, e)))
+            .collect()
+    }
+}
+
+// CSV с заголовком; обязательна колонка user_id, остальные (city, notification_time, cute_mode) - по желанию
+fn parse_csv(content: &str) -> Result<Vec<UserSettings>, String> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = lines
+        .next()
+        .ok_or("пустой файл")?
+        .split(',')
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+
+    let column = |name: &str| header.iter().position(|c| c == name);
+    let user_id_column = column("user_id").ok_or("нет колонки user_id")?;
+    let city_column = column("city");
+    let time_column = column("notification_time");
+    let cute_column = column("cute_mode");
+
+    let mut users = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let values: Vec<&str> = line.split(',').map(str::trim).collect();
+        let value = |column: Option<usize>| {
+            column
+                .and_then(|c| values.get(c))
+                .map(|v| v.to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let user_id = values
+            .get(user_id_column)
+            .and_then(|v| v.parse::<i64>().ok())
+            .ok_or_else(|| format!("строка {}: некорректный user_id", index + 2))?;
+
+        let mut user = UserSettings::new(user_id);
+        if let Some(city) = value(city_column) {
+            user.set_city(&city);
+        }
+        user.notification_time = value(time_column);
+        user.cute_mode = value(cute_column)
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        users.push(user);
+    }
+
+    Ok(users)
+}
+
+// Сливает импортированных пользователей с хранилищем по выбранному правилу
+pub async fn import_users(
+    storage: &dyn UserStorage,
+    weather_client: &WeatherClient,
+    users: Vec<UserSettings>,
+    strategy: ConflictStrategy,
+) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    // Пояс каждого города запрашиваем один раз за весь импорт
+    let mut offsets: HashMap<String, Option<i32>> = HashMap::new();
+
+    for mut imported in users {
+        // Запись из другого экземпляра бота могла прийти без пояса: без него время уведомлений
+        // считалось бы по часам этого бота, а не города
+        if imported.utc_offset.is_none() {
+            imported.utc_offset = city_offset(weather_client, &mut offsets, imported.city()).await;
+        }
+        let merged = match storage.get_user(imported.user_id).await {
+            Err(e) => {
+                warn!("Пользователь {} не импортирован: {}", imported.user_id, e);
+                summary.failed += 1;
+                continue;
+            }
+            Ok(None) => {
+                summary.added += 1;
+                imported
+            }
+            Ok(Some(_)) if strategy == ConflictStrategy::Keep => {
+                summary.skipped += 1;
+                continue;
+            }
+            Ok(Some(_)) if strategy == ConflictStrategy::Overwrite => {
+                summary.updated += 1;
+                imported
+            }
+            Ok(Some(mut existing)) => {
+                let mut changed = false;
+                if existing.cities.is_empty() && !imported.cities.is_empty() {
+                    existing.cities = imported.cities;
+                    existing.utc_offset = imported.utc_offset.or(existing.utc_offset);
+                    changed = true;
+                }
+                if existing.notification_time.is_none() && imported.notification_time.is_some() {
+                    existing.notification_time = imported.notification_time;
+                    changed = true;
+                }
+                if !changed {
+                    summary.skipped += 1;
+                    continue;
+                }
+                summary.updated += 1;
+                existing
+            }
+        };
+
+        if let Err(e) = storage.save_user(merged).await {
+            error!("Не удалось сохранить импортированного пользователя: {}", e);
+            summary.failed += 1;
+        }
+    }
+
+    info!(
+        "Импорт пользователей: добавлено {}, обновлено {}, пропущено {}",
+        summary.added, summary.updated, summary.skipped
+    );
+    summary
+}
+
+async fn city_offset(weather_client: &WeatherClient, offsets: &mut HashMap<String, Option<i32>>, city: Option<&str>) -> Option<i32> {
+    let city = city?;
+    if let Some(offset) = offsets.get(&city.to_lowercase()) {
+        return *offset;
+    }
+    let offset = timezone::city_offset(weather_client, city).await;
+    offsets.insert(city.to_lowercase(), offset);
+    offset
+}
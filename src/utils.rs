@@ -0,0 +1,291 @@
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use crate::locale::Locale;
+use log::info;
+use rand::Rng;
+use teloxide::net::Download;
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, ParseMode};
+use teloxide::{ApiError, RequestError};
+
+// Символы одноразовых кодов: без похожих друг на друга 0/O и 1/I
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 8;
+
+const DURATION_HINT: &str = "укажите, например, 30m, 2h, 3d или «до понедельника»";
+
+// Разбирает длительность для пауз и отсрочек: "30m", "2h", "3d", "1w", "45мин", "2 часа",
+// "до понедельника" (до начала ближайшего такого дня), "до завтра".
+// Ошибка содержит подсказку для пользователя
+pub fn parse_duration(text: &str, now: NaiveDateTime) -> Result<Duration, String> {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return Err(format!("не указана длительность: {}", DURATION_HINT));
+    }
+
+    if let Some(day) = text.strip_prefix("до ") {
+        return until_day(day.trim(), now)
+            .ok_or_else(|| format!("не понял, до какого дня «{}»: {}", day.trim(), DURATION_HINT));
+    }
+
+    let digits_end = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (amount, unit) = text.split_at(digits_end);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("не удалось разобрать «{}»: {}", text, DURATION_HINT))?;
+    if amount == 0 {
+        return Err(format!("длительность должна быть больше нуля: {}", DURATION_HINT));
+    }
+
+    let duration = match unit.trim() {
+        "m" | "min" | "м" | "мин" | "минута" | "минуты" | "минут" => Duration::try_minutes(amount),
+        "h" | "ч" | "час" | "часа" | "часов" => Duration::try_hours(amount),
+        "d" | "д" | "дн" | "день" | "дня" | "дней" => Duration::try_days(amount),
+        "w" | "н" | "нед" | "неделя" | "недели" | "недель" => Duration::try_weeks(amount),
+        "" => return Err(format!("не указана единица для «{}»: {}", amount, DURATION_HINT)),
+        unit => return Err(format!("неизвестная единица «{}»: {}", unit, DURATION_HINT)),
+    };
+
+    // Больше года - почти наверняка опечатка; None - число не помещается даже в Duration
+    match duration {
+        Some(duration) if duration <= Duration::days(366) => Ok(duration),
+        _ => Err("длительность не может быть больше года".to_string()),
+    }
+}
+
+// Время до начала ближайшего указанного дня (не сегодняшнего)
+fn until_day(day: &str, now: NaiveDateTime) -> Option<Duration> {
+    let days_ahead = match day {
+        "завтра" => 1,
+        _ => {
+            let weekday = parse_weekday_genitive(day)?;
+            let diff = (weekday.num_days_from_monday() + 7 - now.weekday().num_days_from_monday()) % 7;
+            if diff == 0 { 7 } else { diff as i64 }
+        }
+    };
+
+    let target = now.date() + Duration::days(days_ahead);
+    Some(target.and_hms_opt(0, 0, 0)? - now)
+}
+
+// День недели в родительном падеже: "до понедельника", "до среды"
+fn parse_weekday_genitive(day: &str) -> Option<Weekday> {
+    match day {
+        "понедельника" | "пн" => Some(Weekday::Mon),
+        "вторника" | "вт" => Some(Weekday::Tue),
+        "среды" | "ср" => Some(Weekday::Wed),
+        "четверга" | "чт" => Some(Weekday::Thu),
+        "пятницы" | "пт" => Some(Weekday::Fri),
+        "субботы" | "сб" => Some(Weekday::Sat),
+        "воскресенья" | "вс" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// Разбирает время уведомлений в привычных записях: "08:00", "8:00", "08.00", "8 00", "8-00", "0800", "800"
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    let text = text.trim();
+    let (hours, minutes) = match text.find([':', '.', ' ', '-']) {
+        Some(index) => (&text[..index], text[index + 1..].trim_start()),
+        // Слитная запись: последние две цифры - минуты
+        None if (3..=4).contains(&text.len()) && text.bytes().all(|b| b.is_ascii_digit()) => {
+            text.split_at(text.len() - 2)
+        }
+        None => return None,
+    };
+
+    let is_number = |part: &str| (1..=2).contains(&part.len()) && part.chars().all(|c| c.is_ascii_digit());
+    if !is_number(hours) || !is_number(minutes) {
+        return None;
+    }
+
+    NaiveTime::from_hms_opt(hours.parse().ok()?, minutes.parse().ok()?, 0)
+}
+
+// Время в каноническом виде ЧЧ:ММ, в котором оно хранится в настройках
+pub fn format_time(time: NaiveTime) -> String {
+    time.format("%H:%M").to_string()
+}
+
+// Приводит введенное время к виду ЧЧ:ММ ("8.00" -> "08:00"), None - если время не распознано
+pub fn normalize_time(text: &str) -> Option<String> {
+    parse_time(text).map(format_time)
+}
+
+// Округляет время до ближайшей границы шага в минутах (шаг 5: 08:03 -> 08:05, 23:58 -> 00:00)
+pub fn round_time(time: NaiveTime, step_minutes: u32) -> NaiveTime {
+    if step_minutes <= 1 {
+        return time;
+    }
+    let minutes = time.hour() * 60 + time.minute();
+    let rounded = (minutes + step_minutes / 2) / step_minutes * step_minutes % (24 * 60);
+    NaiveTime::from_hms_opt(rounded / 60, rounded % 60, 0).unwrap_or(time)
+}
+
+// Форма слова после числа по правилам языка, на котором написаны формы:
+// plural(Locale::Ru, 3, ["день", "дня", "дней"]) -> "дня", plural(Locale::EnGb, 3, ["day", "days", "days"]) -> "days"
+pub fn plural(locale: Locale, n: i64, forms: [&str; 3]) -> &str {
+    forms[locale.plural_form(n)]
+}
+
+// Число вместе со словом: count(Locale::Ru, 21, ["день", "дня", "дней"]) -> "21 день"
+pub fn count(locale: Locale, n: i64, forms: [&str; 3]) -> String {
+    format!("{} {}", n, plural(locale, n, forms))
+}
+
+// Случайный одноразовый код для ссылок-приглашений, например "K7QM2XHD"
+pub fn random_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// Самое длинное название города, которое принимает бот
+pub const MAX_CITY_LENGTH: usize = 100;
+// Сколько символов пользовательского текста бот повторяет в своих ответах
+const MAX_ECHO_LENGTH: usize = 100;
+
+// Обрезает текст до max символов, отмечая обрезку многоточием
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+// Пользовательский текст (город, имя) для вставки в ответ бота: длинный ввод
+// не должен раздувать сообщение до лимита Telegram в 4096 символов
+pub fn echo(text: &str) -> String {
+    truncate(text, MAX_ECHO_LENGTH)
+}
+
+// Скачивает присланный пользователем файл как текст UTF-8
+pub async fn download_text(bot: &Bot, file_id: &str) -> Result<String, String> {
+    let file = bot.get_file(file_id).await.map_err(|e| e.to_string())?;
+    let mut content = Vec::new();
+    bot.download_file(&file.path, &mut content).await.map_err(|e| e.to_string())?;
+    String::from_utf8(content).map_err(|_| "файл должен быть в кодировке UTF-8".to_string())
+}
+
+// Показывает ответ кнопочного диалога на месте сообщения с клавиатурой (текст в MarkdownV2).
+// Если сообщение удалено (пользователь очистил чат) или его уже нельзя изменить,
+// ответ приходит новым сообщением, а не теряется
+pub async fn edit_or_resend(bot: &Bot, chat_id: ChatId, message_id: Option<MessageId>, text: String) -> ResponseResult<()> {
+    if let Some(message_id) = message_id {
+        match bot.edit_message_text(chat_id, message_id, text.clone()).parse_mode(ParseMode::MarkdownV2).await {
+            // Повторное нажатие той же кнопки: текст уже такой
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => return Ok(()),
+            Err(RequestError::Api(
+                ApiError::MessageToEditNotFound | ApiError::MessageCantBeEdited | ApiError::MessageIdInvalid,
+            )) => {
+                info!("Сообщение {} в чате {} нельзя изменить, отправляем ответ заново", message_id.0, chat_id);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    bot.send_message(chat_id, text).parse_mode(ParseMode::MarkdownV2).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    // Среда, 15 января 2025, 10:30
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, 15).unwrap().and_hms_opt(10, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_each_unit() {
+        let cases = [
+            ("30m", Duration::minutes(30)),
+            ("45мин", Duration::minutes(45)),
+            ("2h", Duration::hours(2)),
+            ("2 часа", Duration::hours(2)),
+            ("3d", Duration::days(3)),
+            ("3 дня", Duration::days(3)),
+            ("1w", Duration::weeks(1)),
+            ("2 недели", Duration::weeks(2)),
+            ("  5H ", Duration::hours(5)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_duration(text, now()), Ok(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn parses_until_day() {
+        // До завтрашней полуночи
+        assert_eq!(parse_duration("до завтра", now()), Ok(Duration::hours(13) + Duration::minutes(30)));
+        // Сегодня среда: до пятницы - до полуночи с четверга на пятницу, до среды - до следующей среды
+        assert_eq!(parse_duration("до пятницы", now()), Ok(Duration::days(1) + Duration::hours(13) + Duration::minutes(30)));
+        assert_eq!(parse_duration("до среды", now()), Ok(Duration::days(6) + Duration::hours(13) + Duration::minutes(30)));
+        assert!(parse_duration("до когда-нибудь", now()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        for text in ["", "   ", "abc", "30", "0m", "-5m", "5 лет", "m30", "1.5h"] {
+            assert!(parse_duration(text, now()).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_more_than_a_year() {
+        assert!(parse_duration("366d", now()).is_ok());
+        assert!(parse_duration("367d", now()).is_err());
+        assert!(parse_duration("53w", now()).is_err());
+    }
+
+    #[test]
+    fn rejects_overflow_without_panic() {
+        for text in ["999999999999999d", "999999999999999w", "999999999999999999m", "99999999999999999999999h"] {
+            assert!(parse_duration(text, now()).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn plural_forms_in_russian() {
+        const DAYS: [&str; 3] = ["день", "дня", "дней"];
+        let cases = [
+            (0, "дней"),
+            (1, "день"),
+            (2, "дня"),
+            (5, "дней"),
+            (11, "дней"),
+            (12, "дней"),
+            (14, "дней"),
+            (21, "день"),
+            (22, "дня"),
+            (25, "дней"),
+            (111, "дней"),
+            (112, "дней"),
+            (121, "день"),
+            (-1, "день"),
+            (-22, "дня"),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(plural(Locale::Ru, n, DAYS), expected, "{}", n);
+        }
+        assert_eq!(count(Locale::Ru, 21, DAYS), "21 день");
+        assert_eq!(count(Locale::Ru, 112, DAYS), "112 дней");
+    }
+
+    #[test]
+    fn plural_forms_in_english() {
+        const DAYS: [&str; 3] = ["day", "days", "days"];
+        let cases = [(0, "days"), (1, "day"), (2, "days"), (11, "days"), (21, "days"), (-1, "day")];
+        for locale in [Locale::EnUs, Locale::EnGb] {
+            for (n, expected) in cases {
+                assert_eq!(plural(locale, n, DAYS), expected, "{:?} {}", locale, n);
+            }
+        }
+        assert_eq!(count(Locale::EnGb, 1, DAYS), "1 day");
+        assert_eq!(count(Locale::EnUs, 21, DAYS), "21 days");
+    }
+}
@@ -1,106 +1,486 @@
-use reqwest::Client;
-use serde::Deserialize;
-use chrono::{Utc, TimeZone, Timelike, Datelike};
-use log::error;
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Offset, Utc, TimeZone, Timelike};
+use log::{error, info, warn};
 use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use crate::alerts;
+use crate::api_keys::{ApiKeyPool, KeyOutcome};
+use crate::metrics::metrics;
+use crate::sanity;
+use crate::demo;
+use crate::capabilities::{self, Capabilities};
+use crate::confidence;
+use crate::formatter::{self, ForecastLayout, FormatOptions, ReportStyle, Units};
+use crate::history::WeatherHistory;
+use crate::stats;
+use crate::trends;
+use crate::schema_watch::{self, Endpoint};
+use crate::transliteration;
 
 const OPENWEATHER_URL: &str = "https://api.openweathermap.org/data/2.5/weather";
 const FORECAST_URL: &str = "https://api.openweathermap.org/data/2.5/forecast";
+// Название источника данных для подписи под отчетами
+pub const PROVIDER_NAME: &str = "OpenWeather";
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct OpenWeatherResponse {
-    main: MainInfo,
-    weather: Vec<WeatherInfo>,
-    wind: WindInfo,
-    name: String,
-    dt: i64,
-    clouds: CloudsInfo,
-    sys: SysInfo,
-    visibility: Option<i32>,
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenWeatherResponse {
+    pub main: MainInfo,
+    pub weather: Vec<WeatherInfo>,
+    pub wind: WindInfo,
+    pub name: String,
+    pub dt: i64,
+    pub clouds: CloudsInfo,
+    pub sys: SysInfo,
+    pub visibility: Option<i32>,
+    // Сдвиг местного времени города от UTC, секунды
+    #[serde(default)]
+    pub timezone: i32,
+    #[serde(default)]
+    pub rain: Option<PrecipitationInfo>,
+    #[serde(default)]
+    pub snow: Option<PrecipitationInfo>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct MainInfo {
-    temp: f32,
-    feels_like: f32,
-    humidity: f32,
-    pressure: f32,
-    temp_min: f32,
-    temp_max: f32,
+#[derive(Debug, Clone, Deserialize)]
+pub struct MainInfo {
+    pub temp: f32,
+    pub feels_like: f32,
+    pub humidity: f32,
+    pub pressure: f32,
+    pub temp_min: f32,
+    pub temp_max: f32,
 }
 
-#[derive(Debug, Deserialize)]
-struct WeatherInfo {
-    description: String,
-    icon: String,
-    main: String,
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeatherInfo {
+    pub description: String,
+    pub icon: String,
+    pub main: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct WindInfo {
-    speed: f32,
-    deg: f32,
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindInfo {
+    pub speed: f32,
+    pub deg: f32,
+    #[serde(default)]
+    pub gust: Option<f32>,
 }
 
-#[derive(Debug, Deserialize)]
-struct CloudsInfo {
-    all: i32,
+// Стрелки по направлению, куда дует ветер: северный ветер дует на юг, поэтому ⬇️
+const WIND_ARROWS: [&str; 8] = ["⬇️", "↙️", "⬅️", "↖️", "⬆️", "↗️", "➡️", "↘️"];
+
+const WIND_DIRECTIONS: [&str; 8] = [
+    "северный", "северо-восточный", "восточный", "юго-восточный",
+    "южный", "юго-западный", "западный", "северо-западный",
+];
+
+// Верхние границы баллов шкалы Бофорта, м/с, и их названия
+const BEAUFORT_SCALE: [(f32, &str); 12] = [
+    (0.3, "штиль"),
+    (1.6, "тихий ветер"),
+    (3.4, "легкий ветер"),
+    (5.5, "слабый ветер"),
+    (8.0, "умеренный ветер"),
+    (10.8, "свежий ветер"),
+    (13.9, "сильный ветер"),
+    (17.2, "крепкий ветер"),
+    (20.8, "очень крепкий ветер"),
+    (24.5, "шторм"),
+    (28.5, "сильный шторм"),
+    (32.7, "жестокий шторм"),
+];
+
+// Номер румба (0 - север, 1 - северо-восток, ...); 359.9° и -0.1° - это тоже север
+fn compass_sector(degrees: f32) -> usize {
+    ((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8
+}
+
+impl WindInfo {
+    // Название направления, откуда дует ветер: "северо-западный"
+    pub fn direction_name(&self) -> &'static str {
+        WIND_DIRECTIONS[compass_sector(self.deg)]
+    }
+
+    pub fn direction_arrow(&self) -> &'static str {
+        WIND_ARROWS[compass_sector(self.deg)]
+    }
+
+    // Описание силы ветра по шкале Бофорта: "свежий ветер"
+    pub fn beaufort_description(&self) -> &'static str {
+        BEAUFORT_SCALE
+            .iter()
+            .find(|(limit, _)| self.speed < *limit)
+            .map_or("ураган", |(_, name)| name)
+    }
+
+    // Порывы, если они заметно сильнее среднего ветра; пороги в м/с, скорость - в единицах отчета
+    pub fn gust_note(&self, units: Units) -> Option<String> {
+        let gust = self.gust.filter(|gust| *gust > self.speed)?;
+        let shown = format!("{:.1} {}", units.speed(gust), units.speed_unit());
+        Some(if gust >= STRONG_WIND_SPEED {
+            format!("опасные порывы до {}", shown)
+        } else if gust - self.speed >= 5.0 {
+            format!("порывистый, порывы до {}", shown)
+        } else {
+            format!("порывы до {}", shown)
+        })
+    }
+}
+
+// Индекс духоты (humidex) по температуре (°C) и относительной влажности (%):
+// температура, которую ощущает человек с учетом влажного воздуха
+pub fn humidex(temp: f32, humidity: f32) -> f32 {
+    // Парциальное давление водяного пара, гПа (формула Магнуса)
+    let vapor_pressure = 6.112 * (17.62 * temp / (243.12 + temp)).exp() * humidity / 100.0;
+    temp + 0.5555 * (vapor_pressure - 10.0)
+}
+
+// Насколько душно при таком индексе; None - комфортно
+pub fn mugginess(humidex: f32) -> Option<&'static str> {
+    match humidex {
+        h if h >= 45.0 => Some("опасная духота"),
+        h if h >= 40.0 => Some("очень душно"),
+        h if h >= 30.0 => Some("душно"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CloudsInfo {
+    pub all: i32,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct SysInfo {
-    country: String,
-    sunrise: i64,
-    sunset: i64,
+#[derive(Debug, Clone, Deserialize)]
+pub struct SysInfo {
+    pub country: String,
+    pub sunrise: i64,
+    pub sunset: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForecastResponse {
+    pub list: Vec<ForecastItem>,
+    #[serde(default)]
+    pub city: Option<ForecastCity>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ForecastResponse {
-    list: Vec<ForecastItem>,
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForecastCity {
+    // Сдвиг местного времени города от UTC, секунды
+    #[serde(default)]
+    pub timezone: i32,
+}
+
+impl ForecastResponse {
+    // Сдвиг местного времени города; если город не пришел в ответе - UTC
+    pub fn utc_offset(&self) -> i32 {
+        self.city.as_ref().map_or(0, |city| city.timezone)
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct ForecastItem {
-    dt: i64,
-    main: MainInfo,
-    weather: Vec<WeatherInfo>,
-    dt_txt: String,
+// Местное время города по сдвигу от UTC в секундах из ответа OpenWeather
+pub fn city_time(timestamp: i64, utc_offset: i32) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(utc_offset).unwrap_or_else(|| Utc.fix());
+    offset.timestamp_opt(timestamp, 0).unwrap()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForecastItem {
+    pub dt: i64,
+    pub main: MainInfo,
+    pub weather: Vec<WeatherInfo>,
+    #[serde(default)]
+    pub wind: Option<WindInfo>,
+    #[serde(default)]
+    pub rain: Option<PrecipitationInfo>,
+    #[serde(default)]
+    pub snow: Option<PrecipitationInfo>,
+    pub dt_txt: String,
+}
+
+// Количество осадков, мм: в текущей погоде - за последний час (иногда за 3 часа), в прогнозе - за 3 часа
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrecipitationInfo {
+    #[serde(rename = "1h", default)]
+    pub one_hour: Option<f32>,
+    #[serde(rename = "3h", default)]
+    pub three_hours: f32,
+}
+
+impl ForecastItem {
+    // Дождь и снег за 3 часа, мм
+    pub fn precipitation(&self) -> f32 {
+        self.rain.as_ref().map_or(0.0, |r| r.three_hours) + self.snow.as_ref().map_or(0.0, |s| s.three_hours)
+    }
+}
+
+// Скорость ветра (м/с), с которой считаем ветер сильным
+pub(crate) const STRONG_WIND_SPEED: f32 = 15.0;
+
+// Краткая сводка на вторую половину дня: по ней сравниваем утренний и обновленный прогноз
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayOutlook {
+    pub date: NaiveDate,
+    pub temp_min: f32,
+    pub temp_max: f32,
+    pub precipitation: bool,
+}
+
+// Сводка прогноза за один день, собранная из трехчасовых записей
+#[derive(Debug, Clone)]
+pub struct DaySummary {
+    pub temp_min: f32,
+    pub temp_max: f32,
+    // Температура утром (6-11), днем (12-17) и вечером (18-23) по местному времени
+    pub morning: Option<f32>,
+    pub day: Option<f32>,
+    pub evening: Option<f32>,
+    pub humidity: f32,
+    pub wind_max: Option<f32>,
+    // Сумма осадков, мм
+    pub precipitation: f32,
+    // Самая частая погода за день: описание, иконка и основная категория (Rain, Snow...)
+    pub description: String,
+    pub icon: String,
+    pub weather_main: String,
+}
+
+impl DaySummary {
+    // utc_offset - сдвиг местного времени города: по нему записи делятся на утро, день и вечер
+    fn from_items(items: &[&ForecastItem], utc_offset: i32) -> Option<Self> {
+        if items.is_empty() {
+            return None;
+        }
+
+        let temp_at = |hours: std::ops::Range<u32>| {
+            items
+                .iter()
+                .find(|item| hours.contains(&city_time(item.dt, utc_offset).hour()))
+                .map(|item| item.main.temp)
+        };
+
+        // Самая частая погода; при равенстве берем ту, что встретилась раньше
+        let mut counts: Vec<(&WeatherInfo, usize)> = Vec::new();
+        for weather in items.iter().filter_map(|item| item.weather.first()) {
+            match counts.iter_mut().find(|(w, _)| w.description == weather.description) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((weather, 1)),
+            }
+        }
+        let common = counts
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(weather, _)| *weather)?;
+
+        Some(DaySummary {
+            temp_min: items.iter().map(|item| item.main.temp_min).fold(f32::INFINITY, f32::min),
+            temp_max: items.iter().map(|item| item.main.temp_max).fold(f32::NEG_INFINITY, f32::max),
+            morning: temp_at(6..12),
+            day: temp_at(12..18),
+            evening: temp_at(18..24),
+            humidity: items.iter().map(|item| item.main.humidity).sum::<f32>() / items.len() as f32,
+            wind_max: items
+                .iter()
+                .filter_map(|item| item.wind.as_ref().map(|w| w.speed))
+                .fold(None, |max: Option<f32>, speed| Some(max.map_or(speed, |m| m.max(speed)))),
+            precipitation: items.iter().map(|item| item.precipitation()).sum(),
+            description: common.description.clone(),
+            icon: common.icon.clone(),
+            weather_main: common.main.clone(),
+        })
+    }
+}
+
+// Сколько раз повторяем запрос, если ответ не прошел проверку правдоподобия
+const SANITY_RETRIES: usize = 1;
+// Насколько старыми могут быть последние достоверные данные, чтобы показать их вместо недостоверных
+const FALLBACK_MAX_AGE_HOURS: i64 = 3;
+
+// Последние ответы, прошедшие проверку, по городу (для прогноза - по городу и числу записей)
+type LastGood<T> = LazyLock<Mutex<HashMap<String, (DateTime<Utc>, T)>>>;
+static LAST_GOOD_CURRENT: LastGood<OpenWeatherResponse> = LazyLock::new(|| Mutex::new(HashMap::new()));
+static LAST_GOOD_FORECAST: LastGood<ForecastResponse> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Написания городов, найденные через транслитерацию: "moskva" -> "Москва"
+static RESOLVED_CITIES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn fallback<T: Clone>(cache: &LastGood<T>, key: &str) -> Option<T> {
+    let cache = cache.lock().unwrap();
+    let (saved_at, data) = cache.get(key)?;
+    if Utc::now() - *saved_at > chrono::Duration::hours(FALLBACK_MAX_AGE_HOURS) {
+        return None;
+    }
+    warn!("Используем последние достоверные данные для {} от {}", key, saved_at.format("%H:%M"));
+    metrics().increment("weather_sanity_fallback_total");
+    Some(data.clone())
 }
 
 #[derive(Clone)]
 pub struct WeatherClient {
     client: Client,
-    api_key: String,
+    keys: Arc<ApiKeyPool>,
+    // Подписывать ли отчеты источником данных
+    attribution: bool,
+    // История погоды по городам для заметок в отчетах; None - не ведется
+    history: Option<Arc<WeatherHistory>>,
 }
 
 impl WeatherClient {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_keys: Vec<String>) -> Self {
         Self {
             client: Client::new(),
-            api_key,
+            keys: Arc::new(ApiKeyPool::new(api_keys)),
+            attribution: true,
+            history: None,
+        }
+    }
+
+    // История погоды, если она ведется
+    pub fn history(&self) -> Option<&WeatherHistory> {
+        self.history.as_deref()
+    }
+
+    // Подключает историю погоды: замеры копятся в ней, а отчеты получают заметки о сериях и рекордах
+    pub fn with_history(mut self, history: Arc<WeatherHistory>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    // Какие данные умеет отдавать источник: от этого зависят доступные команды
+    pub fn capabilities(&self) -> Capabilities {
+        capabilities::OPENWEATHER
+    }
+
+    // Включает или отключает подпись источника данных под отчетами
+    pub fn with_attribution(mut self, attribution: bool) -> Self {
+        self.attribution = attribution;
+        self
+    }
+
+    fn attributed(&self, report: String) -> String {
+        if self.attribution {
+            formatter::with_attribution(report, PROVIDER_NAME)
+        } else {
+            report
+        }
+    }
+
+    // Использование ключей OpenWeather для /admin keys
+    pub fn key_report(&self) -> String {
+        self.keys.report()
+    }
+
+    // Запрос по названию города. Если сервис не знает такого написания (404), пробуем
+    // сокращения и транслитерацию в другую раскладку; сработавший вариант запоминаем
+    async fn send_city_request(&self, url: &str, city: &str, query: &[(&str, &str)]) -> Result<Response, String> {
+        let key = city.trim().to_lowercase();
+        let resolved = RESOLVED_CITIES.lock().unwrap().get(&key).cloned();
+        let candidates = match resolved {
+            Some(name) => vec![name],
+            None => transliteration::candidates(city),
+        };
+
+        let mut last_response = None;
+        for candidate in candidates {
+            let mut params = vec![("q", candidate.as_str())];
+            params.extend_from_slice(query);
+            let response = self.send_request(url, &params).await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                last_response = Some(response);
+                continue;
+            }
+            if response.status().is_success() && candidate != city.trim() {
+                info!("Город «{}» найден как «{}»", city, candidate);
+                RESOLVED_CITIES.lock().unwrap().insert(key, candidate);
+            }
+            return Ok(response);
+        }
+        last_response.ok_or_else(|| format!("город не найден: {}", city))
+    }
+
+    // GET-запрос к OpenWeather с очередным ключом из набора. Если ключ уперся в лимит (429)
+    // или отвергнут (401), запрос повторяется со следующим; когда ключи кончились,
+    // возвращается последний ответ
+    async fn send_request(&self, url: &str, query: &[(&str, &str)]) -> Result<Response, String> {
+        let mut last_response = None;
+        for _ in 0..self.keys.key_count() {
+            let Some((index, key)) = self.keys.next_key() else {
+                break;
+            };
+            let response = self.client
+                .get(url)
+                .query(query)
+                .query(&[("appid", key.as_str())])
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            let outcome = match response.status() {
+                StatusCode::TOO_MANY_REQUESTS => KeyOutcome::RateLimited,
+                StatusCode::UNAUTHORIZED => KeyOutcome::Unauthorized,
+                _ => return Ok(response),
+            };
+            self.keys.record(index, outcome);
+            last_response = Some(response);
         }
+        last_response.ok_or_else(|| {
+            alerts::critical("weather_keys", "все ключи OpenWeather временно недоступны (лимит запросов или ключи отозваны)");
+            "все ключи OpenWeather временно недоступны".to_string()
+        })
     }
 
-    pub async fn get_weather(&self, city: &str) -> Result<String, String> {
+    // Отчет о текущей погоде; options - разделы и стиль, которые выбрал пользователь
+    pub async fn get_weather(&self, city: &str, options: FormatOptions) -> Result<String, String> {
         let current_weather = self.fetch_current_weather(city).await?;
-        let forecast = self.fetch_forecast(city).await;
+        let forecast = if options.needs_forecast() {
+            self.fetch_forecast(city).await.ok()
+        } else {
+            None
+        };
         
-        Ok(self.format_weather(&current_weather, forecast.ok()))
-    }
-
-    async fn fetch_current_weather(&self, city: &str) -> Result<OpenWeatherResponse, String> {
-        let response = match self.client
-            .get(OPENWEATHER_URL)
-            .query(&[
-                ("q", city),
-                ("appid", &self.api_key),
-                ("units", "metric"),
-                ("lang", "ru"),
-            ])
-            .send()
+        let mut report = formatter::format_weather(&current_weather, forecast, options);
+        if let (Some(history), ReportStyle::Normal) = (&self.history, options.style) {
+            let days = history.days(city);
+            let notes: Vec<String> = trends::streak_note(&days).into_iter().chain(trends::record_note(&days)).collect();
+            report = formatter::with_notes(report, &notes);
+        }
+        Ok(self.attributed(report))
+    }
+
+    // Текущая погода с проверкой правдоподобия: недостоверный ответ запрашиваем повторно,
+    // а если и он не прошел проверку - берем последние достоверные данные по городу
+    pub async fn fetch_current_weather(&self, city: &str) -> Result<OpenWeatherResponse, String> {
+        if demo::is_demo_city(city) {
+            return Ok(demo::current_weather(Utc::now().timestamp()));
+        }
+        let key = city.to_lowercase();
+        for attempt in 0..=SANITY_RETRIES {
+            let data = self.request_current_weather(city).await?;
+            match sanity::check_current(&data, Utc::now().timestamp()) {
+                Ok(()) => {
+                    LAST_GOOD_CURRENT.lock().unwrap().insert(key, (Utc::now(), data.clone()));
+                    if let Some(history) = &self.history {
+                        history.record(city, &data);
+                    }
+                    return Ok(data);
+                }
+                Err(e) => {
+                    warn!("Недостоверные данные о погоде для {} (попытка {}): {}", city, attempt + 1, e);
+                    metrics().increment("weather_sanity_rejected_total");
+                }
+            }
+        }
+        fallback(&LAST_GOOD_CURRENT, &key)
+            .ok_or_else(|| "Сервис погоды вернул недостоверные данные, попробуйте позже".to_string())
+    }
+
+    async fn request_current_weather(&self, city: &str) -> Result<OpenWeatherResponse, String> {
+        let response = match self
+            .send_city_request(OPENWEATHER_URL, city, &[("units", "metric"), ("lang", "ru")])
             .await
         {
             Ok(resp) => resp,
@@ -121,71 +501,52 @@ impl WeatherClient {
             return Err(format!("Сервис погоды недоступен ({}). Возможно, указан неверный город.", status));
         }
 
-        match response.json::<OpenWeatherResponse>().await {
-            Ok(weather_data) => Ok(weather_data),
-            Err(e) => {
-                error!("Ошибка парсинга ответа погоды: {}", e);
-                Err(format!("Не удалось обработать данные о погоде: {}", e))
-            }
-        }
+        let body = response.text().await.map_err(|e| {
+            error!("Ошибка чтения ответа погоды: {}", e);
+            format!("Не удалось получить данные о погоде: {}", e)
+        })?;
+        schema_watch::parse::<OpenWeatherResponse>(Endpoint::Current, &body)
+            .map_err(|e| format!("Не удалось обработать данные о погоде: {}", e))
     }
 
+    // Прогноз на 72 часа (24 записи с интервалом 3 часа)
     async fn fetch_forecast(&self, city: &str) -> Result<ForecastResponse, String> {
-        let response = match self.client
-            .get(FORECAST_URL)
-            .query(&[
-                ("q", city),
-                ("appid", &self.api_key),
-                ("units", "metric"),
-                ("lang", "ru"),
-                ("cnt", "24"), // получаем прогноз на 24 часа (с интервалом 3 часа)
-            ])
-            .send()
-            .await
-        {
-            Ok(resp) => resp,
-            Err(e) => {
-                error!("Ошибка сетевого запроса прогноза: {}", e);
-                return Err(format!("Не удалось получить данные о прогнозе: {}", e));
-            }
-        };
+        self.fetch_checked_forecast(city, "24").await
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = match response.text().await {
-                Ok(text) => text,
-                Err(_) => "неизвестная ошибка".to_string(),
-            };
-            
-            error!("Сервис прогноза вернул ошибку: {} - {}", status, error_text);
-            return Err(format!("Сервис прогноза недоступен ({})", status));
-        }
+    // Прогноз на 5 дней с 3-часовым интервалом (максимум 40 записей)
+    async fn fetch_forecast_extended(&self, city: &str) -> Result<ForecastResponse, String> {
+        self.fetch_checked_forecast(city, "40").await
+    }
 
-        match response.json::<ForecastResponse>().await {
-            Ok(forecast_data) => Ok(forecast_data),
-            Err(e) => {
-                error!("Ошибка парсинга ответа прогноза: {}", e);
-                Err(format!("Не удалось обработать данные о прогнозе: {}", e))
+    // Прогноз с той же проверкой правдоподобия, что и текущая погода
+    async fn fetch_checked_forecast(&self, city: &str, cnt: &str) -> Result<ForecastResponse, String> {
+        if demo::is_demo_city(city) {
+            return Ok(demo::forecast(Utc::now().timestamp(), cnt.parse().unwrap_or(8)));
+        }
+        let key = format!("{}:{}", city.to_lowercase(), cnt);
+        for attempt in 0..=SANITY_RETRIES {
+            let forecast = self.request_forecast(city, cnt).await?;
+            match sanity::check_forecast(&forecast, Utc::now().timestamp()) {
+                Ok(()) => {
+                    LAST_GOOD_FORECAST.lock().unwrap().insert(key, (Utc::now(), forecast.clone()));
+                    confidence::observe(city, &forecast);
+                    return Ok(forecast);
+                }
+                Err(e) => {
+                    warn!("Недостоверный прогноз для {} (попытка {}): {}", city, attempt + 1, e);
+                    metrics().increment("weather_sanity_rejected_total");
+                }
             }
         }
+        fallback(&LAST_GOOD_FORECAST, &key)
+            .ok_or_else(|| "Сервис прогноза вернул недостоверные данные, попробуйте позже".to_string())
     }
 
-    pub async fn get_weekly_forecast(&self, city: &str) -> Result<String, String> {
-        let forecast = self.fetch_forecast_extended(city).await?;
-        Ok(self.format_weekly_forecast(&forecast))
-    }
-
-    async fn fetch_forecast_extended(&self, city: &str) -> Result<ForecastResponse, String> {
-        let response = match self.client
-            .get(FORECAST_URL)
-            .query(&[
-                ("q", city),
-                ("appid", &self.api_key),
-                ("units", "metric"),
-                ("lang", "ru"),
-                ("cnt", "40"), // получаем прогноз на 5 дней с 3-часовым интервалом (максимум 40)
-            ])
-            .send()
+    // cnt - сколько трехчасовых записей запросить (максимум 40, то есть 5 дней)
+    async fn request_forecast(&self, city: &str, cnt: &str) -> Result<ForecastResponse, String> {
+        let response = match self
+            .send_city_request(FORECAST_URL, city, &[("units", "metric"), ("lang", "ru"), ("cnt", cnt)])
             .await
         {
             Ok(resp) => resp,
@@ -206,272 +567,200 @@ impl WeatherClient {
             return Err(format!("Сервис прогноза недоступен ({})", status));
         }
 
-        match response.json::<ForecastResponse>().await {
-            Ok(forecast_data) => Ok(forecast_data),
-            Err(e) => {
-                error!("Ошибка парсинга ответа прогноза: {}", e);
-                Err(format!("Не удалось обработать данные о прогнозе: {}", e))
-            }
-        }
+        let body = response.text().await.map_err(|e| {
+            error!("Ошибка чтения ответа прогноза: {}", e);
+            format!("Не удалось получить данные о прогнозе: {}", e)
+        })?;
+        schema_watch::parse::<ForecastResponse>(Endpoint::Forecast, &body)
+            .map_err(|e| format!("Не удалось обработать данные о прогнозе: {}", e))
     }
 
-    fn format_weather(&self, data: &OpenWeatherResponse, forecast: Option<ForecastResponse>) -> String {
-        // Получаем эмодзи на основе иконки погоды
-        let weather_emoji = self.get_weather_emoji(&data.weather[0].icon);
-        
-        // Получаем красивое описание направления ветра
-        let wind_direction = self.get_wind_direction(data.wind.deg);
-        
-        // Переводим время восхода и заката в удобный формат
-        let sunrise = Utc.timestamp_opt(data.sys.sunrise, 0).unwrap();
-        let sunset = Utc.timestamp_opt(data.sys.sunset, 0).unwrap();
-        
-        // Форматирование времени
-        let sunrise_time = format!("{:02}:{:02}", sunrise.hour(), sunrise.minute());
-        let sunset_time = format!("{:02}:{:02}", sunset.hour(), sunset.minute());
-        
-        // Рекомендации по одежде
-        let clothing_recommendation = self.get_clothing_recommendation(data.main.temp, data.weather[0].main.as_str());
-        
-        // Получаем температуры на разное время суток
-        let temp_by_time = if let Some(forecast_data) = forecast {
-            self.extract_temperatures_by_time(&forecast_data)
-        } else {
-            "Нет данных".to_string()
-        };
-        
-        format!(
-            "{} *{}*\n\n\
-            🌡 *Температура:* {:.1}°C (ощущается как {:.1}°C)\n\
-            {} \n\
-            🔸 Мин: {:.1}°C, Макс: {:.1}°C\n\
-            💧 *Влажность:* {}%\n\
-            🍃 *Ветер:* {:.1} м/с, направление: {}\n\
-            ☁️ *Облачность:* {}%\n\
-            👁 *Видимость:* {} км\n\
-            🌅 *Восход солнца:* {}\n\
-            🌇 *Закат солнца:* {}\n\n\
-            *Рекомендация:* {}",
-            weather_emoji,
-            self.capitalize_first_letter(&data.weather[0].description),
-            data.main.temp,
-            data.main.feels_like,
-            temp_by_time,
-            data.main.temp_min,
-            data.main.temp_max,
-            data.main.humidity,
-            data.wind.speed,
-            wind_direction,
-            data.clouds.all,
-            data.visibility.unwrap_or(0) / 1000,
-            sunrise_time,
-            sunset_time,
-            clothing_recommendation
-        )
-    }
-    
-    fn extract_temperatures_by_time(&self, forecast: &ForecastResponse) -> String {
-        if forecast.list.is_empty() {
-            return "Нет данных о прогнозе".to_string();
-        }
+    // Сводка прогноза на сегодня с 12:00 до конца дня (по местному времени сервера)
+    pub async fn get_day_outlook(&self, city: &str) -> Result<DayOutlook, String> {
+        let forecast = self.fetch_forecast(city).await?;
+        let today = Local::now().date_naive();
 
-        // Определяем утро (6-11), день (12-17), вечер (18-23)
-        let mut morning_temp: Option<f32> = None;
-        let mut day_temp: Option<f32> = None;
-        let mut evening_temp: Option<f32> = None;
-
-        for item in &forecast.list {
-            let time = Utc.timestamp_opt(item.dt, 0).unwrap();
-            let hour = time.hour();
-
-            if (6..12).contains(&hour) && morning_temp.is_none() {
-                morning_temp = Some(item.main.temp);
-            } else if (12..18).contains(&hour) && day_temp.is_none() {
-                day_temp = Some(item.main.temp);
-            } else if (18..24).contains(&hour) && evening_temp.is_none() {
-                evening_temp = Some(item.main.temp);
-            }
+        let items: Vec<&ForecastItem> = forecast.list
+            .iter()
+            .filter(|item| {
+                let time = Local.timestamp_opt(item.dt, 0).unwrap();
+                time.date_naive() == today && time.hour() >= 12
+            })
+            .collect();
 
-            // Если собрали все температуры, выходим из цикла
-            if morning_temp.is_some() && day_temp.is_some() && evening_temp.is_some() {
-                break;
-            }
+        if items.is_empty() {
+            return Err("Нет данных прогноза на вторую половину дня".to_string());
         }
 
-        format!(
-            "🕒 *Прогноз на сегодня:* Утро: {}, День: {}, Вечер: {}",
-            morning_temp.map_or("Н/Д".to_string(), |t| format!("{:.1}°C", t)),
-            day_temp.map_or("Н/Д".to_string(), |t| format!("{:.1}°C", t)),
-            evening_temp.map_or("Н/Д".to_string(), |t| format!("{:.1}°C", t))
-        )
-    }
-    
-    fn get_weather_emoji(&self, icon: &str) -> &'static str {
-        match icon {
-            "01d" => "☀️",  // ясно (день)
-            "01n" => "🌙",  // ясно (ночь)
-            "02d" => "🌤️", // малооблачно (день)
-            "02n" => "🌙☁️", // малооблачно (ночь)
-            "03d" | "03n" => "☁️", // облачно
-            "04d" | "04n" => "☁️☁️", // пасмурно
-            "09d" | "09n" => "🌧️", // дождь
-            "10d" => "🌦️", // дождь с прояснениями (день)
-            "10n" => "🌧️🌙", // дождь с прояснениями (ночь)
-            "11d" | "11n" => "⛈️", // гроза
-            "13d" | "13n" => "❄️", // снег
-            "50d" | "50n" => "🌫️", // туман
-            _ => "🌡️",
+        let temp_min = items.iter().map(|item| item.main.temp).fold(f32::INFINITY, f32::min);
+        let temp_max = items.iter().map(|item| item.main.temp).fold(f32::NEG_INFINITY, f32::max);
+        let precipitation = items.iter().any(|item| {
+            item.weather.iter().any(|w| matches!(w.main.as_str(), "Rain" | "Drizzle" | "Thunderstorm" | "Snow"))
+        });
+
+        Ok(DayOutlook { date: today, temp_min, temp_max, precipitation })
+    }
+
+    // Температура на ближайшие сутки: 9 точек с шагом 3 часа (время UTC, температура)
+    pub async fn get_day_temperatures(&self, city: &str) -> Result<Vec<(i64, f32)>, String> {
+        let forecast = self.fetch_forecast(city).await?;
+        Ok(forecast.list.iter().take(9).map(|item| (item.dt, item.main.temp)).collect())
+    }
+
+    // Прогноз на завтра (по местному времени города); options - как для отчета о текущей погоде
+    pub async fn get_tomorrow_forecast(&self, city: &str, options: FormatOptions) -> Result<String, String> {
+        let summary = self.get_day_summary(city, 1).await?;
+        let mut report = formatter::format_tomorrow(&summary, options);
+        if options.style == ReportStyle::Normal {
+            report = formatter::with_notes(report, confidence::hint(city).as_slice());
         }
+        Ok(self.attributed(report))
     }
-    
-    fn get_wind_direction(&self, degrees: f32) -> &'static str {
-        let directions = [
-            "северный", "северо-восточный", "восточный", "юго-восточный",
-            "южный", "юго-западный", "западный", "северо-западный"
-        ];
-        
-        let index = ((degrees + 22.5) % 360.0 / 45.0) as usize;
-        directions[index]
-    }
-    
-    fn get_clothing_recommendation(&self, temp: f32, weather_main: &str) -> String {
-        if temp < -25.0 {
-            "🥶 *Крайне холодно!* Нужна очень теплая многослойная одежда: термобелье, теплый свитер, зимняя куртка/пуховик, утепленные брюки, теплая шапка, шарф, варежки/перчатки и зимняя обувь с тёплыми носками.".to_string()
-        } else if temp < -15.0 {
-            "❄️ *Очень холодно!* Наденьте теплую зимнюю куртку/пуховик, утепленные брюки, многослойную одежду (термобелье, свитер), теплую шапку, шарф, перчатки и зимнюю обувь. Не забудьте про теплые носки.".to_string()
-        } else if temp < -5.0 {
-            "🧣 *Холодно.* Необходима зимняя куртка, теплый свитер, шапка, перчатки и шарф. Лучше надеть утепленные брюки и зимнюю обувь. Если планируете долго находиться на улице, подумайте о термобелье.".to_string()
-        } else if temp < 5.0 {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "🌧️ *Холодно и дождливо.* Наденьте теплую водонепроницаемую куртку, шапку, перчатки, шарф. Обязательно возьмите зонт или наденьте куртку с капюшоном. Рекомендуется водонепроницаемая обувь.".to_string()
-            } else if weather_main == "Snow" {
-                "🌨️ *Холодно и снежно.* Наденьте теплую зимнюю куртку, шапку, перчатки, шарф и зимнюю обувь с хорошим протектором. Возможно понадобятся утепленные брюки.".to_string()
-            } else {
-                "🧥 *Прохладно.* Понадобится теплая куртка, свитер или толстовка, шапка и перчатки. Подойдет легкая шапка и шарф, особенно при ветре.".to_string()
-            }
-        } else if temp < 10.0 {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "🌂 *Прохладно и дождливо.* Возьмите водонепроницаемую куртку или плащ, зонт и наденьте водонепроницаемую обувь. Свитер или толстовка не помешают, так как на улице довольно прохладно.".to_string()
-            } else {
-                "🧶 *Прохладно.* Подойдет легкая куртка или плотная кофта, джинсы или брюки. При сильном ветре может понадобиться шарф. Утром и вечером будет прохладнее - возьмите дополнительный слой одежды.".to_string()
-            }
-        } else if temp < 15.0 {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "☔ *Умеренно прохладно и дождливо.* Возьмите зонт и наденьте водонепроницаемую куртку или плащ. Хорошим решением будет легкий свитер или кофта и удобная непромокаемая обувь.".to_string()
-            } else {
-                "👕 *Умеренно прохладно.* Достаточно легкой куртки или кофты, можно надеть джинсы или брюки. Если проведете весь день на улице, возьмите дополнительный слой на вечер.".to_string()
-            }
-        } else if temp < 20.0 {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "🌦️ *Тепло, но дождливо.* Возьмите зонт и легкую водонепроницаемую куртку или дождевик. Подойдет футболка и джинсы/брюки. Не забудьте про удобную непромокаемую обувь.".to_string()
-            } else {
-                "👚 *Тепло.* Достаточно футболки, рубашки или блузки, подойдут легкие брюки, джинсы или юбка. Вечером может быть прохладнее, возьмите с собой легкую кофту или кардиган.".to_string()
-            }
-        } else if temp < 25.0 {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "🌤️ *Довольно тепло, но дождливо.* Легкая одежда (футболка, шорты или легкие брюки) и зонт. Дождевик может пригодиться если дождь сильный. Обувь лучше выбрать непромокаемую.".to_string()
-            } else {
-                "👗 *Довольно тепло.* Легкая одежда: футболка, рубашка или блузка, легкие брюки, шорты или юбка. Вечером может быть прохладнее, так что кофта не помешает.".to_string()
-            }
-        } else if temp < 30.0 {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "🌞 *Жарко, но с дождем.* Максимально легкая одежда и зонтик. После дождя может быть влажно и душно - выбирайте дышащие натуральные ткани.".to_string()
-            } else {
-                "☀️ *Жарко.* Максимально легкая одежда из натуральных тканей: футболка, шорты, сарафан или легкое платье. Обязательны головной убор и солнцезащитный крем. Берегитесь прямых солнечных лучей.".to_string()
-            }
+
+    // Сводка прогноза на день по местному времени города: 0 - сегодня, 1 - завтра
+    pub async fn get_day_summary(&self, city: &str, days_ahead: i64) -> Result<DaySummary, String> {
+        let forecast = self.fetch_forecast(city).await?;
+        let offset = forecast.utc_offset();
+        let date = city_time(Utc::now().timestamp(), offset).date_naive() + chrono::Duration::days(days_ahead);
+
+        let items: Vec<&ForecastItem> = forecast.list
+            .iter()
+            .filter(|item| city_time(item.dt, offset).date_naive() == date)
+            .collect();
+
+        DaySummary::from_items(&items, offset).ok_or_else(|| match days_ahead {
+            0 => "Нет данных прогноза на сегодня".to_string(),
+            1 => "Нет данных прогноза на завтра".to_string(),
+            _ => format!("Нет данных прогноза на {}", date.format("%d.%m")),
+        })
+    }
+
+    // Резкая перемена погоды: прогноз на сегодня против средней температуры за прошедшую неделю
+    // по истории города. None - перемены нет или история не ведется
+    pub async fn get_forecast_anomaly(&self, city: &str) -> Result<Option<String>, String> {
+        let Some(history) = &self.history else {
+            return Ok(None);
+        };
+        let forecast = self.fetch_forecast(city).await?;
+        let offset = forecast.utc_offset();
+        let today = city_time(Utc::now().timestamp(), offset).date_naive();
+        let temps: Vec<f32> = forecast.list
+            .iter()
+            .filter(|item| city_time(item.dt, offset).date_naive() == today)
+            .map(|item| item.main.temp)
+            .collect();
+
+        Ok(stats::mean(&temps).and_then(|forecast_avg| trends::anomaly_note(&history.days(city), today, forecast_avg)))
+    }
+
+    // Непогода в ближайшие часы, из-за которой стоит узнать прогноз заранее: гроза, снег, дождь
+    // или сильный ветер. None - ничего такого не ожидается
+    pub async fn get_upcoming_hazard(&self, city: &str, hours: i64) -> Result<Option<String>, String> {
+        let forecast = self.fetch_forecast(city).await?;
+        let until = Utc::now().timestamp() + hours * 3600;
+        let items: Vec<&ForecastItem> = forecast.list.iter().filter(|item| item.dt <= until).collect();
+
+        let has = |kinds: &[&str]| items.iter().any(|item| item.weather.iter().any(|w| kinds.contains(&w.main.as_str())));
+        let hazard = if has(&["Thunderstorm"]) {
+            Some("гроза")
+        } else if has(&["Snow"]) {
+            Some("снег")
+        } else if has(&["Rain", "Drizzle"]) {
+            Some("дождь")
+        } else if items.iter().any(|item| item.wind.as_ref().map(|w| w.speed >= STRONG_WIND_SPEED).unwrap_or(false)) {
+            Some("сильный ветер")
         } else {
-            if weather_main == "Rain" || weather_main == "Drizzle" {
-                "🔥 *Очень жарко, возможны дожди.* Минимум самой легкой одежды из натуральных тканей. Носите светлые цвета. Зонт может пригодиться как для дождя, так и для защиты от солнца.".to_string()
-            } else {
-                "🔥 *Очень жарко!* Носите минимум самой легкой одежды из натуральных тканей, предпочтительно светлых цветов. Обязательны головной убор и солнцезащитный крем. Пейте больше воды и старайтесь находиться в тени. Избегайте активности на открытом солнце в пиковые часы.".to_string()
+            None
+        };
+
+        Ok(hazard.map(str::to_string))
+    }
+
+    pub async fn get_weekly_forecast(&self, city: &str, layout: ForecastLayout, options: FormatOptions) -> Result<String, String> {
+        let forecast = self.fetch_forecast_extended(city).await?;
+        let now = Utc::now().timestamp();
+        Ok(self.attributed(match layout {
+            ForecastLayout::Text => {
+                let report = formatter::format_weekly_forecast(&forecast, options, now);
+                formatter::with_notes(report, confidence::hint(city).as_slice())
             }
-        }
+            ForecastLayout::Table => formatter::format_weekly_table(&forecast, options, now),
+        }))
     }
-    
-    fn capitalize_first_letter(&self, s: &str) -> String {
-        let mut chars = s.chars();
-        match chars.next() {
-            None => String::new(),
-            Some(first) => first.to_uppercase().chain(chars).collect(),
-        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wind(speed: f32, deg: f32) -> WindInfo {
+        WindInfo { speed, deg, gust: None }
     }
 
-    fn format_weekly_forecast(&self, forecast: &ForecastResponse) -> String {
-        if forecast.list.is_empty() {
-            return "Нет данных о прогнозе".to_string();
+    #[test]
+    fn direction_by_compass_sector() {
+        // Граница румба (x + 22.5°) уже относится к следующему направлению
+        let cases = [
+            (0.0, "северный", "⬇️"),
+            (22.4, "северный", "⬇️"),
+            (22.5, "северо-восточный", "↙️"),
+            (67.4, "северо-восточный", "↙️"),
+            (67.5, "восточный", "⬅️"),
+            (112.5, "юго-восточный", "↖️"),
+            (157.5, "южный", "⬆️"),
+            (180.0, "южный", "⬆️"),
+            (202.5, "юго-западный", "↗️"),
+            (247.5, "западный", "➡️"),
+            (292.5, "северо-западный", "↘️"),
+            (337.4, "северо-западный", "↘️"),
+            (337.5, "северный", "⬇️"),
+            (359.9, "северный", "⬇️"),
+            (360.0, "северный", "⬇️"),
+            (-0.1, "северный", "⬇️"),
+            (-90.0, "западный", "➡️"),
+        ];
+        for (deg, name, arrow) in cases {
+            let wind = wind(3.0, deg);
+            assert_eq!(wind.direction_name(), name, "{}°", deg);
+            assert_eq!(wind.direction_arrow(), arrow, "{}°", deg);
         }
+    }
 
-        // Группируем прогноз по дням
-        let mut days_forecast: HashMap<String, (String, Vec<&ForecastItem>)> = HashMap::new();
-        
-        for item in &forecast.list {
-            // Используем формат даты из dt_txt: "2023-11-21 15:00:00"
-            // Получаем только дату (первые 10 символов)
-            let date_str = if item.dt_txt.len() >= 10 {
-                item.dt_txt[0..10].to_string()
-            } else {
-                // Запасной вариант, если dt_txt имеет неожиданный формат
-                let date = Utc.timestamp_opt(item.dt, 0).unwrap();
-                date.format("%Y-%m-%d").to_string()
-            };
-            
-            // Определяем день недели
-            let date = Utc.timestamp_opt(item.dt, 0).unwrap();
-            let day_name = match date.weekday() {
-                chrono::Weekday::Mon => "Понедельник",
-                chrono::Weekday::Tue => "Вторник",
-                chrono::Weekday::Wed => "Среда",
-                chrono::Weekday::Thu => "Четверг",
-                chrono::Weekday::Fri => "Пятница",
-                chrono::Weekday::Sat => "Суббота",
-                chrono::Weekday::Sun => "Воскресенье",
-            };
-            
-            // Добавляем прогноз в соответствующий день
-            days_forecast.entry(date_str)
-                .or_insert_with(|| (day_name.to_string(), Vec::new()))
-                .1.push(item);
+    #[test]
+    fn beaufort_thresholds() {
+        // Граница балла относится уже к следующему баллу
+        let cases = [
+            (0.0, "штиль"),
+            (0.29, "штиль"),
+            (0.3, "тихий ветер"),
+            (1.6, "легкий ветер"),
+            (3.4, "слабый ветер"),
+            (5.5, "умеренный ветер"),
+            (8.0, "свежий ветер"),
+            (10.8, "сильный ветер"),
+            (13.9, "крепкий ветер"),
+            (17.2, "очень крепкий ветер"),
+            (20.8, "шторм"),
+            (24.5, "сильный шторм"),
+            (28.5, "жестокий шторм"),
+            (32.69, "жестокий шторм"),
+            (32.7, "ураган"),
+            (50.0, "ураган"),
+        ];
+        for (speed, name) in cases {
+            assert_eq!(wind(speed, 0.0).beaufort_description(), name, "{} м/с", speed);
         }
+    }
 
-        // Форматируем прогноз для каждого дня
-        let mut result = String::new();
-        
-        // Сортируем дни
-        let mut days: Vec<(String, (String, Vec<&ForecastItem>))> = days_forecast.into_iter().collect();
-        days.sort_by(|a, b| a.0.cmp(&b.0));
-        
-        for (date, (day_name, forecasts)) in days {
-            // Обрабатываем данные для дня
-            let mut min_temp = f32::MAX;
-            let mut max_temp = f32::MIN;
-            let mut descriptions = Vec::new();
-            
-            for item in &forecasts {
-                min_temp = min_temp.min(item.main.temp_min);
-                max_temp = max_temp.max(item.main.temp_max);
-                
-                if let Some(weather_info) = item.weather.first() {
-                    descriptions.push(self.capitalize_first_letter(&weather_info.description));
-                }
-            }
-            
-            // Убираем дубликаты в описаниях
-            descriptions.sort();
-            descriptions.dedup();
-            
-            // Добавляем прогноз для дня - форматируем дату как день.месяц
-            let date_parts: Vec<&str> = date.split('-').collect();
-            let formatted_date = if date_parts.len() >= 3 {
-                format!("{}.{}", date_parts[2], date_parts[1]) // день.месяц
-            } else {
-                date.clone() // в случае ошибки берем исходную строку
-            };
-            
-            result.push_str(&format!("*{}, {}*:\n", day_name, formatted_date));
-            result.push_str(&format!("🌡 Температура: {:.1}°C — {:.1}°C\n", min_temp, max_temp));
-            result.push_str(&format!("🌤 Погода: {}\n\n", descriptions.join(", ")));
-        }
-        
-        result
+    #[test]
+    fn gust_note_depends_on_gap_and_strength() {
+        let with_gust = |speed: f32, gust: Option<f32>| WindInfo { speed, deg: 0.0, gust }.gust_note(Units::Metric);
+        assert_eq!(with_gust(4.0, None), None);
+        assert_eq!(with_gust(4.0, Some(4.0)), None);
+        assert_eq!(with_gust(4.0, Some(6.0)).as_deref(), Some("порывы до 6.0 м/с"));
+        assert_eq!(with_gust(4.0, Some(9.0)).as_deref(), Some("порывистый, порывы до 9.0 м/с"));
+        assert_eq!(with_gust(10.0, Some(15.0)).as_deref(), Some("опасные порывы до 15.0 м/с"));
+        let imperial = WindInfo { speed: 4.0, deg: 0.0, gust: Some(9.0) }.gust_note(Units::Imperial);
+        assert_eq!(imperial.as_deref(), Some("порывистый, порывы до 20.1 миль/ч"));
     }
-}
\ No newline at end of file
+}
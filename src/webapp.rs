@@ -0,0 +1,339 @@
+use crate::config::Config;
+use crate::formatter::{ReportStyle, TemperaturePrecision, Units};
+use crate::locale::Locale;
+use crate::sections::{ReportSections, SECTIONS};
+use crate::storage::{UserSettings, UserStorage, EXTRA_TIMES_LIMIT};
+use crate::weather::WeatherClient;
+use crate::{moderation, templates, timezone, utils};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode, WebAppInfo};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Страница настроек, которую Telegram открывает внутри приложения
+const SETTINGS_PAGE: &str = include_str!("../static/settings.html");
+// Данные запуска Web App старше этого считаем устаревшими
+const INIT_DATA_MAX_AGE_SECS: i64 = 24 * 3600;
+// Ограничение размера тела запроса с настройками
+const MAX_BODY_BYTES: usize = 16 * 1024;
+
+// Настройки пользователя в том виде, в каком их показывает и присылает страница
+#[derive(Debug, Serialize, Deserialize)]
+struct SettingsForm {
+    city: Option<String>,
+    notification_time: Option<String>,
+    // Дополнительное время уведомлений, ЧЧ:ММ
+    extra_times: Vec<String>,
+    // Вечерний прогноз на завтра - второе время уведомлений
+    night_mode: bool,
+    // Ключи включенных разделов отчета
+    sections: Vec<String>,
+    style: ReportStyle,
+    precision: TemperaturePrecision,
+    units: Units,
+    locale: Locale,
+}
+
+impl SettingsForm {
+    fn from_user(user: &UserSettings) -> Self {
+        SettingsForm {
+            city: user.city().map(str::to_string),
+            notification_time: user.notification_time.clone(),
+            extra_times: user.extra_times.clone(),
+            night_mode: user.night_mode,
+            sections: SECTIONS
+                .iter()
+                .filter(|(section, _, _)| user.report_sections.contains(*section))
+                .map(|(_, key, _)| key.to_string())
+                .collect(),
+            style: user.report_style,
+            precision: user.temperature_precision,
+            units: user.units,
+            locale: user.locale.unwrap_or_default(),
+        }
+    }
+
+    // Переносит настройки в пользователя; ошибка - текст для показа на странице
+    fn apply(self, user: &mut UserSettings, config: &Config) -> Result<(), String> {
+        match self.city.as_deref().map(moderation::sanitize).filter(|city| !city.is_empty()) {
+            Some(city) if city.chars().count() > utils::MAX_CITY_LENGTH => return Err("Слишком длинное название города".to_string()),
+            Some(city) if moderation::is_blocked(&city, &config.blocklist) => return Err("Недопустимое название города".to_string()),
+            Some(city) if user.city() != Some(city.as_str()) => {
+                user.remember_city(&city);
+                user.set_city(&city);
+            }
+            _ => {}
+        }
+
+        if let Some(time) = self.notification_time.as_deref().map(str::trim).filter(|time| !time.is_empty()) {
+            let parsed = utils::parse_time(time).ok_or_else(|| format!("Некорректное время: {}", time))?;
+            let time = utils::format_time(utils::round_time(parsed, config.schedule_granularity));
+            if user.notification_time.as_deref() != Some(time.as_str()) {
+                user.notification_time = Some(time);
+                user.delivery_window = None;
+            }
+        }
+
+        let mut extra_times = Vec::new();
+        for time in self.extra_times.iter().map(|time| time.trim()).filter(|time| !time.is_empty()) {
+            let parsed = utils::parse_time(time).ok_or_else(|| format!("Некорректное время: {}", time))?;
+            let time = utils::format_time(utils::round_time(parsed, config.schedule_granularity));
+            // Совпадающее с основным или уже указанное время второго уведомления не дает
+            if user.notification_time.as_deref() != Some(time.as_str()) && !extra_times.contains(&time) {
+                extra_times.push(time);
+            }
+        }
+        if extra_times.len() > EXTRA_TIMES_LIMIT {
+            return Err(format!("Дополнительного времени уведомлений может быть не больше {}", EXTRA_TIMES_LIMIT));
+        }
+        extra_times.sort();
+        user.extra_times = extra_times;
+
+        user.night_mode = self.night_mode;
+        user.report_sections = SECTIONS
+            .iter()
+            .filter(|(_, key, _)| self.sections.iter().any(|selected| selected == key))
+            .fold(ReportSections::empty(), |sections, (section, _, _)| sections | *section);
+        user.report_style = self.style;
+        user.temperature_precision = self.precision;
+        user.units = self.units;
+        user.locale = Some(self.locale);
+        Ok(())
+    }
+}
+
+// Обработка /settings: кнопка, открывающая страницу настроек
+pub async fn handle_settings_command(bot: &Bot, msg: &Message, config: &Config) -> ResponseResult<()> {
+    let url = config.webapp_url.as_deref().and_then(|url| reqwest::Url::parse(url).ok());
+    let Some(url) = url else {
+        bot.send_message(msg.chat.id, templates::text("settings.unavailable"))
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    };
+
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::web_app(
+        "⚙️ Открыть настройки",
+        WebAppInfo { url },
+    )]]);
+    bot.send_message(msg.chat.id, templates::text("settings.open"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+// HTTP-сервер страницы настроек и ее API; работает, пока работает бот
+pub async fn start_server(addr: SocketAddr, storage: Arc<dyn UserStorage>, weather_client: WeatherClient, config: Arc<Config>) {
+    let make_service = make_service_fn(move |_| {
+        let storage = Arc::clone(&storage);
+        let weather_client = weather_client.clone();
+        let config = Arc::clone(&config);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let storage = Arc::clone(&storage);
+                let weather_client = weather_client.clone();
+                let config = Arc::clone(&config);
+                async move { Ok::<_, Infallible>(route(request, &*storage, &weather_client, &config).await) }
+            }))
+        }
+    });
+
+    let server = match Server::try_bind(&addr) {
+        Ok(builder) => builder.serve(make_service),
+        Err(e) => {
+            error!("Не удалось запустить сервер настроек на {}: {}", addr, e);
+            return;
+        }
+    };
+    info!("Сервер настроек Web App слушает {}", addr);
+    if let Err(e) = server.await {
+        error!("Сервер настроек остановлен с ошибкой: {}", e);
+    }
+}
+
+async fn route(request: Request<Body>, storage: &dyn UserStorage, weather_client: &WeatherClient, config: &Config) -> Response<Body> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/") => Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(SETTINGS_PAGE))
+            .unwrap_or_default(),
+        (&Method::GET, "/api/settings") => match authorize(&request, config) {
+            Ok(user_id) => match load_user(storage, user_id).await {
+                Ok(user) => json(StatusCode::OK, &SettingsForm::from_user(&user)),
+                Err(response) => response,
+            },
+            Err(e) => unauthorized(e),
+        },
+        (&Method::POST, "/api/settings") => {
+            let user_id = match authorize(&request, config) {
+                Ok(user_id) => user_id,
+                Err(e) => return unauthorized(e),
+            };
+            let body = match hyper::body::to_bytes(request.into_body()).await {
+                Ok(body) if body.len() <= MAX_BODY_BYTES => body,
+                _ => return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Слишком большой запрос"),
+            };
+            let form: SettingsForm = match serde_json::from_slice(&body) {
+                Ok(form) => form,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &format!("Некорректные настройки: {}", e)),
+            };
+
+            let mut user = match load_user(storage, user_id).await {
+                Ok(user) => user,
+                Err(response) => return response,
+            };
+            let city_before = user.city().map(str::to_string);
+            if let Err(e) = form.apply(&mut user, config) {
+                return error_response(StatusCode::BAD_REQUEST, &e);
+            }
+            // Время на экране настроек - местное время нового города, спрашивать о поясе здесь негде
+            if let Some(city) = user.city().map(str::to_string).filter(|city| Some(city) != city_before.as_ref()) {
+                timezone::infer_silently(weather_client, &mut user, &city).await;
+            }
+            let saved = SettingsForm::from_user(&user);
+            if let Err(e) = storage.save_user(user).await {
+                error!("Не удалось сохранить настройки пользователя ID: {} из Web App: {}", user_id, e);
+                return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Не удалось сохранить настройки, попробуй позже");
+            }
+            info!("Пользователь ID: {} сохранил настройки через Web App", user_id);
+            json(StatusCode::OK, &saved)
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "Не найдено"),
+    }
+}
+
+// Запись пользователя или новая; если хранилище не ответило - ответ 500,
+// чтобы форма не показала и не сохранила настройки по умолчанию поверх настоящих
+async fn load_user(storage: &dyn UserStorage, user_id: i64) -> Result<UserSettings, Response<Body>> {
+    match storage.get_user(user_id).await {
+        Ok(user) => Ok(user.unwrap_or_else(|| UserSettings::new(user_id))),
+        Err(e) => {
+            error!("Не удалось загрузить настройки пользователя ID: {} для Web App: {}", user_id, e);
+            Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, "Не удалось загрузить настройки, попробуй позже"))
+        }
+    }
+}
+
+// ID пользователя из проверенных данных запуска Web App (заголовок X-Telegram-Init-Data)
+fn authorize(request: &Request<Body>, config: &Config) -> Result<i64, String> {
+    let init_data = request
+        .headers()
+        .get("X-Telegram-Init-Data")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| "нет данных запуска Web App".to_string())?;
+    let token = config.telegram_bot_token.as_deref().ok_or_else(|| "не задан токен бота".to_string())?;
+    validate_init_data(init_data, token, Utc::now().timestamp())
+}
+
+// Проверка подписи initData по правилам Telegram: HMAC-SHA256 от отсортированных пар
+// "ключ=значение" с ключом HMAC-SHA256("WebAppData", токен бота)
+fn validate_init_data(init_data: &str, bot_token: &str, now: i64) -> Result<i64, String> {
+    let mut hash = None;
+    let mut pairs = Vec::new();
+    for (key, value) in form_urlencoded::parse(init_data.as_bytes()) {
+        if key == "hash" {
+            hash = Some(value.into_owned());
+        } else {
+            pairs.push(format!("{}={}", key, value));
+        }
+    }
+    let hash = hash.ok_or_else(|| "в данных запуска нет подписи".to_string())?;
+    let hash = hex::decode(hash).map_err(|_| "некорректная подпись".to_string())?;
+    pairs.sort();
+
+    let mut secret = HmacSha256::new_from_slice(b"WebAppData").map_err(|e| e.to_string())?;
+    secret.update(bot_token.as_bytes());
+    let mut mac = HmacSha256::new_from_slice(&secret.finalize().into_bytes()).map_err(|e| e.to_string())?;
+    mac.update(pairs.join("\n").as_bytes());
+    mac.verify_slice(&hash).map_err(|_| "подпись не совпадает".to_string())?;
+
+    let field = |name: &str| {
+        pairs
+            .iter()
+            .find_map(|pair| pair.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')))
+            .map(str::to_string)
+    };
+    let auth_date = field("auth_date")
+        .and_then(|date| date.parse::<i64>().ok())
+        .ok_or_else(|| "нет времени запуска".to_string())?;
+    if now - auth_date > INIT_DATA_MAX_AGE_SECS {
+        return Err("данные запуска устарели, откройте настройки заново".to_string());
+    }
+
+    let user = field("user").ok_or_else(|| "нет данных пользователя".to_string())?;
+    serde_json::from_str::<serde_json::Value>(&user)
+        .ok()
+        .and_then(|user| user.get("id").and_then(serde_json::Value::as_i64))
+        .ok_or_else(|| "некорректные данные пользователя".to_string())
+}
+
+fn json<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(value).unwrap_or_default()))
+        .unwrap_or_default()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+fn unauthorized(reason: String) -> Response<Body> {
+    warn!("Отклонен запрос к API настроек: {}", reason);
+    error_response(StatusCode::UNAUTHORIZED, "Откройте настройки из Telegram командой /settings")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "123456:TEST-TOKEN";
+    const AUTH_DATE: i64 = 1_736_942_400;
+    // Подписано отдельно от бота по правилам Telegram для TOKEN
+    const INIT_DATA: &str = "auth_date=1736942400&query_id=AAHdF6IQAAAAAN0XohDhrOrc\
+        &user=%7B%22id%22%3A42%2C%22first_name%22%3A%22Ivan%22%7D\
+        &hash=c49da11aa39b19a759b0db4b32bdbce8d3f057aa01dfd5792d4440907ed3ca1f";
+
+    #[test]
+    fn accepts_valid_signature() {
+        assert_eq!(validate_init_data(INIT_DATA, TOKEN, AUTH_DATE + 60), Ok(42));
+        // Порядок полей на подпись не влияет
+        let (fields, hash) = INIT_DATA.rsplit_once("&hash=").unwrap();
+        let reordered = format!("hash={}&{}", hash, fields.split('&').rev().collect::<Vec<_>>().join("&"));
+        assert_eq!(validate_init_data(&reordered, TOKEN, AUTH_DATE + 60), Ok(42));
+    }
+
+    #[test]
+    fn rejects_tampered_field() {
+        let other_user = INIT_DATA.replace("%22id%22%3A42", "%22id%22%3A43");
+        assert!(validate_init_data(&other_user, TOKEN, AUTH_DATE).is_err());
+        let later = INIT_DATA.replace("auth_date=1736942400", "auth_date=1736942401");
+        assert!(validate_init_data(&later, TOKEN, AUTH_DATE).is_err());
+        let unsigned = INIT_DATA.split("&hash=").next().unwrap();
+        assert!(validate_init_data(unsigned, TOKEN, AUTH_DATE).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_bot_token() {
+        assert!(validate_init_data(INIT_DATA, "654321:OTHER-TOKEN", AUTH_DATE).is_err());
+    }
+
+    #[test]
+    fn rejects_stale_auth_date() {
+        assert_eq!(validate_init_data(INIT_DATA, TOKEN, AUTH_DATE + INIT_DATA_MAX_AGE_SECS), Ok(42));
+        let stale = validate_init_data(INIT_DATA, TOKEN, AUTH_DATE + INIT_DATA_MAX_AGE_SECS + 1);
+        assert_eq!(stale, Err("данные запуска устарели, откройте настройки заново".to_string()));
+    }
+}